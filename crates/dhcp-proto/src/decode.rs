@@ -0,0 +1,74 @@
+use core::net::Ipv4Addr;
+
+/// Error returned when a read runs past the end of the buffer
+pub(crate) const UNEXPECTED_END: &str = "Unexpected end of packet";
+
+/// Bounds-checked sequential reader over a byte slice, big-endian like the wire
+pub(crate) struct ByteCursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Number of bytes left to read
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, &'static str> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_ipv4(&mut self) -> Result<Ipv4Addr, &'static str> {
+        Ok(Ipv4Addr::from(self.read_array::<4>()?))
+    }
+
+    /// Borrow the next `len` bytes
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        if len > self.remaining() {
+            return Err(UNEXPECTED_END);
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], &'static str> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_are_big_endian_and_bounds_checked() {
+        let mut cursor = ByteCursor::new(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(cursor.read_u8(), Ok(1));
+        assert_eq!(cursor.read_u16(), Ok(0x0203));
+        assert_eq!(cursor.read_u32(), Ok(0x04050607));
+        assert_eq!(cursor.read_ipv4(), Ok(Ipv4Addr::new(8, 9, 10, 11)));
+        assert_eq!(cursor.remaining(), 0);
+        assert_eq!(cursor.read_u8(), Err(UNEXPECTED_END));
+
+        let mut cursor = ByteCursor::new(&[1, 2, 3]);
+        assert_eq!(cursor.read_u32(), Err(UNEXPECTED_END));
+        // A failed read consumes nothing
+        assert_eq!(cursor.read_bytes(3), Ok(&[1, 2, 3][..]));
+    }
+}
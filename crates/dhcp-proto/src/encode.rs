@@ -0,0 +1,78 @@
+use core::fmt;
+
+/// Error returned when serializing into a caller-provided buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The buffer cannot hold the encoded packet
+    BufferTooSmall { capacity: usize },
+    /// An option's data is longer than the 255 bytes its length byte can
+    /// describe
+    OptionTooLong { code: u8, len: usize },
+    /// A DNS name in an option has a label longer than the 63 bytes allowed
+    /// by RFC 1035
+    LabelTooLong { code: u8, len: usize },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferTooSmall { capacity } => {
+                write!(f, "buffer of {} bytes is too small for the packet", capacity)
+            }
+            Self::OptionTooLong { code, len } => {
+                write!(f, "option {} carries {} bytes, more than 255", code, len)
+            }
+            Self::LabelTooLong { code, len } => {
+                write!(f, "option {} has a DNS label of {} bytes, more than 63", code, len)
+            }
+        }
+    }
+}
+
+impl core::error::Error for EncodeError {}
+
+/// Bounds-checked sequential writer over a byte slice
+pub(crate) struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Number of bytes written so far
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn push(&mut self, byte: u8) -> Result<(), EncodeError> {
+        self.extend(&[byte])
+    }
+
+    pub(crate) fn extend(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        let end = self.pos + bytes.len();
+        if end > self.buf.len() {
+            return Err(EncodeError::BufferTooSmall {
+                capacity: self.buf.len(),
+            });
+        }
+        self.buf[self.pos..end].copy_from_slice(bytes);
+        self.pos = end;
+        Ok(())
+    }
+
+    /// Write `len` zero bytes
+    pub(crate) fn zeroes(&mut self, len: usize) -> Result<(), EncodeError> {
+        let end = self.pos + len;
+        if end > self.buf.len() {
+            return Err(EncodeError::BufferTooSmall {
+                capacity: self.buf.len(),
+            });
+        }
+        self.buf[self.pos..end].fill(0);
+        self.pos = end;
+        Ok(())
+    }
+}
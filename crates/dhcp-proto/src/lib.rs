@@ -3,13 +3,27 @@
 //! This library provides low-level DHCP packet manipulation without any
 //! business logic dependencies. It can be used in any DHCP server or client
 //! implementation.
+//!
+//! Only `core` and `alloc` are used, so the crate builds for targets without
+//! an operating system. The default `std` feature links the standard library;
+//! disable default features to get a `no_std` build that only needs a global
+//! allocator.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
+mod decode;
+pub mod encode;
 pub mod mac;
 pub mod message_type;
+pub mod op_code;
 pub mod option;
 pub mod packet;
 
+pub use encode::EncodeError;
 pub use mac::MacAddress;
-pub use message_type::MessageType;
+pub use message_type::{MessageType, UnknownMessageType};
+pub use op_code::OpCode;
 pub use option::DhcpOption;
 pub use packet::DhcpPacket;
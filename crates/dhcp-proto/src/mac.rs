@@ -1,3 +1,6 @@
+use alloc::vec::Vec;
+use core::fmt;
+
 /// MAC address representation
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MacAddress([u8; 6]);
@@ -24,14 +27,6 @@ impl MacAddress {
         &self.0
     }
 
-    /// Convert to a formatted string (xx:xx:xx:xx:xx:xx)
-    pub fn to_string(&self) -> String {
-        format!(
-            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
-            self.0[0], self.0[1], self.0[2], self.0[3], self.0[4], self.0[5]
-        )
-    }
-
     /// Parse a MAC address from a string (XX:XX:XX:XX:XX:XX)
     pub fn from_string(s: &str) -> Option<Self> {
         let parts: Vec<&str> = s.split(':').collect();
@@ -48,6 +43,17 @@ impl MacAddress {
     }
 }
 
+impl fmt::Display for MacAddress {
+    /// Format as a lowercase colon-separated string (xx:xx:xx:xx:xx:xx)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            self.0[0], self.0[1], self.0[2], self.0[3], self.0[4], self.0[5]
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,3 +1,6 @@
+use core::fmt;
+use core::str::FromStr;
+
 /// DHCP message types as defined in RFC 2132
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
@@ -32,6 +35,68 @@ impl MessageType {
             _ => None,
         }
     }
+
+    /// Every message type, in wire value order
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            Self::Discover,
+            Self::Offer,
+            Self::Request,
+            Self::Decline,
+            Self::Ack,
+            Self::Nak,
+            Self::Release,
+            Self::Inform,
+        ]
+        .into_iter()
+    }
+
+    /// Upper-case name as written in RFC 2131, e.g. `DISCOVER`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Discover => "DISCOVER",
+            Self::Offer => "OFFER",
+            Self::Request => "REQUEST",
+            Self::Decline => "DECLINE",
+            Self::Ack => "ACK",
+            Self::Nak => "NAK",
+            Self::Release => "RELEASE",
+            Self::Inform => "INFORM",
+        }
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when parsing an unknown message type name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownMessageType;
+
+impl fmt::Display for UnknownMessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown DHCP message type")
+    }
+}
+
+impl core::error::Error for UnknownMessageType {}
+
+impl FromStr for MessageType {
+    type Err = UnknownMessageType;
+
+    /// Parse a message type name, ignoring case (`discover`, `DHCPDISCOVER`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = match s.get(..4) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("DHCP") => &s[4..],
+            _ => s,
+        };
+        Self::all()
+            .find(|t| t.name().eq_ignore_ascii_case(name))
+            .ok_or(UnknownMessageType)
+    }
 }
 
 #[cfg(test)]
@@ -53,4 +118,17 @@ mod tests {
         assert_eq!(MessageType::from_u8(5), Some(MessageType::Ack));
         assert_eq!(MessageType::from_u8(99), None);
     }
+
+    #[test]
+    fn test_message_type_names_round_trip() {
+        assert_eq!(MessageType::all().count(), 8);
+        for (value, t) in (1..).zip(MessageType::all()) {
+            assert_eq!(t.to_u8(), value);
+            assert_eq!(t.to_string().parse::<MessageType>(), Ok(t));
+        }
+        assert_eq!(MessageType::Discover.to_string(), "DISCOVER");
+        assert_eq!("ack".parse(), Ok(MessageType::Ack));
+        assert_eq!("DHCPNAK".parse(), Ok(MessageType::Nak));
+        assert_eq!("bogus".parse::<MessageType>(), Err(UnknownMessageType));
+    }
 }
@@ -0,0 +1,47 @@
+/// BOOTP message op code (RFC 951), the first byte of every DHCP packet.
+///
+/// Clients send requests and servers answer with replies, whatever the DHCP
+/// message type carried in option 53.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum OpCode {
+    BootRequest = 1,
+    BootReply = 2,
+}
+
+impl OpCode {
+    /// Convert to u8 representation
+    pub fn to_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Parse from u8 value
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(Self::BootRequest),
+            2 => Some(Self::BootReply),
+            _ => None,
+        }
+    }
+}
+
+impl From<OpCode> for u8 {
+    fn from(op: OpCode) -> Self {
+        op.to_u8()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_op_code_round_trip() {
+        assert_eq!(OpCode::BootRequest.to_u8(), 1);
+        assert_eq!(u8::from(OpCode::BootReply), 2);
+        assert_eq!(OpCode::from_u8(1), Some(OpCode::BootRequest));
+        assert_eq!(OpCode::from_u8(2), Some(OpCode::BootReply));
+        assert_eq!(OpCode::from_u8(0), None);
+        assert_eq!(OpCode::from_u8(3), None);
+    }
+}
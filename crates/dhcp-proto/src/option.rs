@@ -1,5 +1,105 @@
+use crate::encode::{EncodeError, Writer};
 use crate::MessageType;
-use std::net::Ipv4Addr;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::net::Ipv4Addr;
+
+/// Flag bits of the Client FQDN option (RFC 4702 §2.1)
+pub mod fqdn_flags {
+    /// S: the server should perform the A RR (forward) update
+    pub const SERVER_UPDATE: u8 = 0x01;
+    /// O: the server overrode the client's S bit (server replies only)
+    pub const OVERRIDE: u8 = 0x02;
+    /// E: the name uses the canonical DNS wire encoding instead of ASCII
+    pub const CANONICAL_ENCODING: u8 = 0x04;
+    /// N: the server must not perform any DNS updates
+    pub const NO_UPDATE: u8 = 0x08;
+}
+
+/// RCODE1/RCODE2 fields of the Client FQDN option, deprecated by RFC 4702
+/// §2.2 but still filled in by role
+pub mod fqdn_rcode {
+    /// Sent by servers
+    pub const SERVER: [u8; 2] = [255, 255];
+    /// Sent by clients
+    pub const CLIENT: [u8; 2] = [0, 0];
+}
+
+/// Longest label of a DNS name (RFC 1035 §2.3.4)
+const MAX_LABEL_LEN: usize = 63;
+
+/// Decode a domain name in DNS wire format. An absolute name (terminated by
+/// the root label) is returned with a trailing dot; a partial name without.
+fn decode_dns_name(data: &[u8]) -> String {
+    let mut labels = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let len = data[i] as usize;
+        if len == 0 {
+            return format!("{}.", labels.join("."));
+        }
+        if i + 1 + len > data.len() {
+            break;
+        }
+        labels.push(String::from_utf8_lossy(&data[i + 1..i + 1 + len]).to_string());
+        i += 1 + len;
+    }
+    labels.join(".")
+}
+
+/// Length of `name` in DNS wire format, see [`encode_dns_name`]
+fn encoded_dns_name_len(name: &str) -> usize {
+    let labels: usize = name
+        .split('.')
+        .filter(|l| !l.is_empty())
+        .map(|l| l.len() + 1)
+        .sum();
+    labels + usize::from(name.ends_with('.'))
+}
+
+/// Encode a domain name in DNS wire format for option `code`; a trailing dot
+/// adds the root label. Labels over 63 bytes are refused, as their length
+/// byte would be read as a compression pointer.
+fn encode_dns_name(code: u8, name: &str, w: &mut Writer<'_>) -> Result<(), EncodeError> {
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        if label.len() > MAX_LABEL_LEN {
+            return Err(EncodeError::LabelTooLong { code, len: label.len() });
+        }
+        w.push(label.len() as u8)?;
+        w.extend(label.as_bytes())?;
+    }
+    if name.ends_with('.') {
+        w.push(0)?;
+    }
+    Ok(())
+}
+
+/// Length byte of an option carrying `len` bytes of data. Longer data would
+/// have to be split into several instances of the option (RFC 3396), which
+/// is not supported: it is refused instead of silently truncated.
+fn data_len(code: u8, len: usize) -> Result<u8, EncodeError> {
+    u8::try_from(len).map_err(|_| EncodeError::OptionTooLong { code, len })
+}
+
+/// Split a User Class option (RFC 3004) into its length-prefixed instances.
+/// Some clients send one bare string instead; anything that does not parse
+/// as a sequence of non-empty instances is kept whole as a single class.
+fn decode_user_classes(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut classes = Vec::new();
+    let mut rest = data;
+    while let Some((&len, tail)) = rest.split_first() {
+        let len = len as usize;
+        if len == 0 || len > tail.len() {
+            return vec![data.to_vec()];
+        }
+        classes.push(tail[..len].to_vec());
+        rest = &tail[len..];
+    }
+    classes
+}
 
 /// DHCP options as defined in RFC 2132
 #[derive(Debug, Clone, PartialEq)]
@@ -8,18 +108,105 @@ pub enum DhcpOption {
     Router(Vec<Ipv4Addr>),
     DnsServer(Vec<Ipv4Addr>),
     DomainName(String),
+    NtpServers(Vec<Ipv4Addr>),
     RequestedIpAddress(Ipv4Addr),
     LeaseTime(u32),
     MessageType(MessageType),
     ServerIdentifier(Ipv4Addr),
+    /// Parameter Request List (option 55): codes of the options the client
+    /// wants in the reply, in order of preference
+    ParameterRequestList(Vec<u8>),
     RenewalTime(u32),
     RebindingTime(u32),
     Hostname(String),
+    VendorClassIdentifier(String),
+    TftpServerName(String),
+    BootfileName(String),
+    /// Client Identifier (option 61): an opaque key clients may use instead
+    /// of `chaddr`. The first byte is a hardware type (1 for Ethernet, with
+    /// the MAC following), or 0 and 255 for other forms such as a DUID.
+    ClientIdentifier(Vec<u8>),
+    /// User Class (option 77, RFC 3004): the classes a client declares
+    /// itself a member of, e.g. `iot`
+    UserClass(Vec<Vec<u8>>),
+    /// Client FQDN (option 81). `name` is decoded from either the deprecated
+    /// ASCII form or the canonical wire form, as indicated by the E flag.
+    /// `rcode` holds RCODE1/RCODE2 as sent, see [`fqdn_rcode`].
+    ClientFqdn { flags: u8, rcode: [u8; 2], name: String },
+    /// Relay Agent Information (option 82, RFC 3046). The sub-options are
+    /// kept as raw bytes since servers must echo them back unchanged.
+    RelayAgentInfo(Vec<u8>),
+    /// Pad (option 0): a single zero byte used for alignment
+    Pad,
+    /// End (option 255): marks the end of the options field
     End,
     Unknown(u8, Vec<u8>),
 }
 
 impl DhcpOption {
+    /// Option code on the wire
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Pad => 0,
+            Self::SubnetMask(_) => 1,
+            Self::Router(_) => 3,
+            Self::DnsServer(_) => 6,
+            Self::Hostname(_) => 12,
+            Self::DomainName(_) => 15,
+            Self::NtpServers(_) => 42,
+            Self::RequestedIpAddress(_) => 50,
+            Self::LeaseTime(_) => 51,
+            Self::MessageType(_) => 53,
+            Self::ServerIdentifier(_) => 54,
+            Self::ParameterRequestList(_) => 55,
+            Self::RenewalTime(_) => 58,
+            Self::RebindingTime(_) => 59,
+            Self::VendorClassIdentifier(_) => 60,
+            Self::ClientIdentifier(_) => 61,
+            Self::TftpServerName(_) => 66,
+            Self::BootfileName(_) => 67,
+            Self::UserClass(_) => 77,
+            Self::ClientFqdn { .. } => 81,
+            Self::RelayAgentInfo(_) => 82,
+            Self::End => 255,
+            Self::Unknown(code, _) => *code,
+        }
+    }
+
+    /// Short name of the option, e.g. `dns_server`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pad => "pad",
+            Self::SubnetMask(_) => "subnet_mask",
+            Self::Router(_) => "router",
+            Self::DnsServer(_) => "dns_server",
+            Self::Hostname(_) => "hostname",
+            Self::DomainName(_) => "domain_name",
+            Self::NtpServers(_) => "ntp_servers",
+            Self::RequestedIpAddress(_) => "requested_ip",
+            Self::LeaseTime(_) => "lease_time",
+            Self::MessageType(_) => "message_type",
+            Self::ServerIdentifier(_) => "server_id",
+            Self::ParameterRequestList(_) => "parameter_request_list",
+            Self::RenewalTime(_) => "renewal_time",
+            Self::RebindingTime(_) => "rebinding_time",
+            Self::VendorClassIdentifier(_) => "vendor_class",
+            Self::ClientIdentifier(_) => "client_id",
+            Self::TftpServerName(_) => "tftp_server",
+            Self::BootfileName(_) => "bootfile",
+            Self::UserClass(_) => "user_class",
+            Self::ClientFqdn { .. } => "client_fqdn",
+            Self::RelayAgentInfo(_) => "relay_agent_info",
+            Self::End => "end",
+            Self::Unknown(..) => "unknown",
+        }
+    }
+
+    /// Human-readable value, as shown after `=` by the `Display` form
+    pub fn value(&self) -> OptionValue<'_> {
+        OptionValue(self)
+    }
+
     /// Parse a DHCP option from code and data bytes
     pub fn parse(code: u8, data: &[u8]) -> Self {
         match code {
@@ -41,6 +228,13 @@ impl DhcpOption {
                 Self::DnsServer(dns_servers)
             }
             15 => Self::DomainName(String::from_utf8_lossy(data).to_string()),
+            42 => {
+                let mut ntp_servers = Vec::new();
+                for chunk in data.chunks_exact(4) {
+                    ntp_servers.push(Ipv4Addr::new(chunk[0], chunk[1], chunk[2], chunk[3]));
+                }
+                Self::NtpServers(ntp_servers)
+            }
             50 if data.len() == 4 => {
                 Self::RequestedIpAddress(Ipv4Addr::new(data[0], data[1], data[2], data[3]))
             }
@@ -57,6 +251,7 @@ impl DhcpOption {
             54 if data.len() == 4 => {
                 Self::ServerIdentifier(Ipv4Addr::new(data[0], data[1], data[2], data[3]))
             }
+            55 if !data.is_empty() => Self::ParameterRequestList(data.to_vec()),
             58 if data.len() == 4 => {
                 Self::RenewalTime(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
             }
@@ -64,81 +259,280 @@ impl DhcpOption {
                 Self::RebindingTime(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
             }
             12 => Self::Hostname(String::from_utf8_lossy(data).to_string()),
+            60 => Self::VendorClassIdentifier(String::from_utf8_lossy(data).to_string()),
+            66 => Self::TftpServerName(String::from_utf8_lossy(data).to_string()),
+            67 => Self::BootfileName(String::from_utf8_lossy(data).to_string()),
+            // RFC 2132 §9.14: a type byte followed by at least one byte of identifier
+            61 if data.len() >= 2 => Self::ClientIdentifier(data.to_vec()),
+            77 if !data.is_empty() => Self::UserClass(decode_user_classes(data)),
+            81 if data.len() >= 3 => {
+                let flags = data[0];
+                let rcode = [data[1], data[2]];
+                let name = if flags & fqdn_flags::CANONICAL_ENCODING != 0 {
+                    decode_dns_name(&data[3..])
+                } else {
+                    String::from_utf8_lossy(&data[3..]).to_string()
+                };
+                Self::ClientFqdn { flags, rcode, name }
+            }
+            82 => Self::RelayAgentInfo(data.to_vec()),
             _ => Self::Unknown(code, data.to_vec()),
         }
     }
 
     /// Serialize the option to bytes
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut buf = vec![0u8; self.encoded_len()];
+        self.encode(&mut Writer::new(&mut buf))?;
+        Ok(buf)
+    }
+
+    /// Encoded size of the option, including code and length bytes
+    pub fn encoded_len(&self) -> usize {
+        let data_len = match self {
+            Self::SubnetMask(_)
+            | Self::RequestedIpAddress(_)
+            | Self::LeaseTime(_)
+            | Self::ServerIdentifier(_)
+            | Self::RenewalTime(_)
+            | Self::RebindingTime(_) => 4,
+            Self::Router(addrs) | Self::DnsServer(addrs) | Self::NtpServers(addrs) => {
+                addrs.len() * 4
+            }
+            Self::MessageType(_) => 1,
+            Self::DomainName(s)
+            | Self::Hostname(s)
+            | Self::VendorClassIdentifier(s)
+            | Self::TftpServerName(s)
+            | Self::BootfileName(s) => s.len(),
+            Self::UserClass(classes) => classes.iter().map(|c| 1 + c.len()).sum(),
+            Self::ClientFqdn { flags, name, .. } => {
+                if flags & fqdn_flags::CANONICAL_ENCODING != 0 {
+                    3 + encoded_dns_name_len(name)
+                } else {
+                    3 + name.len()
+                }
+            }
+            Self::Pad | Self::End => return 1,
+            Self::ParameterRequestList(data)
+            | Self::ClientIdentifier(data)
+            | Self::RelayAgentInfo(data)
+            | Self::Unknown(_, data) => data.len(),
+        };
+        2 + data_len
+    }
+
+    /// Append the encoded option to `w`
+    pub(crate) fn encode(&self, w: &mut Writer<'_>) -> Result<(), EncodeError> {
         match self {
             Self::SubnetMask(addr) => {
-                bytes.push(1);
-                bytes.push(4);
-                bytes.extend_from_slice(&addr.octets());
+                w.push(1)?;
+                w.push(4)?;
+                w.extend(&addr.octets())?;
             }
             Self::Router(addrs) => {
-                bytes.push(3);
-                bytes.push((addrs.len() * 4) as u8);
+                w.push(3)?;
+                w.push(data_len(3, addrs.len() * 4)?)?;
                 for addr in addrs {
-                    bytes.extend_from_slice(&addr.octets());
+                    w.extend(&addr.octets())?;
                 }
             }
             Self::DnsServer(addrs) => {
-                bytes.push(6);
-                bytes.push((addrs.len() * 4) as u8);
+                w.push(6)?;
+                w.push(data_len(6, addrs.len() * 4)?)?;
                 for addr in addrs {
-                    bytes.extend_from_slice(&addr.octets());
+                    w.extend(&addr.octets())?;
                 }
             }
             Self::DomainName(name) => {
-                bytes.push(15);
-                bytes.push(name.len() as u8);
-                bytes.extend_from_slice(name.as_bytes());
+                w.push(15)?;
+                w.push(data_len(15, name.len())?)?;
+                w.extend(name.as_bytes())?;
+            }
+            Self::NtpServers(addrs) => {
+                w.push(42)?;
+                w.push(data_len(42, addrs.len() * 4)?)?;
+                for addr in addrs {
+                    w.extend(&addr.octets())?;
+                }
             }
             Self::RequestedIpAddress(addr) => {
-                bytes.push(50);
-                bytes.push(4);
-                bytes.extend_from_slice(&addr.octets());
+                w.push(50)?;
+                w.push(4)?;
+                w.extend(&addr.octets())?;
             }
             Self::LeaseTime(time) => {
-                bytes.push(51);
-                bytes.push(4);
-                bytes.extend_from_slice(&time.to_be_bytes());
+                w.push(51)?;
+                w.push(4)?;
+                w.extend(&time.to_be_bytes())?;
             }
             Self::MessageType(mt) => {
-                bytes.push(53);
-                bytes.push(1);
-                bytes.push(mt.to_u8());
+                w.push(53)?;
+                w.push(1)?;
+                w.push(mt.to_u8())?;
             }
             Self::ServerIdentifier(addr) => {
-                bytes.push(54);
-                bytes.push(4);
-                bytes.extend_from_slice(&addr.octets());
+                w.push(54)?;
+                w.push(4)?;
+                w.extend(&addr.octets())?;
+            }
+            Self::ParameterRequestList(codes) => {
+                w.push(55)?;
+                w.push(data_len(55, codes.len())?)?;
+                w.extend(codes)?;
             }
             Self::RenewalTime(time) => {
-                bytes.push(58);
-                bytes.push(4);
-                bytes.extend_from_slice(&time.to_be_bytes());
+                w.push(58)?;
+                w.push(4)?;
+                w.extend(&time.to_be_bytes())?;
             }
             Self::RebindingTime(time) => {
-                bytes.push(59);
-                bytes.push(4);
-                bytes.extend_from_slice(&time.to_be_bytes());
+                w.push(59)?;
+                w.push(4)?;
+                w.extend(&time.to_be_bytes())?;
             }
             Self::Hostname(name) => {
-                bytes.push(12);
-                bytes.push(name.len() as u8);
-                bytes.extend_from_slice(name.as_bytes());
+                w.push(12)?;
+                w.push(data_len(12, name.len())?)?;
+                w.extend(name.as_bytes())?;
+            }
+            Self::VendorClassIdentifier(class) => {
+                w.push(60)?;
+                w.push(data_len(60, class.len())?)?;
+                w.extend(class.as_bytes())?;
+            }
+            Self::TftpServerName(name) => {
+                w.push(66)?;
+                w.push(data_len(66, name.len())?)?;
+                w.extend(name.as_bytes())?;
+            }
+            Self::BootfileName(name) => {
+                w.push(67)?;
+                w.push(data_len(67, name.len())?)?;
+                w.extend(name.as_bytes())?;
+            }
+            Self::ClientIdentifier(id) => {
+                w.push(61)?;
+                w.push(data_len(61, id.len())?)?;
+                w.extend(id)?;
+            }
+            Self::UserClass(classes) => {
+                w.push(77)?;
+                w.push(data_len(77, classes.iter().map(|c| 1 + c.len()).sum::<usize>())?)?;
+                // Each instance is shorter than the whole option, checked above
+                for class in classes {
+                    w.push(class.len() as u8)?;
+                    w.extend(class)?;
+                }
             }
-            Self::End => {}
+            Self::ClientFqdn { flags, rcode, name } => {
+                let canonical = flags & fqdn_flags::CANONICAL_ENCODING != 0;
+                let name_len = if canonical {
+                    encoded_dns_name_len(name)
+                } else {
+                    name.len()
+                };
+                w.push(81)?;
+                w.push(data_len(81, name_len + 3)?)?;
+                w.push(*flags)?;
+                w.extend(rcode)?;
+                if canonical {
+                    encode_dns_name(81, name, w)?;
+                } else {
+                    w.extend(name.as_bytes())?;
+                }
+            }
+            Self::RelayAgentInfo(data) => {
+                w.push(82)?;
+                w.push(data_len(82, data.len())?)?;
+                w.extend(data)?;
+            }
+            Self::Pad => w.push(0)?,
+            Self::End => w.push(255)?,
             Self::Unknown(code, data) => {
-                bytes.push(*code);
-                bytes.push(data.len() as u8);
-                bytes.extend_from_slice(data);
+                w.push(*code)?;
+                w.push(data_len(*code, data.len())?)?;
+                w.extend(data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `addrs` as a comma-separated list
+fn write_addrs(f: &mut fmt::Formatter<'_>, addrs: &[Ipv4Addr]) -> fmt::Result {
+    for (i, addr) in addrs.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+        write!(f, "{}", addr)?;
+    }
+    Ok(())
+}
+
+/// Human-readable `name(code)=value` form, used for debug dumps
+impl fmt::Display for DhcpOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.name(), self.code())?;
+        if matches!(self, Self::Pad | Self::End) {
+            return Ok(());
+        }
+        write!(f, "={}", self.value())
+    }
+}
+
+/// Value of an option without its name and code, see [`DhcpOption::value`]
+pub struct OptionValue<'a>(&'a DhcpOption);
+
+impl fmt::Display for OptionValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            DhcpOption::SubnetMask(addr)
+            | DhcpOption::RequestedIpAddress(addr)
+            | DhcpOption::ServerIdentifier(addr) => write!(f, "{}", addr),
+            DhcpOption::Router(addrs)
+            | DhcpOption::DnsServer(addrs)
+            | DhcpOption::NtpServers(addrs) => write_addrs(f, addrs),
+            DhcpOption::LeaseTime(secs)
+            | DhcpOption::RenewalTime(secs)
+            | DhcpOption::RebindingTime(secs) => write!(f, "{}s", secs),
+            DhcpOption::MessageType(mt) => write!(f, "{:?}", mt),
+            DhcpOption::ParameterRequestList(codes) => {
+                for (i, code) in codes.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", code)?;
+                }
+                Ok(())
+            }
+            DhcpOption::DomainName(s)
+            | DhcpOption::Hostname(s)
+            | DhcpOption::VendorClassIdentifier(s)
+            | DhcpOption::TftpServerName(s)
+            | DhcpOption::BootfileName(s) => write!(f, "{:?}", s),
+            DhcpOption::UserClass(classes) => {
+                for (i, class) in classes.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{:?}", String::from_utf8_lossy(class))?;
+                }
+                Ok(())
+            }
+            DhcpOption::ClientFqdn { flags, name, .. } => {
+                write!(f, "{:?} flags={:#04x}", name, flags)
             }
+            DhcpOption::ClientIdentifier(data)
+            | DhcpOption::RelayAgentInfo(data)
+            | DhcpOption::Unknown(_, data) => {
+                for byte in data {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            DhcpOption::Pad | DhcpOption::End => Ok(()),
         }
-        bytes
     }
 }
 
@@ -170,7 +564,7 @@ mod tests {
     #[test]
     fn test_option_round_trip() {
         let original = DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0));
-        let bytes = original.to_bytes();
+        let bytes = original.to_bytes().unwrap();
         // bytes should be [1, 4, 255, 255, 255, 0]
         assert_eq!(bytes[0], 1); // code
         assert_eq!(bytes[1], 4); // length
@@ -184,4 +578,201 @@ mod tests {
         let opt = DhcpOption::parse(12, data);
         assert_eq!(opt, DhcpOption::Hostname("test-host".to_string()));
     }
+
+    #[test]
+    fn test_pxe_options_round_trip() {
+        for original in [
+            DhcpOption::VendorClassIdentifier("PXEClient:Arch:00000".to_string()),
+            DhcpOption::TftpServerName("tftp.lan".to_string()),
+            DhcpOption::BootfileName("undionly.kpxe".to_string()),
+        ] {
+            let bytes = original.to_bytes().unwrap();
+            assert_eq!(bytes[1] as usize, bytes.len() - 2);
+            assert_eq!(DhcpOption::parse(bytes[0], &bytes[2..]), original);
+        }
+    }
+
+    #[test]
+    fn test_ntp_servers_round_trip() {
+        let original = DhcpOption::NtpServers(vec![
+            Ipv4Addr::new(192, 168, 1, 123),
+            Ipv4Addr::new(10, 0, 0, 123),
+        ]);
+        let bytes = original.to_bytes().unwrap();
+        assert_eq!(bytes, [42, 8, 192, 168, 1, 123, 10, 0, 0, 123]);
+        assert_eq!(DhcpOption::parse(bytes[0], &bytes[2..]), original);
+        assert_eq!(original.to_string(), "ntp_servers(42)=192.168.1.123,10.0.0.123");
+    }
+
+    #[test]
+    fn test_parameter_request_list_round_trip() {
+        let original = DhcpOption::parse(55, &[1, 3, 6, 15]);
+        assert_eq!(original, DhcpOption::ParameterRequestList(vec![1, 3, 6, 15]));
+        let bytes = original.to_bytes().unwrap();
+        assert_eq!(bytes, [55, 4, 1, 3, 6, 15]);
+        assert_eq!(DhcpOption::parse(bytes[0], &bytes[2..]), original);
+        assert_eq!(original.to_string(), "parameter_request_list(55)=1,3,6,15");
+    }
+
+    #[test]
+    fn test_client_identifier_round_trip() {
+        // Hardware type 1 (Ethernet) followed by the MAC
+        let data = [1, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+        let original = DhcpOption::parse(61, &data);
+        assert_eq!(original, DhcpOption::ClientIdentifier(data.to_vec()));
+        let bytes = original.to_bytes().unwrap();
+        assert_eq!(&bytes[..2], &[61, 7]);
+        assert_eq!(DhcpOption::parse(bytes[0], &bytes[2..]), original);
+        assert_eq!(original.to_string(), "client_id(61)=01aabbccddeeff");
+
+        // Too short to hold a type and an identifier
+        assert_eq!(DhcpOption::parse(61, &[1]), DhcpOption::Unknown(61, vec![1]));
+    }
+
+    #[test]
+    fn test_user_class_parse_and_round_trip() {
+        let original = DhcpOption::UserClass(vec![b"iot".to_vec(), b"lab".to_vec()]);
+        let bytes = original.to_bytes().unwrap();
+        assert_eq!(bytes, b"\x4d\x08\x03iot\x03lab");
+        assert_eq!(DhcpOption::parse(bytes[0], &bytes[2..]), original);
+
+        // A bare string without instance lengths is one class
+        assert_eq!(
+            DhcpOption::parse(77, b"voip"),
+            DhcpOption::UserClass(vec![b"voip".to_vec()])
+        );
+    }
+
+    #[test]
+    fn test_client_fqdn_ascii_round_trip() {
+        let original = DhcpOption::ClientFqdn {
+            flags: fqdn_flags::SERVER_UPDATE,
+            rcode: fqdn_rcode::SERVER,
+            name: "laptop.example.com".to_string(),
+        };
+        let bytes = original.to_bytes().unwrap();
+        assert_eq!(&bytes[..5], &[81, 21, 0x01, 255, 255]);
+        assert_eq!(&bytes[5..], b"laptop.example.com");
+        assert_eq!(DhcpOption::parse(bytes[0], &bytes[2..]), original);
+
+        // A client's zero RCODEs are kept as sent
+        let original = DhcpOption::ClientFqdn {
+            flags: fqdn_flags::SERVER_UPDATE,
+            rcode: fqdn_rcode::CLIENT,
+            name: "laptop".to_string(),
+        };
+        let bytes = original.to_bytes().unwrap();
+        assert_eq!(&bytes[..5], &[81, 9, 0x01, 0, 0]);
+        assert_eq!(DhcpOption::parse(bytes[0], &bytes[2..]), original);
+    }
+
+    #[test]
+    fn test_client_fqdn_canonical_round_trip() {
+        let original = DhcpOption::ClientFqdn {
+            flags: fqdn_flags::SERVER_UPDATE | fqdn_flags::CANONICAL_ENCODING,
+            rcode: fqdn_rcode::SERVER,
+            name: "laptop.example.com.".to_string(),
+        };
+        let bytes = original.to_bytes().unwrap();
+        assert_eq!(
+            &bytes[5..],
+            b"\x06laptop\x07example\x03com\x00"
+        );
+        assert_eq!(DhcpOption::parse(bytes[0], &bytes[2..]), original);
+    }
+
+    #[test]
+    fn test_client_fqdn_canonical_label_too_long() {
+        let fqdn = |label: &str| DhcpOption::ClientFqdn {
+            flags: fqdn_flags::CANONICAL_ENCODING,
+            rcode: fqdn_rcode::SERVER,
+            name: format!("{}.example.com.", label),
+        };
+        assert!(fqdn(&"a".repeat(63)).to_bytes().is_ok());
+        assert_eq!(
+            fqdn(&"a".repeat(64)).to_bytes(),
+            Err(EncodeError::LabelTooLong { code: 81, len: 64 })
+        );
+    }
+
+    #[test]
+    fn test_client_fqdn_canonical_partial_name() {
+        // No root label: a partial name the server may qualify
+        let data = [fqdn_flags::CANONICAL_ENCODING, 0, 0, 6, b'l', b'a', b'p', b't', b'o', b'p'];
+        assert_eq!(
+            DhcpOption::parse(81, &data),
+            DhcpOption::ClientFqdn {
+                flags: fqdn_flags::CANONICAL_ENCODING,
+                rcode: fqdn_rcode::CLIENT,
+                name: "laptop".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_relay_agent_info_round_trip() {
+        // Circuit ID "eth0" followed by Remote ID 0x0102
+        let data = [1, 4, b'e', b't', b'h', b'0', 2, 2, 0x01, 0x02];
+        let opt = DhcpOption::parse(82, &data);
+        assert_eq!(opt, DhcpOption::RelayAgentInfo(data.to_vec()));
+        let bytes = opt.to_bytes().unwrap();
+        assert_eq!(&bytes[..2], &[82, 10]);
+        assert_eq!(&bytes[2..], &data);
+    }
+
+    #[test]
+    fn test_option_longer_than_255_bytes_is_refused() {
+        // 63 addresses fit in 252 bytes, 64 need 256
+        let addrs = vec![Ipv4Addr::new(10, 0, 0, 1); 64];
+        assert_eq!(
+            DhcpOption::DnsServer(addrs[..63].to_vec()).to_bytes().unwrap()[1],
+            252
+        );
+        assert_eq!(
+            DhcpOption::DnsServer(addrs).to_bytes(),
+            Err(EncodeError::OptionTooLong { code: 6, len: 256 })
+        );
+        assert_eq!(
+            DhcpOption::Hostname("a".repeat(300)).to_bytes(),
+            Err(EncodeError::OptionTooLong { code: 12, len: 300 })
+        );
+        let fqdn = DhcpOption::ClientFqdn {
+            flags: 0,
+            rcode: fqdn_rcode::SERVER,
+            name: "a".repeat(253),
+        };
+        assert_eq!(
+            fqdn.to_bytes(),
+            Err(EncodeError::OptionTooLong { code: 81, len: 256 })
+        );
+    }
+
+    #[test]
+    fn test_option_display() {
+        let router = DhcpOption::Router(vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]);
+        assert_eq!(router.to_string(), "router(3)=10.0.0.1,10.0.0.2");
+        assert_eq!(DhcpOption::LeaseTime(3600).to_string(), "lease_time(51)=3600s");
+        assert_eq!(DhcpOption::Unknown(224, vec![0xde, 0xad]).to_string(), "unknown(224)=dead");
+        assert_eq!(DhcpOption::Pad.to_string(), "pad(0)");
+    }
+
+    #[test]
+    fn test_code_matches_encoding() {
+        for option in [
+            DhcpOption::DnsServer(vec![Ipv4Addr::new(8, 8, 8, 8)]),
+            DhcpOption::MessageType(MessageType::Ack),
+            DhcpOption::ClientFqdn {
+                flags: 0,
+                rcode: fqdn_rcode::SERVER,
+                name: "laptop".to_string(),
+            },
+            DhcpOption::Unknown(224, vec![0xde, 0xad]),
+            DhcpOption::End,
+        ] {
+            assert_eq!(option.to_bytes().unwrap()[0], option.code(), "{}", option);
+        }
+        let dns = DhcpOption::DnsServer(vec![Ipv4Addr::new(8, 8, 8, 8)]);
+        assert_eq!(dns.name(), "dns_server");
+        assert_eq!(dns.value().to_string(), "8.8.8.8");
+    }
 }
@@ -1,10 +1,16 @@
-use crate::{DhcpOption, MacAddress, MessageType};
-use std::net::Ipv4Addr;
+use crate::decode::ByteCursor;
+use crate::encode::{EncodeError, Writer};
+use crate::{DhcpOption, MacAddress, MessageType, OpCode};
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::net::Ipv4Addr;
 
 /// DHCP packet structure as defined in RFC 2131
 #[derive(Debug, Clone)]
 pub struct DhcpPacket {
-    pub op: u8,             // Message op code / message type
+    pub op: u8,             // Message op code, see op_code()
     pub htype: u8,          // Hardware address type
     pub hlen: u8,           // Hardware address length
     pub hops: u8,           // Client sets to zero
@@ -17,16 +23,21 @@ pub struct DhcpPacket {
     pub giaddr: Ipv4Addr,   // Gateway IP address
     pub chaddr: MacAddress, // Client hardware address
     pub options: Vec<DhcpOption>,
+    pub options_truncated: bool, // Parsing stopped at the option count cap
 }
 
 /// DHCP magic cookie (RFC 2131)
 const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
 
+/// Number of options [`DhcpPacket::parse`] keeps before giving up on the rest.
+/// Legitimate clients send a few dozen at most.
+pub const DEFAULT_MAX_OPTIONS: usize = 256;
+
 impl DhcpPacket {
     /// Create a new empty DHCP packet
     pub fn new() -> Self {
         Self {
-            op: 1,
+            op: OpCode::BootRequest.to_u8(),
             htype: 1,
             hlen: 6,
             hops: 0,
@@ -39,62 +50,95 @@ impl DhcpPacket {
             giaddr: Ipv4Addr::new(0, 0, 0, 0),
             chaddr: MacAddress::new([0; 6]),
             options: Vec::new(),
+            options_truncated: false,
         }
     }
 
+    /// The op code, or `None` for a value other than request or reply
+    pub fn op_code(&self) -> Option<OpCode> {
+        OpCode::from_u8(self.op)
+    }
+
+    /// Set the op code
+    pub fn set_op_code(&mut self, op: OpCode) {
+        self.op = op.to_u8();
+    }
+
+    /// Whether the packet was forwarded by a relay agent (non-zero `giaddr`)
+    pub fn is_relayed(&self) -> bool {
+        !self.giaddr.is_unspecified()
+    }
+
     /// Parse a DHCP packet from raw bytes
     pub fn parse(data: &[u8]) -> Result<Self, String> {
+        Self::parse_with_limit(data, DEFAULT_MAX_OPTIONS)
+    }
+
+    /// Parse a DHCP packet, keeping at most `max_options` options.
+    ///
+    /// Options past the cap are skipped and `options_truncated` is set, which
+    /// bounds the memory a crafted packet full of tiny options can claim.
+    /// Pad options are kept but not counted: a client padding its options to
+    /// a word boundary must not lose real ones to the cap.
+    pub fn parse_with_limit(data: &[u8], max_options: usize) -> Result<Self, String> {
         if data.len() < 240 {
             return Err("Packet too small".to_string());
         }
 
-        let xid = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
-        let secs = u16::from_be_bytes([data[8], data[9]]);
-        let flags = u16::from_be_bytes([data[10], data[11]]);
-
-        let ciaddr = Ipv4Addr::new(data[12], data[13], data[14], data[15]);
-        let yiaddr = Ipv4Addr::new(data[16], data[17], data[18], data[19]);
-        let siaddr = Ipv4Addr::new(data[20], data[21], data[22], data[23]);
-        let giaddr = Ipv4Addr::new(data[24], data[25], data[26], data[27]);
-
-        let chaddr = MacAddress::from_slice(&data[28..34]).ok_or("Invalid MAC address")?;
-
-        // Parse options (starting at byte 236 after magic cookie)
+        let mut cursor = ByteCursor::new(data);
+        let op = cursor.read_u8()?;
+        let htype = cursor.read_u8()?;
+        let hlen = cursor.read_u8()?;
+        let hops = cursor.read_u8()?;
+        let xid = cursor.read_u32()?;
+        let secs = cursor.read_u16()?;
+        let flags = cursor.read_u16()?;
+
+        let ciaddr = cursor.read_ipv4()?;
+        let yiaddr = cursor.read_ipv4()?;
+        let siaddr = cursor.read_ipv4()?;
+        let giaddr = cursor.read_ipv4()?;
+
+        // chaddr (16 bytes, of which the MAC is the first 6), sname (64) and file (128)
+        let chaddr = MacAddress::from_slice(cursor.read_bytes(16)?).ok_or("Invalid MAC address")?;
+        cursor.read_bytes(64 + 128)?;
+
+        // Parse options (starting at byte 240, after the magic cookie)
         let mut options = Vec::new();
-        if data.len() > 240 && &data[236..240] == DHCP_MAGIC_COOKIE {
-            let mut i = 240;
-            while i < data.len() {
-                let option_code = data[i];
+        let mut options_truncated = false;
+        let mut counted = 0;
+        if cursor.read_bytes(4)? == DHCP_MAGIC_COOKIE {
+            while let Ok(option_code) = cursor.read_u8() {
                 if option_code == 255 {
                     options.push(DhcpOption::End);
                     break;
                 }
                 if option_code == 0 {
-                    i += 1;
+                    options.push(DhcpOption::Pad);
                     continue;
                 }
-
-                if i + 1 >= data.len() {
+                if counted >= max_options {
+                    options_truncated = true;
                     break;
                 }
+                counted += 1;
 
-                let option_len = data[i + 1] as usize;
-                if i + 2 + option_len > data.len() {
+                // A truncated trailing option is dropped, not an error
+                let Ok(option_len) = cursor.read_u8() else {
                     break;
-                }
-
-                let option_data = &data[i + 2..i + 2 + option_len];
+                };
+                let Ok(option_data) = cursor.read_bytes(option_len as usize) else {
+                    break;
+                };
                 options.push(DhcpOption::parse(option_code, option_data));
-
-                i += 2 + option_len;
             }
         }
 
         Ok(Self {
-            op: data[0],
-            htype: data[1],
-            hlen: data[2],
-            hops: data[3],
+            op,
+            htype,
+            hlen,
+            hops,
             xid,
             secs,
             flags,
@@ -104,41 +148,69 @@ impl DhcpPacket {
             giaddr,
             chaddr,
             options,
+            options_truncated,
         })
     }
 
     /// Serialize the packet to bytes
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![0u8; 240];
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let mut buf = vec![0u8; self.encoded_len()];
+        let len = self.write_to(&mut buf)?;
+        buf.truncate(len);
+        Ok(buf)
+    }
 
-        bytes[0] = self.op;
-        bytes[1] = self.htype;
-        bytes[2] = self.hlen;
-        bytes[3] = self.hops;
+    /// Number of bytes [`write_to`](Self::write_to) needs for this packet
+    pub fn encoded_len(&self) -> usize {
+        // Fixed header + magic cookie, each option, End unless already present
+        let options: usize = self.options.iter().map(DhcpOption::encoded_len).sum();
+        240 + options + usize::from(!self.has_end())
+    }
+
+    /// Whether the options already carry an explicit End, as parsed ones do
+    fn has_end(&self) -> bool {
+        self.options.contains(&DhcpOption::End)
+    }
 
-        bytes[4..8].copy_from_slice(&self.xid.to_be_bytes());
-        bytes[8..10].copy_from_slice(&self.secs.to_be_bytes());
-        bytes[10..12].copy_from_slice(&self.flags.to_be_bytes());
+    /// Serialize the packet into `buf`, returning the number of bytes written.
+    ///
+    /// Lets a server reuse one scratch buffer for every response instead of
+    /// allocating per packet.
+    pub fn write_to(&self, buf: &mut [u8]) -> Result<usize, EncodeError> {
+        let mut w = Writer::new(buf);
 
-        bytes[12..16].copy_from_slice(&self.ciaddr.octets());
-        bytes[16..20].copy_from_slice(&self.yiaddr.octets());
-        bytes[20..24].copy_from_slice(&self.siaddr.octets());
-        bytes[24..28].copy_from_slice(&self.giaddr.octets());
+        w.push(self.op)?;
+        w.push(self.htype)?;
+        w.push(self.hlen)?;
+        w.push(self.hops)?;
 
-        bytes[28..34].copy_from_slice(self.chaddr.as_bytes());
+        w.extend(&self.xid.to_be_bytes())?;
+        w.extend(&self.secs.to_be_bytes())?;
+        w.extend(&self.flags.to_be_bytes())?;
+
+        w.extend(&self.ciaddr.octets())?;
+        w.extend(&self.yiaddr.octets())?;
+        w.extend(&self.siaddr.octets())?;
+        w.extend(&self.giaddr.octets())?;
+
+        // chaddr (16 bytes), sname (64) and file (128)
+        w.extend(self.chaddr.as_bytes())?;
+        w.zeroes(236 - 34)?;
 
         // Magic cookie
-        bytes[236..240].copy_from_slice(&DHCP_MAGIC_COOKIE);
+        w.extend(&DHCP_MAGIC_COOKIE)?;
 
         // Add options
         for option in &self.options {
-            bytes.extend_from_slice(&option.to_bytes());
+            option.encode(&mut w)?;
         }
 
-        // End option
-        bytes.push(255);
+        // End option, unless the options carry their own
+        if !self.has_end() {
+            w.push(255)?;
+        }
 
-        bytes
+        Ok(w.position())
     }
 
     /// Get the message type from the options
@@ -150,6 +222,76 @@ impl DhcpPacket {
         }
         None
     }
+
+    /// Describe the decoded options for logging.
+    ///
+    /// Nothing is formatted until the returned value is displayed, so passing
+    /// it to a disabled log statement costs no allocation.
+    pub fn describe(&self) -> OptionsSummary<'_> {
+        OptionsSummary(&self.options)
+    }
+
+    /// Get the classes of the User Class option (77); empty when absent
+    pub fn get_user_classes(&self) -> &[Vec<u8>] {
+        self.options
+            .iter()
+            .find_map(|option| {
+                if let DhcpOption::UserClass(classes) = option {
+                    Some(classes.as_slice())
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the Client FQDN option (81) as *(flags, name)*, if present
+    pub fn get_client_fqdn(&self) -> Option<(u8, &str)> {
+        self.options.iter().find_map(|option| {
+            if let DhcpOption::ClientFqdn { flags, name, .. } = option {
+                Some((*flags, name.as_str()))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Get the option codes of the Parameter Request List (55), if present
+    pub fn get_requested_parameters(&self) -> Option<&[u8]> {
+        self.options.iter().find_map(|option| {
+            if let DhcpOption::ParameterRequestList(codes) = option {
+                Some(codes.as_slice())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Get the Client Identifier option (61), type byte included, if present
+    pub fn get_client_identifier(&self) -> Option<&[u8]> {
+        self.options.iter().find_map(|option| {
+            if let DhcpOption::ClientIdentifier(id) = option {
+                Some(id.as_slice())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Lazily formatted summary of a packet's options, see [`DhcpPacket::describe`]
+pub struct OptionsSummary<'a>(&'a [DhcpOption]);
+
+impl fmt::Display for OptionsSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, option) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", option)?;
+        }
+        Ok(())
+    }
 }
 
 impl Default for DhcpPacket {
@@ -165,7 +307,7 @@ mod tests {
     #[test]
     fn test_packet_creation() {
         let packet = DhcpPacket::new();
-        assert_eq!(packet.op, 1);
+        assert_eq!(packet.op_code(), Some(OpCode::BootRequest));
         assert_eq!(packet.htype, 1);
         assert_eq!(packet.hlen, 6);
     }
@@ -176,6 +318,51 @@ mod tests {
         assert!(DhcpPacket::parse(&data).is_err());
     }
 
+    #[test]
+    fn test_is_relayed() {
+        let mut packet = DhcpPacket::new();
+        assert!(!packet.is_relayed());
+        packet.giaddr = Ipv4Addr::new(10, 0, 0, 1);
+        assert!(packet.is_relayed());
+    }
+
+    #[test]
+    fn test_option_count_is_capped() {
+        let mut data = DhcpPacket::new().to_bytes().unwrap();
+        data.truncate(240);
+        // Thousands of empty hostname options
+        for _ in 0..5000 {
+            data.extend_from_slice(&[12, 0]);
+        }
+        data.push(255);
+
+        let packet = DhcpPacket::parse(&data).unwrap();
+        assert_eq!(packet.options.len(), DEFAULT_MAX_OPTIONS);
+        assert!(packet.options_truncated);
+
+        let packet = DhcpPacket::parse_with_limit(&data, 10).unwrap();
+        assert_eq!(packet.options.len(), 10);
+
+        let normal = DhcpPacket::parse(&DhcpPacket::new().to_bytes().unwrap()).unwrap();
+        assert!(!normal.options_truncated);
+    }
+
+    #[test]
+    fn test_pad_does_not_count_toward_option_cap() {
+        let mut data = DhcpPacket::new().to_bytes().unwrap();
+        data.truncate(240);
+        // Every option padded to a four-byte boundary
+        for _ in 0..10 {
+            data.extend_from_slice(&[12, 1, b'a', 0]);
+        }
+        data.push(255);
+
+        let packet = DhcpPacket::parse_with_limit(&data, 10).unwrap();
+        assert!(!packet.options_truncated);
+        assert_eq!(packet.options.len(), 10 + 10 + 1);
+        assert_eq!(packet.to_bytes().unwrap(), data);
+    }
+
     #[test]
     fn test_packet_round_trip() {
         let mut packet = DhcpPacket::new();
@@ -185,7 +372,7 @@ mod tests {
             .options
             .push(DhcpOption::MessageType(MessageType::Discover));
 
-        let bytes = packet.to_bytes();
+        let bytes = packet.to_bytes().unwrap();
         let parsed = DhcpPacket::parse(&bytes).unwrap();
 
         assert_eq!(parsed.xid, 0x12345678);
@@ -196,6 +383,37 @@ mod tests {
         assert_eq!(parsed.get_message_type(), Some(MessageType::Discover));
     }
 
+    #[test]
+    fn test_captured_discover_round_trips_byte_exact() {
+        // DISCOVER from a capture: client identifier, requested address,
+        // parameter request list, then Pad before End
+        let mut data = vec![
+            0x01, 0x01, 0x06, 0x00, // op, htype, hlen, hops
+            0x00, 0x00, 0x3d, 0x1d, // xid
+            0x00, 0x00, 0x00, 0x00, // secs, flags
+        ];
+        data.extend_from_slice(&[0; 16]); // ciaddr, yiaddr, siaddr, giaddr
+        data.extend_from_slice(&[0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42]);
+        data.extend_from_slice(&[0; 10 + 64 + 128]);
+        data.extend_from_slice(&DHCP_MAGIC_COOKIE);
+        data.extend_from_slice(&[
+            53, 1, 1, // DISCOVER
+            61, 7, 0x01, 0x00, 0x0b, 0x82, 0x01, 0xfc, 0x42, // client identifier
+            50, 4, 0, 0, 0, 0, // requested address
+            55, 4, 1, 3, 6, 42, // parameter request list
+            0, 0, // Pad
+            255,
+        ]);
+
+        let packet = DhcpPacket::parse(&data).unwrap();
+        assert_eq!(
+            &packet.options[packet.options.len() - 3..],
+            &[DhcpOption::Pad, DhcpOption::Pad, DhcpOption::End]
+        );
+        assert_eq!(packet.encoded_len(), data.len());
+        assert_eq!(packet.to_bytes().unwrap(), data);
+    }
+
     #[test]
     fn test_get_message_type() {
         let mut packet = DhcpPacket::new();
@@ -206,4 +424,56 @@ mod tests {
             .push(DhcpOption::MessageType(MessageType::Request));
         assert_eq!(packet.get_message_type(), Some(MessageType::Request));
     }
+
+    #[test]
+    fn test_describe() {
+        let mut packet = DhcpPacket::new();
+        packet.options.push(DhcpOption::MessageType(MessageType::Offer));
+        packet
+            .options
+            .push(DhcpOption::SubnetMask(Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(
+            packet.describe().to_string(),
+            "message_type(53)=Offer subnet_mask(1)=255.255.255.0"
+        );
+    }
+
+    #[test]
+    fn test_write_to_matches_to_bytes() {
+        let mut packet = DhcpPacket::new();
+        packet.xid = 0xdeadbeef;
+        packet.options.push(DhcpOption::MessageType(MessageType::Ack));
+        packet.options.push(DhcpOption::LeaseTime(3600));
+
+        let mut buf = [0xffu8; 1500];
+        let len = packet.write_to(&mut buf).unwrap();
+        assert_eq!(len, packet.encoded_len());
+        assert_eq!(&buf[..len], packet.to_bytes().unwrap().as_slice());
+        // Unused header fields are zeroed even in a dirty buffer
+        assert!(buf[34..236].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_write_to_buffer_too_small() {
+        let mut packet = DhcpPacket::new();
+        packet.options.push(DhcpOption::MessageType(MessageType::Offer));
+
+        let mut buf = [0u8; 243];
+        assert_eq!(
+            packet.write_to(&mut buf),
+            Err(EncodeError::BufferTooSmall { capacity: 243 })
+        );
+    }
+
+    #[test]
+    fn test_write_to_refuses_oversized_option() {
+        let mut packet = DhcpPacket::new();
+        packet.options.push(DhcpOption::DomainName("a".repeat(256)));
+
+        let mut buf = [0u8; 1500];
+        assert_eq!(
+            packet.write_to(&mut buf),
+            Err(EncodeError::OptionTooLong { code: 15, len: 256 })
+        );
+    }
 }
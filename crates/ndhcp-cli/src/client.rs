@@ -17,14 +17,29 @@ impl std::fmt::Display for AlreadyExistsError {
 
 impl std::error::Error for AlreadyExistsError {}
 
+/// Error returned when the server responds with 401 Unauthorized, carrying
+/// the server's explanation.
+#[derive(Debug)]
+pub struct UnauthorizedError(pub String);
+
+impl std::fmt::Display for UnauthorizedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "authentication failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for UnauthorizedError {}
+
 pub enum ApiClient {
     Unix {
         client: Client<UnixConnector, Full<Bytes>>,
         socket_path: String,
+        token: Option<String>,
     },
     Http {
         client: Client<hyper_util::client::legacy::connect::HttpConnector, Full<Bytes>>,
         base_url: String,
+        token: Option<String>,
     },
 }
 
@@ -34,6 +49,7 @@ impl ApiClient {
         Self::Unix {
             client,
             socket_path: socket_path.to_string(),
+            token: None,
         }
     }
 
@@ -42,6 +58,23 @@ impl ApiClient {
         Self::Http {
             client,
             base_url: base_url.to_string(),
+            token: None,
+        }
+    }
+
+    /// Send `token` as a bearer token with every request
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        match &mut self {
+            Self::Unix { token: t, .. } | Self::Http { token: t, .. } => *t = token,
+        }
+        self
+    }
+
+    fn request_builder(&self) -> hyper::http::request::Builder {
+        let (Self::Unix { token, .. } | Self::Http { token, .. }) = self;
+        match token {
+            Some(token) => Request::builder().header("Authorization", format!("Bearer {}", token)),
+            None => Request::builder(),
         }
     }
 
@@ -54,7 +87,7 @@ impl ApiClient {
 
     pub async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T> {
         let uri = self.build_uri(path);
-        let req = Request::builder()
+        let req = self.request_builder()
             .method("GET")
             .uri(uri)
             .body(Full::default())?;
@@ -66,6 +99,9 @@ impl ApiClient {
 
         let status = response.status();
         let body = response.into_body().collect().await?.to_bytes();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(UnauthorizedError(String::from_utf8_lossy(&body).to_string()).into());
+        }
         if status != StatusCode::OK {
             let body_str = String::from_utf8_lossy(&body);
             anyhow::bail!("Request failed with status {}: {}", status, body_str);
@@ -83,7 +119,7 @@ impl ApiClient {
         let uri = self.build_uri(path);
         let body_bytes = serde_json::to_vec(body)?;
 
-        let req = Request::builder()
+        let req = self.request_builder()
             .method("POST")
             .uri(uri)
             .header("content-type", "application/json")
@@ -117,7 +153,7 @@ impl ApiClient {
         let uri = self.build_uri(path);
         let body_bytes = serde_json::to_vec(body)?;
 
-        let req = Request::builder()
+        let req = self.request_builder()
             .method("PUT")
             .uri(uri)
             .header("content-type", "application/json")
@@ -147,7 +183,7 @@ impl ApiClient {
         let uri = self.build_uri(path);
         let body_bytes = serde_json::to_vec(body)?;
 
-        let req = Request::builder()
+        let req = self.request_builder()
             .method("PATCH")
             .uri(uri)
             .header("content-type", "application/json")
@@ -175,7 +211,7 @@ impl ApiClient {
 
     pub async fn delete(&self, path: &str) -> Result<()> {
         let uri = self.build_uri(path);
-        let req = Request::builder()
+        let req = self.request_builder()
             .method("DELETE")
             .uri(uri)
             .body(Full::default())?;
@@ -195,9 +231,33 @@ impl ApiClient {
         Ok(())
     }
 
+    /// DELETE `path` and decode the JSON body of the response
+    pub async fn delete_json<R: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<R> {
+        let uri = self.build_uri(path);
+        let req = self.request_builder()
+            .method("DELETE")
+            .uri(uri)
+            .body(Full::default())?;
+
+        let response = match self {
+            Self::Unix { client, .. } => client.request(req).await?,
+            Self::Http { client, .. } => client.request(req).await?,
+        };
+
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        if !status.is_success() {
+            let body_str = String::from_utf8_lossy(&body);
+            anyhow::bail!("Request failed with status {}: {}", status, body_str);
+        }
+
+        let data = serde_json::from_slice(&body)?;
+        Ok(data)
+    }
+
     pub async fn health(&self) -> Result<String> {
         let uri = self.build_uri("/health");
-        let req = Request::builder()
+        let req = self.request_builder()
             .method("GET")
             .uri(uri)
             .body(Full::default())?;
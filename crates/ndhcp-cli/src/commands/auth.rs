@@ -0,0 +1,34 @@
+use crate::client::{ApiClient, UnauthorizedError};
+use crate::AuthCommands;
+use anyhow::Result;
+use ndhcpd::handlers::tokens::WhoAmIResponse;
+
+pub async fn handle(client: ApiClient, action: AuthCommands) -> Result<()> {
+    match action {
+        AuthCommands::Test => test(client).await,
+    }
+}
+
+async fn test(client: ApiClient) -> Result<()> {
+    let identity: WhoAmIResponse = match client.get("/api/auth/whoami").await {
+        Ok(identity) => identity,
+        Err(e) => match e.downcast::<UnauthorizedError>() {
+            Ok(UnauthorizedError(reason)) => anyhow::bail!("Token rejected: {}", reason),
+            Err(e) => return Err(e),
+        },
+    };
+
+    if identity.authenticated {
+        println!(
+            "Token accepted: {} (ID {})",
+            identity.token_name.as_deref().unwrap_or("-"),
+            identity.token_id.unwrap_or(0)
+        );
+    } else {
+        println!(
+            "No token checked: authentication is disabled or the request used the Unix socket"
+        );
+    }
+
+    Ok(())
+}
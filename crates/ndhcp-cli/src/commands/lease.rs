@@ -1,41 +1,180 @@
 use crate::client::ApiClient;
-use anyhow::Result;
+use crate::LeaseCommands;
+use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use ndhcpd::handlers::leases::ImportLeasesResponse;
 use ndhcpd::models::Lease;
+use ndhcpd::utils::isc_leases;
+use std::io::Write;
+use std::net::Ipv4Addr;
+use std::time::Duration;
 
-pub async fn list(client: ApiClient) -> Result<()> {
+pub async fn handle(client: ApiClient, action: LeaseCommands) -> Result<()> {
+    match action {
+        LeaseCommands::List => list(client).await,
+        LeaseCommands::Get { ip } => get(client, ip).await,
+        LeaseCommands::Import { file } => import(client, &file).await,
+    }
+}
+
+fn format_timestamp(ts: i64) -> String {
+    DateTime::<Utc>::from_timestamp(ts, 0)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Human-readable span of `secs` seconds, keeping the two most significant
+/// units: `45s`, `5m 30s`, `23h 59m`, `7d 0h`
+fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, hours, minutes, seconds) =
+        (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60, secs % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Time left until `lease_end`, or `expired`
+fn format_remaining(lease_end: i64, now: i64) -> String {
+    if lease_end <= now {
+        "expired".to_string()
+    } else {
+        format_duration(lease_end - now)
+    }
+}
+
+async fn list(client: ApiClient) -> Result<()> {
     let leases: Vec<Lease> = client.get("/api/leases").await?;
+    print_leases(&leases);
+    Ok(())
+}
+
+/// Redraw the lease list every `interval` until Ctrl-C. Each refresh clears
+/// the screen, so the table follows terminal resizes; a failed poll is shown
+/// in place of the table and retried on the next tick.
+pub async fn watch(client: ApiClient, interval: Duration) -> Result<()> {
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        let leases = client.get::<Vec<Lease>>("/api/leases").await;
 
+        // Clear the screen and move the cursor home
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "Every {}s: ndhcp-cli leases    {}    (Ctrl-C to quit)\n",
+            interval.as_secs(),
+            Utc::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        match leases {
+            Ok(leases) => print_leases(&leases),
+            Err(e) => println!("Failed to fetch leases: {:#}", e),
+        }
+        std::io::stdout().flush()?;
+
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+
+    println!();
+    Ok(())
+}
+
+fn print_leases(leases: &[Lease]) {
     if leases.is_empty() {
         println!("No active leases");
     } else {
         println!(
-            "{:<5} {:<12} {:<20} {:<18} {:<20} {:<20} {:<20}",
-            "ID", "Subnet ID", "MAC Address", "IP Address", "Hostname", "Start", "End"
+            "{:<5} {:<12} {:<20} {:<18} {:<20} {:<20} {:<20} {:<10} {:<10}",
+            "ID",
+            "Subnet ID",
+            "MAC Address",
+            "IP Address",
+            "Hostname",
+            "Start",
+            "End",
+            "Duration",
+            "Remaining"
         );
-        println!("{}", "-".repeat(120));
+        println!("{}", "-".repeat(142));
 
+        let now = Utc::now().timestamp();
         for lease in leases {
-            let start = DateTime::<Utc>::from_timestamp(lease.lease_start, 0)
-                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-                .unwrap_or_else(|| "-".to_string());
-
-            let end = DateTime::<Utc>::from_timestamp(lease.lease_end, 0)
-                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
-                .unwrap_or_else(|| "-".to_string());
+            let start = format_timestamp(lease.lease_start);
+            let end = format_timestamp(lease.lease_end);
 
             println!(
-                "{:<5} {:<12} {:<20} {:<18} {:<20} {:<20} {:<20}",
+                "{:<5} {:<12} {:<20} {:<18} {:<20} {:<20} {:<20} {:<10} {:<10}",
                 lease.id.unwrap_or(0),
                 lease.subnet_id,
                 lease.mac_address,
                 lease.ip_address,
                 lease.hostname.as_deref().unwrap_or("-"),
                 start,
-                end
+                end,
+                format_duration(lease.lease_end - lease.lease_start),
+                format_remaining(lease.lease_end, now)
             );
         }
     }
+}
+
+async fn get(client: ApiClient, ip: Ipv4Addr) -> Result<()> {
+    let lease: Lease = client.get(&format!("/api/leases/by-ip/{}", ip)).await?;
+
+    println!("IP Address: {}", lease.ip_address);
+    println!("MAC Address: {}", lease.mac_address);
+    println!("Hostname: {}", lease.hostname.as_deref().unwrap_or("-"));
+    println!("Subnet ID: {}", lease.subnet_id);
+    println!("Start: {}", format_timestamp(lease.lease_start));
+    println!("End: {}", format_timestamp(lease.lease_end));
+    println!("Duration: {}", format_duration(lease.lease_end - lease.lease_start));
+    println!("Remaining: {}", format_remaining(lease.lease_end, Utc::now().timestamp()));
 
     Ok(())
 }
+
+async fn import(client: ApiClient, file: &str) -> Result<()> {
+    let contents =
+        std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?;
+    let leases = isc_leases::parse(&contents).with_context(|| format!("Failed to parse {}", file))?;
+    if leases.is_empty() {
+        println!("No active leases found in {}", file);
+        return Ok(());
+    }
+
+    let result: ImportLeasesResponse = client.post("/api/leases/import", &leases).await?;
+    println!(
+        "Imported {} lease(s), skipped {}",
+        result.imported, result.skipped
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(45), "45s");
+        assert_eq!(format_duration(330), "5m 30s");
+        assert_eq!(format_duration(86399), "23h 59m");
+        assert_eq!(format_duration(86400), "1d 0h");
+        assert_eq!(format_duration(30 * 86400 + 7200), "30d 2h");
+        assert_eq!(format_duration(-5), "0s");
+
+        assert_eq!(format_remaining(1_000, 2_000), "expired");
+        assert_eq!(format_remaining(5_600, 2_000), "1h 0m");
+    }
+}
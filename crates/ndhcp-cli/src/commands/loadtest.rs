@@ -0,0 +1,163 @@
+use crate::dhcp_client::{Backoff, DhcpClient};
+use anyhow::Result;
+use dhcp_proto::{MacAddress, MessageType};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+
+pub struct LoadTestOptions {
+    pub server: SocketAddr,
+    pub relay: Ipv4Addr,
+    pub clients: u32,
+    pub rate: Option<u32>,
+    pub duration: Duration,
+    pub timeout: Duration,
+    pub backoff: Backoff,
+}
+
+#[derive(Default)]
+struct Stats {
+    exchanges: AtomicU64,
+    offers: AtomicU64,
+    acks: AtomicU64,
+    naks: AtomicU64,
+    timeouts: AtomicU64,
+    errors: AtomicU64,
+    /// DISCOVER to ACK time of every successful exchange
+    latencies: Mutex<Vec<Duration>>,
+}
+
+/// Run `clients` simulated clients, each looping over full DORA exchanges
+/// (releasing the address afterwards) until the duration is over.
+pub async fn run(options: LoadTestOptions) -> Result<()> {
+    let client = DhcpClient::bind(options.relay, options.server).await?;
+    let client = Arc::new(client.with_backoff(options.backoff));
+    let stats = Arc::new(Stats::default());
+    let ticker = options.rate.filter(|rate| *rate > 0).map(|rate| {
+        let mut interval = tokio::time::interval(Duration::from_secs(1) / rate);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Arc::new(tokio::sync::Mutex::new(interval))
+    });
+
+    println!(
+        "Running {} clients against {} for {}s (relay {})",
+        options.clients,
+        options.server,
+        options.duration.as_secs(),
+        options.relay
+    );
+    let started = Instant::now();
+    let deadline = started + options.duration;
+    let tasks: Vec<_> = (0..options.clients)
+        .map(|index| {
+            let client = Arc::clone(&client);
+            let stats = Arc::clone(&stats);
+            let ticker = ticker.clone();
+            let timeout = options.timeout;
+            tokio::spawn(async move {
+                let mac = simulated_mac(index);
+                let mut seq: u32 = 0;
+                while Instant::now() < deadline {
+                    if let Some(ticker) = &ticker {
+                        ticker.lock().await.tick().await;
+                    }
+                    let xid = (index << 16) | (seq & 0xffff);
+                    seq = seq.wrapping_add(1);
+                    if let Err(e) = dora(&client, &mac, xid, timeout, &stats).await {
+                        stats.errors.fetch_add(1, Ordering::Relaxed);
+                        eprintln!("Client {}: {}", mac, e);
+                        tokio::time::sleep(timeout).await;
+                    }
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await?;
+    }
+
+    print_summary(&stats, started.elapsed());
+    Ok(())
+}
+
+/// One DISCOVER / OFFER / REQUEST / ACK exchange, followed by a RELEASE so
+/// the pool does not run dry
+async fn dora(
+    client: &DhcpClient,
+    mac: &MacAddress,
+    xid: u32,
+    timeout: Duration,
+    stats: &Stats,
+) -> Result<()> {
+    stats.exchanges.fetch_add(1, Ordering::Relaxed);
+    let started = Instant::now();
+
+    let discover = client.discover(mac, xid);
+    let offer = client.exchange(&discover, &[MessageType::Offer], timeout).await?;
+    let Some(offer) = offer else {
+        stats.timeouts.fetch_add(1, Ordering::Relaxed);
+        return Ok(());
+    };
+    stats.offers.fetch_add(1, Ordering::Relaxed);
+
+    let request = client.request(mac, &offer);
+    let reply = client
+        .exchange(&request, &[MessageType::Ack, MessageType::Nak], timeout)
+        .await?;
+    match reply.as_ref().and_then(|r| r.get_message_type()) {
+        Some(MessageType::Ack) => {
+            stats.latencies.lock().unwrap().push(started.elapsed());
+            stats.acks.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(MessageType::Nak) => {
+            stats.naks.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        _ => {
+            stats.timeouts.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+    }
+    if let Some(ack) = reply {
+        client.send(&client.release(mac, &ack)).await?;
+    }
+    Ok(())
+}
+
+/// Locally administered MAC unique to simulated client `index`
+fn simulated_mac(index: u32) -> MacAddress {
+    let [a, b, c, d] = index.to_be_bytes();
+    MacAddress::new([0x02, 0x00, a, b, c, d])
+}
+
+/// Value below which `fraction` of the sorted `samples` fall
+fn percentile(samples: &[Duration], fraction: f64) -> Duration {
+    if samples.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((samples.len() - 1) as f64 * fraction).round() as usize;
+    samples[rank]
+}
+
+fn print_summary(stats: &Stats, elapsed: Duration) {
+    let mut latencies = stats.latencies.lock().unwrap().clone();
+    latencies.sort_unstable();
+    let acks = stats.acks.load(Ordering::Relaxed);
+
+    println!();
+    println!("Elapsed:    {:.1}s", elapsed.as_secs_f64());
+    println!("Exchanges:  {}", stats.exchanges.load(Ordering::Relaxed));
+    println!("Offers:     {}", stats.offers.load(Ordering::Relaxed));
+    println!("Acks:       {}", acks);
+    println!("Naks:       {}", stats.naks.load(Ordering::Relaxed));
+    println!("Timeouts:   {}", stats.timeouts.load(Ordering::Relaxed));
+    println!("Errors:     {}", stats.errors.load(Ordering::Relaxed));
+    println!("Throughput: {:.1} acks/s", acks as f64 / elapsed.as_secs_f64());
+    println!(
+        "Latency:    p50 {:.2}ms, p99 {:.2}ms",
+        percentile(&latencies, 0.50).as_secs_f64() * 1000.0,
+        percentile(&latencies, 0.99).as_secs_f64() * 1000.0
+    );
+}
@@ -3,3 +3,5 @@ pub mod range;
 pub mod static_ip;
 pub mod lease;
 pub mod ip6subnet;
+pub mod auth;
+pub mod loadtest;
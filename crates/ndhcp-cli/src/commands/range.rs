@@ -13,6 +13,8 @@ pub async fn handle(client: ApiClient, action: RangeCommands) -> Result<()> {
             end,
         } => create(client, subnet_id, start, end).await,
         RangeCommands::Delete { id } => delete(client, id).await,
+        RangeCommands::Toggle { id } => toggle(client, id).await,
+        RangeCommands::Drain { id, stop } => drain(client, id, !stop).await,
     }
 }
 
@@ -29,19 +31,20 @@ async fn list(client: ApiClient, subnet_id: Option<i64>) -> Result<()> {
         println!("No dynamic ranges configured");
     } else {
         println!(
-            "{:<5} {:<12} {:<18} {:<18} {:<8}",
-            "ID", "Subnet ID", "Start", "End", "Enabled"
+            "{:<5} {:<12} {:<18} {:<18} {:<8} {:<8}",
+            "ID", "Subnet ID", "Start", "End", "Enabled", "Draining"
         );
-        println!("{}", "-".repeat(70));
+        println!("{}", "-".repeat(79));
 
         for range in ranges {
             println!(
-                "{:<5} {:<12} {:<18} {:<18} {:<8}",
+                "{:<5} {:<12} {:<18} {:<18} {:<8} {:<8}",
                 range.id.unwrap_or(0),
                 range.subnet_id,
                 range.range_start,
                 range.range_end,
-                range.enabled
+                range.enabled,
+                range.draining
             );
         }
     }
@@ -59,6 +62,7 @@ async fn create(client: ApiClient, subnet_id: i64, start: String, end: String) -
         range_start: start_ip,
         range_end: end_ip,
         enabled: true,
+        draining: false,
     };
 
     let id: i64 = client
@@ -73,6 +77,30 @@ async fn create(client: ApiClient, subnet_id: i64, start: String, end: String) -
     Ok(())
 }
 
+async fn toggle(client: ApiClient, id: i64) -> Result<()> {
+    let range: DynamicRange = client
+        .patch(&format!("/api/ranges/{}/toggle", id), &serde_json::json!({}))
+        .await?;
+    let state = if range.enabled { "enabled" } else { "disabled" };
+    println!("Range {} ({}-{}) is now {}", id, range.range_start, range.range_end, state);
+    Ok(())
+}
+
+async fn drain(client: ApiClient, id: i64, draining: bool) -> Result<()> {
+    let range: DynamicRange = client
+        .patch(
+            &format!("/api/ranges/{}/draining", id),
+            &serde_json::json!({ "draining": draining }),
+        )
+        .await?;
+    if range.draining {
+        println!("Range {} ({}-{}) is draining", id, range.range_start, range.range_end);
+    } else {
+        println!("Range {} ({}-{}) is allocating again", id, range.range_start, range.range_end);
+    }
+    Ok(())
+}
+
 async fn delete(client: ApiClient, id: i64) -> Result<()> {
     client.delete(&format!("/api/ranges/{}", id)).await?;
     println!("Deleted range {}", id);
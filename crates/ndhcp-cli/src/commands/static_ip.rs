@@ -1,7 +1,9 @@
 use crate::client::{AlreadyExistsError, ApiClient};
 use crate::StaticCommands;
 use anyhow::Result;
+use ndhcpd::handlers::static_ips::DeleteStaticIpsResponse;
 use ndhcpd::models::StaticIP;
+use std::io::{BufRead, Write};
 use std::net::Ipv4Addr;
 
 pub async fn handle(client: ApiClient, action: StaticCommands) -> Result<()> {
@@ -14,6 +16,7 @@ pub async fn handle(client: ApiClient, action: StaticCommands) -> Result<()> {
             hostname,
         } => create(client, subnet_id, mac, ip, hostname).await,
         StaticCommands::Delete { ip } => delete(client, ip).await,
+        StaticCommands::DeleteAll { subnet_id, yes } => delete_all(client, subnet_id, yes).await,
         StaticCommands::SetHostname { ip, hostname } => set_hostname(client, ip, hostname).await,
     }
 }
@@ -88,6 +91,25 @@ async fn delete(client: ApiClient, ip: String) -> Result<()> {
     Ok(())
 }
 
+async fn delete_all(client: ApiClient, subnet_id: i64, yes: bool) -> Result<()> {
+    if !yes {
+        print!("Delete all static IPs of subnet {}? [y/N] ", subnet_id);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Aborted");
+            return Ok(());
+        }
+    }
+
+    let response: DeleteStaticIpsResponse = client
+        .delete_json(&format!("/api/static-ips?subnet_id={}", subnet_id))
+        .await?;
+    println!("Deleted {} static IP(s) from subnet {}", response.deleted, subnet_id);
+    Ok(())
+}
+
 async fn set_hostname(client: ApiClient, ip: String, hostname: Option<String>) -> Result<()> {
     use serde_json::json;
     client
@@ -1,20 +1,59 @@
 use crate::client::{AlreadyExistsError, ApiClient};
 use crate::SubnetCommands;
 use anyhow::Result;
-use ndhcpd::models::Subnet;
+use ndhcpd::handlers::subnets::ResolvedOption;
+use ndhcpd::models::{PrefixLen, Subnet};
 use std::net::Ipv4Addr;
 
 pub async fn handle(client: ApiClient, action: SubnetCommands) -> Result<()> {
     match action {
         SubnetCommands::List => list(client).await,
         SubnetCommands::Create {
+            cidr,
             network,
             netmask,
             gateway,
             dns_servers,
+            ntp_servers,
             domain_name,
-        } => create(client, network, netmask, gateway, dns_servers, domain_name).await,
+            authoritative,
+            lease_time,
+            dry_run,
+        } => {
+            let (network, netmask) = match cidr {
+                Some(cidr) => {
+                    let subnet = Subnet::from_cidr(&cidr)
+                        .map_err(|e| anyhow::anyhow!("Invalid CIDR {}: {}", cidr, e))?;
+                    (subnet.network.to_string(), subnet.netmask.get())
+                }
+                // clap requires both when --cidr is absent
+                None => (network.unwrap_or_default(), netmask.unwrap_or_default()),
+            };
+            let subnet = build(
+                network,
+                netmask,
+                gateway,
+                dns_servers,
+                ntp_servers,
+                domain_name,
+                authoritative,
+                lease_time,
+            )?;
+            if dry_run {
+                preview(&subnet);
+                Ok(())
+            } else {
+                create(client, subnet).await
+            }
+        }
         SubnetCommands::Get { id } => get(client, id).await,
+        SubnetCommands::Options { id } => options(client, id).await,
+        SubnetCommands::Clone {
+            id,
+            network,
+            gateway,
+            with_ranges,
+        } => clone(client, id, network, gateway, with_ranges).await,
         SubnetCommands::Delete { id } => delete(client, id).await,
     }
 }
@@ -54,14 +93,18 @@ async fn list(client: ApiClient) -> Result<()> {
     Ok(())
 }
 
-async fn create(
-    client: ApiClient,
+#[allow(clippy::too_many_arguments)]
+fn build(
     network: String,
     netmask: u8,
     gateway: String,
     dns_servers: String,
+    ntp_servers: Option<String>,
     domain_name: Option<String>,
-) -> Result<()> {
+    authoritative: bool,
+    lease_time: Option<u32>,
+) -> Result<Subnet> {
+    let netmask = PrefixLen::new(netmask)?;
     let network_ip: Ipv4Addr = network.parse()?;
     let gateway_ip: Ipv4Addr = gateway.parse()?;
 
@@ -69,16 +112,53 @@ async fn create(
         .split(',')
         .map(|s| s.trim().parse())
         .collect::<Result<Vec<_>, _>>()?;
+    let ntp_ips: Vec<Ipv4Addr> = ntp_servers
+        .as_deref()
+        .map(|servers| servers.split(',').map(|s| s.trim().parse()).collect())
+        .transpose()?
+        .unwrap_or_default();
 
-    let subnet = Subnet {
+    Ok(Subnet {
         id: None,
         network: network_ip,
         netmask,
         gateway: gateway_ip,
         dns_servers: dns_ips,
+        ntp_servers: ntp_ips,
         domain_name,
-    };
+        authoritative,
+        default_lease_time: lease_time,
+        template_id: None,
+    })
+}
+
+/// Show what `create` would send, computed locally
+fn preview(subnet: &Subnet) {
+    let (first, last) = subnet.usable_range();
+
+    println!(
+        "Would create subnet {}/{}",
+        subnet.network_address(),
+        subnet.netmask
+    );
+    if subnet.network != subnet.network_address() {
+        println!(
+            "  Note: {} has host bits set; the network address is {}",
+            subnet.network,
+            subnet.network_address()
+        );
+    }
+    println!("  Broadcast: {}", subnet.broadcast());
+    println!("  Usable range: {} - {}", first, last);
+    println!("  Hosts: {}", subnet.host_count());
+    println!("  Gateway: {}", subnet.gateway);
+    if !subnet.contains(subnet.gateway) {
+        println!("  Warning: gateway is outside the subnet");
+    }
+}
 
+async fn create(client: ApiClient, subnet: Subnet) -> Result<()> {
+    let (network_ip, netmask) = (subnet.network, subnet.netmask);
     let id: i64 = client
         .post("/api/subnets", &subnet)
         .await
@@ -106,9 +186,67 @@ async fn get(client: ApiClient, id: i64) -> Result<()> {
             .collect::<Vec<_>>()
             .join(", ")
     );
+    if !subnet.ntp_servers.is_empty() {
+        println!(
+            "NTP Servers: {}",
+            subnet
+                .ntp_servers
+                .iter()
+                .map(|ip| ip.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
     if let Some(domain) = &subnet.domain_name {
         println!("Domain: {}", domain);
     }
+    println!("Authoritative: {}", subnet.authoritative);
+    if let Some(lease_time) = subnet.default_lease_time {
+        println!("Lease time: {}s", lease_time);
+    }
+
+    Ok(())
+}
+
+async fn options(client: ApiClient, id: i64) -> Result<()> {
+    let options: Vec<ResolvedOption> =
+        client.get(&format!("/api/subnets/{}/options", id)).await?;
+
+    println!("{:<6} {:<18} Value", "Code", "Option");
+    println!("{}", "-".repeat(50));
+    for option in options {
+        println!("{:<6} {:<18} {}", option.code, option.name, option.value);
+    }
+
+    Ok(())
+}
+
+async fn clone(
+    client: ApiClient,
+    id: i64,
+    network: Ipv4Addr,
+    gateway: Ipv4Addr,
+    with_ranges: bool,
+) -> Result<()> {
+    let body = serde_json::json!({
+        "network": network,
+        "gateway": gateway,
+        "include_ranges": with_ranges,
+    });
+    let subnet: Subnet = client
+        .post(&format!("/api/subnets/{}/clone", id), &body)
+        .await
+        .map_err(|e| match e.downcast::<AlreadyExistsError>() {
+            Ok(_) => anyhow::anyhow!("{} overlaps an existing subnet", network),
+            Err(e) => e,
+        })?;
+    println!(
+        "Cloned subnet {} into {}/{} with ID: {}",
+        id,
+        subnet.network,
+        subnet.netmask,
+        subnet.id.unwrap_or(0)
+    );
 
     Ok(())
 }
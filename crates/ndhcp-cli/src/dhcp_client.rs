@@ -0,0 +1,290 @@
+//! Minimal DHCP client talking to a server the way a relay agent does.
+//!
+//! Replies to relayed requests are unicast to the relay address on port 67,
+//! so the client binds that port and stamps its own address into `giaddr`.
+//! This needs root and a host other than the server's, but lets many
+//! simulated clients share one socket: replies are routed back to the
+//! request that caused them by transaction ID and message type, so a late
+//! OFFER cannot stand in for the ACK of the same exchange. Unanswered
+//! requests are retransmitted on an exponential [`Backoff`] schedule, like
+//! real clients.
+
+use anyhow::Result;
+use dhcp_proto::{DhcpOption, DhcpPacket, MacAddress, MessageType, OpCode};
+use rand::{Rng, RngExt};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Port relay agents receive server replies on
+const RELAY_PORT: u16 = 67;
+
+/// Waiters by transaction ID, with the message types they accept
+type Pending = Arc<Mutex<HashMap<u32, Waiter>>>;
+type Waiter = (&'static [MessageType], oneshot::Sender<DhcpPacket>);
+
+/// Retransmission schedule of RFC 2131 §4.1: the first retransmission
+/// after `initial`, each following one after twice the previous delay up to
+/// `max`, every delay randomized by up to `jitter` either way so clients
+/// started together do not stay synchronized
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub jitter: Duration,
+}
+
+impl Default for Backoff {
+    /// 2s, 4s, 8s... up to the 64s RFC 2131 suggests, ±1s
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(2),
+            max: Duration::from_secs(64),
+            jitter: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Backoff {
+    /// Time to wait for a reply after transmission `attempt` (0 for the
+    /// first one) before sending again
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.delay_with_rng(attempt, &mut rand::rng())
+    }
+
+    /// [`delay`](Self::delay) drawing the jitter from `rng`; tests pass a
+    /// seeded one
+    pub fn delay_with_rng(&self, attempt: u32, rng: &mut impl Rng) -> Duration {
+        let base = self
+            .initial
+            .saturating_mul(1 << attempt.min(31))
+            .min(self.max);
+        let spread = self.jitter.as_millis() as u64;
+        let offset = Duration::from_millis(rng.random_range(0..=2 * spread));
+        (base + offset).saturating_sub(self.jitter)
+    }
+}
+
+pub struct DhcpClient {
+    socket: Arc<UdpSocket>,
+    server: SocketAddr,
+    relay: Ipv4Addr,
+    backoff: Backoff,
+    pending: Pending,
+    receiver: JoinHandle<()>,
+}
+
+impl DhcpClient {
+    /// Bind `relay`:67 and send requests to `server`
+    pub async fn bind(relay: Ipv4Addr, server: SocketAddr) -> Result<Self> {
+        let socket = UdpSocket::bind(SocketAddr::new(relay.into(), RELAY_PORT))
+            .await
+            .map_err(|e| anyhow::anyhow!("cannot bind {}:{}: {}", relay, RELAY_PORT, e))?;
+        let socket = Arc::new(socket);
+        let pending: Pending = Arc::default();
+
+        let receiver = tokio::spawn({
+            let socket = Arc::clone(&socket);
+            let pending = Arc::clone(&pending);
+            async move {
+                let mut buf = [0u8; 1500];
+                while let Ok((len, _)) = socket.recv_from(&mut buf).await {
+                    let Ok(reply) = DhcpPacket::parse(&buf[..len]) else {
+                        continue;
+                    };
+                    if reply.op_code() != Some(OpCode::BootReply) {
+                        continue;
+                    }
+                    let waiter = take_waiter(&mut pending.lock().unwrap(), &reply);
+                    if let Some(waiter) = waiter {
+                        let _ = waiter.send(reply);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            socket,
+            server,
+            relay,
+            backoff: Backoff::default(),
+            pending,
+            receiver,
+        })
+    }
+
+    /// Retransmit unanswered requests on `backoff` instead of the default
+    /// schedule
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Send `packet` and wait up to `timeout` for a reply with the same
+    /// transaction ID and one of the `expected` message types,
+    /// retransmitting it on the backoff schedule meanwhile. Other replies
+    /// are ignored. `None` when the server stayed silent.
+    pub async fn exchange(
+        &self,
+        packet: &DhcpPacket,
+        expected: &'static [MessageType],
+        timeout: Duration,
+    ) -> Result<Option<DhcpPacket>> {
+        let (tx, mut rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(packet.xid, (expected, tx));
+        let deadline = Instant::now() + timeout;
+        let mut attempt = 0;
+        let reply = loop {
+            if let Err(e) = self.send(packet).await {
+                self.pending.lock().unwrap().remove(&packet.xid);
+                return Err(e);
+            }
+            let wait = self
+                .backoff
+                .delay(attempt)
+                .min(deadline.saturating_duration_since(Instant::now()));
+            match tokio::time::timeout(wait, &mut rx).await {
+                Ok(reply) => break reply.ok(),
+                Err(_) if Instant::now() >= deadline => break None,
+                Err(_) => attempt += 1,
+            }
+        };
+        self.pending.lock().unwrap().remove(&packet.xid);
+        Ok(reply)
+    }
+
+    /// Send `packet` without waiting for a reply
+    pub async fn send(&self, packet: &DhcpPacket) -> Result<()> {
+        self.socket.send_to(&packet.to_bytes()?, self.server).await?;
+        Ok(())
+    }
+
+    /// DISCOVER from `mac`
+    pub fn discover(&self, mac: &MacAddress, xid: u32) -> DhcpPacket {
+        self.request_packet(mac, xid, MessageType::Discover)
+    }
+
+    /// REQUEST (SELECTING state) taking the address of `offer`
+    pub fn request(&self, mac: &MacAddress, offer: &DhcpPacket) -> DhcpPacket {
+        let mut packet = self.request_packet(mac, offer.xid, MessageType::Request);
+        packet.options.push(DhcpOption::RequestedIpAddress(offer.yiaddr));
+        if let Some(server_id) = server_identifier(offer) {
+            packet.options.push(DhcpOption::ServerIdentifier(server_id));
+        }
+        packet
+    }
+
+    /// RELEASE of the address acknowledged by `ack`
+    pub fn release(&self, mac: &MacAddress, ack: &DhcpPacket) -> DhcpPacket {
+        let mut packet = self.request_packet(mac, ack.xid, MessageType::Release);
+        packet.ciaddr = ack.yiaddr;
+        if let Some(server_id) = server_identifier(ack) {
+            packet.options.push(DhcpOption::ServerIdentifier(server_id));
+        }
+        packet
+    }
+
+    fn request_packet(
+        &self,
+        mac: &MacAddress,
+        xid: u32,
+        message_type: MessageType,
+    ) -> DhcpPacket {
+        let mut packet = DhcpPacket::new();
+        packet.set_op_code(OpCode::BootRequest);
+        packet.xid = xid;
+        packet.hops = 1;
+        packet.giaddr = self.relay;
+        packet.chaddr = mac.clone();
+        packet.options.push(DhcpOption::MessageType(message_type));
+        packet
+    }
+}
+
+impl Drop for DhcpClient {
+    fn drop(&mut self) {
+        self.receiver.abort();
+    }
+}
+
+/// Remove and return the waiter `reply` answers, leaving waiters in place
+/// when its message type is not one they expect
+fn take_waiter(
+    pending: &mut HashMap<u32, Waiter>,
+    reply: &DhcpPacket,
+) -> Option<oneshot::Sender<DhcpPacket>> {
+    let (expected, _) = pending.get(&reply.xid)?;
+    let message_type = reply.get_message_type()?;
+    if !expected.contains(&message_type) {
+        return None;
+    }
+    pending.remove(&reply.xid).map(|(_, waiter)| waiter)
+}
+
+fn server_identifier(packet: &DhcpPacket) -> Option<Ipv4Addr> {
+    packet.options.iter().find_map(|opt| match opt {
+        DhcpOption::ServerIdentifier(ip) => Some(*ip),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn reply(xid: u32, message_type: MessageType) -> DhcpPacket {
+        let mut packet = DhcpPacket::new();
+        packet.set_op_code(OpCode::BootReply);
+        packet.xid = xid;
+        packet.options.push(DhcpOption::MessageType(message_type));
+        packet
+    }
+
+    #[test]
+    fn test_late_offer_does_not_answer_request() {
+        let mut pending = HashMap::new();
+        let (tx, _rx) = oneshot::channel();
+        pending.insert(7, (&[MessageType::Ack, MessageType::Nak][..], tx));
+
+        assert!(take_waiter(&mut pending, &reply(7, MessageType::Offer)).is_none());
+        assert!(pending.contains_key(&7));
+        assert!(take_waiter(&mut pending, &reply(8, MessageType::Ack)).is_none());
+        assert!(take_waiter(&mut pending, &reply(7, MessageType::Ack)).is_some());
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let backoff = Backoff {
+            jitter: Duration::ZERO,
+            max: Duration::from_secs(16),
+            ..Backoff::default()
+        };
+        let delays: Vec<u64> = (0..6).map(|a| backoff.delay(a).as_secs()).collect();
+        assert_eq!(delays, [2, 4, 8, 16, 16, 16]);
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(16));
+    }
+
+    #[test]
+    fn test_backoff_jitter_stays_within_bounds() {
+        let backoff = Backoff::default();
+        let mut rng = StdRng::seed_from_u64(7);
+        for attempt in 0..8 {
+            let base = Duration::from_secs(2 << attempt).min(backoff.max);
+            let delays: Vec<Duration> =
+                (0..50).map(|_| backoff.delay_with_rng(attempt, &mut rng)).collect();
+            assert!(delays
+                .iter()
+                .all(|d| *d >= base - backoff.jitter && *d <= base + backoff.jitter));
+            // Randomized, so simultaneous clients drift apart
+            assert!(delays.iter().any(|d| *d != delays[0]));
+        }
+    }
+}
@@ -1,8 +1,12 @@
 mod client;
 mod commands;
+mod dhcp_client;
 
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use dhcp_client::Backoff;
+use std::net::Ipv4Addr;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "ndhcp-cli")]
@@ -12,6 +16,10 @@ struct Cli {
     #[arg(long, short = 'u')]
     api_url: Option<String>,
 
+    /// API token sent as a bearer token (default: $NDHCP_TOKEN)
+    #[arg(long, short = 't')]
+    token: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -38,10 +46,73 @@ enum Commands {
         #[command(subcommand)]
         action: Ip6SubnetCommands,
     },
-    /// View leases
-    Leases,
+    /// View leases (lists active leases when no action is given)
+    #[command(args_conflicts_with_subcommands = true)]
+    Leases {
+        #[command(subcommand)]
+        action: Option<LeaseCommands>,
+
+        /// Keep the list on screen, refreshing it until Ctrl-C
+        #[arg(long)]
+        watch: bool,
+
+        /// Seconds between refreshes in --watch mode
+        #[arg(
+            long,
+            default_value_t = 2,
+            requires = "watch",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        interval: u64,
+    },
+    /// Check API authentication
+    Auth {
+        #[command(subcommand)]
+        action: AuthCommands,
+    },
     /// Check API health
     Health,
+    /// Benchmark a DHCP server with simulated clients relayed through this host
+    Loadtest {
+        /// DHCP server address
+        #[arg(long)]
+        server: Ipv4Addr,
+        /// DHCP server port
+        #[arg(long, default_value_t = 67)]
+        port: u16,
+        /// Local address acting as relay agent (giaddr); it must fall in a
+        /// subnet served by the server. Binds port 67, so needs root.
+        #[arg(long)]
+        relay: Ipv4Addr,
+        /// Number of concurrent simulated clients
+        #[arg(
+            long,
+            default_value_t = 10,
+            value_parser = clap::value_parser!(u32).range(1..=65535)
+        )]
+        clients: u32,
+        /// Maximum exchanges started per second, all clients together (default: unlimited)
+        #[arg(long)]
+        rate: Option<u32>,
+        /// Test duration in seconds
+        #[arg(long, default_value_t = 10)]
+        duration: u64,
+        /// Milliseconds to wait for each reply, retransmitting the request
+        /// meanwhile with exponential backoff (2s, 4s, 8s... ±1s). The
+        /// default leaves room for three retransmissions; below 2000 none
+        /// is ever sent
+        #[arg(long, default_value_t = 30000)]
+        timeout_ms: u64,
+        /// Longest delay between two retransmissions, in milliseconds
+        #[arg(long, default_value_t = 64000)]
+        max_backoff_ms: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthCommands {
+    /// Check whether the server accepts the token and show which one it is
+    Test,
 }
 
 #[derive(Subcommand)]
@@ -50,27 +121,61 @@ enum SubnetCommands {
     List,
     /// Create a new subnet
     Create {
+        /// Network in CIDR notation (e.g., 192.168.1.0/24), instead of --network and --netmask
+        #[arg(long, conflicts_with_all = ["network", "netmask"])]
+        cidr: Option<String>,
         /// Network address (e.g., 192.168.1.0)
-        #[arg(long)]
-        network: String,
+        #[arg(long, required_unless_present = "cidr")]
+        network: Option<String>,
         /// Netmask (e.g., 24)
-        #[arg(long)]
-        netmask: u8,
+        #[arg(long, required_unless_present = "cidr")]
+        netmask: Option<u8>,
         /// Gateway address
         #[arg(long)]
         gateway: String,
         /// DNS servers (comma-separated)
         #[arg(long)]
         dns_servers: String,
+        /// NTP servers (comma-separated, optional)
+        #[arg(long)]
+        ntp_servers: Option<String>,
         /// Domain name (optional)
         #[arg(long)]
         domain_name: Option<String>,
+        /// NAK requests that cannot be granted on this subnet
+        #[arg(long)]
+        authoritative: bool,
+        /// Lease time in seconds, overriding the server default (optional)
+        #[arg(long)]
+        lease_time: Option<u32>,
+        /// Print the derived addresses without creating the subnet
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Get subnet details
     Get {
         /// Subnet ID
         id: i64,
     },
+    /// Show the options clients of a subnet receive, as the server resolves them
+    Options {
+        /// Subnet ID
+        id: i64,
+    },
+    /// Create a copy of a subnet on another network
+    Clone {
+        /// ID of the subnet to copy
+        id: i64,
+        /// Network address of the copy
+        #[arg(long)]
+        network: Ipv4Addr,
+        /// Gateway of the copy
+        #[arg(long)]
+        gateway: Ipv4Addr,
+        /// Also copy the dynamic ranges, at the same host offsets
+        #[arg(long)]
+        with_ranges: bool,
+    },
     /// Delete a subnet
     Delete {
         /// Subnet ID
@@ -103,6 +208,36 @@ enum RangeCommands {
         /// Range ID
         id: i64,
     },
+    /// Enable or disable a range without deleting it
+    Toggle {
+        /// Range ID
+        id: i64,
+    },
+    /// Stop new allocations from a range, letting its leases renew
+    Drain {
+        /// Range ID
+        id: i64,
+        /// Resume allocations from the range instead
+        #[arg(long)]
+        stop: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum LeaseCommands {
+    /// List all active leases
+    List,
+    /// Show the active lease holding an address
+    Get {
+        /// Leased IP address
+        #[arg(long)]
+        ip: Ipv4Addr,
+    },
+    /// Import the active leases of an ISC dhcpd lease file
+    Import {
+        /// Path to the dhcpd.leases file
+        file: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -133,6 +268,15 @@ enum StaticCommands {
         /// Static IP address
         ip: String,
     },
+    /// Delete every static IP assignment of a subnet
+    DeleteAll {
+        /// Subnet ID
+        #[arg(long)]
+        subnet_id: i64,
+        /// Do not ask for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
     /// Update the hostname of a static IP assignment
     SetHostname {
         /// Static IP address
@@ -202,6 +346,8 @@ async fn main() -> Result<()> {
             client::ApiClient::new_unix("/var/run/ndhcpd.sock")
         }
     };
+    let token = cli.token.or_else(|| std::env::var("NDHCP_TOKEN").ok());
+    let client = client.with_token(token);
 
     match cli.command {
         Commands::Subnet { action } => {
@@ -216,13 +362,48 @@ async fn main() -> Result<()> {
         Commands::Ip6Subnet { action } => {
             commands::ip6subnet::handle(client, action).await?;
         }
-        Commands::Leases => {
-            commands::lease::list(client).await?;
+        Commands::Leases {
+            action,
+            watch,
+            interval,
+        } => {
+            if watch {
+                commands::lease::watch(client, Duration::from_secs(interval)).await?;
+            } else {
+                commands::lease::handle(client, action.unwrap_or(LeaseCommands::List)).await?;
+            }
+        }
+        Commands::Auth { action } => {
+            commands::auth::handle(client, action).await?;
         }
         Commands::Health => {
             let result = client.health().await?;
             println!("{}", result);
         }
+        Commands::Loadtest {
+            server,
+            port,
+            relay,
+            clients,
+            rate,
+            duration,
+            timeout_ms,
+            max_backoff_ms,
+        } => {
+            commands::loadtest::run(commands::loadtest::LoadTestOptions {
+                server: (server, port).into(),
+                relay,
+                clients,
+                rate,
+                duration: Duration::from_secs(duration),
+                timeout: Duration::from_millis(timeout_ms),
+                backoff: Backoff {
+                    max: Duration::from_millis(max_backoff_ms),
+                    ..Backoff::default()
+                },
+            })
+            .await?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,3 @@
+-- Per-subnet authoritative mode: NAK unservable requests instead of staying silent.
+
+ALTER TABLE subnets ADD COLUMN authoritative INTEGER NOT NULL DEFAULT 0;
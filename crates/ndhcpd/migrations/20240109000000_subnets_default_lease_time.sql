@@ -0,0 +1,3 @@
+-- Per-subnet lease time overriding dhcp.default_lease_time; NULL uses the global value.
+
+ALTER TABLE subnets ADD COLUMN default_lease_time INTEGER;
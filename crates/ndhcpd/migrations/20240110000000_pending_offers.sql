@@ -0,0 +1,12 @@
+-- Addresses offered to a client that has not REQUESTed them yet, held so a
+-- concurrent DISCOVER is not offered the same address.
+
+CREATE TABLE IF NOT EXISTS pending_offers (
+    ip_address TEXT NOT NULL PRIMARY KEY,
+    subnet_id INTEGER NOT NULL,
+    mac_address TEXT NOT NULL,
+    xid INTEGER NOT NULL,
+    offered_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL,
+    FOREIGN KEY (subnet_id) REFERENCES subnets(id) ON DELETE CASCADE
+);
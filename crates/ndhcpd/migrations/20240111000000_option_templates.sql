@@ -0,0 +1,12 @@
+-- Named sets of DHCP options shared by subnets. A subnet's own DNS servers,
+-- domain name and lease time take precedence over its template's.
+
+CREATE TABLE IF NOT EXISTS option_templates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    dns_servers TEXT NOT NULL,
+    domain_name TEXT,
+    default_lease_time INTEGER
+);
+
+ALTER TABLE subnets ADD COLUMN template_id INTEGER REFERENCES option_templates(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Dynamic range each lease was allocated from; NULL for static assignments
+-- and reservations, or once the range is deleted.
+
+ALTER TABLE leases ADD COLUMN range_id INTEGER REFERENCES dynamic_ranges(id) ON DELETE SET NULL;
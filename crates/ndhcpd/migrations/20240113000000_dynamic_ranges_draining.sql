@@ -0,0 +1,3 @@
+-- Draining ranges hand out no new addresses but keep renewing existing leases
+
+ALTER TABLE dynamic_ranges ADD COLUMN draining INTEGER NOT NULL DEFAULT 0;
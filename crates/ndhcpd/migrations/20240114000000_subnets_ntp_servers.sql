@@ -0,0 +1,3 @@
+-- NTP servers handed out as option 42, comma-separated like dns_servers
+
+ALTER TABLE subnets ADD COLUMN ntp_servers TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,4 @@
+-- Set when the client released the lease (DHCPRELEASE) instead of letting
+-- it run out; only released addresses are held back by the release grace.
+
+ALTER TABLE leases ADD COLUMN released INTEGER NOT NULL DEFAULT 0;
@@ -18,11 +18,18 @@ pub enum ConnectionType {
     Tcp,
 }
 
+/// Token that authenticated the request, stored in request extensions by
+/// [`auth_middleware`]. Absent when authentication was not checked.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedToken {
+    pub id: i64,
+}
+
 /// Middleware to check API authentication
 pub async fn auth_middleware(
     State(db): State<DynDatabase>,
     headers: HeaderMap,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, impl IntoResponse> {
     // Check if this is a Unix socket connection (already set by router)
@@ -36,39 +43,38 @@ pub async fn auth_middleware(
     let auth_header = headers
         .get("Authorization")
         .and_then(|v| v.to_str().ok())
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing Authorization header"))?;
+        .ok_or((StatusCode::UNAUTHORIZED, "Missing Authorization header"))?;
 
     // Extract Bearer token
-    let token = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
-        (
-            StatusCode::UNAUTHORIZED,
-            "Invalid Authorization header format. Expected: Bearer <token>",
-        )
-    })?;
+    let token = auth_header.strip_prefix("Bearer ").ok_or((
+        StatusCode::UNAUTHORIZED,
+        "Invalid Authorization header format. Expected: Bearer <token>",
+    ))?;
 
     // Verify token against database
-    let valid = verify_token_in_db(&db, token).await.map_err(|e| {
+    let id = verify_token_in_db(&db, token).await.map_err(|e| {
         warn!("Token verification error: {}", e);
         (StatusCode::UNAUTHORIZED, "Invalid token")
     })?;
 
-    if !valid {
+    let Some(id) = id else {
         return Err((StatusCode::UNAUTHORIZED, "Invalid or disabled token"));
-    }
+    };
 
+    request.extensions_mut().insert(AuthenticatedToken { id });
     Ok(next.run(request).await)
 }
 
-/// Verify a token exists in the database and is enabled
-async fn verify_token_in_db(db: &DynDatabase, token: &str) -> Result<bool> {
+/// Verify a token exists in the database and is enabled, returning its ID
+async fn verify_token_in_db(db: &DynDatabase, token: &str) -> Result<Option<i64>> {
     let tokens = db.list_tokens().await?;
 
-    for (token_hash, enabled) in tokens {
+    for (id, token_hash, enabled) in tokens {
         if token::verify(token, &token_hash)? {
             let _ = db.update_token_last_used(&token_hash).await;
-            return Ok(enabled == 1);
+            return Ok((enabled == 1).then_some(id));
         }
     }
 
-    Ok(false)
+    Ok(None)
 }
@@ -4,14 +4,18 @@ use argon2::{
     Argon2, PasswordHash, PasswordVerifier,
 };
 use base64::{engine::general_purpose, Engine as _};
-use rand::RngExt;
+use rand::{Rng, RngExt};
 use rand_core::OsRng;
 
 const TOKEN_LENGTH: usize = 32;
 
 /// Generate a new random API token
 pub fn generate() -> String {
-    let mut rng = rand::rng();
+    generate_with_rng(&mut rand::rng())
+}
+
+/// Generate an API token from `rng`; tests pass a seeded one to get a known token
+pub fn generate_with_rng(rng: &mut impl Rng) -> String {
     let token_bytes: Vec<u8> = (0..TOKEN_LENGTH).map(|_| rng.random()).collect();
     general_purpose::STANDARD.encode(&token_bytes)
 }
@@ -62,6 +66,22 @@ mod tests {
         assert_eq!(t.len(), 44);
     }
 
+    #[test]
+    fn test_generate_with_seeded_rng_is_deterministic() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let t1 = generate_with_rng(&mut StdRng::seed_from_u64(42));
+        let t2 = generate_with_rng(&mut StdRng::seed_from_u64(42));
+        assert_eq!(t1, t2);
+        assert_eq!(t1.len(), 44);
+        let decoded = general_purpose::STANDARD.decode(&t1).unwrap();
+        assert_eq!(decoded.len(), TOKEN_LENGTH);
+
+        let t3 = generate_with_rng(&mut StdRng::seed_from_u64(43));
+        assert_ne!(t1, t3);
+    }
+
     #[test]
     fn test_hash_produces_valid_argon2_hash() {
         let (hash_str, salt_str) = hash("mysecrettoken").unwrap();
@@ -1,11 +1,19 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+use std::num::NonZeroUsize;
+use utoipa::ToSchema;
 
 fn default_log_level() -> String {
     "info".to_string()
 }
 
+fn default_log_max_size_mb() -> u64 {
+    100
+}
+
 /// Logging configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct LoggingConfig {
     /// Send logs to syslog in addition to stdout
     #[serde(default)]
@@ -14,6 +22,18 @@ pub struct LoggingConfig {
     /// Minimum log level: error, warn, info, debug, trace (default: info)
     #[serde(default = "default_log_level")]
     pub level: String,
+
+    /// Write logs to this file instead of stdout
+    #[serde(default)]
+    pub file: Option<String>,
+
+    /// When to rotate `file` (default: daily)
+    #[serde(default)]
+    pub rotation: LogRotation,
+
+    /// Size in megabytes at which `file` is rotated with `rotation: size`
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
 }
 
 impl Default for LoggingConfig {
@@ -21,12 +41,29 @@ impl Default for LoggingConfig {
         Self {
             syslog: false,
             level: default_log_level(),
+            file: None,
+            rotation: LogRotation::default(),
+            max_size_mb: default_log_max_size_mb(),
         }
     }
 }
 
+/// Log file rotation policy. The rotated file keeps its name with a
+/// timestamp suffix; old files are left for the system's cleanup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// Start a new file on the first event of each local day
+    #[default]
+    Daily,
+    /// Start a new file once the current one reaches `max_size_mb`
+    Size,
+    /// Keep appending to the same file
+    Never,
+}
+
 /// Configuration structure loaded from YAML
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Config {
     /// Network interfaces the DHCP server should listen on
     pub listen_interfaces: Vec<String>,
@@ -35,6 +72,10 @@ pub struct Config {
     #[serde(default = "default_db_path")]
     pub database_path: String,
 
+    /// Destination file of `POST /api/maintenance/backup` (disabled when unset)
+    #[serde(default)]
+    pub backup_path: Option<String>,
+
     /// API server configuration
     pub api: ApiConfig,
 
@@ -48,6 +89,20 @@ pub struct Config {
     /// Logging configuration
     #[serde(default)]
     pub logging: LoggingConfig,
+
+    /// Data protection configuration
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    /// Subnets declared here and reconciled into the database at startup
+    #[serde(default)]
+    pub subnets: Option<SubnetsConfig>,
+
+    /// Worker threads of the async runtime shared by the API and DHCP
+    /// servers (default: one per CPU core). Zero is rejected.
+    #[serde(default)]
+    #[schema(value_type = Option<usize>, minimum = 1)]
+    pub worker_threads: Option<NonZeroUsize>,
 }
 
 fn default_db_path() -> String {
@@ -57,7 +112,7 @@ fn default_db_path() -> String {
     return "/var/lib/ndhcpd/dhcp.db".to_string();
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ApiConfig {
     /// API listening address
     #[serde(default = "default_api_address")]
@@ -74,6 +129,17 @@ pub struct ApiConfig {
     /// Require token authentication for TCP API (not Unix socket)
     #[serde(default)]
     pub require_authentication: Option<bool>,
+
+    /// Serve the Swagger UI and the OpenAPI spec (only when built with the
+    /// `swagger-ui` feature). Disable on public-facing instances.
+    #[serde(default = "default_enable_swagger")]
+    pub enable_swagger: bool,
+
+    /// Maximum number of requests each listener serves at once; further
+    /// requests wait for a slot. Unlimited when unset; zero is rejected.
+    #[serde(default)]
+    #[schema(value_type = Option<usize>, minimum = 1)]
+    pub max_connections: Option<NonZeroUsize>,
 }
 
 fn default_api_address() -> String {
@@ -88,7 +154,11 @@ fn default_unix_socket() -> Option<String> {
     Some("/var/run/ndhcpd.sock".to_string())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_enable_swagger() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DhcpConfig {
     /// Default lease time in seconds
     #[serde(default = "default_lease_time")]
@@ -101,6 +171,271 @@ pub struct DhcpConfig {
     /// Interval in minutes between automatic expired-lease cleanup runs
     #[serde(default = "default_lease_cleanup_interval")]
     pub lease_cleanup_interval_minutes: u32,
+
+    /// Seconds an expired lease is remembered: a DISCOVER from the same
+    /// client within that window is offered the old address again if it is
+    /// still free. 0 disables it.
+    #[serde(default)]
+    pub lease_memory_secs: u64,
+
+    /// Seconds an address released by its client is held back from other
+    /// clients before it can be allocated again. 0 frees it immediately.
+    #[serde(default)]
+    pub release_grace_seconds: u64,
+
+    /// How a subnet with several dynamic ranges spreads new allocations
+    #[serde(default)]
+    pub multi_range: MultiRangeStrategy,
+
+    /// Maximum number of active leases a single client (chaddr) may hold;
+    /// further allocations are refused. Unlimited when unset.
+    #[serde(default)]
+    pub max_leases_per_client: Option<u32>,
+
+    /// Ping fresh dynamic addresses before offering them and skip those that
+    /// answer. Each probe delays the OFFER by up to the probe timeout.
+    #[serde(default)]
+    pub probe_before_offer: bool,
+
+    /// How long to wait for an answer when probing a candidate address, in milliseconds
+    #[serde(default = "default_probe_timeout_ms")]
+    pub probe_timeout_ms: u64,
+
+    /// Probes sent after the first one went unanswered
+    #[serde(default = "default_probe_retries")]
+    pub probe_retries: u32,
+
+    /// How long subnet lookups are served from memory, in seconds. Subnet
+    /// changes made through the API are visible immediately; 0 disables the cache.
+    #[serde(default = "default_subnet_cache_ttl_secs")]
+    pub subnet_cache_ttl_secs: u64,
+
+    /// Answer legacy BOOTP requests (no option 53) from clients with a static
+    /// assignment. BOOTP has no leases, so dynamic ranges are never used.
+    #[serde(default)]
+    pub enable_bootp: bool,
+
+    /// Only answer DISCOVER and SELECTING/INIT-REBOOT REQUEST once the client
+    /// has been trying for this many seconds (`secs` field), leaving it to a
+    /// primary server until then. Renewals are always answered. 0 answers
+    /// immediately.
+    #[serde(default)]
+    pub answer_after_secs: u16,
+
+    /// Packets that crossed more relay hops than this are dropped (loop protection)
+    #[serde(default = "default_max_hops")]
+    pub max_hops: u8,
+
+    /// Serve clients whose hardware type is not Ethernet but which still use
+    /// 6-byte addresses, such as IEEE 802 (htype 6). Other packets from
+    /// non-Ethernet hardware are dropped, as their chaddr is no MAC address.
+    #[serde(default)]
+    pub allow_non_ethernet: bool,
+
+    /// Copy a relayed request's Relay Agent Information (option 82) unchanged
+    /// into the OFFER and ACK, as RFC 3046 expects of servers. Disable for
+    /// relays that do not strip it before forwarding to the client.
+    #[serde(default = "default_echo_relay_agent_info")]
+    pub echo_relay_agent_info: bool,
+
+    /// NAK relayed REQUESTs whose relay address (giaddr) matches no subnet
+    /// instead of dropping them, so misrouted clients fail fast
+    #[serde(default)]
+    pub nak_unknown_relay: bool,
+
+    /// Options kept per received packet; a packet carrying more is parsed up
+    /// to the cap and counted as suspicious
+    #[serde(default = "default_max_options")]
+    pub max_options: usize,
+
+    /// Subnet IDs served on each listen interface. When set, only the listed
+    /// subnets are offered on an interface instead of whichever subnets
+    /// contain one of its addresses. Checked against the database at startup.
+    #[serde(default)]
+    pub interface_subnets: BTreeMap<String, Vec<i64>>,
+
+    /// Network boot parameters keyed by vendor class identifier (option 60)
+    /// prefix, e.g. `PXEClient` or `iPXE`. The longest matching prefix wins.
+    #[serde(default)]
+    pub pxe: BTreeMap<String, PxeBootConfig>,
+
+    /// Reply overrides keyed by the User Class (option 77) a client declares,
+    /// e.g. `iot`. A client sending several classes gets the first one configured.
+    #[serde(default)]
+    pub user_classes: BTreeMap<String, UserClassPolicy>,
+
+    /// Pin static IPs created through the API into the host's ARP table on
+    /// the listen interface holding their subnet, so replies to those clients
+    /// need no ARP resolution. Entries follow the static IPs when they are
+    /// deleted or renumbered. Linux only; ignored elsewhere.
+    #[serde(default)]
+    pub static_arp: bool,
+
+    /// Serve or ignore clients by hardware vendor (MAC OUI)
+    #[serde(default)]
+    pub vendor_filter: Option<VendorFilter>,
+}
+
+impl Default for DhcpConfig {
+    fn default() -> Self {
+        Self {
+            default_lease_time: default_lease_time(),
+            max_lease_time: default_max_lease_time(),
+            lease_cleanup_interval_minutes: default_lease_cleanup_interval(),
+            lease_memory_secs: 0,
+            release_grace_seconds: 0,
+            multi_range: MultiRangeStrategy::default(),
+            max_leases_per_client: None,
+            probe_before_offer: false,
+            probe_timeout_ms: default_probe_timeout_ms(),
+            probe_retries: default_probe_retries(),
+            subnet_cache_ttl_secs: default_subnet_cache_ttl_secs(),
+            enable_bootp: false,
+            answer_after_secs: 0,
+            max_hops: default_max_hops(),
+            allow_non_ethernet: false,
+            echo_relay_agent_info: default_echo_relay_agent_info(),
+            nak_unknown_relay: false,
+            max_options: default_max_options(),
+            interface_subnets: BTreeMap::new(),
+            pxe: BTreeMap::new(),
+            user_classes: BTreeMap::new(),
+            static_arp: false,
+            vendor_filter: None,
+        }
+    }
+}
+
+/// Boot parameters sent to network-booting clients of one vendor class
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PxeBootConfig {
+    /// Next server (`siaddr`) to fetch the boot file from; defaults to the subnet gateway
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub next_server: Option<Ipv4Addr>,
+
+    /// Boot file name or URL (option 67)
+    pub boot_file: String,
+
+    /// TFTP server name (option 66)
+    #[serde(default)]
+    pub tftp_server_name: Option<String>,
+}
+
+/// Options replacing the subnet's values for clients of one user class
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct UserClassPolicy {
+    /// DNS servers (option 6) sent instead of the subnet's
+    #[serde(default)]
+    #[schema(value_type = Option<Vec<String>>)]
+    pub dns_servers: Option<Vec<Ipv4Addr>>,
+
+    /// Domain name (option 15) sent instead of the subnet's
+    #[serde(default)]
+    pub domain_name: Option<String>,
+
+    /// Lease time in seconds, still capped at `max_lease_time`
+    #[serde(default)]
+    pub lease_time: Option<u32>,
+}
+
+/// Clients to answer or ignore by the vendor part of their hardware address
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VendorFilter {
+    /// Whether `ouis` lists the only vendors served or vendors ignored
+    #[serde(default)]
+    pub mode: VendorFilterMode,
+
+    /// Vendor prefixes as the first three octets of a MAC, e.g. `00:1b:63`
+    #[schema(value_type = Vec<String>)]
+    pub ouis: Vec<Oui>,
+}
+
+/// Range picked for a new allocation when a subnet has several
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiRangeStrategy {
+    /// Fill ranges in order, the next one only once the previous is full
+    #[default]
+    Sequential,
+    /// Allocate from the range with the most free addresses, keeping ranges
+    /// evenly used so that one can be drained for maintenance
+    Balanced,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VendorFilterMode {
+    /// Only clients of a listed vendor are answered
+    #[default]
+    Allow,
+    /// Clients of a listed vendor are ignored
+    Deny,
+}
+
+impl VendorFilter {
+    /// Whether the client with hardware address `mac` may be answered
+    pub fn permits(&self, mac: &[u8; 6]) -> bool {
+        let oui = Oui([mac[0], mac[1], mac[2]]);
+        self.ouis.contains(&oui) == (self.mode == VendorFilterMode::Allow)
+    }
+}
+
+/// Organizationally Unique Identifier: the vendor part of a MAC address,
+/// written `00:1b:63` or `00-1B-63`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Oui(pub [u8; 3]);
+
+impl TryFrom<String> for Oui {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let octets: Vec<u8> = s
+            .split([':', '-'])
+            .map(|octet| match octet.len() {
+                2 => u8::from_str_radix(octet, 16).ok(),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("invalid OUI {:?}, expected e.g. 00:1b:63", s))?;
+        let octets: [u8; 3] = octets
+            .try_into()
+            .map_err(|_| format!("invalid OUI {:?}, expected three octets", s))?;
+        Ok(Self(octets))
+    }
+}
+
+impl From<Oui> for String {
+    fn from(oui: Oui) -> Self {
+        oui.to_string()
+    }
+}
+
+impl std::fmt::Display for Oui {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [a, b, c] = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}", a, b, c)
+    }
+}
+
+impl DhcpConfig {
+    /// Returns the boot parameters for the longest configured prefix of `vendor_class`.
+    pub fn pxe_for_vendor_class(&self, vendor_class: &str) -> Option<&PxeBootConfig> {
+        self.pxe
+            .iter()
+            .filter(|(prefix, _)| vendor_class.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, boot)| boot)
+    }
+
+    /// Returns the policy of the first of `classes` that has one configured.
+    pub fn policy_for_user_classes(&self, classes: &[Vec<u8>]) -> Option<&UserClassPolicy> {
+        classes.iter().find_map(|class| {
+            let class = std::str::from_utf8(class).ok()?;
+            self.user_classes.get(class)
+        })
+    }
 }
 
 fn default_lease_time() -> u32 {
@@ -115,8 +450,32 @@ fn default_lease_cleanup_interval() -> u32 {
     60 // 60 minutes
 }
 
+fn default_probe_timeout_ms() -> u64 {
+    500
+}
+
+fn default_probe_retries() -> u32 {
+    1
+}
+
+fn default_subnet_cache_ttl_secs() -> u64 {
+    30
+}
+
+fn default_max_hops() -> u8 {
+    16
+}
+
+fn default_echo_relay_agent_info() -> bool {
+    true
+}
+
+fn default_max_options() -> usize {
+    dhcp_proto::packet::DEFAULT_MAX_OPTIONS
+}
+
 /// Router Advertisement (IPv6) configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RaConfig {
     /// Whether Router Advertisement is enabled
     #[serde(default)]
@@ -163,6 +522,78 @@ impl Default for RaConfig {
     }
 }
 
+/// Subnets managed from the configuration file. Each declared subnet is
+/// matched to a stored one by network address, then created or updated.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SubnetsConfig {
+    /// What happens to stored subnets that are not declared (default: merge)
+    #[serde(default)]
+    pub reconcile: ReconcileMode,
+
+    /// Declared subnets; their `id` is ignored
+    #[serde(default)]
+    pub networks: Vec<crate::models::Subnet>,
+}
+
+/// How declared subnets are reconciled with the database
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconcileMode {
+    /// Keep subnets created through the API alongside the declared ones
+    #[default]
+    Merge,
+    /// Delete every stored subnet that is not declared
+    Replace,
+}
+
+/// Environment variable holding the PII encryption key; overrides `security.pii_key`.
+pub const PII_KEY_ENV: &str = "NDHCPD_PII_KEY";
+
+/// Data protection configuration
+#[derive(Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct SecurityConfig {
+    /// Encrypt client MAC addresses and hostnames stored in the database
+    #[serde(default)]
+    pub encrypt_pii: bool,
+
+    /// Base64-encoded 256-bit key used when `encrypt_pii` is set.
+    /// Prefer the `NDHCPD_PII_KEY` environment variable over storing it here.
+    #[serde(default, skip_serializing)]
+    pub pii_key: Option<String>,
+}
+
+impl std::fmt::Debug for SecurityConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecurityConfig")
+            .field("encrypt_pii", &self.encrypt_pii)
+            .field("pii_key", &self.pii_key.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl SecurityConfig {
+    /// Resolve the PII encryption key, preferring the environment over the config file.
+    pub fn resolve_pii_key(&self) -> anyhow::Result<[u8; 32]> {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let encoded = std::env::var(PII_KEY_ENV)
+            .ok()
+            .or_else(|| self.pii_key.clone())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "security.encrypt_pii is enabled but no key was provided (set {} or security.pii_key)",
+                    PII_KEY_ENV
+                )
+            })?;
+        let bytes = general_purpose::STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid PII key encoding: {}", e))?;
+        bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("PII key must decode to exactly 32 bytes"))
+    }
+}
+
 impl Config {
     /// Load configuration from a YAML file
     pub fn from_file(path: &str) -> anyhow::Result<Self> {
@@ -179,24 +610,111 @@ impl Config {
     }
 }
 
+impl Config {
+    /// JSON Schema of the configuration file, for editor completion and CI
+    /// validation of YAML configs. Nested types are referenced through
+    /// `#/components/schemas/<Name>` pointers resolved within the document.
+    pub fn json_schema() -> serde_json::Value {
+        use utoipa::PartialSchema;
+
+        let mut components = Vec::new();
+        <Config as ToSchema>::schemas(&mut components);
+        let components: serde_json::Map<String, serde_json::Value> = components
+            .into_iter()
+            .map(|(name, schema)| (name, serde_json::json!(schema)))
+            .collect();
+
+        let mut schema = serde_json::json!(<Config as PartialSchema>::schema());
+        schema["$schema"] = "https://json-schema.org/draft/2020-12/schema".into();
+        schema["title"] = "ndhcpd configuration".into();
+        schema["components"] = serde_json::json!({ "schemas": components });
+        schema
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             listen_interfaces: vec!["eth0".to_string()],
             database_path: default_db_path(),
+            backup_path: None,
             api: ApiConfig {
                 listen_address: default_api_address(),
                 port: default_api_port(),
                 unix_socket: default_unix_socket(),
                 require_authentication: Some(false),
+                enable_swagger: default_enable_swagger(),
+                max_connections: None,
             },
-            dhcp: DhcpConfig {
-                default_lease_time: default_lease_time(),
-                max_lease_time: default_max_lease_time(),
-                lease_cleanup_interval_minutes: default_lease_cleanup_interval(),
-            },
+            dhcp: DhcpConfig::default(),
             ra: None,
             logging: LoggingConfig::default(),
+            security: SecurityConfig::default(),
+            subnets: None,
+            worker_threads: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_refs(value: &serde_json::Value, refs: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(serde_json::Value::String(r)) = map.get("$ref") {
+                    refs.push(r.clone());
+                }
+                map.values().for_each(|v| collect_refs(v, refs));
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
         }
     }
+
+    #[test]
+    fn test_json_schema_is_self_contained() {
+        let schema = Config::json_schema();
+        assert_eq!(schema["required"], serde_json::json!(["listen_interfaces", "api", "dhcp"]));
+        let dhcp = &schema["components"]["schemas"]["DhcpConfig"];
+        assert!(dhcp["properties"]["max_hops"].is_object());
+
+        let mut refs = Vec::new();
+        collect_refs(&schema, &mut refs);
+        assert!(!refs.is_empty());
+        for r in refs {
+            let pointer = r.strip_prefix('#').expect("local reference");
+            assert!(schema.pointer(pointer).is_some(), "dangling {r}");
+        }
+    }
+
+    #[test]
+    fn test_vendor_filter_parses_ouis() {
+        let filter: VendorFilter =
+            serde_yaml::from_str("mode: deny\nouis: [\"00:1b:63\", \"3C-22-FB\"]").unwrap();
+        assert_eq!(filter.mode, VendorFilterMode::Deny);
+        assert_eq!(filter.ouis[1].to_string(), "3c:22:fb");
+        assert!(!filter.permits(&[0x3c, 0x22, 0xfb, 1, 2, 3]));
+        assert!(filter.permits(&[0x3c, 0x22, 0xfc, 1, 2, 3]));
+
+        for bad in ["00:1b", "00:1b:63:01", "0:1b:63", "zz:1b:63"] {
+            let yaml = format!("ouis: [\"{bad}\"]");
+            assert!(serde_yaml::from_str::<VendorFilter>(&yaml).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_zero_threads_and_connections_are_rejected() {
+        let api: ApiConfig = serde_yaml::from_str("max_connections: 2").unwrap();
+        assert_eq!(api.max_connections, NonZeroUsize::new(2));
+        assert!(serde_yaml::from_str::<ApiConfig>("max_connections: 0").is_err());
+
+        let mut config = serde_yaml::to_value(Config::default()).unwrap();
+        config["worker_threads"] = 0.into();
+        assert!(serde_yaml::from_value::<Config>(config.clone()).is_err());
+        config["worker_threads"] = 4.into();
+        let config: Config = serde_yaml::from_value(config).unwrap();
+        assert_eq!(config.worker_threads, NonZeroUsize::new(4));
+    }
 }
@@ -0,0 +1,425 @@
+//! Read-through cache of the subnet table.
+//!
+//! Every DISCOVER and REQUEST looks subnets up, while subnets change only
+//! through the API. [`CachedDatabase`] keeps the full subnet list for a short
+//! TTL and drops it whenever a subnet is written through it, so API changes
+//! are visible to the DHCP path immediately.
+
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use crate::models::{
+    ApiToken, DynamicRange, IAPrefix, Lease, OptionTemplate, PendingOffer, StaticIP, Subnet,
+    SubnetAddress, SubnetPatch,
+};
+
+use super::{
+    ClientIdentityRewrite, Database, DynDatabase, RenumberPlan, Renumbering, SubnetCheck,
+    SubnetContents,
+};
+
+/// Subnet list as last read from the inner database
+struct Snapshot {
+    fetched_at: Instant,
+    subnets: Vec<Subnet>,
+}
+
+/// [`Database`] wrapper serving subnet reads from memory for up to `ttl`
+pub struct CachedDatabase {
+    inner: DynDatabase,
+    ttl: Duration,
+    snapshot: RwLock<Option<Snapshot>>,
+    /// Bumped on every invalidation so a read that raced with a write does
+    /// not store the list it fetched before the write
+    generation: AtomicU64,
+}
+
+impl CachedDatabase {
+    pub fn new(inner: DynDatabase, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            snapshot: RwLock::new(None),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.snapshot.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// The cached subnet list, refreshed from the inner database once stale
+    async fn subnets(&self) -> anyhow::Result<Vec<Subnet>> {
+        {
+            let snapshot = self.snapshot.read().unwrap_or_else(|e| e.into_inner());
+            let fresh = snapshot.as_ref().filter(|s| s.fetched_at.elapsed() < self.ttl);
+            if let Some(snapshot) = fresh {
+                return Ok(snapshot.subnets.clone());
+            }
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let subnets = self.inner.list_subnets().await?;
+        let mut snapshot = self.snapshot.write().unwrap_or_else(|e| e.into_inner());
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *snapshot = Some(Snapshot {
+                fetched_at: Instant::now(),
+                subnets: subnets.clone(),
+            });
+        }
+        Ok(subnets)
+    }
+}
+
+#[async_trait::async_trait]
+impl Database for CachedDatabase {
+    // Subnet operations
+    async fn create_subnet(&self, subnet: &Subnet) -> anyhow::Result<i64> {
+        let id = self.inner.create_subnet(subnet).await?;
+        self.invalidate();
+        Ok(id)
+    }
+
+    async fn get_subnet(&self, id: i64) -> anyhow::Result<Option<Subnet>> {
+        Ok(self.subnets().await?.into_iter().find(|s| s.id == Some(id)))
+    }
+
+    async fn list_subnets(&self) -> anyhow::Result<Vec<Subnet>> {
+        self.subnets().await
+    }
+
+    async fn get_subnets_by_ids(&self, ids: &[i64]) -> anyhow::Result<Vec<Subnet>> {
+        Ok(self
+            .subnets()
+            .await?
+            .into_iter()
+            .filter(|s| s.id.is_some_and(|id| ids.contains(&id)))
+            .collect())
+    }
+
+    async fn update_subnet(&self, id: i64, subnet: &Subnet) -> anyhow::Result<()> {
+        let result = self.inner.update_subnet(id, subnet).await;
+        self.invalidate();
+        result
+    }
+
+    async fn patch_subnet(
+        &self,
+        id: i64,
+        patch: &SubnetPatch,
+        check: &SubnetCheck<'_>,
+    ) -> anyhow::Result<Option<Subnet>> {
+        let result = self.inner.patch_subnet(id, patch, check).await;
+        self.invalidate();
+        result
+    }
+
+    async fn delete_subnet(&self, id: i64) -> anyhow::Result<()> {
+        let result = self.inner.delete_subnet(id).await;
+        self.invalidate();
+        result
+    }
+
+    async fn create_subnet_with_ranges(
+        &self,
+        subnet: &Subnet,
+        ranges: &[DynamicRange],
+        static_ips: &[StaticIP],
+    ) -> anyhow::Result<(i64, Vec<i64>)> {
+        let created = self
+            .inner
+            .create_subnet_with_ranges(subnet, ranges, static_ips)
+            .await?;
+        self.invalidate();
+        Ok(created)
+    }
+
+    async fn renumber_subnet(
+        &self,
+        id: i64,
+        plan: &RenumberPlan<'_>,
+    ) -> anyhow::Result<Option<(SubnetContents, Renumbering)>> {
+        let result = self.inner.renumber_subnet(id, plan).await;
+        self.invalidate();
+        result
+    }
+
+    // Dynamic Range operations
+    async fn create_range(&self, range: &DynamicRange) -> anyhow::Result<i64> {
+        self.inner.create_range(range).await
+    }
+
+    async fn list_ranges(&self, subnet_id: Option<i64>) -> anyhow::Result<Vec<DynamicRange>> {
+        self.inner.list_ranges(subnet_id).await
+    }
+
+    async fn delete_range(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.delete_range(id).await
+    }
+
+    async fn toggle_range(&self, id: i64, enabled: bool) -> anyhow::Result<()> {
+        self.inner.toggle_range(id, enabled).await
+    }
+
+    async fn set_range_draining(&self, id: i64, draining: bool) -> anyhow::Result<()> {
+        self.inner.set_range_draining(id, draining).await
+    }
+
+    // Static IP operations
+    async fn create_static_ip(&self, static_ip: &StaticIP) -> anyhow::Result<()> {
+        self.inner.create_static_ip(static_ip).await
+    }
+
+    async fn list_static_ips(&self, subnet_id: Option<i64>) -> anyhow::Result<Vec<StaticIP>> {
+        self.inner.list_static_ips(subnet_id).await
+    }
+
+    async fn get_static_ip_by_mac(&self, mac: &str) -> anyhow::Result<Option<StaticIP>> {
+        self.inner.get_static_ip_by_mac(mac).await
+    }
+
+    async fn update_static_ip_hostname(&self, ip_address: &str, hostname: Option<String>) -> anyhow::Result<()> {
+        self.inner.update_static_ip_hostname(ip_address, hostname).await
+    }
+
+    async fn delete_static_ip(&self, ip_address: &str) -> anyhow::Result<()> {
+        self.inner.delete_static_ip(ip_address).await
+    }
+
+    async fn delete_static_ips_by_subnet(&self, subnet_id: i64) -> anyhow::Result<u64> {
+        self.inner.delete_static_ips_by_subnet(subnet_id).await
+    }
+
+    async fn create_static_ip_if_free(&self, static_ip: &StaticIP) -> anyhow::Result<bool> {
+        self.inner.create_static_ip_if_free(static_ip).await
+    }
+
+    // Lease operations
+    async fn create_lease(&self, lease: &Lease) -> anyhow::Result<i64> {
+        self.inner.create_lease(lease).await
+    }
+
+    async fn create_lease_if_free(&self, lease: &Lease) -> anyhow::Result<Option<i64>> {
+        self.inner.create_lease_if_free(lease).await
+    }
+
+    async fn replace_lease(&self, old_id: i64, lease: &Lease) -> anyhow::Result<Option<i64>> {
+        self.inner.replace_lease(old_id, lease).await
+    }
+
+    async fn get_active_lease(&self, mac: &str) -> anyhow::Result<Option<Lease>> {
+        self.inner.get_active_lease(mac).await
+    }
+
+    async fn get_active_lease_by_ip(&self, ip: Ipv4Addr) -> anyhow::Result<Option<Lease>> {
+        self.inner.get_active_lease_by_ip(ip).await
+    }
+
+    async fn list_active_leases(&self) -> anyhow::Result<Vec<Lease>> {
+        self.inner.list_active_leases().await
+    }
+
+    async fn expire_lease(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.expire_lease(id).await
+    }
+
+    async fn release_lease(&self, id: i64, at: i64) -> anyhow::Result<()> {
+        self.inner.release_lease(id, at).await
+    }
+
+    async fn list_leases_released_since(&self, since: i64) -> anyhow::Result<Vec<Lease>> {
+        self.inner.list_leases_released_since(since).await
+    }
+
+    async fn get_recent_lease(&self, mac: &str, since: i64) -> anyhow::Result<Option<Lease>> {
+        self.inner.get_recent_lease(mac, since).await
+    }
+
+    async fn delete_expired_leases(&self) -> anyhow::Result<u64> {
+        self.inner.delete_expired_leases().await
+    }
+
+    async fn delete_leases_expired_before(&self, before: i64) -> anyhow::Result<u64> {
+        self.inner.delete_leases_expired_before(before).await
+    }
+
+    async fn list_subnet_addresses(&self, subnet_id: i64) -> anyhow::Result<Vec<SubnetAddress>> {
+        self.inner.list_subnet_addresses(subnet_id).await
+    }
+
+    // Pending offer operations
+    async fn record_pending_offer(&self, offer: &PendingOffer) -> anyhow::Result<()> {
+        self.inner.record_pending_offer(offer).await
+    }
+
+    async fn list_pending_offers(&self) -> anyhow::Result<Vec<PendingOffer>> {
+        self.inner.list_pending_offers().await
+    }
+
+    async fn delete_pending_offer(&self, ip: Ipv4Addr) -> anyhow::Result<()> {
+        self.inner.delete_pending_offer(ip).await
+    }
+
+    // Option template operations
+    async fn create_option_template(&self, template: &OptionTemplate) -> anyhow::Result<i64> {
+        self.inner.create_option_template(template).await
+    }
+
+    async fn get_option_template(&self, id: i64) -> anyhow::Result<Option<OptionTemplate>> {
+        self.inner.get_option_template(id).await
+    }
+
+    async fn list_option_templates(&self) -> anyhow::Result<Vec<OptionTemplate>> {
+        self.inner.list_option_templates().await
+    }
+
+    async fn update_option_template(&self, id: i64, template: &OptionTemplate) -> anyhow::Result<()> {
+        self.inner.update_option_template(id, template).await
+    }
+
+    async fn delete_option_template(&self, id: i64) -> anyhow::Result<()> {
+        // Detaches the subnets using the template
+        let result = self.inner.delete_option_template(id).await;
+        self.invalidate();
+        result
+    }
+
+    // IPv6 Prefix (IA Prefix) operations
+    async fn create_ia_prefix(&self, prefix: &IAPrefix) -> anyhow::Result<i64> {
+        self.inner.create_ia_prefix(prefix).await
+    }
+
+    async fn get_ia_prefix(&self, id: i64) -> anyhow::Result<Option<IAPrefix>> {
+        self.inner.get_ia_prefix(id).await
+    }
+
+    async fn list_ia_prefixes(&self, interface: Option<&str>) -> anyhow::Result<Vec<IAPrefix>> {
+        self.inner.list_ia_prefixes(interface).await
+    }
+
+    async fn update_ia_prefix(&self, id: i64, prefix: &IAPrefix) -> anyhow::Result<()> {
+        self.inner.update_ia_prefix(id, prefix).await
+    }
+
+    async fn delete_ia_prefix(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.delete_ia_prefix(id).await
+    }
+
+    // Token operations (for auth)
+    async fn list_tokens(&self) -> anyhow::Result<Vec<(i64, String, i64)>> {
+        self.inner.list_tokens().await
+    }
+
+    async fn list_api_tokens(&self) -> anyhow::Result<Vec<ApiToken>> {
+        self.inner.list_api_tokens().await
+    }
+
+    async fn create_token(&self, name: &str, token_hash: &str, salt: &str) -> anyhow::Result<i64> {
+        self.inner.create_token(name, token_hash, salt).await
+    }
+
+    async fn delete_token(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.delete_token(id).await
+    }
+
+    async fn toggle_token(&self, id: i64, enabled: bool) -> anyhow::Result<()> {
+        self.inner.toggle_token(id, enabled).await
+    }
+
+    async fn update_token_last_used(&self, token_hash: &str) -> anyhow::Result<()> {
+        self.inner.update_token_last_used(token_hash).await
+    }
+
+    // Maintenance
+    async fn backup_to(&self, path: &Path) -> anyhow::Result<u64> {
+        self.inner.backup_to(path).await
+    }
+
+    async fn rewrite_client_identities(
+        &self,
+        rewrite: &ClientIdentityRewrite<'_>,
+    ) -> anyhow::Result<u64> {
+        self.inner.rewrite_client_identities(rewrite).await
+    }
+
+    fn refresh_cache(&self) {
+        self.invalidate();
+        self.inner.refresh_cache();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::suite;
+    use crate::db::InMemoryDatabase;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_cached_database_suite() {
+        let db = CachedDatabase::new(Arc::new(InMemoryDatabase::new()), Duration::from_secs(60));
+        suite::run_all(&db).await;
+    }
+
+    #[tokio::test]
+    async fn test_update_invalidates_cached_subnet() {
+        let inner: DynDatabase = Arc::new(InMemoryDatabase::new());
+        let db = CachedDatabase::new(Arc::clone(&inner), Duration::from_secs(60));
+        let subnet = Subnet::from_cidr("192.168.1.0/24").unwrap();
+        let id = db.create_subnet(&subnet).await.unwrap();
+        assert!(db.get_subnet(id).await.unwrap().is_some());
+
+        // A write behind the cache's back is not seen until the TTL expires...
+        let renamed = Subnet {
+            domain_name: Some("behind.lan".to_string()),
+            ..subnet.clone()
+        };
+        inner.update_subnet(id, &renamed).await.unwrap();
+        let cached = db.get_subnet(id).await.unwrap().unwrap();
+        assert_eq!(cached.domain_name, None);
+
+        // ...while one through the cache is visible right away
+        let updated = Subnet {
+            domain_name: Some("new.lan".to_string()),
+            ..subnet
+        };
+        db.update_subnet(id, &updated).await.unwrap();
+        let fresh = db.get_subnet(id).await.unwrap().unwrap();
+        assert_eq!(fresh.domain_name.as_deref(), Some("new.lan"));
+
+        db.delete_subnet(id).await.unwrap();
+        assert!(db.get_subnet(id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stale_snapshot_is_refreshed() {
+        let inner: DynDatabase = Arc::new(InMemoryDatabase::new());
+        let db = CachedDatabase::new(Arc::clone(&inner), Duration::ZERO);
+        assert!(db.list_subnets().await.unwrap().is_empty());
+
+        inner
+            .create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(db.list_subnets().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_cache_drops_snapshot() {
+        let inner: DynDatabase = Arc::new(InMemoryDatabase::new());
+        let db = CachedDatabase::new(Arc::clone(&inner), Duration::from_secs(60));
+        assert!(db.list_subnets().await.unwrap().is_empty());
+
+        inner
+            .create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap())
+            .await
+            .unwrap();
+        assert!(db.list_subnets().await.unwrap().is_empty());
+        db.refresh_cache();
+        assert_eq!(db.list_subnets().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,728 @@
+use aes_gcm_siv::{
+    aead::{Aead, KeyInit},
+    Aes256GcmSiv, Nonce,
+};
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngExt;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use tracing::info;
+
+use crate::models::{
+    ApiToken, DynamicRange, IAPrefix, Lease, OptionTemplate, PendingOffer, StaticIP, Subnet,
+    SubnetAddress, SubnetPatch,
+};
+
+use super::{
+    ClientIdentityRewrite, Database, DynDatabase, RenumberPlan, Renumbering, SubnetCheck,
+    SubnetContents,
+};
+
+/// Prefix marking a column value as ciphertext produced by [`PiiCipher`].
+const CIPHERTEXT_PREFIX: &str = "enc1:";
+const NONCE_LEN: usize = 12;
+
+/// AES-256-GCM-SIV sealing of personally identifiable column values.
+///
+/// MAC addresses are encrypted deterministically (fixed nonce, which GCM-SIV
+/// tolerates) so that equality lookups by MAC keep working against the stored
+/// ciphertext; this reveals which rows share a MAC but nothing else.
+/// Hostnames are never looked up and use a random nonce per write.
+pub struct PiiCipher {
+    cipher: Aes256GcmSiv,
+}
+
+impl PiiCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: Aes256GcmSiv::new(key.into()),
+        }
+    }
+
+    /// Returns true if the stored value was produced by this cipher.
+    pub fn is_encrypted(value: &str) -> bool {
+        value.starts_with(CIPHERTEXT_PREFIX)
+    }
+
+    fn seal(&self, nonce: [u8; NONCE_LEN], plaintext: &str) -> anyhow::Result<String> {
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| anyhow::anyhow!("PII encryption failed"))?;
+        let mut blob = nonce.to_vec();
+        blob.extend_from_slice(&ciphertext);
+        Ok(format!(
+            "{}{}",
+            CIPHERTEXT_PREFIX,
+            general_purpose::STANDARD.encode(blob)
+        ))
+    }
+
+    /// Encrypt a MAC address. The address is normalized to lowercase first so
+    /// that lookups do not depend on the caller's casing.
+    pub fn encrypt_mac(&self, mac: &str) -> anyhow::Result<String> {
+        self.seal([0u8; NONCE_LEN], &mac.to_lowercase())
+    }
+
+    /// Encrypt a free-form value with a fresh random nonce.
+    pub fn encrypt(&self, plaintext: &str) -> anyhow::Result<String> {
+        let mut rng = rand::rng();
+        let nonce: [u8; NONCE_LEN] = rng.random();
+        self.seal(nonce, plaintext)
+    }
+
+    /// Decrypt a stored value. Values without the ciphertext prefix are legacy
+    /// plaintext rows and are returned unchanged.
+    pub fn decrypt(&self, stored: &str) -> anyhow::Result<String> {
+        let Some(encoded) = stored.strip_prefix(CIPHERTEXT_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let blob = general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| anyhow::anyhow!("Invalid PII ciphertext encoding: {}", e))?;
+        if blob.len() < NONCE_LEN {
+            anyhow::bail!("PII ciphertext is truncated");
+        }
+        let (nonce, ciphertext) = blob.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow::anyhow!("PII decryption failed (wrong key or corrupted row)"))?;
+        String::from_utf8(plaintext).map_err(|e| anyhow::anyhow!("PII plaintext is not UTF-8: {}", e))
+    }
+
+    fn encrypt_opt(&self, value: &Option<String>) -> anyhow::Result<Option<String>> {
+        value.as_deref().map(|v| self.encrypt(v)).transpose()
+    }
+
+    fn decrypt_opt(&self, value: &Option<String>) -> anyhow::Result<Option<String>> {
+        value.as_deref().map(|v| self.decrypt(v)).transpose()
+    }
+}
+
+/// Database decorator that transparently encrypts client MAC addresses and
+/// hostnames on write and decrypts them on read.
+pub struct EncryptedDatabase {
+    inner: DynDatabase,
+    cipher: PiiCipher,
+}
+
+impl EncryptedDatabase {
+    pub fn new(inner: DynDatabase, key: &[u8; 32]) -> Self {
+        Self {
+            inner,
+            cipher: PiiCipher::new(key),
+        }
+    }
+
+    fn seal_static_ip(&self, static_ip: &StaticIP) -> anyhow::Result<StaticIP> {
+        Ok(StaticIP {
+            mac_address: self.cipher.encrypt_mac(&static_ip.mac_address)?,
+            hostname: self.cipher.encrypt_opt(&static_ip.hostname)?,
+            ..static_ip.clone()
+        })
+    }
+
+    fn open_static_ip(&self, static_ip: StaticIP) -> anyhow::Result<StaticIP> {
+        Ok(StaticIP {
+            mac_address: self.cipher.decrypt(&static_ip.mac_address)?,
+            hostname: self.cipher.decrypt_opt(&static_ip.hostname)?,
+            ..static_ip
+        })
+    }
+
+    fn seal_lease(&self, lease: &Lease) -> anyhow::Result<Lease> {
+        Ok(Lease {
+            mac_address: self.cipher.encrypt_mac(&lease.mac_address)?,
+            hostname: self.cipher.encrypt_opt(&lease.hostname)?,
+            ..lease.clone()
+        })
+    }
+
+    fn open_contents(&self, contents: SubnetContents) -> anyhow::Result<SubnetContents> {
+        Ok(SubnetContents {
+            static_ips: contents
+                .static_ips
+                .into_iter()
+                .map(|s| self.open_static_ip(s))
+                .collect::<anyhow::Result<_>>()?,
+            leases: contents
+                .leases
+                .into_iter()
+                .map(|l| self.open_lease(l))
+                .collect::<anyhow::Result<_>>()?,
+            ..contents
+        })
+    }
+
+    fn open_lease(&self, lease: Lease) -> anyhow::Result<Lease> {
+        Ok(Lease {
+            mac_address: self.cipher.decrypt(&lease.mac_address)?,
+            hostname: self.cipher.decrypt_opt(&lease.hostname)?,
+            ..lease
+        })
+    }
+
+    /// Re-write plaintext static assignments and leases, expired ones
+    /// included, left over from before encryption was enabled. Rows are
+    /// updated in place in a single transaction. Returns the number of rows
+    /// converted.
+    pub async fn migrate_plaintext(&self) -> anyhow::Result<u64> {
+        let cipher = &self.cipher;
+        let migrated = self
+            .inner
+            .rewrite_client_identities(&|mac, hostname| {
+                let needs_migration = !PiiCipher::is_encrypted(mac)
+                    || hostname.is_some_and(|h| !PiiCipher::is_encrypted(h));
+                if !needs_migration {
+                    return Ok(None);
+                }
+                // Values already sealed are kept; only plaintext is encrypted
+                let mac = cipher.encrypt_mac(&cipher.decrypt(mac)?)?;
+                let hostname = hostname
+                    .map(|h| {
+                        if PiiCipher::is_encrypted(h) {
+                            Ok(h.to_string())
+                        } else {
+                            cipher.encrypt(h)
+                        }
+                    })
+                    .transpose()?;
+                Ok(Some((mac, hostname)))
+            })
+            .await?;
+
+        if migrated > 0 {
+            info!("Encrypted {} plaintext row(s) at rest", migrated);
+        }
+        Ok(migrated)
+    }
+}
+
+#[async_trait::async_trait]
+impl Database for EncryptedDatabase {
+    // Subnet operations
+    async fn create_subnet(&self, subnet: &Subnet) -> anyhow::Result<i64> {
+        self.inner.create_subnet(subnet).await
+    }
+
+    async fn get_subnet(&self, id: i64) -> anyhow::Result<Option<Subnet>> {
+        self.inner.get_subnet(id).await
+    }
+
+    async fn list_subnets(&self) -> anyhow::Result<Vec<Subnet>> {
+        self.inner.list_subnets().await
+    }
+
+    async fn get_subnets_by_ids(&self, ids: &[i64]) -> anyhow::Result<Vec<Subnet>> {
+        self.inner.get_subnets_by_ids(ids).await
+    }
+
+    async fn update_subnet(&self, id: i64, subnet: &Subnet) -> anyhow::Result<()> {
+        self.inner.update_subnet(id, subnet).await
+    }
+
+    async fn patch_subnet(
+        &self,
+        id: i64,
+        patch: &SubnetPatch,
+        check: &SubnetCheck<'_>,
+    ) -> anyhow::Result<Option<Subnet>> {
+        self.inner.patch_subnet(id, patch, check).await
+    }
+
+    async fn delete_subnet(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.delete_subnet(id).await
+    }
+
+    async fn create_subnet_with_ranges(
+        &self,
+        subnet: &Subnet,
+        ranges: &[DynamicRange],
+        static_ips: &[StaticIP],
+    ) -> anyhow::Result<(i64, Vec<i64>)> {
+        let static_ips = static_ips
+            .iter()
+            .map(|s| self.seal_static_ip(s))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.inner
+            .create_subnet_with_ranges(subnet, ranges, &static_ips)
+            .await
+    }
+
+    async fn renumber_subnet(
+        &self,
+        id: i64,
+        plan: &RenumberPlan<'_>,
+    ) -> anyhow::Result<Option<(SubnetContents, Renumbering)>> {
+        // The plan sees clear values; the static IPs it writes are sealed
+        let sealed_plan = |contents: &SubnetContents| -> anyhow::Result<Renumbering> {
+            let mut renumbering = plan(&self.open_contents(contents.clone())?)?;
+            renumbering.static_ips = renumbering
+                .static_ips
+                .iter()
+                .map(|s| self.seal_static_ip(s))
+                .collect::<anyhow::Result<_>>()?;
+            Ok(renumbering)
+        };
+        let Some((contents, mut renumbering)) =
+            self.inner.renumber_subnet(id, &sealed_plan).await?
+        else {
+            return Ok(None);
+        };
+        renumbering.static_ips = renumbering
+            .static_ips
+            .into_iter()
+            .map(|s| self.open_static_ip(s))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Some((self.open_contents(contents)?, renumbering)))
+    }
+
+    // Dynamic Range operations
+    async fn create_range(&self, range: &DynamicRange) -> anyhow::Result<i64> {
+        self.inner.create_range(range).await
+    }
+
+    async fn list_ranges(&self, subnet_id: Option<i64>) -> anyhow::Result<Vec<DynamicRange>> {
+        self.inner.list_ranges(subnet_id).await
+    }
+
+    async fn delete_range(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.delete_range(id).await
+    }
+
+    async fn toggle_range(&self, id: i64, enabled: bool) -> anyhow::Result<()> {
+        self.inner.toggle_range(id, enabled).await
+    }
+
+    async fn set_range_draining(&self, id: i64, draining: bool) -> anyhow::Result<()> {
+        self.inner.set_range_draining(id, draining).await
+    }
+
+    // Static IP operations
+    async fn create_static_ip(&self, static_ip: &StaticIP) -> anyhow::Result<()> {
+        self.inner.create_static_ip(&self.seal_static_ip(static_ip)?).await
+    }
+
+    async fn list_static_ips(&self, subnet_id: Option<i64>) -> anyhow::Result<Vec<StaticIP>> {
+        self.inner
+            .list_static_ips(subnet_id)
+            .await?
+            .into_iter()
+            .map(|s| self.open_static_ip(s))
+            .collect()
+    }
+
+    async fn get_static_ip_by_mac(&self, mac: &str) -> anyhow::Result<Option<StaticIP>> {
+        self.inner
+            .get_static_ip_by_mac(&self.cipher.encrypt_mac(mac)?)
+            .await?
+            .map(|s| self.open_static_ip(s))
+            .transpose()
+    }
+
+    async fn update_static_ip_hostname(&self, ip_address: &str, hostname: Option<String>) -> anyhow::Result<()> {
+        let hostname = self.cipher.encrypt_opt(&hostname)?;
+        self.inner.update_static_ip_hostname(ip_address, hostname).await
+    }
+
+    async fn delete_static_ip(&self, ip_address: &str) -> anyhow::Result<()> {
+        self.inner.delete_static_ip(ip_address).await
+    }
+
+    async fn delete_static_ips_by_subnet(&self, subnet_id: i64) -> anyhow::Result<u64> {
+        self.inner.delete_static_ips_by_subnet(subnet_id).await
+    }
+
+    async fn create_static_ip_if_free(&self, static_ip: &StaticIP) -> anyhow::Result<bool> {
+        self.inner
+            .create_static_ip_if_free(&self.seal_static_ip(static_ip)?)
+            .await
+    }
+
+    // Lease operations
+    async fn create_lease(&self, lease: &Lease) -> anyhow::Result<i64> {
+        self.inner.create_lease(&self.seal_lease(lease)?).await
+    }
+
+    async fn create_lease_if_free(&self, lease: &Lease) -> anyhow::Result<Option<i64>> {
+        self.inner.create_lease_if_free(&self.seal_lease(lease)?).await
+    }
+
+    async fn replace_lease(&self, old_id: i64, lease: &Lease) -> anyhow::Result<Option<i64>> {
+        self.inner.replace_lease(old_id, &self.seal_lease(lease)?).await
+    }
+
+    async fn get_active_lease(&self, mac: &str) -> anyhow::Result<Option<Lease>> {
+        self.inner
+            .get_active_lease(&self.cipher.encrypt_mac(mac)?)
+            .await?
+            .map(|l| self.open_lease(l))
+            .transpose()
+    }
+
+    async fn get_active_lease_by_ip(&self, ip: Ipv4Addr) -> anyhow::Result<Option<Lease>> {
+        self.inner
+            .get_active_lease_by_ip(ip)
+            .await?
+            .map(|l| self.open_lease(l))
+            .transpose()
+    }
+
+    async fn list_active_leases(&self) -> anyhow::Result<Vec<Lease>> {
+        self.inner
+            .list_active_leases()
+            .await?
+            .into_iter()
+            .map(|l| self.open_lease(l))
+            .collect()
+    }
+
+    async fn expire_lease(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.expire_lease(id).await
+    }
+
+    async fn release_lease(&self, id: i64, at: i64) -> anyhow::Result<()> {
+        self.inner.release_lease(id, at).await
+    }
+
+    async fn list_leases_released_since(&self, since: i64) -> anyhow::Result<Vec<Lease>> {
+        self.inner
+            .list_leases_released_since(since)
+            .await?
+            .into_iter()
+            .map(|l| self.open_lease(l))
+            .collect()
+    }
+
+    async fn get_recent_lease(&self, mac: &str, since: i64) -> anyhow::Result<Option<Lease>> {
+        self.inner
+            .get_recent_lease(&self.cipher.encrypt_mac(mac)?, since)
+            .await?
+            .map(|l| self.open_lease(l))
+            .transpose()
+    }
+
+    async fn delete_expired_leases(&self) -> anyhow::Result<u64> {
+        self.inner.delete_expired_leases().await
+    }
+
+    async fn delete_leases_expired_before(&self, before: i64) -> anyhow::Result<u64> {
+        self.inner.delete_leases_expired_before(before).await
+    }
+
+    async fn list_subnet_addresses(&self, subnet_id: i64) -> anyhow::Result<Vec<SubnetAddress>> {
+        self.inner
+            .list_subnet_addresses(subnet_id)
+            .await?
+            .into_iter()
+            .map(|a| {
+                Ok(SubnetAddress {
+                    mac: self.cipher.decrypt(&a.mac)?,
+                    hostname: self.cipher.decrypt_opt(&a.hostname)?,
+                    ..a
+                })
+            })
+            .collect()
+    }
+
+    // Pending offer operations
+    async fn record_pending_offer(&self, offer: &PendingOffer) -> anyhow::Result<()> {
+        let sealed = PendingOffer {
+            mac_address: self.cipher.encrypt_mac(&offer.mac_address)?,
+            ..offer.clone()
+        };
+        self.inner.record_pending_offer(&sealed).await
+    }
+
+    async fn list_pending_offers(&self) -> anyhow::Result<Vec<PendingOffer>> {
+        self.inner
+            .list_pending_offers()
+            .await?
+            .into_iter()
+            .map(|o| {
+                Ok(PendingOffer {
+                    mac_address: self.cipher.decrypt(&o.mac_address)?,
+                    ..o
+                })
+            })
+            .collect()
+    }
+
+    async fn delete_pending_offer(&self, ip: Ipv4Addr) -> anyhow::Result<()> {
+        self.inner.delete_pending_offer(ip).await
+    }
+
+    // Option template operations
+    async fn create_option_template(&self, template: &OptionTemplate) -> anyhow::Result<i64> {
+        self.inner.create_option_template(template).await
+    }
+
+    async fn get_option_template(&self, id: i64) -> anyhow::Result<Option<OptionTemplate>> {
+        self.inner.get_option_template(id).await
+    }
+
+    async fn list_option_templates(&self) -> anyhow::Result<Vec<OptionTemplate>> {
+        self.inner.list_option_templates().await
+    }
+
+    async fn update_option_template(&self, id: i64, template: &OptionTemplate) -> anyhow::Result<()> {
+        self.inner.update_option_template(id, template).await
+    }
+
+    async fn delete_option_template(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.delete_option_template(id).await
+    }
+
+    // IPv6 Prefix (IA Prefix) operations
+    async fn create_ia_prefix(&self, prefix: &IAPrefix) -> anyhow::Result<i64> {
+        self.inner.create_ia_prefix(prefix).await
+    }
+
+    async fn get_ia_prefix(&self, id: i64) -> anyhow::Result<Option<IAPrefix>> {
+        self.inner.get_ia_prefix(id).await
+    }
+
+    async fn list_ia_prefixes(&self, interface: Option<&str>) -> anyhow::Result<Vec<IAPrefix>> {
+        self.inner.list_ia_prefixes(interface).await
+    }
+
+    async fn update_ia_prefix(&self, id: i64, prefix: &IAPrefix) -> anyhow::Result<()> {
+        self.inner.update_ia_prefix(id, prefix).await
+    }
+
+    async fn delete_ia_prefix(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.delete_ia_prefix(id).await
+    }
+
+    // Token operations (for auth)
+    async fn list_tokens(&self) -> anyhow::Result<Vec<(i64, String, i64)>> {
+        self.inner.list_tokens().await
+    }
+
+    async fn list_api_tokens(&self) -> anyhow::Result<Vec<ApiToken>> {
+        self.inner.list_api_tokens().await
+    }
+
+    async fn create_token(&self, name: &str, token_hash: &str, salt: &str) -> anyhow::Result<i64> {
+        self.inner.create_token(name, token_hash, salt).await
+    }
+
+    async fn delete_token(&self, id: i64) -> anyhow::Result<()> {
+        self.inner.delete_token(id).await
+    }
+
+    async fn toggle_token(&self, id: i64, enabled: bool) -> anyhow::Result<()> {
+        self.inner.toggle_token(id, enabled).await
+    }
+
+    async fn update_token_last_used(&self, token_hash: &str) -> anyhow::Result<()> {
+        self.inner.update_token_last_used(token_hash).await
+    }
+
+    // Maintenance
+    async fn backup_to(&self, path: &Path) -> anyhow::Result<u64> {
+        // The snapshot holds the ciphertext exactly as stored
+        self.inner.backup_to(path).await
+    }
+
+    async fn rewrite_client_identities(
+        &self,
+        rewrite: &ClientIdentityRewrite<'_>,
+    ) -> anyhow::Result<u64> {
+        // `rewrite` sees and returns plaintext, like every other caller
+        let cipher = &self.cipher;
+        self.inner
+            .rewrite_client_identities(&|mac, hostname| {
+                let mac = cipher.decrypt(mac)?;
+                let hostname = hostname.map(|h| cipher.decrypt(h)).transpose()?;
+                rewrite(&mac, hostname.as_deref())?
+                    .map(|(mac, hostname)| {
+                        Ok((cipher.encrypt_mac(&mac)?, cipher.encrypt_opt(&hostname)?))
+                    })
+                    .transpose()
+            })
+            .await
+    }
+
+    fn refresh_cache(&self) {
+        self.inner.refresh_cache();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::suite;
+    use crate::db::{InMemoryDatabase, SqliteDatabase};
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    const KEY: [u8; 32] = [7u8; 32];
+
+    fn sample_static(subnet_id: i64) -> StaticIP {
+        StaticIP {
+            subnet_id,
+            mac_address: "AA:BB:CC:DD:EE:FF".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 50),
+            hostname: Some("secret-laptop".to_string()),
+        }
+    }
+
+    fn sample_lease(subnet_id: i64) -> Lease {
+        let now = chrono::Utc::now().timestamp();
+        Lease {
+            id: None,
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:01".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 100),
+            lease_start: now,
+            lease_end: now + 3600,
+            hostname: Some("secret-phone".to_string()),
+            range_id: None,
+        }
+    }
+
+    #[test]
+    fn test_cipher_round_trip() {
+        let cipher = PiiCipher::new(&KEY);
+        let sealed = cipher.encrypt("hello").unwrap();
+        assert!(PiiCipher::is_encrypted(&sealed));
+        assert_eq!(cipher.decrypt(&sealed).unwrap(), "hello");
+        // Random nonces: the same plaintext seals differently each time
+        assert_ne!(sealed, cipher.encrypt("hello").unwrap());
+    }
+
+    #[test]
+    fn test_cipher_mac_is_deterministic_and_case_insensitive() {
+        let cipher = PiiCipher::new(&KEY);
+        assert_eq!(
+            cipher.encrypt_mac("AA:BB:CC:DD:EE:FF").unwrap(),
+            cipher.encrypt_mac("aa:bb:cc:dd:ee:ff").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cipher_wrong_key_fails() {
+        let sealed = PiiCipher::new(&KEY).encrypt("hello").unwrap();
+        assert!(PiiCipher::new(&[8u8; 32]).decrypt(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_cipher_passes_through_plaintext() {
+        let cipher = PiiCipher::new(&KEY);
+        assert_eq!(cipher.decrypt("aa:bb:cc:dd:ee:ff").unwrap(), "aa:bb:cc:dd:ee:ff");
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_database_suite() {
+        let db = EncryptedDatabase::new(Arc::new(InMemoryDatabase::new()), &KEY);
+        suite::run_all(&db).await;
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_stores_ciphertext() {
+        let inner: DynDatabase = Arc::new(InMemoryDatabase::new());
+        let db = EncryptedDatabase::new(Arc::clone(&inner), &KEY);
+        let subnet = Subnet::from_cidr("192.168.1.0/24").unwrap();
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+
+        db.create_static_ip(&sample_static(subnet_id)).await.unwrap();
+        db.create_lease(&sample_lease(subnet_id)).await.unwrap();
+
+        let found = db.get_static_ip_by_mac("aa:bb:cc:dd:ee:ff").await.unwrap().unwrap();
+        assert_eq!(found.mac_address, "aa:bb:cc:dd:ee:ff");
+        assert_eq!(found.hostname.as_deref(), Some("secret-laptop"));
+        let lease = db.get_active_lease("AA:BB:CC:DD:EE:01").await.unwrap().unwrap();
+        assert_eq!(lease.hostname.as_deref(), Some("secret-phone"));
+
+        let raw_static = &inner.list_static_ips(None).await.unwrap()[0];
+        assert!(PiiCipher::is_encrypted(&raw_static.mac_address));
+        assert!(PiiCipher::is_encrypted(raw_static.hostname.as_deref().unwrap()));
+        let raw_lease = &inner.list_active_leases().await.unwrap()[0];
+        assert!(PiiCipher::is_encrypted(&raw_lease.mac_address));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_plaintext_rows() {
+        let backends: [DynDatabase; 2] = [
+            Arc::new(InMemoryDatabase::new()),
+            Arc::new(SqliteDatabase::new("sqlite::memory:").await.unwrap()),
+        ];
+        for inner in backends {
+            let subnet = Subnet::from_cidr("192.168.1.0/24").unwrap();
+            let subnet_id = inner.create_subnet(&subnet).await.unwrap();
+            inner.create_static_ip(&sample_static(subnet_id)).await.unwrap();
+            let active_id = inner.create_lease(&sample_lease(subnet_id)).await.unwrap();
+            let now = chrono::Utc::now().timestamp();
+            inner
+                .create_lease(&Lease {
+                    mac_address: "aa:bb:cc:dd:ee:02".to_string(),
+                    ip_address: Ipv4Addr::new(192, 168, 1, 101),
+                    hostname: Some("secret-tablet".to_string()),
+                    lease_start: now - 7200,
+                    lease_end: now - 3600,
+                    ..sample_lease(subnet_id)
+                })
+                .await
+                .unwrap();
+
+            let db = EncryptedDatabase::new(Arc::clone(&inner), &KEY);
+            assert_eq!(db.migrate_plaintext().await.unwrap(), 3);
+            assert_eq!(db.migrate_plaintext().await.unwrap(), 0);
+
+            // Rewritten in place: the active lease keeps its ID
+            let lease = db.get_active_lease("aa:bb:cc:dd:ee:01").await.unwrap().unwrap();
+            assert_eq!(lease.id, Some(active_id));
+            assert_eq!(lease.hostname.as_deref(), Some("secret-phone"));
+            assert!(db.get_static_ip_by_mac("aa:bb:cc:dd:ee:ff").await.unwrap().is_some());
+            let expired = db.get_recent_lease("aa:bb:cc:dd:ee:02", 0).await.unwrap().unwrap();
+            assert_eq!(expired.hostname.as_deref(), Some("secret-tablet"));
+
+            // No row of the underlying database holds plaintext any more
+            let mut raw_leases = inner.list_active_leases().await.unwrap();
+            let expired_mac = db.cipher.encrypt_mac("aa:bb:cc:dd:ee:02").unwrap();
+            raw_leases.extend(inner.get_recent_lease(&expired_mac, 0).await.unwrap());
+            assert_eq!(raw_leases.len(), 2);
+            let raw_statics = inner.list_static_ips(None).await.unwrap();
+            let raw_values = raw_leases
+                .iter()
+                .flat_map(|l| [Some(&l.mac_address), l.hostname.as_ref()])
+                .chain(
+                    raw_statics
+                        .iter()
+                        .flat_map(|s| [Some(&s.mac_address), s.hostname.as_ref()]),
+                );
+            for value in raw_values {
+                assert!(PiiCipher::is_encrypted(value.unwrap()), "plaintext {:?}", value);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_file_has_no_plaintext_pii() {
+        let path = std::env::temp_dir().join(format!(
+            "ndhcpd-encrypted-{}-{}.db",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let url = format!("sqlite:{}", path.display());
+        {
+            let sqlite = SqliteDatabase::new(&url).await.unwrap();
+            let db = EncryptedDatabase::new(Arc::new(sqlite), &KEY);
+            let subnet = Subnet::from_cidr("192.168.1.0/24").unwrap();
+            let subnet_id = db.create_subnet(&subnet).await.unwrap();
+            db.create_static_ip(&sample_static(subnet_id)).await.unwrap();
+            db.create_lease(&sample_lease(subnet_id)).await.unwrap();
+            assert!(db.get_active_lease("aa:bb:cc:dd:ee:01").await.unwrap().is_some());
+        }
+
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let haystack = String::from_utf8_lossy(&bytes).to_lowercase();
+        assert!(!haystack.contains("aa:bb:cc:dd:ee:ff"));
+        assert!(!haystack.contains("aa:bb:cc:dd:ee:01"));
+        assert!(!haystack.contains("secret-laptop"));
+        assert!(!haystack.contains("secret-phone"));
+    }
+}
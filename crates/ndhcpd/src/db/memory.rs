@@ -1,8 +1,19 @@
-use crate::models::{ApiToken, DynamicRange, IAPrefix, Lease, StaticIP, Subnet};
+use crate::models::{
+    AddressSource, ApiToken, DynamicRange, IAPrefix, Lease, OptionTemplate, PendingOffer,
+    StaticIP, Subnet, SubnetAddress, SubnetPatch,
+};
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use super::Database;
+use super::{
+    ClientIdentityRewrite, Database, RenumberPlan, Renumbering, SubnetCheck, SubnetContents,
+};
+
+/// Stored token row: id, name, token_hash, enabled
+type TokenRow = (i64, String, String, i64);
 
 /// In-memory implementation of the Database trait (for testing)
 pub struct InMemoryDatabase {
@@ -10,11 +21,16 @@ pub struct InMemoryDatabase {
     ranges: Arc<RwLock<Vec<DynamicRange>>>,
     static_ips: Arc<RwLock<Vec<StaticIP>>>,
     leases: Arc<RwLock<Vec<Lease>>>,
+    /// IDs of the leases ended by a release
+    released_leases: Arc<RwLock<HashSet<i64>>>,
+    pending_offers: Arc<RwLock<Vec<PendingOffer>>>,
+    option_templates: Arc<RwLock<Vec<OptionTemplate>>>,
     ia_prefixes: Arc<RwLock<Vec<IAPrefix>>>,
-    tokens: Arc<RwLock<Vec<(i64, String, String, i64)>>>, // id, name, token_hash, enabled
+    tokens: Arc<RwLock<Vec<TokenRow>>>,
     next_subnet_id: Arc<RwLock<i64>>,
     next_range_id: Arc<RwLock<i64>>,
     next_lease_id: Arc<RwLock<i64>>,
+    next_option_template_id: Arc<RwLock<i64>>,
     next_ia_prefix_id: Arc<RwLock<i64>>,
     next_token_id: Arc<RwLock<i64>>,
 }
@@ -27,11 +43,15 @@ impl InMemoryDatabase {
             ranges: Arc::new(RwLock::new(Vec::new())),
             static_ips: Arc::new(RwLock::new(Vec::new())),
             leases: Arc::new(RwLock::new(Vec::new())),
+            released_leases: Arc::new(RwLock::new(HashSet::new())),
+            pending_offers: Arc::new(RwLock::new(Vec::new())),
+            option_templates: Arc::new(RwLock::new(Vec::new())),
             ia_prefixes: Arc::new(RwLock::new(Vec::new())),
             tokens: Arc::new(RwLock::new(Vec::new())),
             next_subnet_id: Arc::new(RwLock::new(1)),
             next_range_id: Arc::new(RwLock::new(1)),
             next_lease_id: Arc::new(RwLock::new(1)),
+            next_option_template_id: Arc::new(RwLock::new(1)),
             next_ia_prefix_id: Arc::new(RwLock::new(1)),
             next_token_id: Arc::new(RwLock::new(1)),
         }
@@ -70,6 +90,15 @@ impl Database for InMemoryDatabase {
         Ok(subnets.clone())
     }
 
+    async fn get_subnets_by_ids(&self, ids: &[i64]) -> anyhow::Result<Vec<Subnet>> {
+        let subnets = self.subnets.read().await;
+        Ok(subnets
+            .iter()
+            .filter(|s| s.id.is_some_and(|id| ids.contains(&id)))
+            .cloned()
+            .collect())
+    }
+
     async fn update_subnet(&self, id: i64, subnet: &Subnet) -> anyhow::Result<()> {
         let mut subnets = self.subnets.write().await;
         if let Some(existing) = subnets.iter_mut().find(|s| s.id == Some(id)) {
@@ -79,12 +108,120 @@ impl Database for InMemoryDatabase {
         Ok(())
     }
 
+    async fn patch_subnet(
+        &self,
+        id: i64,
+        patch: &SubnetPatch,
+        check: &SubnetCheck<'_>,
+    ) -> anyhow::Result<Option<Subnet>> {
+        let mut subnets = self.subnets.write().await;
+        let Some(i) = subnets.iter().position(|s| s.id == Some(id)) else {
+            return Ok(None);
+        };
+        let mut merged = subnets[i].clone();
+        patch.apply(&mut merged);
+        let others: Vec<Subnet> = subnets.iter().filter(|s| s.id != Some(id)).cloned().collect();
+        check(&merged, &others)?;
+        subnets[i] = merged.clone();
+        Ok(Some(merged))
+    }
+
     async fn delete_subnet(&self, id: i64) -> anyhow::Result<()> {
         let mut subnets = self.subnets.write().await;
         subnets.retain(|s| s.id != Some(id));
         Ok(())
     }
 
+    async fn create_subnet_with_ranges(
+        &self,
+        subnet: &Subnet,
+        ranges: &[DynamicRange],
+        static_ips: &[StaticIP],
+    ) -> anyhow::Result<(i64, Vec<i64>)> {
+        // Hold every lock up front so readers never see a partial clone
+        let mut subnets = self.subnets.write().await;
+        let mut all_ranges = self.ranges.write().await;
+        let mut all_static_ips = self.static_ips.write().await;
+        let mut next_subnet_id = self.next_subnet_id.write().await;
+        let mut next_range_id = self.next_range_id.write().await;
+
+        let subnet_id = *next_subnet_id;
+        *next_subnet_id += 1;
+        let mut new_subnet = subnet.clone();
+        new_subnet.id = Some(subnet_id);
+        subnets.push(new_subnet);
+
+        let mut range_ids = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let mut new_range = range.clone();
+            new_range.id = Some(*next_range_id);
+            new_range.subnet_id = subnet_id;
+            range_ids.push(*next_range_id);
+            *next_range_id += 1;
+            all_ranges.push(new_range);
+        }
+
+        all_static_ips.extend(static_ips.iter().map(|s| StaticIP {
+            subnet_id,
+            ..s.clone()
+        }));
+
+        Ok((subnet_id, range_ids))
+    }
+
+    async fn renumber_subnet(
+        &self,
+        id: i64,
+        plan: &RenumberPlan<'_>,
+    ) -> anyhow::Result<Option<(SubnetContents, Renumbering)>> {
+        let mut subnets = self.subnets.write().await;
+        let mut all_ranges = self.ranges.write().await;
+        let mut all_static_ips = self.static_ips.write().await;
+        let mut leases = self.leases.write().await;
+
+        let Some(i) = subnets.iter().position(|s| s.id == Some(id)) else {
+            return Ok(None);
+        };
+        let now = chrono::Utc::now().timestamp();
+        let contents = SubnetContents {
+            subnet: subnets[i].clone(),
+            others: subnets.iter().filter(|s| s.id != Some(id)).cloned().collect(),
+            ranges: all_ranges.iter().filter(|r| r.subnet_id == id).cloned().collect(),
+            static_ips: all_static_ips.iter().filter(|s| s.subnet_id == id).cloned().collect(),
+            leases: leases
+                .iter()
+                .filter(|l| l.subnet_id == id && l.lease_end > now)
+                .cloned()
+                .collect(),
+        };
+        let renumbering = plan(&contents)?;
+
+        subnets[i] = Subnet {
+            id: Some(id),
+            ..renumbering.subnet.clone()
+        };
+        all_ranges.retain(|r| r.subnet_id != id);
+        all_ranges.extend(renumbering.ranges.iter().map(|r| DynamicRange {
+            subnet_id: id,
+            ..r.clone()
+        }));
+        all_static_ips.retain(|s| s.subnet_id != id);
+        all_static_ips.extend(renumbering.static_ips.iter().map(|s| StaticIP {
+            subnet_id: id,
+            ..s.clone()
+        }));
+        for lease in leases.iter_mut().filter(|l| l.subnet_id == id) {
+            if lease.id.is_some_and(|lid| renumbering.expired_leases.contains(&lid)) {
+                lease.lease_end = lease.lease_end.min(now);
+            }
+            if !renumbering.ranges.iter().any(|r| r.id.is_some() && r.id == lease.range_id) {
+                lease.range_id = None;
+            }
+        }
+
+        Ok(Some((contents, renumbering)))
+    }
+
     // Dynamic Range operations
     async fn create_range(&self, range: &DynamicRange) -> anyhow::Result<i64> {
         let mut id = self.next_range_id.write().await;
@@ -114,6 +251,26 @@ impl Database for InMemoryDatabase {
     async fn delete_range(&self, id: i64) -> anyhow::Result<()> {
         let mut ranges = self.ranges.write().await;
         ranges.retain(|r| r.id != Some(id));
+        let mut leases = self.leases.write().await;
+        for lease in leases.iter_mut().filter(|l| l.range_id == Some(id)) {
+            lease.range_id = None;
+        }
+        Ok(())
+    }
+
+    async fn toggle_range(&self, id: i64, enabled: bool) -> anyhow::Result<()> {
+        let mut ranges = self.ranges.write().await;
+        if let Some(range) = ranges.iter_mut().find(|r| r.id == Some(id)) {
+            range.enabled = enabled;
+        }
+        Ok(())
+    }
+
+    async fn set_range_draining(&self, id: i64, draining: bool) -> anyhow::Result<()> {
+        let mut ranges = self.ranges.write().await;
+        if let Some(range) = ranges.iter_mut().find(|r| r.id == Some(id)) {
+            range.draining = draining;
+        }
         Ok(())
     }
 
@@ -159,6 +316,27 @@ impl Database for InMemoryDatabase {
         Ok(())
     }
 
+    async fn delete_static_ips_by_subnet(&self, subnet_id: i64) -> anyhow::Result<u64> {
+        let mut static_ips = self.static_ips.write().await;
+        let before = static_ips.len();
+        static_ips.retain(|s| s.subnet_id != subnet_id);
+        Ok((before - static_ips.len()) as u64)
+    }
+
+    async fn create_static_ip_if_free(&self, static_ip: &StaticIP) -> anyhow::Result<bool> {
+        let now = chrono::Utc::now().timestamp();
+        let ip = static_ip.ip_address;
+        let mut static_ips = self.static_ips.write().await;
+        let leases = self.leases.read().await;
+        if static_ips.iter().any(|s| s.ip_address == ip)
+            || leases.iter().any(|l| l.ip_address == ip && l.lease_end > now)
+        {
+            return Ok(false);
+        }
+        static_ips.push(static_ip.clone());
+        Ok(true)
+    }
+
     // Lease operations
     async fn create_lease(&self, lease: &Lease) -> anyhow::Result<i64> {
         let mut id = self.next_lease_id.write().await;
@@ -173,6 +351,53 @@ impl Database for InMemoryDatabase {
         Ok(new_id)
     }
 
+    async fn create_lease_if_free(&self, lease: &Lease) -> anyhow::Result<Option<i64>> {
+        let now = chrono::Utc::now().timestamp();
+        let ip = lease.ip_address;
+        let mut id = self.next_lease_id.write().await;
+        let static_ips = self.static_ips.read().await;
+        let mut leases = self.leases.write().await;
+        if static_ips.iter().any(|s| s.ip_address == ip)
+            || leases.iter().any(|l| l.ip_address == ip && l.lease_end > now)
+        {
+            return Ok(None);
+        }
+
+        let new_id = *id;
+        *id += 1;
+        leases.push(Lease {
+            id: Some(new_id),
+            ..lease.clone()
+        });
+        Ok(Some(new_id))
+    }
+
+    async fn replace_lease(&self, old_id: i64, lease: &Lease) -> anyhow::Result<Option<i64>> {
+        let now = chrono::Utc::now().timestamp();
+        let ip = lease.ip_address;
+        let mut id = self.next_lease_id.write().await;
+        let static_ips = self.static_ips.read().await;
+        let mut leases = self.leases.write().await;
+        if static_ips.iter().any(|s| s.ip_address == ip)
+            || leases
+                .iter()
+                .any(|l| l.id != Some(old_id) && l.ip_address == ip && l.lease_end > now)
+        {
+            return Ok(None);
+        }
+
+        if let Some(old) = leases.iter_mut().find(|l| l.id == Some(old_id)) {
+            old.lease_end = old.lease_end.min(now);
+        }
+        let new_id = *id;
+        *id += 1;
+        leases.push(Lease {
+            id: Some(new_id),
+            ..lease.clone()
+        });
+        Ok(Some(new_id))
+    }
+
     async fn get_active_lease(&self, mac: &str) -> anyhow::Result<Option<Lease>> {
         let mac_lower = mac.to_lowercase();
         let now = chrono::Utc::now().timestamp();
@@ -183,6 +408,16 @@ impl Database for InMemoryDatabase {
             .cloned())
     }
 
+    async fn get_active_lease_by_ip(&self, ip: Ipv4Addr) -> anyhow::Result<Option<Lease>> {
+        let now = chrono::Utc::now().timestamp();
+        let leases = self.leases.read().await;
+        Ok(leases
+            .iter()
+            .filter(|l| l.ip_address == ip && l.lease_end > now)
+            .max_by_key(|l| l.lease_end)
+            .cloned())
+    }
+
     async fn list_active_leases(&self) -> anyhow::Result<Vec<Lease>> {
         let now = chrono::Utc::now().timestamp();
         let leases = self.leases.read().await;
@@ -199,12 +434,138 @@ impl Database for InMemoryDatabase {
         Ok(())
     }
 
-    async fn delete_expired_leases(&self) -> anyhow::Result<u64> {
+    async fn release_lease(&self, id: i64, at: i64) -> anyhow::Result<()> {
+        let mut leases = self.leases.write().await;
+        let mut released = self.released_leases.write().await;
+        if let Some(lease) = leases.iter_mut().find(|l| l.id == Some(id)) {
+            lease.lease_end = at;
+            released.insert(id);
+        }
+        Ok(())
+    }
+
+    async fn list_leases_released_since(&self, since: i64) -> anyhow::Result<Vec<Lease>> {
         let now = chrono::Utc::now().timestamp();
+        let leases = self.leases.read().await;
+        let released = self.released_leases.read().await;
+        Ok(leases
+            .iter()
+            .filter(|l| l.id.is_some_and(|id| released.contains(&id)))
+            .filter(|l| l.lease_end > since && l.lease_end <= now)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_recent_lease(&self, mac: &str, since: i64) -> anyhow::Result<Option<Lease>> {
+        let mac_lower = mac.to_lowercase();
+        let leases = self.leases.read().await;
+        Ok(leases
+            .iter()
+            .filter(|l| l.mac_address.to_lowercase() == mac_lower && l.lease_end > since)
+            .max_by_key(|l| l.lease_end)
+            .cloned())
+    }
+
+    async fn delete_expired_leases(&self) -> anyhow::Result<u64> {
+        self.delete_leases_expired_before(chrono::Utc::now().timestamp())
+            .await
+    }
+
+    async fn delete_leases_expired_before(&self, before: i64) -> anyhow::Result<u64> {
         let mut leases = self.leases.write().await;
-        let before = leases.len();
-        leases.retain(|l| l.lease_end >= now);
-        Ok((before - leases.len()) as u64)
+        let count = leases.len();
+        leases.retain(|l| l.lease_end >= before);
+        Ok((count - leases.len()) as u64)
+    }
+
+    async fn list_subnet_addresses(&self, subnet_id: i64) -> anyhow::Result<Vec<SubnetAddress>> {
+        let now = chrono::Utc::now().timestamp();
+        let leases = self.leases.read().await;
+        let static_ips = self.static_ips.read().await;
+        let mut addresses: Vec<SubnetAddress> = leases
+            .iter()
+            .filter(|l| l.subnet_id == subnet_id && l.lease_end > now)
+            .map(|l| SubnetAddress {
+                ip: l.ip_address,
+                mac: l.mac_address.clone(),
+                hostname: l.hostname.clone(),
+                source: AddressSource::Lease,
+            })
+            .chain(static_ips.iter().filter(|s| s.subnet_id == subnet_id).map(|s| {
+                SubnetAddress {
+                    ip: s.ip_address,
+                    mac: s.mac_address.clone(),
+                    hostname: s.hostname.clone(),
+                    source: AddressSource::Static,
+                }
+            }))
+            .collect();
+        addresses.sort_by_key(|a| a.ip);
+        Ok(addresses)
+    }
+
+    // Pending offer operations
+    async fn record_pending_offer(&self, offer: &PendingOffer) -> anyhow::Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let mut offers = self.pending_offers.write().await;
+        offers.retain(|o| o.expires_at > now && o.ip_address != offer.ip_address);
+        offers.push(offer.clone());
+        Ok(())
+    }
+
+    async fn list_pending_offers(&self) -> anyhow::Result<Vec<PendingOffer>> {
+        let now = chrono::Utc::now().timestamp();
+        let offers = self.pending_offers.read().await;
+        Ok(offers.iter().filter(|o| o.expires_at > now).cloned().collect())
+    }
+
+    async fn delete_pending_offer(&self, ip: Ipv4Addr) -> anyhow::Result<()> {
+        let mut offers = self.pending_offers.write().await;
+        offers.retain(|o| o.ip_address != ip);
+        Ok(())
+    }
+
+    // Option template operations
+    async fn create_option_template(&self, template: &OptionTemplate) -> anyhow::Result<i64> {
+        let mut id = self.next_option_template_id.write().await;
+        let new_id = *id;
+        *id += 1;
+
+        let mut templates = self.option_templates.write().await;
+        let mut new_template = template.clone();
+        new_template.id = Some(new_id);
+        templates.push(new_template);
+
+        Ok(new_id)
+    }
+
+    async fn get_option_template(&self, id: i64) -> anyhow::Result<Option<OptionTemplate>> {
+        let templates = self.option_templates.read().await;
+        Ok(templates.iter().find(|t| t.id == Some(id)).cloned())
+    }
+
+    async fn list_option_templates(&self) -> anyhow::Result<Vec<OptionTemplate>> {
+        let templates = self.option_templates.read().await;
+        Ok(templates.clone())
+    }
+
+    async fn update_option_template(&self, id: i64, template: &OptionTemplate) -> anyhow::Result<()> {
+        let mut templates = self.option_templates.write().await;
+        if let Some(existing) = templates.iter_mut().find(|t| t.id == Some(id)) {
+            *existing = template.clone();
+            existing.id = Some(id);
+        }
+        Ok(())
+    }
+
+    async fn delete_option_template(&self, id: i64) -> anyhow::Result<()> {
+        let mut subnets = self.subnets.write().await;
+        let mut templates = self.option_templates.write().await;
+        templates.retain(|t| t.id != Some(id));
+        for subnet in subnets.iter_mut().filter(|s| s.template_id == Some(id)) {
+            subnet.template_id = None;
+        }
+        Ok(())
     }
 
     // IPv6 Prefix (IA Prefix) operations
@@ -254,12 +615,12 @@ impl Database for InMemoryDatabase {
     }
 
     // Token operations
-    async fn list_tokens(&self) -> anyhow::Result<Vec<(String, i64)>> {
+    async fn list_tokens(&self) -> anyhow::Result<Vec<(i64, String, i64)>> {
         let tokens = self.tokens.read().await;
         Ok(tokens
             .iter()
             .filter(|(_, _, _, enabled)| *enabled == 1)
-            .map(|(_, _, token_hash, enabled)| (token_hash.clone(), *enabled))
+            .map(|(id, _, token_hash, enabled)| (*id, token_hash.clone(), *enabled))
             .collect())
     }
 
@@ -309,6 +670,47 @@ impl Database for InMemoryDatabase {
         // No-op for in-memory database
         Ok(())
     }
+
+    // Maintenance
+    async fn backup_to(&self, _path: &Path) -> anyhow::Result<u64> {
+        anyhow::bail!("Backups are not supported by the in-memory database")
+    }
+
+    async fn rewrite_client_identities(
+        &self,
+        rewrite: &ClientIdentityRewrite<'_>,
+    ) -> anyhow::Result<u64> {
+        let mut static_ips = self.static_ips.write().await;
+        let mut leases = self.leases.write().await;
+
+        // Rewrite everything before storing anything, so a failure leaves
+        // every row as it was
+        let lease_updates = leases
+            .iter()
+            .map(|l| rewrite(&l.mac_address, l.hostname.as_deref()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let static_updates = static_ips
+            .iter()
+            .map(|s| rewrite(&s.mac_address, s.hostname.as_deref()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut rewritten = 0;
+        for (lease, update) in leases.iter_mut().zip(lease_updates) {
+            if let Some((mac, hostname)) = update {
+                lease.mac_address = mac;
+                lease.hostname = hostname;
+                rewritten += 1;
+            }
+        }
+        for (static_ip, update) in static_ips.iter_mut().zip(static_updates) {
+            if let Some((mac, hostname)) = update {
+                static_ip.mac_address = mac;
+                static_ip.hostname = hostname;
+                rewritten += 1;
+            }
+        }
+        Ok(rewritten)
+    }
 }
 
 #[cfg(test)]
@@ -330,14 +732,20 @@ mod tests {
         suite::test_update_subnet(&db).await;
         suite::test_delete_subnet(&db).await;
         suite::test_get_subnet_not_found(&db).await;
+        suite::test_get_subnets_by_ids(&db).await;
+        suite::test_option_templates(&db).await;
+        suite::test_renumber_subnet(&db).await;
     }
 
     #[tokio::test]
     async fn test_range_crud() {
         let db = InMemoryDatabase::new();
         suite::test_create_and_list_range(&db).await;
+        suite::test_create_subnet_with_ranges(&db).await;
         suite::test_list_ranges_all(&db).await;
         suite::test_delete_range(&db).await;
+        suite::test_toggle_range(&db).await;
+        suite::test_set_range_draining(&db).await;
     }
 
     #[tokio::test]
@@ -347,6 +755,7 @@ mod tests {
         suite::test_get_static_ip_by_mac(&db).await;
         suite::test_get_static_ip_by_mac_not_found(&db).await;
         suite::test_delete_static_ip(&db).await;
+        suite::test_delete_static_ips_by_subnet(&db).await;
     }
 
     #[tokio::test]
@@ -356,6 +765,12 @@ mod tests {
         suite::test_list_active_leases(&db).await;
         suite::test_expire_lease(&db).await;
         suite::test_expired_lease_not_returned(&db).await;
+        suite::test_get_active_lease_by_ip(&db).await;
+        suite::test_list_subnet_addresses(&db).await;
+        suite::test_pending_offers(&db).await;
+        suite::test_recent_lease_outlives_expiry(&db).await;
+        suite::test_release_lease(&db).await;
+        suite::test_lease_range_id(&db).await;
     }
 
     #[tokio::test]
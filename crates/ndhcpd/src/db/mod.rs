@@ -1,11 +1,22 @@
-use crate::models::{ApiToken, DynamicRange, IAPrefix, Lease, StaticIP, Subnet};
+use crate::models::{
+    ApiToken, DynamicRange, IAPrefix, Lease, OptionTemplate, PendingOffer, StaticIP, Subnet,
+    SubnetAddress, SubnetPatch,
+};
+use std::net::Ipv4Addr;
+use std::path::Path;
 use std::sync::Arc;
 
+pub mod cached;
+pub mod encrypted;
 pub mod memory;
+pub mod retry;
+pub mod seed;
 pub mod sqlite;
 #[cfg(test)]
 pub(crate) mod tests;
 
+pub use cached::CachedDatabase;
+pub use encrypted::EncryptedDatabase;
 pub use memory::InMemoryDatabase;
 pub use sqlite::SqliteDatabase;
 
@@ -23,6 +34,45 @@ pub fn is_unique_violation(e: &anyhow::Error) -> bool {
         .unwrap_or(false)
 }
 
+/// Replacement `(mac_address, hostname)` for a row's current values, or
+/// `None` to leave the row as it is. See [`Database::rewrite_client_identities`].
+pub type ClientIdentityRewrite<'a> = dyn Fn(&str, Option<&str>) -> anyhow::Result<Option<(String, Option<String>)>>
+    + Send
+    + Sync
+    + 'a;
+
+/// Check run by [`Database::patch_subnet`] on the merged subnet, given every
+/// other subnet; an error aborts the patch and is returned as is.
+pub type SubnetCheck<'a> = dyn Fn(&Subnet, &[Subnet]) -> anyhow::Result<()> + Send + Sync + 'a;
+
+/// A subnet and what it holds, as read by [`Database::renumber_subnet`]
+#[derive(Debug, Clone)]
+pub struct SubnetContents {
+    pub subnet: Subnet,
+    /// Every other subnet
+    pub others: Vec<Subnet>,
+    pub ranges: Vec<DynamicRange>,
+    pub static_ips: Vec<StaticIP>,
+    /// Active leases of the subnet
+    pub leases: Vec<Lease>,
+}
+
+/// What [`Database::renumber_subnet`] writes: the subnet moved onto its new
+/// network, the ranges replacing its own (matched by ID), the static IPs
+/// replacing its own, and the IDs of the leases to end
+#[derive(Debug, Clone)]
+pub struct Renumbering {
+    pub subnet: Subnet,
+    pub ranges: Vec<DynamicRange>,
+    pub static_ips: Vec<StaticIP>,
+    pub expired_leases: Vec<i64>,
+}
+
+/// Plan run by [`Database::renumber_subnet`] on the subnet's current
+/// contents; an error aborts the renumbering and is returned as is.
+pub type RenumberPlan<'a> =
+    dyn Fn(&SubnetContents) -> anyhow::Result<Renumbering> + Send + Sync + 'a;
+
 /// Database trait - defines the interface for all database implementations
 #[async_trait::async_trait]
 pub trait Database: Send + Sync {
@@ -30,13 +80,46 @@ pub trait Database: Send + Sync {
     async fn create_subnet(&self, subnet: &Subnet) -> anyhow::Result<i64>;
     async fn get_subnet(&self, id: i64) -> anyhow::Result<Option<Subnet>>;
     async fn list_subnets(&self) -> anyhow::Result<Vec<Subnet>>;
+    /// Fetch the subnets matching `ids`; ids that do not exist are skipped.
+    async fn get_subnets_by_ids(&self, ids: &[i64]) -> anyhow::Result<Vec<Subnet>>;
     async fn update_subnet(&self, id: i64, subnet: &Subnet) -> anyhow::Result<()>;
+    /// Merge `patch` over the stored subnet and, if `check` accepts the
+    /// result, store it, all atomically. Returns the merged subnet, or `None`
+    /// if no subnet has this ID.
+    async fn patch_subnet(
+        &self,
+        id: i64,
+        patch: &SubnetPatch,
+        check: &SubnetCheck<'_>,
+    ) -> anyhow::Result<Option<Subnet>>;
     async fn delete_subnet(&self, id: i64) -> anyhow::Result<()>;
+    /// Create `subnet` together with `ranges` and `static_ips` (attached to
+    /// the new subnet whatever their `subnet_id`) atomically: on error nothing
+    /// is created. Returns the new subnet ID and the IDs of its ranges.
+    async fn create_subnet_with_ranges(
+        &self,
+        subnet: &Subnet,
+        ranges: &[DynamicRange],
+        static_ips: &[StaticIP],
+    ) -> anyhow::Result<(i64, Vec<i64>)>;
+    /// Move subnet `id` onto a new network atomically: its contents are read
+    /// and the [`Renumbering`] planned from them is written in the same
+    /// transaction. Ended leases keep their row, with `lease_end` set to now.
+    /// Returns `None` if the subnet does not exist.
+    async fn renumber_subnet(
+        &self,
+        id: i64,
+        plan: &RenumberPlan<'_>,
+    ) -> anyhow::Result<Option<(SubnetContents, Renumbering)>>;
 
     // Dynamic Range operations
     async fn create_range(&self, range: &DynamicRange) -> anyhow::Result<i64>;
     async fn list_ranges(&self, subnet_id: Option<i64>) -> anyhow::Result<Vec<DynamicRange>>;
     async fn delete_range(&self, id: i64) -> anyhow::Result<()>;
+    /// Put a range in or out of the allocation pool, keeping its record.
+    async fn toggle_range(&self, id: i64, enabled: bool) -> anyhow::Result<()>;
+    /// Stop or resume new allocations from a range; its leases still renew.
+    async fn set_range_draining(&self, id: i64, draining: bool) -> anyhow::Result<()>;
 
     // Static IP operations
     async fn create_static_ip(&self, static_ip: &StaticIP) -> anyhow::Result<()>;
@@ -44,14 +127,60 @@ pub trait Database: Send + Sync {
     async fn get_static_ip_by_mac(&self, mac: &str) -> anyhow::Result<Option<StaticIP>>;
     async fn update_static_ip_hostname(&self, ip_address: &str, hostname: Option<String>) -> anyhow::Result<()>;
     async fn delete_static_ip(&self, ip_address: &str) -> anyhow::Result<()>;
+    /// Delete every static IP of a subnet at once. Returns how many were deleted.
+    async fn delete_static_ips_by_subnet(&self, subnet_id: i64) -> anyhow::Result<u64>;
+    /// Create `static_ip` unless its address is held by an active lease or
+    /// another static IP, checked and written atomically. Returns false,
+    /// storing nothing, when the address is taken.
+    async fn create_static_ip_if_free(&self, static_ip: &StaticIP) -> anyhow::Result<bool>;
 
     // Lease operations
     async fn create_lease(&self, lease: &Lease) -> anyhow::Result<i64>;
+    /// Create `lease` unless its address is held by an active lease or a
+    /// static IP, checked and written atomically. Returns the new lease ID,
+    /// or `None`, storing nothing, when the address is taken.
+    async fn create_lease_if_free(&self, lease: &Lease) -> anyhow::Result<Option<i64>>;
+    /// End lease `old_id` and create `lease` in its place in one step, as
+    /// [`create_lease_if_free`](Self::create_lease_if_free) with the ended
+    /// lease no longer holding its address. Returns `None`, changing nothing,
+    /// when the address is taken by another lease or a static IP.
+    async fn replace_lease(&self, old_id: i64, lease: &Lease) -> anyhow::Result<Option<i64>>;
     async fn get_active_lease(&self, mac: &str) -> anyhow::Result<Option<Lease>>;
+    /// Active lease currently holding `ip`, if any.
+    async fn get_active_lease_by_ip(&self, ip: Ipv4Addr) -> anyhow::Result<Option<Lease>>;
     async fn list_active_leases(&self) -> anyhow::Result<Vec<Lease>>;
     async fn expire_lease(&self, id: i64) -> anyhow::Result<()>;
+    /// End a lease released by its client at `at`, keeping its record,
+    /// unlike `expire_lease`.
+    async fn release_lease(&self, id: i64, at: i64) -> anyhow::Result<()>;
+    /// Released leases that ended after `since`. Leases that ran out or were
+    /// ended otherwise are not listed.
+    async fn list_leases_released_since(&self, since: i64) -> anyhow::Result<Vec<Lease>>;
+    /// Most recent lease of `mac` ending after `since`, expired or not.
+    async fn get_recent_lease(&self, mac: &str, since: i64) -> anyhow::Result<Option<Lease>>;
     /// Delete all leases whose `lease_end` is in the past.
     async fn delete_expired_leases(&self) -> anyhow::Result<u64>;
+    /// Delete all leases whose `lease_end` is before `before`.
+    async fn delete_leases_expired_before(&self, before: i64) -> anyhow::Result<u64>;
+    /// Every address of a subnet held by an active lease or a static
+    /// assignment, sorted by IP.
+    async fn list_subnet_addresses(&self, subnet_id: i64) -> anyhow::Result<Vec<SubnetAddress>>;
+
+    // Pending offer operations
+    /// Remember an OFFER, replacing any previous offer of the same address.
+    async fn record_pending_offer(&self, offer: &PendingOffer) -> anyhow::Result<()>;
+    /// Offers whose `expires_at` is still in the future.
+    async fn list_pending_offers(&self) -> anyhow::Result<Vec<PendingOffer>>;
+    /// Forget the offer of `ip`, typically once its client REQUESTed it.
+    async fn delete_pending_offer(&self, ip: Ipv4Addr) -> anyhow::Result<()>;
+
+    // Option template operations
+    async fn create_option_template(&self, template: &OptionTemplate) -> anyhow::Result<i64>;
+    async fn get_option_template(&self, id: i64) -> anyhow::Result<Option<OptionTemplate>>;
+    async fn list_option_templates(&self) -> anyhow::Result<Vec<OptionTemplate>>;
+    async fn update_option_template(&self, id: i64, template: &OptionTemplate) -> anyhow::Result<()>;
+    /// Delete a template; the subnets referencing it are detached from it.
+    async fn delete_option_template(&self, id: i64) -> anyhow::Result<()>;
 
     // IPv6 Prefix (IA Prefix) operations
     async fn create_ia_prefix(&self, prefix: &IAPrefix) -> anyhow::Result<i64>;
@@ -61,13 +190,30 @@ pub trait Database: Send + Sync {
     async fn delete_ia_prefix(&self, id: i64) -> anyhow::Result<()>;
 
     // Token operations (for auth)
-    async fn list_tokens(&self) -> anyhow::Result<Vec<(String, i64)>>;
+    /// List enabled tokens as *(id, token_hash, enabled)*
+    async fn list_tokens(&self) -> anyhow::Result<Vec<(i64, String, i64)>>;
     /// List all tokens with full metadata (for API handlers)
     async fn list_api_tokens(&self) -> anyhow::Result<Vec<ApiToken>>;
     async fn create_token(&self, name: &str, token_hash: &str, salt: &str) -> anyhow::Result<i64>;
     async fn delete_token(&self, id: i64) -> anyhow::Result<()>;
     async fn toggle_token(&self, id: i64, enabled: bool) -> anyhow::Result<()>;
     async fn update_token_last_used(&self, token_hash: &str) -> anyhow::Result<()>;
+
+    // Maintenance
+    /// Write a consistent snapshot of the database to `path` without taking
+    /// it offline, replacing any previous file. Returns the snapshot size in bytes.
+    async fn backup_to(&self, path: &Path) -> anyhow::Result<u64>;
+    /// Pass the `mac_address` and `hostname` of every lease, expired ones
+    /// included, and of every static IP through `rewrite` and store the
+    /// results in place, atomically: if `rewrite` fails no row is changed.
+    /// Returns the number of rows rewritten.
+    async fn rewrite_client_identities(
+        &self,
+        rewrite: &ClientIdentityRewrite<'_>,
+    ) -> anyhow::Result<u64>;
+    /// Drop anything held in memory so the next read goes to storage.
+    /// Backends that read through on every call have nothing to drop.
+    fn refresh_cache(&self) {}
 }
 
 /// Type alias for a boxed Database trait object
@@ -0,0 +1,81 @@
+//! Bounded retries for database calls on the DHCP packet path.
+//!
+//! A locked or briefly unavailable SQLite file must not look like "no record":
+//! callers retry a few times with a short backoff and then handle the error as
+//! an error instead of, say, treating a known client as unknown.
+
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::metrics::METRICS;
+
+/// Total attempts made before the error is returned
+pub const DB_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled by the attempt number afterwards
+const BACKOFF: Duration = Duration::from_millis(20);
+
+/// Run `f` until it succeeds or [`DB_ATTEMPTS`] attempts failed. Every failure
+/// is counted in `ndhcpd_db_errors_total` under `operation`.
+pub async fn with_retry<T, F, Fut>(operation: &str, mut f: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                METRICS.db_errors.inc(&[operation]);
+                if attempt >= DB_ATTEMPTS {
+                    return Err(e);
+                }
+                warn!(
+                    "Database {} failed (attempt {}/{}): {}",
+                    operation, attempt, DB_ATTEMPTS, e
+                );
+                tokio::time::sleep(BACKOFF * attempt).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_transient_error_is_retried() {
+        let calls = AtomicU32::new(0);
+        let before = METRICS.db_errors.get(&["test_flaky_lookup"]);
+
+        let result = with_retry("test_flaky_lookup", || async {
+            if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                anyhow::bail!("database is locked");
+            }
+            Ok(Some(42))
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), Some(42));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(METRICS.db_errors.get(&["test_flaky_lookup"]), before + 1);
+    }
+
+    #[tokio::test]
+    async fn test_persistent_error_gives_up() {
+        let calls = AtomicU32::new(0);
+        let result: anyhow::Result<Option<i64>> = with_retry("test_broken_lookup", || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            anyhow::bail!("disk I/O error")
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), DB_ATTEMPTS);
+    }
+}
@@ -0,0 +1,292 @@
+//! Startup reconciliation of the subnets declared in `config.yaml`.
+//!
+//! Declared subnets are matched to stored ones by network address so that
+//! their IDs, and with them ranges, static IPs and leases, survive a restart.
+//! Stored subnets that are not declared are only deleted in `replace` mode.
+//! [`diff_subnets`] previews the same matching without writing anything.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::config::{ReconcileMode, SubnetsConfig};
+use crate::db::Database;
+use crate::models::Subnet;
+use crate::validation::Validate;
+
+/// What [`reconcile_subnets`] changed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReconcileReport {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+/// Declared subnets compared with the stored ones, as [`diff_subnets`]
+/// reports them
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubnetsDiff {
+    /// What happens to `only_in_database` on reconciliation
+    pub reconcile: ReconcileMode,
+    /// Why reconciliation would refuse the declared subnets, see
+    /// [`check_subnets`]: nothing is applied while any remain
+    pub problems: Vec<String>,
+    /// Declared subnets with no stored counterpart, to be created
+    pub only_in_config: Vec<Subnet>,
+    /// Stored subnets that are not declared, deleted in `replace` mode only
+    pub only_in_database: Vec<Subnet>,
+    /// Declared subnets whose stored counterpart has other settings
+    pub differing: Vec<SubnetDifference>,
+}
+
+/// A declared subnet and its stored counterpart
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubnetDifference {
+    pub declared: Subnet,
+    pub stored: Subnet,
+    /// Names of the fields that differ (e.g. `gateway`)
+    pub fields: Vec<String>,
+}
+
+/// Stored subnet a declared one is reconciled with: the one on the same
+/// network address
+fn counterpart<'a>(declared: &Subnet, stored: &'a [Subnet]) -> Option<&'a Subnet> {
+    stored.iter().find(|s| s.network == declared.network)
+}
+
+/// Fields on which `declared` and `stored` disagree, the ID aside
+fn differing_fields(declared: &Subnet, stored: &Subnet) -> Vec<String> {
+    let checks = [
+        ("netmask", declared.netmask != stored.netmask),
+        ("gateway", declared.gateway != stored.gateway),
+        ("dns_servers", declared.dns_servers != stored.dns_servers),
+        ("ntp_servers", declared.ntp_servers != stored.ntp_servers),
+        ("domain_name", declared.domain_name != stored.domain_name),
+        ("authoritative", declared.authoritative != stored.authoritative),
+        ("default_lease_time", declared.default_lease_time != stored.default_lease_time),
+        ("template_id", declared.template_id != stored.template_id),
+    ];
+    checks
+        .into_iter()
+        .filter(|(_, differs)| *differs)
+        .map(|(field, _)| field.to_string())
+        .collect()
+}
+
+/// What [`reconcile_subnets`] would do with `config`, without writing
+/// anything to `db`
+pub async fn diff_subnets(
+    config: &SubnetsConfig,
+    db: &dyn Database,
+) -> anyhow::Result<SubnetsDiff> {
+    let stored = db.list_subnets().await?;
+    let mut diff = SubnetsDiff {
+        reconcile: config.reconcile,
+        problems: check_subnets(config),
+        only_in_config: Vec::new(),
+        only_in_database: Vec::new(),
+        differing: Vec::new(),
+    };
+
+    for declared in &config.networks {
+        match counterpart(declared, &stored) {
+            Some(existing) => {
+                let fields = differing_fields(declared, existing);
+                if !fields.is_empty() {
+                    diff.differing.push(SubnetDifference {
+                        declared: Subnet {
+                            id: existing.id,
+                            ..declared.clone()
+                        },
+                        stored: existing.clone(),
+                        fields,
+                    });
+                }
+            }
+            None => diff.only_in_config.push(Subnet {
+                id: None,
+                ..declared.clone()
+            }),
+        }
+    }
+    diff.only_in_database = stored
+        .into_iter()
+        .filter(|s| !config.networks.iter().any(|d| d.network == s.network))
+        .collect();
+
+    Ok(diff)
+}
+
+/// Why [`reconcile_subnets`] refuses `config`: invalid declared subnets and
+/// networks declared more than once. Empty when it can be applied.
+pub fn check_subnets(config: &SubnetsConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut networks = HashSet::new();
+    for subnet in &config.networks {
+        let name = format!("{}/{}", subnet.network, subnet.netmask);
+        if let Err(errors) = subnet.validate() {
+            for e in errors.errors {
+                problems.push(format!("{name}: {}: {}", e.field, e.message));
+            }
+        }
+        if !networks.insert(subnet.network) {
+            problems.push(format!("{name}: network declared more than once"));
+        }
+    }
+    problems
+}
+
+/// Upsert the declared subnets into `db`. Nothing is written unless every
+/// declared subnet is valid and no network is declared twice.
+pub async fn reconcile_subnets(
+    config: &SubnetsConfig,
+    db: &dyn Database,
+) -> anyhow::Result<ReconcileReport> {
+    let problems = check_subnets(config);
+    if !problems.is_empty() {
+        anyhow::bail!("invalid subnets configuration: {}", problems.join("; "));
+    }
+    let networks: HashSet<_> = config.networks.iter().map(|s| s.network).collect();
+
+    let stored = db.list_subnets().await?;
+    let mut report = ReconcileReport::default();
+
+    for declared in &config.networks {
+        match counterpart(declared, &stored) {
+            Some(existing) => {
+                let id = existing.id.expect("stored subnet has an id");
+                let mut subnet = declared.clone();
+                subnet.id = Some(id);
+                db.update_subnet(id, &subnet).await?;
+                report.updated += 1;
+            }
+            None => {
+                let mut subnet = declared.clone();
+                subnet.id = None;
+                db.create_subnet(&subnet).await?;
+                report.created += 1;
+            }
+        }
+    }
+
+    if config.reconcile == ReconcileMode::Replace {
+        for subnet in stored.iter().filter(|s| !networks.contains(&s.network)) {
+            if let Some(id) = subnet.id {
+                db.delete_subnet(id).await?;
+                report.deleted += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::InMemoryDatabase;
+
+    fn declared(cidr: &str) -> Subnet {
+        Subnet::from_cidr(cidr).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_two_subnets_into_empty_db() {
+        let db = InMemoryDatabase::new();
+        let config = SubnetsConfig {
+            reconcile: ReconcileMode::Merge,
+            networks: vec![declared("192.168.1.0/24"), declared("10.0.0.0/16")],
+        };
+
+        let report = reconcile_subnets(&config, &db).await.unwrap();
+        assert_eq!(report, ReconcileReport { created: 2, updated: 0, deleted: 0 });
+
+        let mut networks: Vec<_> = db
+            .list_subnets()
+            .await
+            .unwrap()
+            .iter()
+            .map(|s| format!("{}/{}", s.network, s.netmask))
+            .collect();
+        networks.sort();
+        assert_eq!(networks, ["10.0.0.0/16", "192.168.1.0/24"]);
+
+        // A second boot updates in place and keeps the IDs
+        let ids: Vec<_> = db.list_subnets().await.unwrap().iter().map(|s| s.id).collect();
+        let report = reconcile_subnets(&config, &db).await.unwrap();
+        assert_eq!(report, ReconcileReport { created: 0, updated: 2, deleted: 0 });
+        let after: Vec<_> = db.list_subnets().await.unwrap().iter().map(|s| s.id).collect();
+        assert_eq!(ids, after);
+    }
+
+    #[tokio::test]
+    async fn test_merge_keeps_api_subnets_and_replace_deletes_them() {
+        let db = InMemoryDatabase::new();
+        db.create_subnet(&declared("172.16.0.0/24")).await.unwrap();
+        let mut config = SubnetsConfig {
+            reconcile: ReconcileMode::Merge,
+            networks: vec![declared("192.168.1.0/24")],
+        };
+
+        reconcile_subnets(&config, &db).await.unwrap();
+        assert_eq!(db.list_subnets().await.unwrap().len(), 2);
+
+        config.reconcile = ReconcileMode::Replace;
+        let report = reconcile_subnets(&config, &db).await.unwrap();
+        assert_eq!(report.deleted, 1);
+        let subnets = db.list_subnets().await.unwrap();
+        assert_eq!(subnets.len(), 1);
+        assert_eq!(subnets[0].network, declared("192.168.1.0/24").network);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_declaration_writes_nothing() {
+        let db = InMemoryDatabase::new();
+        let config = SubnetsConfig {
+            reconcile: ReconcileMode::Merge,
+            networks: vec![declared("192.168.1.0/24"), declared("192.168.1.0/24")],
+        };
+
+        let err = reconcile_subnets(&config, &db).await.unwrap_err().to_string();
+        assert!(err.contains("declared more than once"), "{err}");
+        assert!(db.list_subnets().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_diff_reports_without_writing() {
+        let db = InMemoryDatabase::new();
+        let stored_id = db.create_subnet(&declared("192.168.1.0/24")).await.unwrap();
+        db.create_subnet(&declared("10.0.0.0/16")).await.unwrap();
+        db.create_subnet(&declared("172.16.0.0/24")).await.unwrap();
+        let differing = Subnet {
+            gateway: "192.168.1.254".parse().unwrap(),
+            authoritative: true,
+            ..declared("192.168.1.0/24")
+        };
+        let config = SubnetsConfig {
+            reconcile: ReconcileMode::Merge,
+            networks: vec![differing, declared("10.0.0.0/16"), declared("192.168.2.0/24")],
+        };
+
+        let diff = diff_subnets(&config, &db).await.unwrap();
+        assert_eq!(diff.reconcile, ReconcileMode::Merge);
+        assert_eq!(diff.differing.len(), 1);
+        let difference = &diff.differing[0];
+        assert_eq!(difference.stored.id, Some(stored_id));
+        assert_eq!(difference.declared.id, Some(stored_id));
+        assert_eq!(difference.stored.gateway, declared("192.168.1.0/24").gateway);
+        assert_eq!(difference.fields, ["gateway", "authoritative"]);
+        let networks = |subnets: &[Subnet]| {
+            subnets.iter().map(|s| format!("{}/{}", s.network, s.netmask)).collect::<Vec<_>>()
+        };
+        assert_eq!(networks(&diff.only_in_config), ["192.168.2.0/24"]);
+        assert_eq!(networks(&diff.only_in_database), ["172.16.0.0/24"]);
+
+        // Nothing was written
+        let subnets = db.list_subnets().await.unwrap();
+        assert_eq!(subnets.len(), 3);
+        let stored = db.get_subnet(stored_id).await.unwrap().unwrap();
+        assert!(!stored.authoritative);
+    }
+}
@@ -1,8 +1,111 @@
-use crate::models::{ApiToken, DynamicRange, IAPrefix, Lease, StaticIP, Subnet};
-use sqlx::{sqlite::SqliteConnectOptions, Row, SqlitePool};
+use crate::models::{
+    AddressSource, ApiToken, DynamicRange, IAPrefix, Lease, OptionTemplate, PendingOffer,
+    PrefixLen, StaticIP, Subnet, SubnetAddress, SubnetPatch,
+};
+use anyhow::Context;
+use sqlx::{
+    migrate::MigrateError,
+    sqlite::{SqliteConnectOptions, SqliteRow},
+    Row, SqlitePool,
+};
+use std::net::Ipv4Addr;
+use std::path::Path;
 use std::str::FromStr;
 
-use super::Database;
+use super::{
+    ClientIdentityRewrite, Database, RenumberPlan, Renumbering, SubnetCheck, SubnetContents,
+};
+
+/// Why the schema migrations could not be applied on startup, with what the
+/// operator can do about it
+#[derive(Debug)]
+pub enum MigrationError {
+    /// An applied migration was modified after it ran
+    ChecksumMismatch(i64),
+    /// An applied migration is unknown to this build, e.g. after a downgrade
+    MissingMigration(i64),
+    /// A migration was interrupted half-way
+    PartiallyApplied(i64),
+    /// A migration's SQL failed
+    Sql {
+        version: Option<i64>,
+        source: sqlx::Error,
+    },
+    /// Any other failure of the migrator
+    Other(MigrateError),
+}
+
+impl From<MigrateError> for MigrationError {
+    fn from(e: MigrateError) -> Self {
+        match e {
+            MigrateError::VersionMismatch(v) => Self::ChecksumMismatch(v),
+            MigrateError::VersionMissing(v) => Self::MissingMigration(v),
+            MigrateError::Dirty(v) => Self::PartiallyApplied(v),
+            MigrateError::ExecuteMigration(source, v) => Self::Sql {
+                version: Some(v),
+                source,
+            },
+            MigrateError::Execute(source) => Self::Sql {
+                version: None,
+                source,
+            },
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ChecksumMismatch(v) => write!(
+                f,
+                "migration {v} was modified after it was applied; reinstall the release that \
+                 applied it or restore the database from a backup"
+            ),
+            Self::MissingMigration(v) => write!(
+                f,
+                "the database was migrated by a newer release (migration {v} is unknown); \
+                 run that release again or restore a backup taken before the upgrade"
+            ),
+            Self::PartiallyApplied(v) => write!(
+                f,
+                "migration {v} is only partially applied; restore the database from a backup \
+                 or finish it by hand and delete its row from _sqlx_migrations"
+            ),
+            Self::Sql {
+                version: Some(v),
+                source,
+            } => write!(
+                f,
+                "migration {v} failed: {source}; the database was left at the previous \
+                 version, check disk space and file permissions before restarting"
+            ),
+            Self::Sql {
+                version: None,
+                source,
+            } => write!(
+                f,
+                "could not read the migration history: {source}; check that the file is a \
+                 readable ndhcpd database"
+            ),
+            Self::Other(e) => write!(f, "database migration failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Sql { source, .. } => Some(source),
+            Self::Other(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Most `?` parameters bound in one statement. SQLite builds before 3.32
+/// refuse more than 999, so longer ID lists are queried in chunks.
+const MAX_BIND_PARAMS: usize = 999;
 
 /// SQLite implementation of the Database trait
 pub struct SqliteDatabase {
@@ -17,7 +120,10 @@ impl SqliteDatabase {
         let pool = SqlitePool::connect_with(options).await?;
 
         // Run migrations
-        sqlx::migrate!("./migrations").run(&pool).await?;
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .map_err(MigrationError::from)?;
 
         Ok(Self { pool })
     }
@@ -28,19 +134,79 @@ impl SqliteDatabase {
     }
 }
 
+/// Query with arguments still being bound
+type SqliteQuery<'q> = sqlx::query::Query<'q, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'q>>;
+
+/// Subnet read from a row selecting every column of `subnets`
+fn subnet_from_row(r: &SqliteRow) -> anyhow::Result<Subnet> {
+    Ok(Subnet {
+        id: r.get("id"),
+        network: r.get::<String, _>("network").parse()?,
+        netmask: PrefixLen::new(r.get::<i64, _>("netmask") as u8)?,
+        gateway: r.get::<String, _>("gateway").parse()?,
+        dns_servers: Subnet::dns_servers_from_string(&r.get::<String, _>("dns_servers")),
+        ntp_servers: Subnet::ntp_servers_from_string(&r.get::<String, _>("ntp_servers")),
+        domain_name: r.get("domain_name"),
+        authoritative: r.get::<i64, _>("authoritative") != 0,
+        default_lease_time: r
+            .get::<Option<i64>, _>("default_lease_time")
+            .map(|t| t as u32),
+        template_id: r.get("template_id"),
+    })
+}
+
+/// Bind the columns of `subnet` after its ID, in table order, as the
+/// leading parameters of an INSERT or UPDATE
+fn bind_subnet<'q>(query: SqliteQuery<'q>, subnet: &'q Subnet) -> SqliteQuery<'q> {
+    query
+        .bind(subnet.network.to_string())
+        .bind(subnet.netmask.get() as i64)
+        .bind(subnet.gateway.to_string())
+        .bind(subnet.dns_servers_to_string())
+        .bind(subnet.ntp_servers_to_string())
+        .bind(&subnet.domain_name)
+        .bind(subnet.authoritative as i64)
+        .bind(subnet.default_lease_time.map(|t| t as i64))
+        .bind(subnet.template_id)
+}
+
+/// Insert `lease` unless its address is held at `now` by an active lease or
+/// a static IP, in a single statement. Returns the new lease ID, if inserted.
+async fn insert_lease_if_free<'e>(
+    executor: impl sqlx::SqliteExecutor<'e>,
+    lease: &Lease,
+    now: i64,
+) -> anyhow::Result<Option<i64>> {
+    let ip = lease.ip_address.to_string();
+    let result = sqlx::query(
+        "INSERT INTO leases (subnet_id, mac_address, ip_address, lease_start, lease_end, hostname, range_id) SELECT ?, ?, ?, ?, ?, ?, ? WHERE NOT EXISTS (SELECT 1 FROM leases WHERE ip_address = ? AND lease_end > ?) AND NOT EXISTS (SELECT 1 FROM static_ips WHERE ip_address = ?)"
+    )
+    .bind(lease.subnet_id)
+    .bind(&lease.mac_address)
+    .bind(&ip)
+    .bind(lease.lease_start)
+    .bind(lease.lease_end)
+    .bind(&lease.hostname)
+    .bind(lease.range_id)
+    .bind(&ip)
+    .bind(now)
+    .bind(&ip)
+    .execute(executor)
+    .await?;
+
+    Ok((result.rows_affected() > 0).then(|| result.last_insert_rowid()))
+}
+
 #[async_trait::async_trait]
 impl Database for SqliteDatabase {
     // Subnet operations
     async fn create_subnet(&self, subnet: &Subnet) -> anyhow::Result<i64> {
-        let dns_servers = subnet.dns_servers_to_string();
-        let result = sqlx::query(
-            "INSERT INTO subnets (network, netmask, gateway, dns_servers, domain_name) VALUES (?, ?, ?, ?, ?)"
+        let result = bind_subnet(
+            sqlx::query(
+                "INSERT INTO subnets (network, netmask, gateway, dns_servers, ntp_servers, domain_name, authoritative, default_lease_time, template_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            ),
+            subnet,
         )
-        .bind(subnet.network.to_string())
-        .bind(subnet.netmask as i64)
-        .bind(subnet.gateway.to_string())
-        .bind(dns_servers)
-        .bind(&subnet.domain_name)
         .execute(&self.pool)
         .await?;
 
@@ -49,52 +215,50 @@ impl Database for SqliteDatabase {
 
     async fn get_subnet(&self, id: i64) -> anyhow::Result<Option<Subnet>> {
         let row = sqlx::query(
-            "SELECT id, network, netmask, gateway, dns_servers, domain_name FROM subnets WHERE id = ?"
+            "SELECT id, network, netmask, gateway, dns_servers, ntp_servers, domain_name, authoritative, default_lease_time, template_id FROM subnets WHERE id = ?"
         )
         .bind(id)
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(row.map(|r| Subnet {
-            id: r.get("id"),
-            network: r.get::<String, _>("network").parse().unwrap(),
-            netmask: r.get::<i64, _>("netmask") as u8,
-            gateway: r.get::<String, _>("gateway").parse().unwrap(),
-            dns_servers: Subnet::dns_servers_from_string(&r.get::<String, _>("dns_servers")),
-            domain_name: r.get("domain_name"),
-        }))
+        row.as_ref().map(subnet_from_row).transpose()
     }
 
     async fn list_subnets(&self) -> anyhow::Result<Vec<Subnet>> {
         let rows = sqlx::query(
-            "SELECT id, network, netmask, gateway, dns_servers, domain_name FROM subnets",
+            "SELECT id, network, netmask, gateway, dns_servers, ntp_servers, domain_name, authoritative, default_lease_time, template_id FROM subnets",
         )
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows
-            .into_iter()
-            .map(|r| Subnet {
-                id: r.get("id"),
-                network: r.get::<String, _>("network").parse().unwrap(),
-                netmask: r.get::<i64, _>("netmask") as u8,
-                gateway: r.get::<String, _>("gateway").parse().unwrap(),
-                dns_servers: Subnet::dns_servers_from_string(&r.get::<String, _>("dns_servers")),
-                domain_name: r.get("domain_name"),
-            })
-            .collect())
+        rows.iter().map(subnet_from_row).collect()
+    }
+
+    async fn get_subnets_by_ids(&self, ids: &[i64]) -> anyhow::Result<Vec<Subnet>> {
+        let mut rows = Vec::new();
+        for chunk in ids.chunks(MAX_BIND_PARAMS) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let sql = format!(
+                "SELECT id, network, netmask, gateway, dns_servers, ntp_servers, domain_name, authoritative, default_lease_time, template_id FROM subnets WHERE id IN ({})",
+                placeholders
+            );
+            let mut query = sqlx::query(&sql);
+            for id in chunk {
+                query = query.bind(id);
+            }
+            rows.extend(query.fetch_all(&self.pool).await?);
+        }
+
+        rows.iter().map(subnet_from_row).collect()
     }
 
     async fn update_subnet(&self, id: i64, subnet: &Subnet) -> anyhow::Result<()> {
-        let dns_servers = subnet.dns_servers_to_string();
-        sqlx::query(
-            "UPDATE subnets SET network = ?, netmask = ?, gateway = ?, dns_servers = ?, domain_name = ? WHERE id = ?"
+        bind_subnet(
+            sqlx::query(
+                "UPDATE subnets SET network = ?, netmask = ?, gateway = ?, dns_servers = ?, ntp_servers = ?, domain_name = ?, authoritative = ?, default_lease_time = ?, template_id = ? WHERE id = ?"
+            ),
+            subnet,
         )
-        .bind(subnet.network.to_string())
-        .bind(subnet.netmask as i64)
-        .bind(subnet.gateway.to_string())
-        .bind(dns_servers)
-        .bind(&subnet.domain_name)
         .bind(id)
         .execute(&self.pool)
         .await?;
@@ -102,6 +266,46 @@ impl Database for SqliteDatabase {
         Ok(())
     }
 
+    async fn patch_subnet(
+        &self,
+        id: i64,
+        patch: &SubnetPatch,
+        check: &SubnetCheck<'_>,
+    ) -> anyhow::Result<Option<Subnet>> {
+        let mut tx = self.pool.begin().await?;
+
+        // Every subnet is read in the transaction so that `check` sees the
+        // others as they are when the patched one is written
+        let rows = sqlx::query(
+            "SELECT id, network, netmask, gateway, dns_servers, ntp_servers, domain_name, authoritative, default_lease_time, template_id FROM subnets"
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut others = rows
+            .iter()
+            .map(subnet_from_row)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let Some(i) = others.iter().position(|s| s.id == Some(id)) else {
+            return Ok(None);
+        };
+        let mut subnet = others.swap_remove(i);
+        patch.apply(&mut subnet);
+        check(&subnet, &others)?;
+
+        bind_subnet(
+            sqlx::query(
+                "UPDATE subnets SET network = ?, netmask = ?, gateway = ?, dns_servers = ?, ntp_servers = ?, domain_name = ?, authoritative = ?, default_lease_time = ?, template_id = ? WHERE id = ?"
+            ),
+            &subnet,
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(subnet))
+    }
+
     async fn delete_subnet(&self, id: i64) -> anyhow::Result<()> {
         sqlx::query("DELETE FROM subnets WHERE id = ?")
             .bind(id)
@@ -110,15 +314,229 @@ impl Database for SqliteDatabase {
         Ok(())
     }
 
+    async fn create_subnet_with_ranges(
+        &self,
+        subnet: &Subnet,
+        ranges: &[DynamicRange],
+        static_ips: &[StaticIP],
+    ) -> anyhow::Result<(i64, Vec<i64>)> {
+        let mut tx = self.pool.begin().await?;
+
+        let subnet_id = bind_subnet(
+            sqlx::query(
+                "INSERT INTO subnets (network, netmask, gateway, dns_servers, ntp_servers, domain_name, authoritative, default_lease_time, template_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+            ),
+            subnet,
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        let mut range_ids = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let range_id = sqlx::query(
+                "INSERT INTO dynamic_ranges (subnet_id, range_start, range_end, enabled, draining) VALUES (?, ?, ?, ?, ?)"
+            )
+            .bind(subnet_id)
+            .bind(range.range_start.to_string())
+            .bind(range.range_end.to_string())
+            .bind(range.enabled as i64)
+            .bind(range.draining as i64)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+            range_ids.push(range_id);
+        }
+
+        for static_ip in static_ips {
+            sqlx::query(
+                "INSERT INTO static_ips (ip_address, subnet_id, mac_address, hostname) VALUES (?, ?, ?, ?)"
+            )
+            .bind(static_ip.ip_address.to_string())
+            .bind(subnet_id)
+            .bind(&static_ip.mac_address)
+            .bind(&static_ip.hostname)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok((subnet_id, range_ids))
+    }
+
+    async fn renumber_subnet(
+        &self,
+        id: i64,
+        plan: &RenumberPlan<'_>,
+    ) -> anyhow::Result<Option<(SubnetContents, Renumbering)>> {
+        let mut tx = self.pool.begin().await?;
+
+        // The plan sees the subnet, its contents and the other subnets as
+        // they are when its changes are written
+        let rows = sqlx::query(
+            "SELECT id, network, netmask, gateway, dns_servers, ntp_servers, domain_name, authoritative, default_lease_time, template_id FROM subnets"
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let mut others = rows
+            .iter()
+            .map(subnet_from_row)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let Some(i) = others.iter().position(|s| s.id == Some(id)) else {
+            return Ok(None);
+        };
+        let source = others.swap_remove(i);
+
+        let ranges = sqlx::query(
+            "SELECT id, subnet_id, range_start, range_end, enabled, draining FROM dynamic_ranges WHERE subnet_id = ?"
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|r| DynamicRange {
+            id: r.get("id"),
+            subnet_id: r.get("subnet_id"),
+            range_start: r.get::<String, _>("range_start").parse().unwrap(),
+            range_end: r.get::<String, _>("range_end").parse().unwrap(),
+            enabled: r.get::<i64, _>("enabled") != 0,
+            draining: r.get::<i64, _>("draining") != 0,
+        })
+        .collect();
+        let static_ips = sqlx::query(
+            "SELECT subnet_id, mac_address, ip_address, hostname FROM static_ips WHERE subnet_id = ?"
+        )
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|r| StaticIP {
+            subnet_id: r.get("subnet_id"),
+            mac_address: r.get("mac_address"),
+            ip_address: r.get::<String, _>("ip_address").parse().unwrap(),
+            hostname: r.get("hostname"),
+        })
+        .collect();
+        let now = chrono::Utc::now().timestamp();
+        let leases = sqlx::query(
+            "SELECT id, subnet_id, mac_address, ip_address, lease_start, lease_end, hostname, range_id FROM leases WHERE subnet_id = ? AND lease_end > ?"
+        )
+        .bind(id)
+        .bind(now)
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|r| Lease {
+            id: r.get("id"),
+            subnet_id: r.get("subnet_id"),
+            mac_address: r.get("mac_address"),
+            ip_address: r.get::<String, _>("ip_address").parse().unwrap(),
+            lease_start: r.get("lease_start"),
+            lease_end: r.get("lease_end"),
+            hostname: r.get("hostname"),
+            range_id: r.get("range_id"),
+        })
+        .collect();
+        let contents = SubnetContents {
+            subnet: source,
+            others,
+            ranges,
+            static_ips,
+            leases,
+        };
+        let renumbering = plan(&contents)?;
+        let Renumbering {
+            subnet,
+            ranges,
+            static_ips,
+            expired_leases,
+        } = &renumbering;
+
+        bind_subnet(
+            sqlx::query(
+                "UPDATE subnets SET network = ?, netmask = ?, gateway = ?, dns_servers = ?, ntp_servers = ?, domain_name = ?, authoritative = ?, default_lease_time = ?, template_id = ? WHERE id = ?"
+            ),
+            subnet,
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        // Kept ranges are updated in place so leases keep their range_id
+        let kept: Vec<i64> = ranges.iter().filter_map(|r| r.id).collect();
+        let existing = contents.ranges.iter().filter_map(|r| r.id);
+        for range_id in existing.filter(|rid| !kept.contains(rid)) {
+            sqlx::query("DELETE FROM dynamic_ranges WHERE id = ?")
+                .bind(range_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        for range in ranges {
+            let updated = sqlx::query(
+                "UPDATE dynamic_ranges SET range_start = ?, range_end = ?, enabled = ?, draining = ? WHERE id = ? AND subnet_id = ?"
+            )
+            .bind(range.range_start.to_string())
+            .bind(range.range_end.to_string())
+            .bind(range.enabled as i64)
+            .bind(range.draining as i64)
+            .bind(range.id)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            if updated.rows_affected() == 0 {
+                sqlx::query(
+                    "INSERT INTO dynamic_ranges (subnet_id, range_start, range_end, enabled, draining) VALUES (?, ?, ?, ?, ?)"
+                )
+                .bind(id)
+                .bind(range.range_start.to_string())
+                .bind(range.range_end.to_string())
+                .bind(range.enabled as i64)
+                .bind(range.draining as i64)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        sqlx::query("DELETE FROM static_ips WHERE subnet_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        for static_ip in static_ips {
+            sqlx::query(
+                "INSERT INTO static_ips (ip_address, subnet_id, mac_address, hostname) VALUES (?, ?, ?, ?)"
+            )
+            .bind(static_ip.ip_address.to_string())
+            .bind(id)
+            .bind(&static_ip.mac_address)
+            .bind(&static_ip.hostname)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // Ended rather than deleted, so the lease history keeps them
+        for lease_id in expired_leases {
+            sqlx::query("UPDATE leases SET lease_end = ? WHERE id = ? AND lease_end > ?")
+                .bind(now)
+                .bind(lease_id)
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(Some((contents, renumbering)))
+    }
+
     // Dynamic Range operations
     async fn create_range(&self, range: &DynamicRange) -> anyhow::Result<i64> {
         let result = sqlx::query(
-            "INSERT INTO dynamic_ranges (subnet_id, range_start, range_end, enabled) VALUES (?, ?, ?, ?)"
+            "INSERT INTO dynamic_ranges (subnet_id, range_start, range_end, enabled, draining) VALUES (?, ?, ?, ?, ?)"
         )
         .bind(range.subnet_id)
         .bind(range.range_start.to_string())
         .bind(range.range_end.to_string())
         .bind(range.enabled as i64)
+        .bind(range.draining as i64)
         .execute(&self.pool)
         .await?;
 
@@ -128,13 +546,13 @@ impl Database for SqliteDatabase {
     async fn list_ranges(&self, subnet_id: Option<i64>) -> anyhow::Result<Vec<DynamicRange>> {
         let rows = if let Some(subnet_id) = subnet_id {
             sqlx::query(
-                "SELECT id, subnet_id, range_start, range_end, enabled FROM dynamic_ranges WHERE subnet_id = ?"
+                "SELECT id, subnet_id, range_start, range_end, enabled, draining FROM dynamic_ranges WHERE subnet_id = ?"
             )
             .bind(subnet_id)
             .fetch_all(&self.pool)
             .await?
         } else {
-            sqlx::query("SELECT id, subnet_id, range_start, range_end, enabled FROM dynamic_ranges")
+            sqlx::query("SELECT id, subnet_id, range_start, range_end, enabled, draining FROM dynamic_ranges")
                 .fetch_all(&self.pool)
                 .await?
         };
@@ -147,6 +565,7 @@ impl Database for SqliteDatabase {
                 range_start: r.get::<String, _>("range_start").parse().unwrap(),
                 range_end: r.get::<String, _>("range_end").parse().unwrap(),
                 enabled: r.get::<i64, _>("enabled") != 0,
+                draining: r.get::<i64, _>("draining") != 0,
             })
             .collect())
     }
@@ -159,6 +578,24 @@ impl Database for SqliteDatabase {
         Ok(())
     }
 
+    async fn toggle_range(&self, id: i64, enabled: bool) -> anyhow::Result<()> {
+        sqlx::query("UPDATE dynamic_ranges SET enabled = ? WHERE id = ?")
+            .bind(enabled as i64)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_range_draining(&self, id: i64, draining: bool) -> anyhow::Result<()> {
+        sqlx::query("UPDATE dynamic_ranges SET draining = ? WHERE id = ?")
+            .bind(draining as i64)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     // Static IP operations
     async fn create_static_ip(&self, static_ip: &StaticIP) -> anyhow::Result<()> {
         sqlx::query(
@@ -226,6 +663,34 @@ impl Database for SqliteDatabase {
         Ok(())
     }
 
+    async fn delete_static_ips_by_subnet(&self, subnet_id: i64) -> anyhow::Result<u64> {
+        let result = sqlx::query("DELETE FROM static_ips WHERE subnet_id = ?")
+            .bind(subnet_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn create_static_ip_if_free(&self, static_ip: &StaticIP) -> anyhow::Result<bool> {
+        // A single statement, so the check cannot race with another writer
+        let now = chrono::Utc::now().timestamp();
+        let ip = static_ip.ip_address.to_string();
+        let result = sqlx::query(
+            "INSERT INTO static_ips (ip_address, subnet_id, mac_address, hostname) SELECT ?, ?, ?, ? WHERE NOT EXISTS (SELECT 1 FROM leases WHERE ip_address = ? AND lease_end > ?) AND NOT EXISTS (SELECT 1 FROM static_ips WHERE ip_address = ?)"
+        )
+        .bind(&ip)
+        .bind(static_ip.subnet_id)
+        .bind(&static_ip.mac_address)
+        .bind(&static_ip.hostname)
+        .bind(&ip)
+        .bind(now)
+        .bind(&ip)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     async fn delete_static_ip(&self, ip_address: &str) -> anyhow::Result<()> {
         sqlx::query("DELETE FROM static_ips WHERE ip_address = ?")
             .bind(ip_address)
@@ -237,7 +702,7 @@ impl Database for SqliteDatabase {
     // Lease operations
     async fn create_lease(&self, lease: &Lease) -> anyhow::Result<i64> {
         let result = sqlx::query(
-            "INSERT INTO leases (subnet_id, mac_address, ip_address, lease_start, lease_end, hostname) VALUES (?, ?, ?, ?, ?, ?)"
+            "INSERT INTO leases (subnet_id, mac_address, ip_address, lease_start, lease_end, hostname, range_id) VALUES (?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(lease.subnet_id)
         .bind(&lease.mac_address)
@@ -245,16 +710,40 @@ impl Database for SqliteDatabase {
         .bind(lease.lease_start)
         .bind(lease.lease_end)
         .bind(&lease.hostname)
+        .bind(lease.range_id)
         .execute(&self.pool)
         .await?;
 
         Ok(result.last_insert_rowid())
     }
 
+    async fn create_lease_if_free(&self, lease: &Lease) -> anyhow::Result<Option<i64>> {
+        // A single statement, so the check cannot race with another writer
+        let now = chrono::Utc::now().timestamp();
+        insert_lease_if_free(&self.pool, lease, now).await
+    }
+
+    async fn replace_lease(&self, old_id: i64, lease: &Lease) -> anyhow::Result<Option<i64>> {
+        let now = chrono::Utc::now().timestamp();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE leases SET lease_end = ? WHERE id = ? AND lease_end > ?")
+            .bind(now)
+            .bind(old_id)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        // Dropping the transaction keeps the old lease when the address is taken
+        let Some(id) = insert_lease_if_free(&mut *tx, lease, now).await? else {
+            return Ok(None);
+        };
+        tx.commit().await?;
+        Ok(Some(id))
+    }
+
     async fn get_active_lease(&self, mac: &str) -> anyhow::Result<Option<Lease>> {
         let now = chrono::Utc::now().timestamp();
         let row = sqlx::query(
-            "SELECT id, subnet_id, mac_address, ip_address, lease_start, lease_end, hostname FROM leases WHERE LOWER(mac_address) = LOWER(?) AND lease_end > ? ORDER BY lease_end DESC LIMIT 1"
+            "SELECT id, subnet_id, mac_address, ip_address, lease_start, lease_end, hostname, range_id FROM leases WHERE LOWER(mac_address) = LOWER(?) AND lease_end > ? ORDER BY lease_end DESC LIMIT 1"
         )
         .bind(mac)
         .bind(now)
@@ -269,13 +758,36 @@ impl Database for SqliteDatabase {
             lease_start: r.get("lease_start"),
             lease_end: r.get("lease_end"),
             hostname: r.get("hostname"),
+            range_id: r.get("range_id"),
+        }))
+    }
+
+    async fn get_active_lease_by_ip(&self, ip: Ipv4Addr) -> anyhow::Result<Option<Lease>> {
+        let now = chrono::Utc::now().timestamp();
+        let row = sqlx::query(
+            "SELECT id, subnet_id, mac_address, ip_address, lease_start, lease_end, hostname, range_id FROM leases WHERE ip_address = ? AND lease_end > ? ORDER BY lease_end DESC LIMIT 1"
+        )
+        .bind(ip.to_string())
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Lease {
+            id: r.get("id"),
+            subnet_id: r.get("subnet_id"),
+            mac_address: r.get("mac_address"),
+            ip_address: r.get::<String, _>("ip_address").parse().unwrap(),
+            lease_start: r.get("lease_start"),
+            lease_end: r.get("lease_end"),
+            hostname: r.get("hostname"),
+            range_id: r.get("range_id"),
         }))
     }
 
     async fn list_active_leases(&self) -> anyhow::Result<Vec<Lease>> {
         let now = chrono::Utc::now().timestamp();
         let rows = sqlx::query(
-            "SELECT id, subnet_id, mac_address, ip_address, lease_start, lease_end, hostname FROM leases WHERE lease_end > ?"
+            "SELECT id, subnet_id, mac_address, ip_address, lease_start, lease_end, hostname, range_id FROM leases WHERE lease_end > ?"
         )
         .bind(now)
         .fetch_all(&self.pool)
@@ -291,6 +803,7 @@ impl Database for SqliteDatabase {
                 lease_start: r.get("lease_start"),
                 lease_end: r.get("lease_end"),
                 hostname: r.get("hostname"),
+                range_id: r.get("range_id"),
             })
             .collect())
     }
@@ -303,15 +816,236 @@ impl Database for SqliteDatabase {
         Ok(())
     }
 
-    async fn delete_expired_leases(&self) -> anyhow::Result<u64> {
+    async fn release_lease(&self, id: i64, at: i64) -> anyhow::Result<()> {
+        sqlx::query("UPDATE leases SET lease_end = ?, released = 1 WHERE id = ?")
+            .bind(at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_leases_released_since(&self, since: i64) -> anyhow::Result<Vec<Lease>> {
         let now = chrono::Utc::now().timestamp();
+        let rows = sqlx::query(
+            "SELECT id, subnet_id, mac_address, ip_address, lease_start, lease_end, hostname, range_id FROM leases WHERE released = 1 AND lease_end > ? AND lease_end <= ?"
+        )
+        .bind(since)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| Lease {
+                id: r.get("id"),
+                subnet_id: r.get("subnet_id"),
+                mac_address: r.get("mac_address"),
+                ip_address: r.get::<String, _>("ip_address").parse().unwrap(),
+                lease_start: r.get("lease_start"),
+                lease_end: r.get("lease_end"),
+                hostname: r.get("hostname"),
+                range_id: r.get("range_id"),
+            })
+            .collect())
+    }
+
+    async fn get_recent_lease(&self, mac: &str, since: i64) -> anyhow::Result<Option<Lease>> {
+        let row = sqlx::query(
+            "SELECT id, subnet_id, mac_address, ip_address, lease_start, lease_end, hostname, range_id FROM leases WHERE LOWER(mac_address) = LOWER(?) AND lease_end > ? ORDER BY lease_end DESC LIMIT 1"
+        )
+        .bind(mac)
+        .bind(since)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| Lease {
+            id: r.get("id"),
+            subnet_id: r.get("subnet_id"),
+            mac_address: r.get("mac_address"),
+            ip_address: r.get::<String, _>("ip_address").parse().unwrap(),
+            lease_start: r.get("lease_start"),
+            lease_end: r.get("lease_end"),
+            hostname: r.get("hostname"),
+            range_id: r.get("range_id"),
+        }))
+    }
+
+    async fn delete_expired_leases(&self) -> anyhow::Result<u64> {
+        self.delete_leases_expired_before(chrono::Utc::now().timestamp())
+            .await
+    }
+
+    async fn delete_leases_expired_before(&self, before: i64) -> anyhow::Result<u64> {
         let result = sqlx::query("DELETE FROM leases WHERE lease_end < ?")
-            .bind(now)
+            .bind(before)
             .execute(&self.pool)
             .await?;
         Ok(result.rows_affected())
     }
 
+    async fn list_subnet_addresses(&self, subnet_id: i64) -> anyhow::Result<Vec<SubnetAddress>> {
+        let now = chrono::Utc::now().timestamp();
+        let rows = sqlx::query(
+            "SELECT ip_address, mac_address, hostname, 'lease' AS source FROM leases WHERE subnet_id = ? AND lease_end > ? \
+             UNION ALL \
+             SELECT ip_address, mac_address, hostname, 'static' AS source FROM static_ips WHERE subnet_id = ?"
+        )
+        .bind(subnet_id)
+        .bind(now)
+        .bind(subnet_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut addresses: Vec<SubnetAddress> = rows
+            .into_iter()
+            .map(|r| SubnetAddress {
+                ip: r.get::<String, _>("ip_address").parse().unwrap(),
+                mac: r.get("mac_address"),
+                hostname: r.get("hostname"),
+                source: if r.get::<String, _>("source") == "static" {
+                    AddressSource::Static
+                } else {
+                    AddressSource::Lease
+                },
+            })
+            .collect();
+        // Stored as text, so order numerically here rather than in SQL
+        addresses.sort_by_key(|a| a.ip);
+        Ok(addresses)
+    }
+
+    // Pending offer operations
+    async fn record_pending_offer(&self, offer: &PendingOffer) -> anyhow::Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM pending_offers WHERE expires_at <= ?")
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT OR REPLACE INTO pending_offers (ip_address, subnet_id, mac_address, xid, offered_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)"
+        )
+        .bind(offer.ip_address.to_string())
+        .bind(offer.subnet_id)
+        .bind(&offer.mac_address)
+        .bind(offer.xid as i64)
+        .bind(offer.offered_at)
+        .bind(offer.expires_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_pending_offers(&self) -> anyhow::Result<Vec<PendingOffer>> {
+        let now = chrono::Utc::now().timestamp();
+        let rows = sqlx::query(
+            "SELECT ip_address, subnet_id, mac_address, xid, offered_at, expires_at FROM pending_offers WHERE expires_at > ? ORDER BY offered_at"
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| PendingOffer {
+                subnet_id: r.get("subnet_id"),
+                mac_address: r.get("mac_address"),
+                ip_address: r.get::<String, _>("ip_address").parse().unwrap(),
+                xid: r.get::<i64, _>("xid") as u32,
+                offered_at: r.get("offered_at"),
+                expires_at: r.get("expires_at"),
+            })
+            .collect())
+    }
+
+    async fn delete_pending_offer(&self, ip: Ipv4Addr) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM pending_offers WHERE ip_address = ?")
+            .bind(ip.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Option template operations
+    async fn create_option_template(&self, template: &OptionTemplate) -> anyhow::Result<i64> {
+        let result = sqlx::query(
+            "INSERT INTO option_templates (name, dns_servers, domain_name, default_lease_time) VALUES (?, ?, ?, ?)"
+        )
+        .bind(&template.name)
+        .bind(template.dns_servers_to_string())
+        .bind(&template.domain_name)
+        .bind(template.default_lease_time.map(|t| t as i64))
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn get_option_template(&self, id: i64) -> anyhow::Result<Option<OptionTemplate>> {
+        let row = sqlx::query(
+            "SELECT id, name, dns_servers, domain_name, default_lease_time FROM option_templates WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| OptionTemplate {
+            id: r.get("id"),
+            name: r.get("name"),
+            dns_servers: OptionTemplate::dns_servers_from_string(&r.get::<String, _>("dns_servers")),
+            domain_name: r.get("domain_name"),
+            default_lease_time: r
+                .get::<Option<i64>, _>("default_lease_time")
+                .map(|t| t as u32),
+        }))
+    }
+
+    async fn list_option_templates(&self) -> anyhow::Result<Vec<OptionTemplate>> {
+        let rows = sqlx::query(
+            "SELECT id, name, dns_servers, domain_name, default_lease_time FROM option_templates",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| OptionTemplate {
+                id: r.get("id"),
+                name: r.get("name"),
+                dns_servers: OptionTemplate::dns_servers_from_string(&r.get::<String, _>("dns_servers")),
+                domain_name: r.get("domain_name"),
+                default_lease_time: r
+                    .get::<Option<i64>, _>("default_lease_time")
+                    .map(|t| t as u32),
+            })
+            .collect())
+    }
+
+    async fn update_option_template(&self, id: i64, template: &OptionTemplate) -> anyhow::Result<()> {
+        sqlx::query(
+            "UPDATE option_templates SET name = ?, dns_servers = ?, domain_name = ?, default_lease_time = ? WHERE id = ?"
+        )
+        .bind(&template.name)
+        .bind(template.dns_servers_to_string())
+        .bind(&template.domain_name)
+        .bind(template.default_lease_time.map(|t| t as i64))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_option_template(&self, id: i64) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM option_templates WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
     // IPv6 Prefix (IA Prefix) operations
     async fn create_ia_prefix(&self, prefix: &IAPrefix) -> anyhow::Result<i64> {
         let dns_servers = prefix.dns_servers_to_string();
@@ -410,9 +1144,9 @@ impl Database for SqliteDatabase {
     }
 
     // Token operations
-    async fn list_tokens(&self) -> anyhow::Result<Vec<(String, i64)>> {
-        let rows = sqlx::query_as::<_, (String, i64)>(
-            "SELECT token_hash, enabled FROM api_tokens WHERE enabled = 1",
+    async fn list_tokens(&self) -> anyhow::Result<Vec<(i64, String, i64)>> {
+        let rows = sqlx::query_as::<_, (i64, String, i64)>(
+            "SELECT id, token_hash, enabled FROM api_tokens WHERE enabled = 1",
         )
         .fetch_all(&self.pool)
         .await?;
@@ -478,6 +1212,78 @@ impl Database for SqliteDatabase {
         .await?;
         Ok(())
     }
+
+    // Maintenance
+    async fn backup_to(&self, path: &Path) -> anyhow::Result<u64> {
+        // VACUUM INTO reads a single transaction snapshot, so concurrent writers
+        // cannot tear the copy. It refuses to overwrite an existing file: write
+        // next to the target and rename over it once complete.
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = std::path::PathBuf::from(tmp);
+        let _ = tokio::fs::remove_file(&tmp).await;
+
+        sqlx::query("VACUUM INTO ?")
+            .bind(tmp.to_string_lossy().into_owned())
+            .execute(&self.pool)
+            .await
+            .with_context(|| format!("VACUUM INTO {} failed", tmp.display()))?;
+        // In-memory databases vacuum into another in-memory database
+        if !tokio::fs::try_exists(&tmp).await.unwrap_or(false) {
+            anyhow::bail!("In-memory SQLite databases cannot be backed up to a file");
+        }
+        tokio::fs::rename(&tmp, path)
+            .await
+            .with_context(|| format!("Failed to move backup to {}", path.display()))?;
+        Ok(tokio::fs::metadata(path).await?.len())
+    }
+
+    async fn rewrite_client_identities(
+        &self,
+        rewrite: &ClientIdentityRewrite<'_>,
+    ) -> anyhow::Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let mut rewritten = 0;
+
+        let leases = sqlx::query("SELECT id, mac_address, hostname FROM leases")
+            .fetch_all(&mut *tx)
+            .await?;
+        for row in leases {
+            let mac: String = row.get("mac_address");
+            let hostname: Option<String> = row.get("hostname");
+            if let Some((mac, hostname)) = rewrite(&mac, hostname.as_deref())? {
+                sqlx::query("UPDATE leases SET mac_address = ?, hostname = ? WHERE id = ?")
+                    .bind(mac)
+                    .bind(hostname)
+                    .bind(row.get::<i64, _>("id"))
+                    .execute(&mut *tx)
+                    .await?;
+                rewritten += 1;
+            }
+        }
+
+        let static_ips = sqlx::query("SELECT ip_address, mac_address, hostname FROM static_ips")
+            .fetch_all(&mut *tx)
+            .await?;
+        for row in static_ips {
+            let mac: String = row.get("mac_address");
+            let hostname: Option<String> = row.get("hostname");
+            if let Some((mac, hostname)) = rewrite(&mac, hostname.as_deref())? {
+                sqlx::query(
+                    "UPDATE static_ips SET mac_address = ?, hostname = ? WHERE ip_address = ?",
+                )
+                .bind(mac)
+                .bind(hostname)
+                .bind(row.get::<String, _>("ip_address"))
+                .execute(&mut *tx)
+                .await?;
+                rewritten += 1;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(rewritten)
+    }
 }
 
 #[cfg(test)]
@@ -503,14 +1309,70 @@ mod tests {
         suite::test_update_subnet(&db).await;
         suite::test_delete_subnet(&db).await;
         suite::test_get_subnet_not_found(&db).await;
+        suite::test_get_subnets_by_ids(&db).await;
+        suite::test_option_templates(&db).await;
+        suite::test_renumber_subnet(&db).await;
+    }
+
+    #[tokio::test]
+    async fn test_get_subnets_by_ids_past_bind_limit() {
+        let db = new_test_db().await;
+        let first = db.create_subnet(&Subnet::from_cidr("10.8.0.0/24").unwrap()).await.unwrap();
+        let last = db.create_subnet(&Subnet::from_cidr("10.8.1.0/24").unwrap()).await.unwrap();
+
+        // Spans three chunks, with the existing IDs in the first and the last
+        let mut ids: Vec<i64> = (10_000..12_500).collect();
+        ids.insert(0, first);
+        ids.push(last);
+        let mut found: Vec<i64> = db
+            .get_subnets_by_ids(&ids)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|s| s.id)
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![first, last]);
     }
 
     #[tokio::test]
     async fn test_range_crud() {
         let db = new_test_db().await;
         suite::test_create_and_list_range(&db).await;
+        suite::test_create_subnet_with_ranges(&db).await;
         suite::test_list_ranges_all(&db).await;
         suite::test_delete_range(&db).await;
+        suite::test_toggle_range(&db).await;
+        suite::test_set_range_draining(&db).await;
+    }
+
+    #[tokio::test]
+    async fn test_create_subnet_with_ranges_rolls_back_on_error() {
+        let db = new_test_db().await;
+        let existing = db.create_subnet(&Subnet::from_cidr("10.9.0.0/24").unwrap()).await.unwrap();
+        let taken = StaticIP {
+            subnet_id: existing,
+            mac_address: "aa:bb:cc:dd:ee:01".to_string(),
+            ip_address: "10.9.0.10".parse().unwrap(),
+            hostname: None,
+        };
+        db.create_static_ip(&taken).await.unwrap();
+
+        let range = DynamicRange {
+            id: None,
+            subnet_id: 0,
+            range_start: "10.9.1.100".parse().unwrap(),
+            range_end: "10.9.1.200".parse().unwrap(),
+            enabled: true,
+            draining: false,
+        };
+        // The static IP's address is already assigned: the subnet and the
+        // range inserted before it are rolled back
+        let subnet = Subnet::from_cidr("10.9.1.0/24").unwrap();
+        let result = db.create_subnet_with_ranges(&subnet, &[range], &[taken]).await;
+        assert!(crate::db::is_unique_violation(&result.unwrap_err()));
+        assert_eq!(db.list_subnets().await.unwrap().len(), 1);
+        assert!(db.list_ranges(None).await.unwrap().is_empty());
     }
 
     #[tokio::test]
@@ -520,6 +1382,7 @@ mod tests {
         suite::test_get_static_ip_by_mac(&db).await;
         suite::test_get_static_ip_by_mac_not_found(&db).await;
         suite::test_delete_static_ip(&db).await;
+        suite::test_delete_static_ips_by_subnet(&db).await;
     }
 
     #[tokio::test]
@@ -529,6 +1392,12 @@ mod tests {
         suite::test_list_active_leases(&db).await;
         suite::test_expire_lease(&db).await;
         suite::test_expired_lease_not_returned(&db).await;
+        suite::test_get_active_lease_by_ip(&db).await;
+        suite::test_list_subnet_addresses(&db).await;
+        suite::test_pending_offers(&db).await;
+        suite::test_recent_lease_outlives_expiry(&db).await;
+        suite::test_release_lease(&db).await;
+        suite::test_lease_range_id(&db).await;
     }
 
     #[tokio::test]
@@ -549,4 +1418,69 @@ mod tests {
         suite::test_toggle_token(&db).await;
         suite::test_update_token_last_used(&db).await;
     }
+
+    fn temp_db_path(prefix: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "ndhcpd-{}-{}-{}.db",
+            prefix,
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_modified_migration_is_reported_as_checksum_mismatch() {
+        let path = temp_db_path("checksum");
+        let url = format!("sqlite:{}", path.display());
+        let db = SqliteDatabase::new(&url).await.unwrap();
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 20240101000000")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        db.pool().close().await;
+
+        let err = SqliteDatabase::new(&url).await.err().expect("migration must fail");
+        let _ = std::fs::remove_file(&path);
+        assert!(
+            matches!(
+                err.downcast_ref::<MigrationError>(),
+                Some(MigrationError::ChecksumMismatch(20240101000000))
+            ),
+            "{err:?}"
+        );
+        assert!(err.to_string().contains("restore the database from a backup"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_backup_to_reopens_with_data() {
+        let source = temp_db_path("backup-source");
+        let db = SqliteDatabase::new(&format!("sqlite:{}", source.display()))
+            .await
+            .unwrap();
+        suite::test_create_and_get_subnet(&db).await;
+        let subnets = db.list_subnets().await.unwrap();
+
+        let path = temp_db_path("backup");
+        let size = db.backup_to(&path).await.unwrap();
+        assert_eq!(size, std::fs::metadata(&path).unwrap().len());
+        // A second backup replaces the first
+        db.backup_to(&path).await.unwrap();
+
+        let restored = SqliteDatabase::new(&format!("sqlite:{}", path.display()))
+            .await
+            .unwrap();
+        let restored_subnets = restored.list_subnets().await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&source);
+        assert_eq!(restored_subnets.len(), subnets.len());
+        assert_eq!(restored_subnets[0].network, subnets[0].network);
+    }
+
+    #[tokio::test]
+    async fn test_backup_of_memory_database_fails() {
+        let db = new_test_db().await;
+        let path = temp_db_path("backup-memory");
+        assert!(db.backup_to(&path).await.is_err());
+        assert!(!path.exists());
+    }
 }
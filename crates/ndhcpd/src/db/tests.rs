@@ -3,18 +3,18 @@
 /// composed into run_all() without conflicting within the same DB instance.
 #[cfg(test)]
 pub(crate) mod suite {
-    use crate::db::Database;
-    use crate::models::{DynamicRange, IAPrefix, Lease, StaticIP, Subnet};
+    use crate::db::{Database, Renumbering, SubnetContents};
+    use crate::models::{
+        AddressSource, DynamicRange, IAPrefix, Lease, OptionTemplate, PendingOffer, PrefixLen,
+        StaticIP, Subnet, SubnetPatch,
+    };
     use std::net::{Ipv4Addr, Ipv6Addr};
 
     fn subnet(third_octet: u8) -> Subnet {
         Subnet {
-            id: None,
-            network: Ipv4Addr::new(10, 0, third_octet, 0),
-            netmask: 24,
-            gateway: Ipv4Addr::new(10, 0, third_octet, 1),
             dns_servers: vec![Ipv4Addr::new(8, 8, 8, 8)],
             domain_name: Some("local".to_string()),
+            ..Subnet::from_cidr(&format!("10.0.{third_octet}.0/24")).unwrap()
         }
     }
 
@@ -25,6 +25,7 @@ pub(crate) mod suite {
             range_start: Ipv4Addr::new(10, 0, third_octet, 100),
             range_end: Ipv4Addr::new(10, 0, third_octet, 200),
             enabled: true,
+            draining: false,
         }
     }
 
@@ -47,6 +48,7 @@ pub(crate) mod suite {
             lease_start: now,
             lease_end: now + 3600,
             hostname: Some("test-host".to_string()),
+            range_id: None,
         }
     }
 
@@ -90,11 +92,64 @@ pub(crate) mod suite {
         let id = db.create_subnet(&subnet(4)).await.unwrap();
 
         let mut updated = subnet(4);
-        updated.netmask = 16;
+        updated.netmask = PrefixLen::new(16).unwrap();
+        updated.authoritative = true;
+        updated.default_lease_time = Some(3600);
+        updated.ntp_servers = vec![Ipv4Addr::new(10, 0, 4, 123), Ipv4Addr::new(10, 0, 4, 124)];
         db.update_subnet(id, &updated).await.unwrap();
 
         let got = db.get_subnet(id).await.unwrap().expect("subnet not found");
         assert_eq!(got.netmask, 16);
+        assert!(got.authoritative);
+        assert_eq!(got.default_lease_time, Some(3600));
+        assert_eq!(got.ntp_servers, updated.ntp_servers);
+    }
+
+    pub async fn test_patch_subnet(db: &dyn Database) {
+        let id = db.create_subnet(&subnet(8)).await.unwrap();
+
+        let patch: SubnetPatch =
+            serde_json::from_str(r#"{"domain_name": "patched.lan"}"#).unwrap();
+        let accept = |_: &Subnet, _: &[Subnet]| Ok(());
+        let patched = db
+            .patch_subnet(id, &patch, &accept)
+            .await
+            .unwrap()
+            .expect("subnet not found");
+        assert_eq!(patched.domain_name.as_deref(), Some("patched.lan"));
+
+        let got = db.get_subnet(id).await.unwrap().expect("subnet not found");
+        let original = subnet(8);
+        assert_eq!(got.domain_name.as_deref(), Some("patched.lan"));
+        assert_eq!(got.network, original.network);
+        assert_eq!(got.netmask, original.netmask);
+        assert_eq!(got.gateway, original.gateway);
+        assert_eq!(got.dns_servers, original.dns_servers);
+        assert!(!got.authoritative);
+
+        // null clears an optional field
+        let patch: SubnetPatch = serde_json::from_str(r#"{"domain_name": null}"#).unwrap();
+        db.patch_subnet(id, &patch, &accept).await.unwrap();
+        let got = db.get_subnet(id).await.unwrap().expect("subnet not found");
+        assert_eq!(got.domain_name, None);
+
+        assert!(db.patch_subnet(99999, &patch, &accept).await.unwrap().is_none());
+
+        // The check sees the merged subnet and the others; refusing leaves
+        // the stored subnet unchanged
+        let other = db.create_subnet(&subnet(48)).await.unwrap();
+        let patch: SubnetPatch =
+            serde_json::from_str(r#"{"domain_name": "refused.lan"}"#).unwrap();
+        let refuse = |merged: &Subnet, others: &[Subnet]| {
+            assert_eq!(merged.domain_name.as_deref(), Some("refused.lan"));
+            assert!(others.iter().any(|s| s.id == Some(other)));
+            assert!(others.iter().all(|s| s.id != Some(id)));
+            anyhow::bail!("refused")
+        };
+        let err = db.patch_subnet(id, &patch, &refuse).await.unwrap_err();
+        assert_eq!(err.to_string(), "refused");
+        let got = db.get_subnet(id).await.unwrap().expect("subnet not found");
+        assert_eq!(got.domain_name, None);
     }
 
     pub async fn test_delete_subnet(db: &dyn Database) {
@@ -108,6 +163,22 @@ pub(crate) mod suite {
         assert!(db.get_subnet(99999).await.unwrap().is_none());
     }
 
+    pub async fn test_get_subnets_by_ids(db: &dyn Database) {
+        let a = db.create_subnet(&subnet(6)).await.unwrap();
+        let b = db.create_subnet(&subnet(7)).await.unwrap();
+
+        // Missing ids are skipped rather than failing the whole lookup
+        let mut got = db.get_subnets_by_ids(&[a, 99999, b]).await.unwrap();
+        got.sort_by_key(|s| s.id);
+        assert_eq!(got.len(), 2);
+        assert_eq!(got[0].id, Some(a));
+        assert_eq!(got[0].network, Ipv4Addr::new(10, 0, 6, 0));
+        assert_eq!(got[1].id, Some(b));
+
+        assert!(db.get_subnets_by_ids(&[99999]).await.unwrap().is_empty());
+        assert!(db.get_subnets_by_ids(&[]).await.unwrap().is_empty());
+    }
+
     // --- Dynamic Range tests ---
 
     pub async fn test_create_and_list_range(db: &dyn Database) {
@@ -122,6 +193,25 @@ pub(crate) mod suite {
         assert_eq!(ranges[0].range_end, Ipv4Addr::new(10, 0, 10, 200));
     }
 
+    pub async fn test_create_subnet_with_ranges(db: &dyn Database) {
+        // The own subnet_id of ranges and static IPs is ignored in favour of
+        // the new subnet
+        let (sid, range_ids) = db
+            .create_subnet_with_ranges(&subnet(14), &[range(0, 14)], &[static_ip(0, "14", 14)])
+            .await
+            .unwrap();
+
+        let created = db.get_subnet(sid).await.unwrap().unwrap();
+        assert_eq!(created.network, Ipv4Addr::new(10, 0, 14, 0));
+        let ranges = db.list_ranges(Some(sid)).await.unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].id, Some(range_ids[0]));
+        assert_eq!(ranges[0].range_start, Ipv4Addr::new(10, 0, 14, 100));
+        let static_ips = db.list_static_ips(Some(sid)).await.unwrap();
+        assert_eq!(static_ips.len(), 1);
+        assert_eq!(static_ips[0].ip_address, Ipv4Addr::new(10, 0, 14, 50));
+    }
+
     pub async fn test_list_ranges_all(db: &dyn Database) {
         let sid1 = db.create_subnet(&subnet(11)).await.unwrap();
         let sid2 = db.create_subnet(&subnet(12)).await.unwrap();
@@ -142,6 +232,33 @@ pub(crate) mod suite {
         assert!(ranges.iter().all(|r| r.id != Some(range_id)));
     }
 
+    pub async fn test_toggle_range(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(44)).await.unwrap();
+        let range_id = db.create_range(&range(sid, 44)).await.unwrap();
+
+        db.toggle_range(range_id, false).await.unwrap();
+        let ranges = db.list_ranges(Some(sid)).await.unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert!(!ranges[0].enabled);
+
+        db.toggle_range(range_id, true).await.unwrap();
+        assert!(db.list_ranges(Some(sid)).await.unwrap()[0].enabled);
+    }
+
+    pub async fn test_set_range_draining(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(45)).await.unwrap();
+        let range_id = db.create_range(&range(sid, 45)).await.unwrap();
+        assert!(!db.list_ranges(Some(sid)).await.unwrap()[0].draining);
+
+        db.set_range_draining(range_id, true).await.unwrap();
+        let ranges = db.list_ranges(Some(sid)).await.unwrap();
+        assert!(ranges[0].draining);
+        assert!(ranges[0].enabled);
+
+        db.set_range_draining(range_id, false).await.unwrap();
+        assert!(!db.list_ranges(Some(sid)).await.unwrap()[0].draining);
+    }
+
     // --- Static IP tests ---
 
     pub async fn test_create_and_list_static_ip(db: &dyn Database) {
@@ -187,6 +304,126 @@ pub(crate) mod suite {
         assert!(ips.iter().all(|s| s.ip_address.to_string() != ip_str));
     }
 
+    pub async fn test_delete_static_ips_by_subnet(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(23)).await.unwrap();
+        let other = db.create_subnet(&subnet(24)).await.unwrap();
+        db.create_static_ip(&static_ip(sid, "04", 23)).await.unwrap();
+        let mut second = static_ip(sid, "05", 23);
+        second.ip_address = Ipv4Addr::new(10, 0, 23, 51);
+        db.create_static_ip(&second).await.unwrap();
+        db.create_static_ip(&static_ip(other, "06", 24)).await.unwrap();
+
+        assert_eq!(db.delete_static_ips_by_subnet(sid).await.unwrap(), 2);
+        assert!(db.list_static_ips(Some(sid)).await.unwrap().is_empty());
+        assert_eq!(db.list_static_ips(Some(other)).await.unwrap().len(), 1);
+        assert_eq!(db.delete_static_ips_by_subnet(sid).await.unwrap(), 0);
+    }
+
+    // --- Pending offer tests ---
+
+    pub async fn test_pending_offers(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(36)).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let offer = |mac_suffix: &str, last_octet: u8, expires_at: i64| PendingOffer {
+            subnet_id: sid,
+            mac_address: format!("aa:bb:cc:dd:ee:{mac_suffix}"),
+            ip_address: Ipv4Addr::new(10, 0, 36, last_octet),
+            xid: 0xdeadbeef,
+            offered_at: now,
+            expires_at,
+        };
+        db.record_pending_offer(&offer("40", 100, now + 60)).await.unwrap();
+        db.record_pending_offer(&offer("41", 101, now - 1)).await.unwrap();
+        // A new offer of the same address replaces the previous one
+        db.record_pending_offer(&offer("42", 100, now + 60)).await.unwrap();
+
+        let offers = db.list_pending_offers().await.unwrap();
+        assert_eq!(offers, vec![offer("42", 100, now + 60)]);
+
+        db.delete_pending_offer(Ipv4Addr::new(10, 0, 36, 100)).await.unwrap();
+        assert!(db.list_pending_offers().await.unwrap().is_empty());
+    }
+
+    // --- Option template tests ---
+
+    pub async fn test_option_templates(db: &dyn Database) {
+        let mut template = OptionTemplate {
+            id: None,
+            name: "office".to_string(),
+            dns_servers: vec![Ipv4Addr::new(10, 0, 39, 53)],
+            domain_name: Some("office.lan".to_string()),
+            default_lease_time: Some(7200),
+        };
+        let tid = db.create_option_template(&template).await.unwrap();
+        template.id = Some(tid);
+        assert_eq!(db.get_option_template(tid).await.unwrap(), Some(template.clone()));
+
+        template.dns_servers.push(Ipv4Addr::new(10, 0, 39, 54));
+        template.domain_name = None;
+        db.update_option_template(tid, &template).await.unwrap();
+        assert_eq!(db.list_option_templates().await.unwrap(), vec![template.clone()]);
+
+        let sid = db
+            .create_subnet(&Subnet {
+                template_id: Some(tid),
+                ..subnet(39)
+            })
+            .await
+            .unwrap();
+        assert_eq!(db.get_subnet(sid).await.unwrap().unwrap().template_id, Some(tid));
+
+        // Deleting the template detaches its subnets
+        db.delete_option_template(tid).await.unwrap();
+        assert!(db.get_option_template(tid).await.unwrap().is_none());
+        assert_eq!(db.get_subnet(sid).await.unwrap().unwrap().template_id, None);
+    }
+
+    pub async fn test_renumber_subnet(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(40)).await.unwrap();
+        let rid = db.create_range(&range(sid, 40)).await.unwrap();
+        db.create_static_ip(&static_ip(sid, "50", 40)).await.unwrap();
+        let lid = db.create_lease(&active_lease(sid, "51", 40)).await.unwrap();
+
+        let target = Subnet { id: Some(sid), ..subnet(41) };
+        let plan = |contents: &SubnetContents| -> anyhow::Result<Renumbering> {
+            // The plan is given what the subnet holds
+            assert_eq!(contents.subnet.network, Ipv4Addr::new(10, 0, 40, 0));
+            assert!(contents.others.iter().all(|s| s.id != Some(sid)));
+            assert_eq!(contents.ranges.len(), 1);
+            assert_eq!(contents.static_ips[0].mac_address, "aa:bb:cc:dd:ee:50");
+            assert_eq!(contents.leases[0].id, Some(lid));
+            Ok(Renumbering {
+                subnet: target.clone(),
+                ranges: vec![DynamicRange { id: Some(rid), ..range(sid, 41) }],
+                static_ips: vec![static_ip(sid, "50", 41)],
+                expired_leases: vec![lid],
+            })
+        };
+        let since = chrono::Utc::now().timestamp() - 1;
+        assert!(db.renumber_subnet(sid, &plan).await.unwrap().is_some());
+
+        let stored = db.get_subnet(sid).await.unwrap().unwrap();
+        assert_eq!((stored.network, stored.gateway), (target.network, target.gateway));
+        let ranges = db.list_ranges(Some(sid)).await.unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].id, Some(rid));
+        assert_eq!(ranges[0].range_start, Ipv4Addr::new(10, 0, 41, 100));
+        let statics = db.list_static_ips(Some(sid)).await.unwrap();
+        assert_eq!(statics.len(), 1);
+        assert_eq!(statics[0].ip_address, Ipv4Addr::new(10, 0, 41, 50));
+        assert!(db.get_active_lease("aa:bb:cc:dd:ee:51").await.unwrap().is_none());
+        // The expired lease is ended, not deleted
+        let ended = db.get_recent_lease("aa:bb:cc:dd:ee:51", since).await.unwrap();
+        assert_eq!(ended.and_then(|l| l.id), Some(lid));
+
+        // A missing subnet leaves everything untouched, as does a failing plan
+        assert!(db.renumber_subnet(sid + 1000, &plan).await.unwrap().is_none());
+        let refuse = |_: &SubnetContents| -> anyhow::Result<Renumbering> { anyhow::bail!("no") };
+        assert!(db.renumber_subnet(sid, &refuse).await.is_err());
+        assert_eq!(db.list_ranges(Some(sid)).await.unwrap().len(), 1);
+        assert_eq!(db.get_subnet(sid).await.unwrap().unwrap().network, target.network);
+    }
+
     // --- Lease tests ---
 
     pub async fn test_create_and_get_active_lease(db: &dyn Database) {
@@ -235,6 +472,7 @@ pub(crate) mod suite {
             lease_start: now - 7200,
             lease_end: now - 3600,
             hostname: None,
+            range_id: None,
         };
         db.create_lease(&expired).await.unwrap();
 
@@ -245,6 +483,275 @@ pub(crate) mod suite {
             .is_none());
     }
 
+    pub async fn test_get_active_lease_by_ip(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(34)).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let ip = Ipv4Addr::new(10, 0, 34, 80);
+        let expired = Lease {
+            id: None,
+            subnet_id: sid,
+            mac_address: "aa:bb:cc:dd:ee:15".to_string(),
+            ip_address: ip,
+            lease_start: now - 7200,
+            lease_end: now - 3600,
+            hostname: None,
+            range_id: None,
+        };
+        db.create_lease(&expired).await.unwrap();
+        assert!(db.get_active_lease_by_ip(ip).await.unwrap().is_none());
+
+        db.create_lease(&active_lease(sid, "14", 34)).await.unwrap();
+        let lease = db
+            .get_active_lease_by_ip(ip)
+            .await
+            .unwrap()
+            .expect("lease not found");
+        assert_eq!(lease.mac_address, "aa:bb:cc:dd:ee:14");
+    }
+
+    pub async fn test_recent_lease_outlives_expiry(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(38)).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        for (last_octet, lease_end) in [(80, now - 100_000), (81, now - 600)] {
+            db.create_lease(&Lease {
+                id: None,
+                subnet_id: sid,
+                mac_address: "aa:bb:cc:dd:ee:38".to_string(),
+                ip_address: Ipv4Addr::new(10, 0, 38, last_octet),
+                lease_start: lease_end - 3600,
+                lease_end,
+                hostname: None,
+                range_id: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let recent = db
+            .get_recent_lease("AA:BB:CC:DD:EE:38", now - 3600)
+            .await
+            .unwrap()
+            .expect("recent lease not found");
+        assert_eq!(recent.ip_address, Ipv4Addr::new(10, 0, 38, 81));
+        assert!(db
+            .get_recent_lease("aa:bb:cc:dd:ee:38", now)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Only the lease that expired before the cutoff is purged
+        db.delete_leases_expired_before(now - 3600).await.unwrap();
+        assert!(db
+            .get_recent_lease("aa:bb:cc:dd:ee:38", 0)
+            .await
+            .unwrap()
+            .is_some_and(|l| l.ip_address == Ipv4Addr::new(10, 0, 38, 81)));
+        db.delete_expired_leases().await.unwrap();
+        assert!(db
+            .get_recent_lease("aa:bb:cc:dd:ee:38", 0)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    pub async fn test_lease_range_id(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(43)).await.unwrap();
+        let rid = db.create_range(&range(sid, 43)).await.unwrap();
+        db.create_lease(&Lease {
+            range_id: Some(rid),
+            ..active_lease(sid, "44", 43)
+        })
+        .await
+        .unwrap();
+        let lease = db.get_active_lease("aa:bb:cc:dd:ee:44").await.unwrap().unwrap();
+        assert_eq!(lease.range_id, Some(rid));
+
+        // Deleting the range keeps the lease but forgets where it came from
+        db.delete_range(rid).await.unwrap();
+        let lease = db.get_active_lease("aa:bb:cc:dd:ee:44").await.unwrap().unwrap();
+        assert_eq!(lease.range_id, None);
+    }
+
+    pub async fn test_release_lease(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(42)).await.unwrap();
+        let id = db.create_lease(&active_lease(sid, "43", 42)).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        // A lease that ran out on its own is not a released one
+        db.create_lease(&Lease {
+            ip_address: Ipv4Addr::new(10, 0, 42, 81),
+            lease_end: now - 5,
+            ..active_lease(sid, "45", 42)
+        })
+        .await
+        .unwrap();
+
+        db.release_lease(id, now - 5).await.unwrap();
+        assert!(db.get_active_lease("aa:bb:cc:dd:ee:43").await.unwrap().is_none());
+        let ended: Vec<_> = db
+            .list_leases_released_since(now - 60)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|l| l.subnet_id == sid)
+            .collect();
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].ip_address, Ipv4Addr::new(10, 0, 42, 80));
+        assert!(db
+            .list_leases_released_since(now)
+            .await
+            .unwrap()
+            .iter()
+            .all(|l| l.subnet_id != sid));
+    }
+
+    pub async fn test_rewrite_client_identities(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(46)).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            lease_start: now - 7200,
+            lease_end: now - 3600,
+            ..active_lease(sid, "60", 46)
+        })
+        .await
+        .unwrap();
+        db.create_static_ip(&StaticIP {
+            subnet_id: sid,
+            mac_address: "aa:bb:cc:dd:ee:61".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 46, 50),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+
+        let rewritten = db
+            .rewrite_client_identities(&|mac, hostname| {
+                Ok(mac.strip_prefix("aa:bb:cc:dd:ee:6").map(|suffix| {
+                    (format!("aa:bb:cc:dd:ef:6{suffix}"), hostname.map(|h| h.to_uppercase()))
+                }))
+            })
+            .await
+            .unwrap();
+        assert_eq!(rewritten, 2);
+
+        // The expired lease was rewritten too, and kept its end
+        let lease = db.get_recent_lease("aa:bb:cc:dd:ef:60", 0).await.unwrap().unwrap();
+        assert_eq!(lease.hostname.as_deref(), Some("TEST-HOST"));
+        assert_eq!(lease.lease_end, now - 3600);
+        assert!(db.get_recent_lease("aa:bb:cc:dd:ee:60", 0).await.unwrap().is_none());
+        let static_ip = db.get_static_ip_by_mac("aa:bb:cc:dd:ef:61").await.unwrap().unwrap();
+        assert_eq!(static_ip.ip_address, Ipv4Addr::new(10, 0, 46, 50));
+
+        // A failing rewrite changes nothing
+        assert!(db
+            .rewrite_client_identities(&|_, _| anyhow::bail!("refused"))
+            .await
+            .is_err());
+        assert!(db.get_static_ip_by_mac("aa:bb:cc:dd:ef:61").await.unwrap().is_some());
+    }
+
+    pub async fn test_create_if_free(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(47)).await.unwrap();
+        let lease = active_lease(sid, "62", 47);
+        let id = db.create_lease_if_free(&lease).await.unwrap();
+        assert!(id.is_some());
+        assert_eq!(db.get_active_lease("aa:bb:cc:dd:ee:62").await.unwrap().unwrap().id, id);
+
+        // The address is now held: neither a lease nor a static IP may claim it
+        let other = Lease {
+            mac_address: "aa:bb:cc:dd:ee:63".to_string(),
+            ..lease.clone()
+        };
+        assert_eq!(db.create_lease_if_free(&other).await.unwrap(), None);
+        let static_ip = StaticIP {
+            subnet_id: sid,
+            mac_address: "aa:bb:cc:dd:ee:63".to_string(),
+            ip_address: lease.ip_address,
+            hostname: None,
+        };
+        assert!(!db.create_static_ip_if_free(&static_ip).await.unwrap());
+        assert!(db.get_static_ip_by_mac("aa:bb:cc:dd:ee:63").await.unwrap().is_none());
+
+        // A free address is claimed by the static IP, which then blocks leases
+        let static_ip = StaticIP {
+            ip_address: Ipv4Addr::new(10, 0, 47, 81),
+            ..static_ip
+        };
+        assert!(db.create_static_ip_if_free(&static_ip).await.unwrap());
+        let blocked = Lease {
+            ip_address: static_ip.ip_address,
+            ..other
+        };
+        assert_eq!(db.create_lease_if_free(&blocked).await.unwrap(), None);
+        assert!(db.get_active_lease("aa:bb:cc:dd:ee:63").await.unwrap().is_none());
+    }
+
+    pub async fn test_replace_lease(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(49)).await.unwrap();
+        let lease = active_lease(sid, "52", 49);
+        let old_id = db.create_lease(&lease).await.unwrap();
+
+        // Renewing the same address ends the old lease in favour of the new one
+        let new_id = db.replace_lease(old_id, &lease).await.unwrap();
+        assert!(new_id.is_some());
+        let active = db.get_active_lease("aa:bb:cc:dd:ee:52").await.unwrap().unwrap();
+        assert_eq!(active.id, new_id);
+        let holders = db.list_active_leases().await.unwrap();
+        assert_eq!(holders.iter().filter(|l| l.ip_address == lease.ip_address).count(), 1);
+
+        // Moving onto an address leased to another client changes nothing
+        let other = Lease {
+            mac_address: "aa:bb:cc:dd:ee:53".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 49, 81),
+            ..lease.clone()
+        };
+        db.create_lease(&other).await.unwrap();
+        let taken = Lease {
+            ip_address: other.ip_address,
+            ..lease
+        };
+        assert_eq!(db.replace_lease(new_id.unwrap(), &taken).await.unwrap(), None);
+        let active = db.get_active_lease("aa:bb:cc:dd:ee:52").await.unwrap().unwrap();
+        assert_eq!(active.id, new_id);
+    }
+
+    pub async fn test_list_subnet_addresses(db: &dyn Database) {
+        let sid = db.create_subnet(&subnet(35)).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        // Lease at .80, static at .50, expired lease at .90 (ignored)
+        db.create_lease(&active_lease(sid, "16", 35)).await.unwrap();
+        db.create_static_ip(&StaticIP {
+            subnet_id: sid,
+            mac_address: "aa:bb:cc:dd:ee:17".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 35, 50),
+            hostname: Some("printer".to_string()),
+        })
+        .await
+        .unwrap();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id: sid,
+            mac_address: "aa:bb:cc:dd:ee:18".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 35, 90),
+            lease_start: now - 7200,
+            lease_end: now - 3600,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+
+        let addresses = db.list_subnet_addresses(sid).await.unwrap();
+        let summary: Vec<_> = addresses.iter().map(|a| (a.ip, a.mac.as_str(), a.source)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Ipv4Addr::new(10, 0, 35, 50), "aa:bb:cc:dd:ee:17", AddressSource::Static),
+                (Ipv4Addr::new(10, 0, 35, 80), "aa:bb:cc:dd:ee:16", AddressSource::Lease),
+            ]
+        );
+        assert_eq!(addresses[0].hostname.as_deref(), Some("printer"));
+    }
+
     // --- IA Prefix tests ---
 
     pub async fn test_create_and_get_ia_prefix(db: &dyn Database) {
@@ -304,7 +811,7 @@ pub(crate) mod suite {
         assert!(id > 0);
 
         let tokens = db.list_tokens().await.unwrap();
-        assert!(tokens.iter().any(|(h, _)| h == "hash_tok1"));
+        assert!(tokens.iter().any(|(i, h, _)| *i == id && h == "hash_tok1"));
     }
 
     pub async fn test_list_api_tokens(db: &dyn Database) {
@@ -324,7 +831,7 @@ pub(crate) mod suite {
         db.delete_token(id).await.unwrap();
 
         let tokens = db.list_tokens().await.unwrap();
-        assert!(!tokens.iter().any(|(h, _)| h == "hash_tok3"));
+        assert!(!tokens.iter().any(|(_, h, _)| h == "hash_tok3"));
     }
 
     pub async fn test_toggle_token(db: &dyn Database) {
@@ -335,11 +842,11 @@ pub(crate) mod suite {
 
         db.toggle_token(id, false).await.unwrap();
         let tokens = db.list_tokens().await.unwrap();
-        assert!(!tokens.iter().any(|(h, _)| h == "hash_tok4"));
+        assert!(!tokens.iter().any(|(_, h, _)| h == "hash_tok4"));
 
         db.toggle_token(id, true).await.unwrap();
         let tokens = db.list_tokens().await.unwrap();
-        assert!(tokens.iter().any(|(h, _)| h == "hash_tok4"));
+        assert!(tokens.iter().any(|(_, h, _)| h == "hash_tok4"));
     }
 
     pub async fn test_update_token_last_used(db: &dyn Database) {
@@ -356,22 +863,41 @@ pub(crate) mod suite {
         test_create_and_get_subnet(db).await;
         test_list_subnets(db).await;
         test_update_subnet(db).await;
+        test_patch_subnet(db).await;
         test_delete_subnet(db).await;
         test_get_subnet_not_found(db).await;
+        test_get_subnets_by_ids(db).await;
 
         test_create_and_list_range(db).await;
+        test_create_subnet_with_ranges(db).await;
         test_list_ranges_all(db).await;
         test_delete_range(db).await;
+        test_toggle_range(db).await;
+        test_set_range_draining(db).await;
 
         test_create_and_list_static_ip(db).await;
         test_get_static_ip_by_mac(db).await;
         test_get_static_ip_by_mac_not_found(db).await;
         test_delete_static_ip(db).await;
+        test_delete_static_ips_by_subnet(db).await;
 
         test_create_and_get_active_lease(db).await;
         test_list_active_leases(db).await;
         test_expire_lease(db).await;
         test_expired_lease_not_returned(db).await;
+        test_get_active_lease_by_ip(db).await;
+        test_list_subnet_addresses(db).await;
+
+        test_pending_offers(db).await;
+        test_recent_lease_outlives_expiry(db).await;
+        test_release_lease(db).await;
+        test_lease_range_id(db).await;
+        test_rewrite_client_identities(db).await;
+        test_replace_lease(db).await;
+        test_create_if_free(db).await;
+
+        test_option_templates(db).await;
+        test_renumber_subnet(db).await;
 
         test_create_and_get_ia_prefix(db).await;
         test_list_ia_prefixes_by_interface(db).await;
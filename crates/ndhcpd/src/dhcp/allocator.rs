@@ -0,0 +1,406 @@
+//! Free address selection shared by DISCOVER handling and the allocation API.
+//!
+//! Keeping a single implementation guarantees that an address handed out by
+//! `POST /api/subnets/{id}/allocate` is never offered to another DHCP client.
+
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+
+use crate::config::MultiRangeStrategy;
+use crate::db::Database;
+use crate::models::{DynamicRange, Subnet};
+
+/// Addresses that must not be handed out dynamically to `client`: active
+/// leases (including short-lived reservations), static assignments, and
+/// addresses another client released less than `release_grace_secs` ago.
+/// With no `client`, no released address is handed out within the grace.
+pub async fn used_addresses(
+    db: &dyn Database,
+    release_grace_secs: u64,
+    client: Option<&str>,
+) -> anyhow::Result<HashSet<Ipv4Addr>> {
+    let mut used: HashSet<Ipv4Addr> = db
+        .list_active_leases()
+        .await?
+        .into_iter()
+        .map(|l| l.ip_address)
+        .collect();
+    used.extend(db.list_static_ips(None).await?.into_iter().map(|s| s.ip_address));
+    if release_grace_secs > 0 {
+        let since = chrono::Utc::now().timestamp() - release_grace_secs as i64;
+        used.extend(
+            db.list_leases_released_since(since)
+                .await?
+                .into_iter()
+                .filter(|l| !client.is_some_and(|mac| l.mac_address.eq_ignore_ascii_case(mac)))
+                .map(|l| l.ip_address),
+        );
+    }
+    Ok(used)
+}
+
+/// First address not in `used` of an enabled, non-draining range of `subnet`: the first
+/// range with room under [`MultiRangeStrategy::Sequential`], the one with the
+/// most free addresses (the first of them on a tie) under `Balanced`.
+/// The network, broadcast and gateway addresses are skipped even when a
+/// range covers them.
+pub async fn next_free_ip(
+    db: &dyn Database,
+    subnet: &Subnet,
+    used: &HashSet<Ipv4Addr>,
+    strategy: MultiRangeStrategy,
+) -> anyhow::Result<Option<Ipv4Addr>> {
+    let Some(subnet_id) = subnet.id else {
+        return Ok(None);
+    };
+    let ranges = db.list_ranges(Some(subnet_id)).await?;
+    let mut active = ranges.iter().filter(|r| r.enabled && !r.draining);
+    let first_free = |r: &DynamicRange| {
+        (u32::from(r.range_start)..=u32::from(r.range_end))
+            .map(Ipv4Addr::from)
+            .find(|ip| !subnet.is_reserved(*ip) && !used.contains(ip))
+    };
+    Ok(match strategy {
+        MultiRangeStrategy::Sequential => active.find_map(first_free),
+        MultiRangeStrategy::Balanced => {
+            let mut best: Option<(u64, &DynamicRange)> = None;
+            for range in active {
+                let count = free_count(range, subnet, used);
+                if count > 0 && best.is_none_or(|(most, _)| count > most) {
+                    best = Some((count, range));
+                }
+            }
+            best.and_then(|(_, range)| first_free(range))
+        }
+    })
+}
+
+/// Number of addresses of `range` that [`next_free_ip`] could hand out,
+/// counted from `used` rather than by walking the range
+fn free_count(range: &DynamicRange, subnet: &Subnet, used: &HashSet<Ipv4Addr>) -> u64 {
+    let (first, last) = subnet.usable_range();
+    let span = range.range_start.max(first)..=range.range_end.min(last);
+    if span.is_empty() {
+        return 0;
+    }
+    let size = u64::from(u32::from(*span.end()) - u32::from(*span.start())) + 1;
+    let gateway = span.contains(&subnet.gateway) && !used.contains(&subnet.gateway);
+    let taken = used.iter().filter(|ip| span.contains(*ip)).count() as u64;
+    size - taken - u64::from(gateway)
+}
+
+/// Whether `ip` could be handed out by [`next_free_ip`]: it lies in an
+/// enabled, non-draining range of `subnet`, is not reserved and not in `used`.
+pub async fn is_free_ip(
+    db: &dyn Database,
+    subnet: &Subnet,
+    ip: Ipv4Addr,
+    used: &HashSet<Ipv4Addr>,
+) -> anyhow::Result<bool> {
+    let Some(subnet_id) = subnet.id else {
+        return Ok(false);
+    };
+    if subnet.is_reserved(ip) || used.contains(&ip) {
+        return Ok(false);
+    }
+    let ranges = db.list_ranges(Some(subnet_id)).await?;
+    Ok(ranges
+        .iter()
+        .any(|r| r.enabled && !r.draining && (r.range_start..=r.range_end).contains(&ip)))
+}
+
+/// Size of a subnet's dynamic pool and how much of it is still free
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Utilization {
+    /// Addresses of enabled, non-draining ranges that may be handed out
+    pub total: u64,
+    /// Those not held by an active lease or a static assignment
+    pub free: u64,
+}
+
+/// [`Utilization`] of every subnet, from a fixed number of queries whatever
+/// the number of subnets. Overlapping ranges are counted once.
+pub async fn subnet_utilization(db: &dyn Database) -> anyhow::Result<Vec<(Subnet, Utilization)>> {
+    let subnets = db.list_subnets().await?;
+    let ranges = db.list_ranges(None).await?;
+    let used = used_addresses(db, 0, None).await?;
+
+    Ok(subnets
+        .into_iter()
+        .map(|subnet| {
+            let mut pool: Vec<(u32, u32)> = ranges
+                .iter()
+                .filter(|r| r.enabled && !r.draining && Some(r.subnet_id) == subnet.id)
+                .map(|r| (u32::from(r.range_start), u32::from(r.range_end)))
+                .filter(|(start, end)| start <= end)
+                .collect();
+            pool.sort_unstable();
+            let mut merged: Vec<(u32, u32)> = Vec::with_capacity(pool.len());
+            for (start, end) in pool {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            let in_pool = |ip: Ipv4Addr| {
+                let ip = u32::from(ip);
+                merged.iter().any(|(start, end)| (*start..=*end).contains(&ip))
+            };
+
+            let mut reserved = vec![subnet.network_address(), subnet.broadcast(), subnet.gateway];
+            reserved.sort_unstable();
+            reserved.dedup();
+            let size: u64 = merged.iter().map(|(start, end)| u64::from(end - start) + 1).sum();
+            let total = size
+                - reserved
+                    .iter()
+                    .filter(|ip| subnet.is_reserved(**ip) && in_pool(**ip))
+                    .count() as u64;
+            let taken = used
+                .iter()
+                .filter(|ip| !subnet.is_reserved(**ip) && in_pool(**ip))
+                .count() as u64;
+            (subnet, Utilization { total, free: total - taken })
+        })
+        .collect())
+}
+
+/// Whether `mac` must not be given another address because it already holds
+/// `cap` active leases, not counting a lease on `renewing`. `None` is unlimited.
+pub async fn at_lease_cap(
+    db: &dyn Database,
+    mac: &str,
+    cap: Option<u32>,
+    renewing: Option<Ipv4Addr>,
+) -> anyhow::Result<bool> {
+    let Some(cap) = cap else {
+        return Ok(false);
+    };
+    let held = db
+        .list_active_leases()
+        .await?
+        .iter()
+        .filter(|l| l.mac_address.eq_ignore_ascii_case(mac) && Some(l.ip_address) != renewing)
+        .count();
+    Ok(held >= cap as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::InMemoryDatabase;
+    use crate::models::{DynamicRange, Lease, StaticIP};
+
+    #[tokio::test]
+    async fn test_next_free_ip_skips_leases_and_statics() {
+        let db = InMemoryDatabase::new();
+        let mut subnet = Subnet::from_cidr("10.0.0.0/24").unwrap();
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        subnet.id = Some(subnet_id);
+        db.create_range(&DynamicRange {
+            id: None,
+            subnet_id,
+            range_start: Ipv4Addr::new(10, 0, 0, 10),
+            range_end: Ipv4Addr::new(10, 0, 0, 12),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id,
+            mac_address: "aa:aa:aa:aa:aa:01".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 0, 10),
+            lease_start: now,
+            lease_end: now + 3600,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "aa:aa:aa:aa:aa:02".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 0, 11),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+
+        let used = used_addresses(&db, 0, None).await.unwrap();
+        assert_eq!(
+            next_free_ip(&db, &subnet, &used, MultiRangeStrategy::Sequential).await.unwrap(),
+            Some(Ipv4Addr::new(10, 0, 0, 12))
+        );
+
+        let mut used = used;
+        used.insert(Ipv4Addr::new(10, 0, 0, 12));
+        assert_eq!(
+            next_free_ip(&db, &subnet, &used, MultiRangeStrategy::Sequential).await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_balanced_allocation_spreads_across_ranges() {
+        let db = InMemoryDatabase::new();
+        let mut subnet = Subnet::from_cidr("10.0.0.0/24").unwrap();
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        subnet.id = Some(subnet_id);
+        for (start, end) in [(10, 19), (100, 109)] {
+            db.create_range(&DynamicRange {
+                id: None,
+                subnet_id,
+                range_start: Ipv4Addr::new(10, 0, 0, start),
+                range_end: Ipv4Addr::new(10, 0, 0, end),
+                enabled: true,
+                draining: false,
+            })
+            .await
+            .unwrap();
+        }
+
+        let allocate = |strategy| {
+            let db = &db;
+            let subnet = &subnet;
+            async move {
+                let mut used = HashSet::new();
+                for _ in 0..6 {
+                    let ip = next_free_ip(db, subnet, &used, strategy).await.unwrap().unwrap();
+                    used.insert(ip);
+                }
+                used
+            }
+        };
+        let in_second =
+            |used: &HashSet<Ipv4Addr>| used.iter().filter(|ip| ip.octets()[3] >= 100).count();
+
+        let sequential = allocate(MultiRangeStrategy::Sequential).await;
+        assert_eq!(in_second(&sequential), 0);
+
+        let balanced = allocate(MultiRangeStrategy::Balanced).await;
+        assert_eq!(in_second(&balanced), 3);
+        assert!(balanced.contains(&Ipv4Addr::new(10, 0, 0, 10)));
+        assert!(balanced.contains(&Ipv4Addr::new(10, 0, 0, 100)));
+    }
+
+    #[test]
+    fn test_free_count_skips_reserved_and_used() {
+        let subnet = Subnet::from_cidr("10.0.0.0/24").unwrap();
+        let range = |start, end| DynamicRange {
+            id: None,
+            subnet_id: 1,
+            range_start: Ipv4Addr::new(10, 0, 0, start),
+            range_end: Ipv4Addr::new(10, 0, 0, end),
+            enabled: true,
+            draining: false,
+        };
+        let used = HashSet::from([Ipv4Addr::new(10, 0, 0, 5), Ipv4Addr::new(10, 0, 0, 50)]);
+        // .0 is the network address and .1 the gateway
+        assert_eq!(free_count(&range(0, 10), &subnet, &used), 8);
+        assert_eq!(free_count(&range(250, 255), &subnet, &used), 5);
+        assert_eq!(free_count(&range(6, 6), &subnet, &used), 1);
+        assert_eq!(free_count(&range(5, 5), &subnet, &used), 0);
+    }
+
+    #[tokio::test]
+    async fn test_balanced_allocation_in_large_range() {
+        let db = InMemoryDatabase::new();
+        let mut subnet = Subnet::from_cidr("10.0.0.0/8").unwrap();
+        subnet.id = Some(db.create_subnet(&subnet).await.unwrap());
+        let small = (Ipv4Addr::new(10, 0, 0, 10), Ipv4Addr::new(10, 0, 0, 19));
+        let large = (Ipv4Addr::new(10, 1, 0, 0), Ipv4Addr::new(10, 254, 255, 255));
+        for (start, end) in [small, large] {
+            db.create_range(&DynamicRange {
+                id: None,
+                subnet_id: subnet.id.unwrap(),
+                range_start: start,
+                range_end: end,
+                enabled: true,
+                draining: false,
+            })
+            .await
+            .unwrap();
+        }
+
+        // Picked from the counts: the millions of addresses are not walked
+        let used = HashSet::from([Ipv4Addr::new(10, 1, 0, 0)]);
+        let ip = next_free_ip(&db, &subnet, &used, MultiRangeStrategy::Balanced).await.unwrap();
+        assert_eq!(ip, Some(Ipv4Addr::new(10, 1, 0, 1)));
+    }
+
+    #[tokio::test]
+    async fn test_subnet_utilization() {
+        let db = InMemoryDatabase::new();
+        let subnet = Subnet::from_cidr("10.0.0.0/24").unwrap();
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        // Overlapping ranges covering the gateway (.1) and network address
+        for (start, end) in [(0, 10), (5, 20)] {
+            db.create_range(&DynamicRange {
+                id: None,
+                subnet_id,
+                range_start: Ipv4Addr::new(10, 0, 0, start),
+                range_end: Ipv4Addr::new(10, 0, 0, end),
+                enabled: true,
+                draining: false,
+            })
+            .await
+            .unwrap();
+        }
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id,
+            mac_address: "aa:aa:aa:aa:aa:01".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 0, 12),
+            lease_start: now,
+            lease_end: now + 3600,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "aa:aa:aa:aa:aa:02".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 0, 200),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+
+        let utilization = subnet_utilization(&db).await.unwrap();
+        assert_eq!(utilization.len(), 1);
+        // .0 to .20 without .0 and .1; the static IP is outside the pool
+        assert_eq!(utilization[0].1, Utilization { total: 19, free: 18 });
+    }
+
+    #[tokio::test]
+    async fn test_at_lease_cap() {
+        let db = InMemoryDatabase::new();
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id: 1,
+            mac_address: "aa:aa:aa:aa:aa:01".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 0, 10),
+            lease_start: now,
+            lease_end: now + 3600,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+
+        let mac = "AA:AA:AA:AA:AA:01";
+        assert!(!at_lease_cap(&db, mac, None, None).await.unwrap());
+        assert!(!at_lease_cap(&db, mac, Some(2), None).await.unwrap());
+        assert!(at_lease_cap(&db, mac, Some(1), None).await.unwrap());
+        // Renewing the lease it already holds does not count against the cap
+        assert!(!at_lease_cap(&db, mac, Some(1), Some(Ipv4Addr::new(10, 0, 0, 10)))
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,78 @@
+//! Dynamic DNS coordination driven by the Client FQDN option (RFC 4702).
+//!
+//! This server performs no DNS updates. Its option 81 replies say so with
+//! the N flag, and a client that asked the server to update its forward (A)
+//! record is told to do it itself: S is cleared and O set.
+
+use dhcp_proto::option::fqdn_flags;
+
+/// Party a client asked to update its forward (A) record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardUpdater {
+    /// The client asked the server to update the A record (S=1)
+    Server,
+    /// The client updates its own A record (S=0)
+    Client,
+    /// The client asked that no updates be performed at all (N=1)
+    Nobody,
+}
+
+/// Decide who the client wants to own the forward record from its option 81 flags.
+pub fn forward_updater(client_flags: u8) -> ForwardUpdater {
+    if client_flags & fqdn_flags::NO_UPDATE != 0 {
+        ForwardUpdater::Nobody
+    } else if client_flags & fqdn_flags::SERVER_UPDATE != 0 {
+        ForwardUpdater::Server
+    } else {
+        ForwardUpdater::Client
+    }
+}
+
+/// Flags the server echoes back in its option 81 reply (RFC 4702 §2.1).
+///
+/// N is always set since no update will be performed, and O when that
+/// overrides a request for a server update; E is echoed so the reply uses
+/// the same name encoding as the request.
+pub fn reply_flags(client_flags: u8) -> u8 {
+    let encoding = client_flags & fqdn_flags::CANONICAL_ENCODING;
+    let overridden = match forward_updater(client_flags) {
+        ForwardUpdater::Server => fqdn_flags::OVERRIDE,
+        ForwardUpdater::Client | ForwardUpdater::Nobody => 0,
+    };
+    fqdn_flags::NO_UPDATE | overridden | encoding
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forward_updater() {
+        assert_eq!(
+            forward_updater(fqdn_flags::SERVER_UPDATE),
+            ForwardUpdater::Server
+        );
+        assert_eq!(forward_updater(0), ForwardUpdater::Client);
+        assert_eq!(
+            forward_updater(fqdn_flags::NO_UPDATE),
+            ForwardUpdater::Nobody
+        );
+    }
+
+    #[test]
+    fn test_reply_flags_never_claim_an_update() {
+        let client = fqdn_flags::SERVER_UPDATE | fqdn_flags::CANONICAL_ENCODING;
+        assert_eq!(
+            reply_flags(client),
+            fqdn_flags::NO_UPDATE | fqdn_flags::OVERRIDE | fqdn_flags::CANONICAL_ENCODING
+        );
+        assert_eq!(
+            reply_flags(fqdn_flags::NO_UPDATE | fqdn_flags::SERVER_UPDATE),
+            fqdn_flags::NO_UPDATE
+        );
+        assert_eq!(reply_flags(0), fqdn_flags::NO_UPDATE);
+        for flags in 0..=0x0f {
+            assert_eq!(reply_flags(flags) & fqdn_flags::SERVER_UPDATE, 0);
+        }
+    }
+}
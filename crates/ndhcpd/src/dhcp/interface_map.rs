@@ -0,0 +1,135 @@
+//! Explicit interface-to-subnet mapping (`dhcp.interface_subnets`).
+//!
+//! Without a mapping a subnet is served on every interface that holds an
+//! address inside it, which silently picks the wrong subnet when addressing
+//! overlaps or an interface is renumbered. The mapping is checked against the
+//! database once at startup so a typo fails fast instead of at the first DISCOVER.
+
+use std::net::Ipv4Addr;
+
+use crate::config::Config;
+use crate::db::Database;
+use crate::models::Subnet;
+
+/// Check `dhcp.interface_subnets` against the configured interfaces and the
+/// subnets stored in `db`. An empty mapping is always valid.
+pub async fn validate(config: &Config, db: &dyn Database) -> anyhow::Result<()> {
+    let mapping = &config.dhcp.interface_subnets;
+    if mapping.is_empty() {
+        return Ok(());
+    }
+
+    let subnet_ids: Vec<i64> = db.list_subnets().await?.iter().filter_map(|s| s.id).collect();
+    let mut problems = Vec::new();
+
+    for (iface, ids) in mapping {
+        if !config.listen_interfaces.contains(iface) {
+            problems.push(format!("interface {iface} is not in listen_interfaces"));
+        }
+        for id in ids.iter().filter(|id| !subnet_ids.contains(id)) {
+            problems.push(format!("interface {iface} references unknown subnet {id}"));
+        }
+    }
+
+    if subnet_ids.len() > 1 {
+        for iface in config.listen_interfaces.iter().filter(|i| !mapping.contains_key(*i)) {
+            problems.push(format!(
+                "interface {iface} has no mapping but {} subnets exist",
+                subnet_ids.len()
+            ));
+        }
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!("invalid dhcp.interface_subnets: {}", problems.join("; "));
+    }
+    Ok(())
+}
+
+/// Whether `subnet` may be served to a packet received on `iface`, whose
+/// addresses are `iface_ips`. A mapped interface serves exactly its listed
+/// subnets; otherwise the interface must hold an address inside the subnet.
+pub fn serves_subnet(
+    config: &Config,
+    iface: Option<&str>,
+    iface_ips: &[Ipv4Addr],
+    subnet: &Subnet,
+) -> bool {
+    match iface.and_then(|i| config.dhcp.interface_subnets.get(i)) {
+        Some(ids) => subnet.id.is_some_and(|id| ids.contains(&id)),
+        None => iface_ips.iter().any(|ip| subnet.contains(*ip)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::InMemoryDatabase;
+    use crate::dhcp::test_helpers::{create_test_config, create_test_subnet};
+
+    async fn db_with_subnets(count: u8) -> InMemoryDatabase {
+        let db = InMemoryDatabase::new();
+        for i in 0..count {
+            let mut subnet = create_test_subnet();
+            subnet.id = None;
+            subnet.network = Ipv4Addr::new(192, 168, 1 + i, 0);
+            subnet.gateway = Ipv4Addr::new(192, 168, 1 + i, 1);
+            db.create_subnet(&subnet).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_empty_mapping_is_valid() {
+        let db = db_with_subnets(2).await;
+        assert!(validate(&create_test_config(), &db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mapping_to_missing_subnet_fails() {
+        let db = db_with_subnets(1).await;
+        let mut config = create_test_config();
+        config.dhcp.interface_subnets.insert("lo".to_string(), vec![1, 42]);
+
+        let err = validate(&config, &db).await.unwrap_err().to_string();
+        assert!(err.contains("unknown subnet 42"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_unmapped_interface_with_several_subnets_fails() {
+        let db = db_with_subnets(2).await;
+        let mut config = create_test_config();
+        config.listen_interfaces = vec!["eth0".to_string(), "eth1".to_string()];
+        config.dhcp.interface_subnets.insert("eth0".to_string(), vec![1]);
+
+        let err = validate(&config, &db).await.unwrap_err().to_string();
+        assert!(err.contains("interface eth1 has no mapping"), "{err}");
+
+        config.dhcp.interface_subnets.insert("eth1".to_string(), vec![2]);
+        assert!(validate(&config, &db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mapping_for_unknown_interface_fails() {
+        let db = db_with_subnets(1).await;
+        let mut config = create_test_config();
+        config.dhcp.interface_subnets.insert("eth9".to_string(), vec![1]);
+
+        let err = validate(&config, &db).await.unwrap_err().to_string();
+        assert!(err.contains("eth9 is not in listen_interfaces"), "{err}");
+    }
+
+    #[test]
+    fn test_mapping_overrides_interface_addresses() {
+        let mut config = create_test_config();
+        let subnet = create_test_subnet();
+        let on_link = [Ipv4Addr::new(192, 168, 1, 1)];
+        assert!(serves_subnet(&config, Some("lo"), &on_link, &subnet));
+
+        config.dhcp.interface_subnets.insert("lo".to_string(), vec![2]);
+        assert!(!serves_subnet(&config, Some("lo"), &on_link, &subnet));
+        // Interfaces missing from the mapping keep the address-based rule
+        assert!(serves_subnet(&config, Some("eth0"), &on_link, &subnet));
+        assert!(serves_subnet(&config, None, &on_link, &subnet));
+    }
+}
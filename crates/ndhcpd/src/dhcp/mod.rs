@@ -1,4 +1,10 @@
+pub mod allocator;
+pub mod ddns;
+pub mod interface_map;
+pub mod offers;
 pub mod packet;
+pub mod probe;
+pub mod rebinding;
 pub mod server;
 
 #[cfg(test)]
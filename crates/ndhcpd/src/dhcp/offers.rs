@@ -0,0 +1,191 @@
+//! Recently sent OFFERs, so a retransmitted DISCOVER gets the same answer.
+//!
+//! Clients resend DISCOVER with the same transaction ID until an OFFER
+//! arrives. Answering each copy from scratch re-runs allocation, which may
+//! pick another address once the first one looks taken. A retransmission is
+//! recognised by its *(interface, xid, chaddr)* within [`RETRANSMIT_WINDOW`],
+//! and is only answered from the cache once the packet filters have passed it
+//! and the offered address is confirmed to still be held for it. Copies
+//! whose `secs` field rose since the previous one are counted in
+//! `ndhcpd_dhcp_client_retransmissions_total`: the client gave up waiting for
+//! an answer, rather than the same packet arriving twice (e.g. via two relays).
+//!
+//! Offered addresses are also stored as pending offers in the database for
+//! [`PENDING_OFFER_TTL`], so that no other client is offered them meanwhile.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use dhcp_proto::{DhcpPacket, MacAddress};
+
+use super::packet::ReceivedPacket;
+use crate::metrics::METRICS;
+
+/// How long an OFFER is reused for retransmissions of its DISCOVER.
+/// Covers the first few client retries (4s, 8s, ... per RFC 2131 §4.1).
+pub const RETRANSMIT_WINDOW: Duration = Duration::from_secs(15);
+
+/// How long an offered address stays held for its client in the
+/// `pending_offers` table, waiting for the REQUEST.
+pub const PENDING_OFFER_TTL: Duration = Duration::from_secs(60);
+
+/// Number of OFFERs remembered; the least recently used is evicted first
+pub const OFFER_CACHE_CAPACITY: usize = 256;
+
+struct CachedOffer {
+    iface: Option<String>,
+    xid: u32,
+    chaddr: MacAddress,
+    sent_at: Instant,
+    offer: DhcpPacket,
+    /// `secs` of the latest copy of the DISCOVER
+    secs: u16,
+    retransmissions: u32,
+}
+
+impl CachedOffer {
+    fn answers(&self, discover: &ReceivedPacket) -> bool {
+        self.xid == discover.packet.xid
+            && self.chaddr == discover.packet.chaddr
+            && self.iface == discover.iface
+    }
+}
+
+/// Small LRU of recent OFFERs keyed by the DISCOVER's *(interface, xid, chaddr)*
+pub struct OfferCache {
+    offers: Mutex<VecDeque<CachedOffer>>,
+    capacity: usize,
+}
+
+impl OfferCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            offers: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    /// The OFFER already sent for this DISCOVER, if it is a retransmission,
+    /// with how many times the client has retransmitted it so far.
+    pub fn lookup(&self, discover: &ReceivedPacket) -> Option<(DhcpPacket, u32)> {
+        self.lookup_at(discover, Instant::now())
+    }
+
+    /// Remember the OFFER sent in answer to `discover`.
+    pub fn remember(&self, discover: &ReceivedPacket, offer: &DhcpPacket) {
+        self.remember_at(discover, offer, Instant::now())
+    }
+
+    fn lookup_at(&self, discover: &ReceivedPacket, now: Instant) -> Option<(DhcpPacket, u32)> {
+        let mut offers = self.offers.lock().unwrap_or_else(|e| e.into_inner());
+        offers.retain(|o| now.duration_since(o.sent_at) < RETRANSMIT_WINDOW);
+        let i = offers.iter().position(|o| o.answers(discover))?;
+        // Move to the back so the entry is evicted last
+        let mut entry = offers.remove(i)?;
+        let secs = discover.packet.secs;
+        if secs > entry.secs {
+            entry.retransmissions += 1;
+            METRICS.client_retransmissions.inc(&[]);
+        }
+        entry.secs = entry.secs.max(secs);
+        let found = (entry.offer.clone(), entry.retransmissions);
+        offers.push_back(entry);
+        Some(found)
+    }
+
+    fn remember_at(&self, discover: &ReceivedPacket, offer: &DhcpPacket, now: Instant) {
+        let mut offers = self.offers.lock().unwrap_or_else(|e| e.into_inner());
+        offers.retain(|o| !o.answers(discover));
+        if offers.len() == self.capacity {
+            offers.pop_front();
+        }
+        offers.push_back(CachedOffer {
+            iface: discover.iface.clone(),
+            xid: discover.packet.xid,
+            chaddr: discover.packet.chaddr.clone(),
+            sent_at: now,
+            offer: offer.clone(),
+            secs: discover.packet.secs,
+            retransmissions: 0,
+        });
+    }
+}
+
+impl Default for OfferCache {
+    fn default() -> Self {
+        Self::new(OFFER_CACHE_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn discover(xid: u32, mac: &str) -> ReceivedPacket {
+        ReceivedPacket::from_packet(DhcpPacket {
+            xid,
+            chaddr: MacAddress::from_string(mac).unwrap(),
+            ..DhcpPacket::new()
+        })
+    }
+
+    fn offer(last_octet: u8) -> DhcpPacket {
+        DhcpPacket {
+            yiaddr: Ipv4Addr::new(10, 0, 0, last_octet),
+            ..DhcpPacket::new()
+        }
+    }
+
+    #[test]
+    fn test_offers_expire_and_are_evicted_least_recently_used() {
+        let cache = OfferCache::new(2);
+        let start = Instant::now();
+        let (a, b, c) = (
+            discover(1, "aa:bb:cc:dd:ee:01"),
+            discover(2, "aa:bb:cc:dd:ee:02"),
+            discover(3, "aa:bb:cc:dd:ee:03"),
+        );
+        cache.remember_at(&a, &offer(1), start);
+        cache.remember_at(&b, &offer(2), start);
+
+        // Same xid from another client is a different transaction
+        assert!(cache.lookup_at(&discover(1, "aa:bb:cc:dd:ee:02"), start).is_none());
+        // So is the same DISCOVER heard on another interface
+        let elsewhere = ReceivedPacket {
+            iface: Some("eth1".to_string()),
+            ..a.clone()
+        };
+        assert!(cache.lookup_at(&elsewhere, start).is_none());
+
+        // Touching `a` makes `b` the one evicted by `c`
+        assert_eq!(cache.lookup_at(&a, start).unwrap().0.yiaddr, offer(1).yiaddr);
+        cache.remember_at(&c, &offer(3), start);
+        assert!(cache.lookup_at(&b, start).is_none());
+        assert!(cache.lookup_at(&a, start).is_some());
+
+        assert!(cache.lookup_at(&c, start + RETRANSMIT_WINDOW).is_none());
+    }
+
+    #[test]
+    fn test_rising_secs_counts_retransmissions() {
+        let cache = OfferCache::new(4);
+        let start = Instant::now();
+        let counter = &METRICS.client_retransmissions;
+        let before = counter.get(&[]);
+        let copy = |secs: u16| {
+            let mut received = discover(0x5ec5, "aa:bb:cc:dd:ee:5e");
+            received.packet.secs = secs;
+            received
+        };
+        cache.remember_at(&copy(0), &offer(1), start);
+
+        // A duplicate of the same copy is not a retransmission
+        assert_eq!(cache.lookup_at(&copy(0), start).unwrap().1, 0);
+        assert_eq!(cache.lookup_at(&copy(4), start).unwrap().1, 1);
+        assert_eq!(cache.lookup_at(&copy(4), start).unwrap().1, 1);
+        assert_eq!(cache.lookup_at(&copy(12), start).unwrap().1, 2);
+        assert_eq!(counter.get(&[]), before + 2);
+    }
+}
@@ -1,2 +1,96 @@
 // Re-export types from dhcp-proto crate
-pub use dhcp_proto::{DhcpOption, DhcpPacket, MacAddress, MessageType};
+pub use dhcp_proto::{DhcpOption, DhcpPacket, MacAddress, MessageType, OpCode};
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+/// Prefix of client keys taken from a Client Identifier that is not a MAC
+/// address, e.g. a DUID
+pub const CLIENT_ID_PREFIX: &str = "id:";
+
+/// A parsed DHCP packet together with where and when it was received.
+///
+/// Built once in the listen loop so every handler and downstream record sees
+/// the same source, interface and receive time.
+#[derive(Debug, Clone)]
+pub struct ReceivedPacket {
+    pub packet: DhcpPacket,
+    /// Source address of the datagram (the client, or the relay agent)
+    pub src: SocketAddr,
+    /// Interface the datagram arrived on, when known
+    pub iface: Option<String>,
+    /// Receive time (Unix timestamp in milliseconds)
+    pub received_at: i64,
+    /// Local address the datagram was received on, when known
+    pub local_addr: Option<Ipv4Addr>,
+}
+
+impl ReceivedPacket {
+    /// Wrap a packet that has just been received.
+    pub fn new(packet: DhcpPacket, src: SocketAddr, iface: Option<String>) -> Self {
+        Self {
+            packet,
+            src,
+            iface,
+            received_at: chrono::Utc::now().timestamp_millis(),
+            local_addr: None,
+        }
+    }
+
+    /// Record the local address the datagram was received on.
+    pub fn with_local_addr(mut self, local_addr: Option<Ipv4Addr>) -> Self {
+        self.local_addr = local_addr;
+        self
+    }
+
+    /// Wrap a bare packet with an unspecified source and no interface.
+    pub fn from_packet(packet: DhcpPacket) -> Self {
+        Self::new(packet, SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 68), None)
+    }
+
+    /// Interface name for log messages
+    pub fn iface_name(&self) -> &str {
+        self.iface.as_deref().unwrap_or("unknown interface")
+    }
+
+    /// Key identifying the client in leases and static assignments: its
+    /// Client Identifier (option 61) when sent, else `chaddr`. An Ethernet
+    /// identifier (type 1) holds the MAC, so it is written as one and matches
+    /// records keyed by `chaddr`; any other is written as colon-separated hex
+    /// after [`CLIENT_ID_PREFIX`], so that it never parses as a MAC.
+    pub fn client_key(&self) -> String {
+        match self.packet.get_client_identifier() {
+            Some([1, mac @ ..]) if mac.len() == 6 => {
+                MacAddress::from_slice(mac).map_or_else(String::new, |mac| mac.to_string())
+            }
+            Some(id) => {
+                let hex: Vec<_> = id.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("{}{}", CLIENT_ID_PREFIX, hex.join(":"))
+            }
+            None => self.packet.chaddr.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_key() {
+        let mut packet = DhcpPacket::new();
+        packet.chaddr = MacAddress::new([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        let key = |packet: &DhcpPacket| ReceivedPacket::from_packet(packet.clone()).client_key();
+        assert_eq!(key(&packet), "aa:bb:cc:dd:ee:ff");
+
+        packet.options.push(DhcpOption::ClientIdentifier(vec![1, 0x02, 0, 0, 0, 0, 0x01]));
+        assert_eq!(key(&packet), "02:00:00:00:00:01");
+
+        packet.options[0] = DhcpOption::ClientIdentifier(b"\0laptop".to_vec());
+        assert_eq!(key(&packet), "id:00:6c:61:70:74:6f:70");
+        assert!(MacAddress::from_string(&key(&packet)).is_none());
+
+        // A 6 byte identifier is not mistaken for a MAC either
+        packet.options[0] = DhcpOption::ClientIdentifier(vec![0, 1, 2, 3, 4, 5]);
+        assert!(MacAddress::from_string(&key(&packet)).is_none());
+    }
+}
@@ -0,0 +1,153 @@
+//! Liveness probing of candidate addresses before they are offered.
+//!
+//! The transport (ICMP echo, ARP, ...) is abstracted behind [`Prober`]; this
+//! module owns the timeout and retry policy configured by
+//! `dhcp.probe_timeout_ms` and `dhcp.probe_retries`.
+
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+use crate::config::DhcpConfig;
+
+/// Sends a single liveness probe to an address
+#[async_trait::async_trait]
+pub trait Prober: Send + Sync {
+    /// Returns true if `ip` answered within `timeout`.
+    async fn probe(&self, ip: Ipv4Addr, timeout: Duration) -> bool;
+}
+
+/// Probes with a single ICMP echo sent by ping(8).
+///
+/// The timeout is left to [`address_in_use`], which drops (and so kills) a
+/// ping still waiting for its reply.
+pub struct PingProber;
+
+#[async_trait::async_trait]
+impl Prober for PingProber {
+    async fn probe(&self, ip: Ipv4Addr, _timeout: Duration) -> bool {
+        tokio::process::Command::new("ping")
+            .args(["-c", "1", "-q", &ip.to_string()])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .status()
+            .await
+            .is_ok_and(|status| status.success())
+    }
+}
+
+/// Timeout and retry policy for address probes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeSettings {
+    /// How long to wait for an answer to each probe
+    pub timeout: Duration,
+    /// Probes sent after the first one went unanswered
+    pub retries: u32,
+}
+
+impl ProbeSettings {
+    pub fn from_config(config: &DhcpConfig) -> Self {
+        Self {
+            timeout: Duration::from_millis(config.probe_timeout_ms),
+            retries: config.probe_retries,
+        }
+    }
+}
+
+/// Probe `ip` up to `1 + retries` times and report whether anything answered.
+///
+/// Each attempt is bounded by the configured timeout even if the prober
+/// itself does not honour it.
+pub async fn address_in_use(prober: &dyn Prober, ip: Ipv4Addr, settings: ProbeSettings) -> bool {
+    for _ in 0..=settings.retries {
+        let answered = tokio::time::timeout(settings.timeout, prober.probe(ip, settings.timeout))
+            .await
+            .unwrap_or(false);
+        if answered {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Answers on the `answer_on`-th attempt (1-based), never if 0
+    struct MockProber {
+        attempts: AtomicU32,
+        answer_on: u32,
+        delay: Duration,
+    }
+
+    impl MockProber {
+        fn new(answer_on: u32) -> Self {
+            Self {
+                attempts: AtomicU32::new(0),
+                answer_on,
+                delay: Duration::ZERO,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Prober for MockProber {
+        async fn probe(&self, _ip: Ipv4Addr, _timeout: Duration) -> bool {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(self.delay).await;
+            attempt == self.answer_on
+        }
+    }
+
+    fn settings(retries: u32) -> ProbeSettings {
+        ProbeSettings {
+            timeout: Duration::from_millis(20),
+            retries,
+        }
+    }
+
+    #[test]
+    fn test_settings_from_config() {
+        let config = DhcpConfig {
+            probe_timeout_ms: 250,
+            probe_retries: 3,
+            ..DhcpConfig::default()
+        };
+        assert_eq!(
+            ProbeSettings::from_config(&config),
+            ProbeSettings {
+                timeout: Duration::from_millis(250),
+                retries: 3,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_silent_address_uses_every_retry() {
+        let prober = MockProber::new(0);
+        let ip = Ipv4Addr::new(192, 168, 1, 100);
+        assert!(!address_in_use(&prober, ip, settings(2)).await);
+        assert_eq!(prober.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_answer_stops_retrying() {
+        let prober = MockProber::new(2);
+        let ip = Ipv4Addr::new(192, 168, 1, 100);
+        assert!(address_in_use(&prober, ip, settings(5)).await);
+        assert_eq!(prober.attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_late_answer_counts_as_silent() {
+        let prober = MockProber {
+            delay: Duration::from_millis(200),
+            ..MockProber::new(1)
+        };
+        let ip = Ipv4Addr::new(192, 168, 1, 100);
+        assert!(!address_in_use(&prober, ip, settings(0)).await);
+        assert_eq!(prober.attempts.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,135 @@
+//! Warnings for leases that reach their rebinding time (T2) unrenewed.
+//!
+//! Clients renew at T1 (half the lease) and start broadcasting to any server
+//! at T2 (7/8 of it, as sent in option 59). A lease still unrenewed at T2
+//! usually belongs to a client that went away or lost its server, so one
+//! [`LeaseEventKind::Rebinding`] event is recorded per lease. A renewal
+//! creates a new lease row, which gets its own warning later if needed.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::events::{LeaseEvent, LeaseEventKind, EVENTS};
+use crate::models::Lease;
+
+/// How often active leases are checked against their rebinding time
+pub const REBINDING_SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Rebinding time of a lease, matching the T2 advertised in the ACK.
+/// Computed in i128 so that far-future lease ends (e.g. imported infinite
+/// leases) cannot overflow; the result always lies within the lease.
+pub fn rebinding_time(lease: &Lease) -> i64 {
+    let start = i128::from(lease.lease_start);
+    let t2 = start + (i128::from(lease.lease_end) - start) * 7 / 8;
+    t2 as i64
+}
+
+/// Remembers which leases were already reported as rebinding
+#[derive(Default)]
+pub struct RebindingWatch {
+    warned: Mutex<HashSet<i64>>,
+}
+
+impl RebindingWatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event for each lease in `active` that entered its rebinding
+    /// window since the last scan. Returns the number of events recorded.
+    pub fn scan(&self, active: &[Lease]) -> usize {
+        self.scan_at(active, chrono::Utc::now().timestamp_millis())
+    }
+
+    fn scan_at(&self, active: &[Lease], now_ms: i64) -> usize {
+        let now = now_ms / 1000;
+        let mut warned = self.warned.lock().unwrap_or_else(|e| e.into_inner());
+        // Forget leases that expired or were replaced by a renewal
+        warned.retain(|id| active.iter().any(|l| l.id == Some(*id)));
+
+        let mut recorded = 0;
+        for lease in active {
+            let Some(id) = lease.id else { continue };
+            if now < rebinding_time(lease) || now >= lease.lease_end || !warned.insert(id) {
+                continue;
+            }
+            EVENTS.record(LeaseEvent {
+                kind: LeaseEventKind::Rebinding,
+                mac_address: lease.mac_address.clone(),
+                ip_address: lease.ip_address,
+                subnet_id: lease.subnet_id,
+                timestamp: now_ms,
+            });
+            recorded += 1;
+        }
+        recorded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_rebinding_event_fires_once_per_lease() {
+        let lease = Lease {
+            id: Some(1),
+            subnet_id: 197_201,
+            mac_address: "aa:bb:cc:19:72:01".to_string(),
+            ip_address: Ipv4Addr::new(10, 197, 2, 1),
+            lease_start: 1_000,
+            lease_end: 1_800,
+            hostname: None,
+            range_id: None,
+        };
+        let count = || {
+            EVENTS
+                .recent()
+                .iter()
+                .filter(|e| e.kind == LeaseEventKind::Rebinding && e.subnet_id == 197_201)
+                .count()
+        };
+        let watch = RebindingWatch::new();
+        let active = [lease];
+
+        // T2 is 1000 + 800 * 7 / 8 = 1700
+        assert_eq!(watch.scan_at(&active, 1_699_000), 0);
+        assert_eq!(watch.scan_at(&active, 1_700_000), 1);
+        assert_eq!(watch.scan_at(&active, 1_750_000), 0);
+        assert_eq!(count(), 1);
+
+        // A renewal is a new lease row and is watched afresh
+        let renewed = Lease {
+            id: Some(2),
+            lease_start: 1_750,
+            lease_end: 2_550,
+            ..active[0].clone()
+        };
+        assert_eq!(watch.scan_at(std::slice::from_ref(&renewed), 1_760_000), 0);
+        assert_eq!(watch.scan_at(std::slice::from_ref(&renewed), 2_450_000), 1);
+        assert_eq!(count(), 2);
+    }
+
+    #[test]
+    fn test_rebinding_time_of_never_ending_lease() {
+        let lease = Lease {
+            id: Some(1),
+            subnet_id: 197_202,
+            mac_address: "aa:bb:cc:19:72:02".to_string(),
+            ip_address: Ipv4Addr::new(10, 197, 2, 2),
+            lease_start: 1_000,
+            lease_end: i64::MAX,
+            hostname: None,
+            range_id: None,
+        };
+        let t2 = rebinding_time(&lease);
+        assert!(t2 > lease.lease_start && t2 < lease.lease_end);
+        assert!(t2 > i64::MAX / 8 * 7);
+
+        // Scanning it does not panic and reports nothing yet
+        let watch = RebindingWatch::new();
+        assert_eq!(watch.scan_at(&[lease], 2_000_000), 0);
+    }
+}
@@ -1,16 +1,29 @@
-use std::collections::HashSet;
 use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
 use std::os::unix::io::AsRawFd;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing::{debug, error, info, warn};
 
-use super::packet::{DhcpOption, DhcpPacket, MessageType};
-use crate::config::Config;
-use crate::db::{Database, DynDatabase};
-use crate::utils::network::{build_l2_udp_frame, get_iface_mac};
+use super::allocator;
+use super::interface_map::serves_subnet;
+use super::offers::{OfferCache, PENDING_OFFER_TTL};
+use super::packet::{DhcpOption, DhcpPacket, MessageType, OpCode, ReceivedPacket};
+use super::probe::{self, ProbeSettings, Prober};
+use super::rebinding::{RebindingWatch, REBINDING_SCAN_INTERVAL};
+use crate::config::{Config, Oui};
+use crate::db::{retry::with_retry, Database, DynDatabase};
+use crate::events::{LeaseEvent, LeaseEventKind, EVENTS};
+use crate::models::PrefixLen;
+use crate::utils::hostname::sanitize_hostname;
+use crate::utils::network::{build_l2_udp_frame, get_iface_mac, get_interface_ips};
 
 const DHCP_SERVER_PORT: u16 = 67;
 const DHCP_CLIENT_PORT: u16 = 68;
+/// BROADCAST bit (bit 15) of the BOOTP `flags` field (RFC 2131 §2).
+pub(crate) const BROADCAST_FLAG: u16 = 0x8000;
+
+/// Hardware type of Ethernet (`htype`), whose 6-byte MAC fills `chaddr`
+const HTYPE_ETHERNET: u8 = 1;
 
 /// Enable reception of per-packet interface information through ancillary data.
 ///
@@ -48,21 +61,23 @@ fn enable_recv_interface(socket: &UdpSocket) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Receive one UDP datagram and return *(bytes_received, source_addr, interface_name)*.
+/// Receive one UDP datagram and return *(bytes_received, source_addr,
+/// interface_name, local_addr)*.
 ///
 /// Uses `recvmsg(2)` to harvest ancillary data that identifies the network
 /// interface the datagram arrived on (`IP_RECVIF` on FreeBSD, `IP_PKTINFO` on
 /// Linux).  If the interface cannot be determined, `"unknown"` is returned as
 /// the interface name so that the caller can apply its filter and drop the packet.
+/// The local address it was received on is only known on Linux.
 fn recv_with_interface(
     socket: &UdpSocket,
     buf: &mut [u8],
-) -> anyhow::Result<(usize, SocketAddr, String)> {
+) -> anyhow::Result<(usize, SocketAddr, String, Option<Ipv4Addr>)> {
     // 256 bytes is more than enough for one IP_RECVIF / IP_PKTINFO cmsg.
     let mut ctrl = [0u8; 256];
     let mut src_storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
 
-    let (len, iface_name) = unsafe {
+    let (len, iface_name, local_addr) = unsafe {
         let mut iov = libc::iovec {
             iov_base: buf.as_mut_ptr() as *mut libc::c_void,
             iov_len: buf.len(),
@@ -83,13 +98,13 @@ fn recv_with_interface(
 
         let name = parse_interface_from_cmsg(&msg)
             .unwrap_or_else(|| "unknown".to_string());
-        (n as usize, name)
+        (n as usize, name, parse_local_addr_from_cmsg(&msg))
     };
 
     let src = unsafe { sockaddr_storage_to_socketaddr(&src_storage) }
         .unwrap_or_else(|| SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0));
 
-    Ok((len, src, iface_name))
+    Ok((len, src, iface_name, local_addr))
 }
 
 /// Extract the incoming interface name from `recvmsg` ancillary data.
@@ -155,6 +170,34 @@ unsafe fn parse_interface_from_cmsg(_msg: &libc::msghdr) -> Option<String> {
     None
 }
 
+/// Extract the local address a datagram was received on from `IP_PKTINFO`:
+/// its destination when that is one of our unicast addresses, otherwise
+/// (broadcast) the address the kernel picked on the receiving interface.
+#[cfg(target_os = "linux")]
+unsafe fn parse_local_addr_from_cmsg(msg: &libc::msghdr) -> Option<Ipv4Addr> {
+    let mut cmsg = libc::CMSG_FIRSTHDR(msg);
+    while !cmsg.is_null() {
+        let cm = &*cmsg;
+        if cm.cmsg_level == libc::IPPROTO_IP && cm.cmsg_type == libc::IP_PKTINFO {
+            let pktinfo = &*(libc::CMSG_DATA(cmsg) as *const libc::in_pktinfo);
+            let dst = Ipv4Addr::from(u32::from_be(pktinfo.ipi_addr.s_addr));
+            let local = if dst.is_broadcast() || dst.is_unspecified() || dst.is_multicast() {
+                Ipv4Addr::from(u32::from_be(pktinfo.ipi_spec_dst.s_addr))
+            } else {
+                dst
+            };
+            return Some(local).filter(|ip| !ip.is_unspecified());
+        }
+        cmsg = libc::CMSG_NXTHDR(msg, cmsg);
+    }
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+unsafe fn parse_local_addr_from_cmsg(_msg: &libc::msghdr) -> Option<Ipv4Addr> {
+    None
+}
+
 /// Convert a `sockaddr_storage` populated by `recvmsg` into a `SocketAddr`.
 unsafe fn sockaddr_storage_to_socketaddr(
     ss: &libc::sockaddr_storage,
@@ -188,6 +231,7 @@ unsafe fn sockaddr_storage_to_socketaddr(
 /// On Linux, `SO_BINDTODEVICE` on the receive socket already pins the
 /// interface, and the routing table normally has a broadcast route, so
 /// neither workaround is needed.
+#[cfg_attr(not(target_os = "freebsd"), allow(unused_variables))]
 fn create_broadcast_send_socket(interface: &str) -> anyhow::Result<UdpSocket> {
     #[cfg(target_os = "freebsd")]
     {
@@ -325,7 +369,7 @@ fn send_raw_l2_frame(iface: &str, frame: &[u8]) -> anyhow::Result<()> {
 ///
 /// * Ethernet dst  : `ff:ff:ff:ff:ff:ff`  (broadcast at L2)
 /// * IP dst        : `dst_ip`  – either `yiaddr` or `255.255.255.255` depending
-///                   on whether the client set the BROADCAST flag (RFC 2131 §4.1)
+///   on whether the client set the BROADCAST flag (RFC 2131 §4.1)
 /// * IP src        : first IPv4 address of `iface` (server identifier)
 /// * Ethernet src  : MAC address of `iface`
 ///
@@ -355,9 +399,10 @@ fn send_dhcp_raw_l2_broadcast(
 ///
 /// Rules applied in order:
 /// 1. `giaddr` != 0 (relay agent): send to relay agent on port 67.
-/// 2. `ciaddr` != 0 (client has a configured IP): unicast to `ciaddr:68`.
-/// 3. BROADCAST flag set in client request: `255.255.255.255:68`.
-/// 4. Otherwise: unicast to `yiaddr:68`.
+/// 2. DHCPNAK: `255.255.255.255:68`, the client's address is not valid.
+/// 3. `ciaddr` != 0 (client has a configured IP): unicast to `ciaddr:68`.
+/// 4. BROADCAST flag set in client request: `255.255.255.255:68`.
+/// 5. Otherwise: unicast to `yiaddr:68`.
 ///
 /// When `ciaddr=0` (client in SELECTING state), the caller uses
 /// [`send_dhcp_raw_l2_broadcast`] which forges the complete L2 frame with
@@ -366,12 +411,13 @@ fn response_dest(
     request: &DhcpPacket,
     response: &DhcpPacket,
 ) -> SocketAddr {
-    const BROADCAST_FLAG: u16 = 0x8000;
     let unspecified = Ipv4Addr::UNSPECIFIED;
 
     if request.giaddr != unspecified {
         // Relay agent present – return to relay on the DHCP server port
         SocketAddr::new(request.giaddr.into(), DHCP_SERVER_PORT)
+    } else if response.get_message_type() == Some(MessageType::Nak) {
+        SocketAddr::new(Ipv4Addr::BROADCAST.into(), DHCP_CLIENT_PORT)
     } else if request.ciaddr != unspecified {
         // Client already has an IP address (RENEWING/REBINDING)
         SocketAddr::new(request.ciaddr.into(), DHCP_CLIENT_PORT)
@@ -389,11 +435,25 @@ fn response_dest(
 pub struct DhcpServer {
     config: Arc<Config>,
     db: DynDatabase,
+    offers: OfferCache,
+    prober: Option<Arc<dyn Prober>>,
 }
 
 impl DhcpServer {
     pub fn new(config: Arc<Config>, db: DynDatabase) -> Self {
-        Self { config, db }
+        Self {
+            config,
+            db,
+            offers: OfferCache::default(),
+            prober: None,
+        }
+    }
+
+    /// Probe fresh dynamic addresses with `prober` before offering them,
+    /// skipping those that answer.
+    pub fn with_prober(mut self, prober: Arc<dyn Prober>) -> Self {
+        self.prober = Some(prober);
+        self
     }
 
     pub async fn run(&self) -> anyhow::Result<()> {
@@ -402,10 +462,9 @@ impl DhcpServer {
         // A single socket listens on 0.0.0.0:67. The incoming interface is
         // identified from ancillary data (IP_RECVIF / IP_PKTINFO) and packets
         // are filtered against config.listen_interfaces before processing.
-        let server = Arc::new(Self {
-            config: Arc::clone(&self.config),
-            db: Arc::clone(&self.db),
-        });
+        let mut server = Self::new(Arc::clone(&self.config), Arc::clone(&self.db));
+        server.prober = self.prober.clone();
+        let server = Arc::new(server);
 
         tokio::spawn(async move {
             if let Err(e) = server.listen_loop().await {
@@ -416,12 +475,20 @@ impl DhcpServer {
         // Spawn a background task that periodically purges expired leases.
         let cleanup_db = Arc::clone(&self.db);
         let cleanup_interval = self.config.dhcp.lease_cleanup_interval_minutes;
+        // Leases within the lease memory window are kept for re-offering, and
+        // released ones for their grace period
+        let lease_memory = self
+            .config
+            .dhcp
+            .lease_memory_secs
+            .max(self.config.dhcp.release_grace_seconds) as i64;
         tokio::spawn(async move {
             let interval =
                 tokio::time::Duration::from_secs(cleanup_interval as u64 * 60);
             loop {
                 tokio::time::sleep(interval).await;
-                match cleanup_db.delete_expired_leases().await {
+                let cutoff = chrono::Utc::now().timestamp() - lease_memory;
+                match cleanup_db.delete_leases_expired_before(cutoff).await {
                     Ok(n) if n > 0 => info!("Lease cleanup: deleted {} expired lease(s)", n),
                     Ok(_) => debug!("Lease cleanup: no expired leases"),
                     Err(e) => warn!("Lease cleanup failed: {}", e),
@@ -429,6 +496,24 @@ impl DhcpServer {
             }
         });
 
+        // Spawn a background task that reports leases entering rebinding.
+        let rebinding_db = Arc::clone(&self.db);
+        tokio::spawn(async move {
+            let watch = RebindingWatch::new();
+            loop {
+                tokio::time::sleep(REBINDING_SCAN_INTERVAL).await;
+                match rebinding_db.list_active_leases().await {
+                    Ok(leases) => {
+                        let n = watch.scan(&leases);
+                        if n > 0 {
+                            info!("{} lease(s) entered rebinding without renewal", n);
+                        }
+                    }
+                    Err(e) => warn!("Rebinding scan failed: {}", e),
+                }
+            }
+        });
+
         // Keep running
         loop {
             tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
@@ -457,9 +542,11 @@ impl DhcpServer {
         );
 
         let mut buf = vec![0u8; 1500];
+        // Responses are serialized into this scratch buffer, reused for every packet.
+        let mut send_buf = vec![0u8; 1500];
 
         loop {
-            let (len, src, iface_name) = match recv_with_interface(&socket, &mut buf) {
+            let (len, src, iface_name, local_addr) = match recv_with_interface(&socket, &mut buf) {
                 Ok(v) => v,
                 Err(e) => {
                     warn!("recvmsg error: {}", e);
@@ -479,25 +566,46 @@ impl DhcpServer {
             debug!("Received {} bytes from {} on {}", len, src, iface_name);
 
             let packet_data = &buf[..len];
-            let packet = match DhcpPacket::parse(packet_data) {
+            let max_options = self.config.dhcp.max_options;
+            let packet = match DhcpPacket::parse_with_limit(packet_data, max_options) {
                 Ok(p) => p,
                 Err(e) => {
                     warn!("Failed to parse DHCP packet: {}", e);
                     continue;
                 }
             };
+            if packet.options_truncated {
+                warn!(
+                    "Packet from {} on {} exceeds {} options, ignoring the rest",
+                    src, iface_name, max_options
+                );
+                crate::metrics::METRICS.truncated_option_packets.inc(&[]);
+            }
 
             let iface_ips = get_interface_ips(&iface_name);
-            let response = Self::handle_packet(&packet, &iface_name, &iface_ips, &self.config, &*self.db).await;
+            let received = ReceivedPacket::new(packet, src, Some(iface_name.clone()))
+                .with_local_addr(local_addr);
+            let response = self.respond(&received, &iface_ips).await;
+            let packet = &received.packet;
 
             if let Some(response_packet) = response {
-                let response_bytes = response_packet.to_bytes();
+                let response_bytes = match response_packet.write_to(&mut send_buf) {
+                    Ok(len) => &send_buf[..len],
+                    Err(e) => {
+                        warn!(
+                            "Failed to encode DHCP response ({:?}): {}",
+                            response_packet.get_message_type(),
+                            e
+                        );
+                        continue;
+                    }
+                };
                 // Determine destination per RFC 2131 §4.1:
                 //   giaddr != 0        → relay agent on port 67
                 //   ciaddr != 0        → unicast to ciaddr:68
                 //   BROADCAST flag set → 255.255.255.255:68
                 //   otherwise          → unicast to yiaddr:68
-                let dest = response_dest(&packet, &response_packet);
+                let dest = response_dest(packet, &response_packet);
                 let is_broadcast =
                     dest.ip() == std::net::IpAddr::V4(Ipv4Addr::BROADCAST);
 
@@ -505,11 +613,17 @@ impl DhcpServer {
                     "Sending DHCP response type {} to {} ({}), broadcast={}",
                     response_packet
                         .get_message_type()
-                        .map_or("Unknown".to_string(), |t| format!("{t:?}")),
+                        .map_or("Unknown".to_string(), |t| t.to_string()),
                     dest,
                     iface_name,
                     is_broadcast
                 );
+                // `%` defers formatting until the event is known to be enabled.
+                debug!(
+                    xid = format_args!("{:#010x}", response_packet.xid),
+                    options = %response_packet.describe(),
+                    "DHCP response options"
+                );
 
                 // When ciaddr=0 the client is in SELECTING state and has no ARP entry
                 // for yiaddr yet. Forge the full L2/IP/UDP frame directly so that:
@@ -526,7 +640,7 @@ impl DhcpServer {
                         response_packet.yiaddr
                     };
                     let src_ip = iface_ips.first().copied().unwrap_or(Ipv4Addr::UNSPECIFIED);
-                    if let Err(e) = send_dhcp_raw_l2_broadcast(&iface_name, src_ip, dst_ip, &response_bytes) {
+                    if let Err(e) = send_dhcp_raw_l2_broadcast(&iface_name, src_ip, dst_ip, response_bytes) {
                         warn!("Failed to send raw L2 response ({:?}) to {}: {}",
                             response_packet.get_message_type(), dst_ip, e);
                     }
@@ -541,11 +655,11 @@ impl DhcpServer {
                         .entry(iface_name.clone())
                         .or_insert_with(|| create_broadcast_send_socket(&iface_name).ok());
                     match bcast_sock {
-                        Some(s) => s.send_to(&response_bytes, dest),
-                        None => socket.send_to(&response_bytes, dest),
+                        Some(s) => s.send_to(response_bytes, dest),
+                        None => socket.send_to(response_bytes, dest),
                     }
                 } else {
-                    socket.send_to(&response_bytes, dest)
+                    socket.send_to(response_bytes, dest)
                 };
 
                 if let Err(e) = result {
@@ -555,53 +669,326 @@ impl DhcpServer {
         }
     }
 
+    /// Answer a received packet, repeating the OFFER already sent when a
+    /// DISCOVER is a retransmission instead of allocating again.
+    pub async fn respond(
+        &self,
+        received: &ReceivedPacket,
+        iface_ips: &[Ipv4Addr],
+    ) -> Option<DhcpPacket> {
+        let prober = self.prober.as_deref();
+        let offers = Some(&self.offers);
+        Self::handle_packet(received, iface_ips, &self.config, &*self.db, prober, offers).await
+    }
+
+    /// Handle one received packet, recording how long it took in
+    /// `ndhcpd_dhcp_handle_duration_seconds`.
     async fn handle_packet(
-        packet: &DhcpPacket,
-        iface_name: &str,
+        received: &ReceivedPacket,
+        iface_ips: &[Ipv4Addr],
+        config: &Config,
+        db: &dyn Database,
+        prober: Option<&dyn Prober>,
+        offers: Option<&OfferCache>,
+    ) -> Option<DhcpPacket> {
+        let started = Instant::now();
+        let response =
+            Self::dispatch_packet(received, iface_ips, config, db, prober, offers).await;
+        let message_type = received
+            .packet
+            .get_message_type()
+            .map_or("bootp".to_string(), |t| t.to_string().to_lowercase());
+        crate::metrics::METRICS
+            .handle_duration
+            .observe(&[&message_type], started.elapsed());
+        response
+    }
+
+    /// Whether `packet` comes from hardware whose address is the 6-byte
+    /// `chaddr` every client is identified by. Non-Ethernet types qualify
+    /// only when `dhcp.allow_non_ethernet` is set.
+    fn hardware_type_served(packet: &DhcpPacket, config: &Config) -> bool {
+        packet.htype == HTYPE_ETHERNET
+            || (config.dhcp.allow_non_ethernet && packet.hlen == 6)
+    }
+
+    async fn dispatch_packet(
+        received: &ReceivedPacket,
         iface_ips: &[Ipv4Addr],
         config: &Config,
         db: &dyn Database,
+        prober: Option<&dyn Prober>,
+        offers: Option<&OfferCache>,
     ) -> Option<DhcpPacket> {
-        let msg_type = packet.get_message_type()?;
+        let packet = &received.packet;
         let mac = packet.chaddr.to_string();
+        let iface_name = received.iface_name();
+        let src = received.src;
+
+        if packet.hops > config.dhcp.max_hops {
+            warn!(
+                "Dropping packet from {} via relay {}: {} hops exceeds limit of {}",
+                mac, packet.giaddr, packet.hops, config.dhcp.max_hops
+            );
+            return None;
+        }
+
+        if !Self::hardware_type_served(packet, config) {
+            warn!(
+                "Dropping packet from {} on {}: unsupported hardware type {} (hlen {})",
+                mac, iface_name, packet.htype, packet.hlen
+            );
+            crate::metrics::METRICS
+                .unsupported_hardware_packets
+                .inc(&[&packet.htype.to_string()]);
+            return None;
+        }
+
+        if let Some(filter) = &config.dhcp.vendor_filter {
+            if !filter.permits(packet.chaddr.as_bytes()) {
+                let [a, b, c, ..] = *packet.chaddr.as_bytes();
+                info!(
+                    "Ignoring packet from {} on {}: vendor {} is not permitted",
+                    mac,
+                    iface_name,
+                    Oui([a, b, c])
+                );
+                return None;
+            }
+        }
+
+        let Some(msg_type) = packet.get_message_type() else {
+            // Without option 53 this is a plain BOOTP request
+            if config.dhcp.enable_bootp && packet.op_code() == Some(OpCode::BootRequest) {
+                info!("BOOTP request from {} on {}", mac, iface_name);
+                return Self::handle_bootp(received, iface_ips, config, db).await;
+            }
+            return None;
+        };
+
+        // A backup server leaves the client to the primary until it has been
+        // retrying for a while; RFC 2131 §4.4.1 has REQUEST repeat the secs of
+        // the DISCOVER, so both are held back alike. A REQUEST with ciaddr set
+        // renews or rebinds a lease already granted and is always answered.
+        let min_secs = config.dhcp.answer_after_secs;
+        let acquiring = match msg_type {
+            MessageType::Discover => true,
+            MessageType::Request => packet.ciaddr.is_unspecified(),
+            _ => false,
+        };
+        if packet.secs < min_secs && acquiring {
+            debug!(
+                "Leaving {:?} from {} to another server: secs {} < {}",
+                msg_type, mac, packet.secs, min_secs
+            );
+            return None;
+        }
+
+        let relayed = packet.is_relayed();
+        if relayed && matches!(msg_type, MessageType::Discover | MessageType::Request) {
+            let subnets = match with_retry("list_subnets", || db.list_subnets()).await {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Failed to list subnets: {}", e);
+                    return None;
+                }
+            };
+            if !subnets.iter().any(|s| s.contains(packet.giaddr)) {
+                return Self::refuse_unknown_relay(received, msg_type, iface_ips, config);
+            }
+        }
 
         match msg_type {
             MessageType::Discover => {
                 info!("DHCP DISCOVER from {} on {}", mac, iface_name);
-                Self::handle_discover(packet, iface_ips, config, db).await
+                let Some(offers) = offers else {
+                    return Self::handle_discover(received, iface_ips, config, db, prober).await;
+                };
+                if let Some(offer) = Self::repeat_offer(received, offers, db).await {
+                    return Some(offer);
+                }
+                let response = Self::handle_discover(received, iface_ips, config, db, prober).await;
+                if let Some(offer) = &response {
+                    if offer.get_message_type() == Some(MessageType::Offer) {
+                        offers.remember(received, offer);
+                    }
+                }
+                response
             }
             MessageType::Request => {
                 info!("DHCP REQUEST from {} on {}", mac, iface_name);
-                Self::handle_request(packet, iface_ips, config, db).await
+                Self::handle_request(received, iface_ips, config, db).await
             }
             MessageType::Release => {
                 info!("DHCP RELEASE from {} on {}", mac, iface_name);
-                Self::handle_release(packet, db).await;
+                Self::handle_release(received, config, db).await;
                 None
             }
             MessageType::Inform => {
                 info!("DHCP INFORM from {} on {}", mac, iface_name);
                 None // Not implemented yet
             }
-            _ => {
+            MessageType::Decline => {
                 debug!("Unhandled DHCP message type: {:?}", msg_type);
                 None
             }
+            MessageType::Offer | MessageType::Ack | MessageType::Nak => {
+                // Only servers emit these; seeing one on port 67 means another
+                // DHCP server is answering on this segment or a relay is looping.
+                warn!(
+                    "Received server-origin DHCP {} from {} (chaddr {}) on {}: possible rogue DHCP server",
+                    msg_type, src, mac, iface_name
+                );
+                let message_type = msg_type.to_string().to_lowercase();
+                crate::metrics::METRICS
+                    .rogue_server_packets
+                    .inc(&[&message_type]);
+                None
+            }
+        }
+    }
+
+    /// A relay forwarded a packet from a segment matching no configured
+    /// subnet, which usually means the relay or the subnet list is
+    /// misconfigured. The packet is dropped, or a REQUEST is NAKed when
+    /// `dhcp.nak_unknown_relay` is set so the client fails fast.
+    fn refuse_unknown_relay(
+        received: &ReceivedPacket,
+        msg_type: MessageType,
+        iface_ips: &[Ipv4Addr],
+        config: &Config,
+    ) -> Option<DhcpPacket> {
+        let packet = &received.packet;
+        warn!(
+            "{:?} from {} relayed by {} on {}: giaddr matches no configured subnet",
+            msg_type,
+            packet.chaddr,
+            packet.giaddr,
+            received.iface_name()
+        );
+        crate::metrics::METRICS.unknown_relay_subnet.inc(&[]);
+
+        if config.dhcp.nak_unknown_relay && msg_type == MessageType::Request {
+            let server_id = iface_ips.first().copied().unwrap_or(Ipv4Addr::UNSPECIFIED);
+            return Some(Self::create_nak(packet, server_id));
+        }
+        None
+    }
+
+    /// The OFFER already sent for a retransmitted DISCOVER, provided its
+    /// address is still held for the client: no one else has leased it or
+    /// been assigned it statically since.
+    async fn repeat_offer(
+        received: &ReceivedPacket,
+        offers: &OfferCache,
+        db: &dyn Database,
+    ) -> Option<DhcpPacket> {
+        let packet = &received.packet;
+        let (offer, retransmissions) = offers.lookup(received)?;
+        let ip = offer.yiaddr;
+        let held = match Self::client_key(received, db).await {
+            Ok(mac) => Self::still_held_for(ip, &mac, db).await,
+            Err(e) => Err(e),
+        };
+        let held = match held {
+            Ok(held) => held,
+            Err(e) => {
+                warn!("Failed to check offered IP {} of {}: {}", ip, packet.chaddr, e);
+                false
+            }
+        };
+        if !held {
+            debug!(
+                "Retransmitted DISCOVER from {} (xid {:#010x}): {} was taken meanwhile, allocating again",
+                packet.chaddr, packet.xid, ip
+            );
+            return None;
+        }
+        debug!(
+            "Retransmitted DISCOVER from {} (xid {:#010x}, secs {}, {} retransmissions), repeating offer of {}",
+            packet.chaddr, packet.xid, packet.secs, retransmissions, ip
+        );
+        Some(offer)
+    }
+
+    /// Key the client of `received` is recorded under. A client sending a
+    /// Client Identifier is known by it, unless nothing is assigned or leased
+    /// to that identifier but something is to its `chaddr`: its reservations
+    /// and leases from before it sent one keep matching.
+    async fn client_key(received: &ReceivedPacket, db: &dyn Database) -> anyhow::Result<String> {
+        let key = received.client_key();
+        let chaddr = received.packet.chaddr.to_string();
+        if key == chaddr || Self::knows_client(&key, db).await? {
+            return Ok(key);
+        }
+        Ok(if Self::knows_client(&chaddr, db).await? { chaddr } else { key })
+    }
+
+    /// Whether `key` has a static assignment or an active lease
+    async fn knows_client(key: &str, db: &dyn Database) -> anyhow::Result<bool> {
+        if with_retry("get_static_ip_by_mac", || db.get_static_ip_by_mac(key)).await?.is_some() {
+            return Ok(true);
+        }
+        Ok(with_retry("get_active_lease", || db.get_active_lease(key)).await?.is_some())
+    }
+
+    /// Whether `ip` is still reserved for `mac`: by its own active lease, or
+    /// by its pending offer with no static assignment to another client.
+    async fn still_held_for(ip: Ipv4Addr, mac: &str, db: &dyn Database) -> anyhow::Result<bool> {
+        if let Some(lease) =
+            with_retry("get_active_lease_by_ip", || db.get_active_lease_by_ip(ip)).await?
+        {
+            return Ok(lease.mac_address.eq_ignore_ascii_case(mac));
+        }
+        let offers = with_retry("list_pending_offers", || db.list_pending_offers()).await?;
+        let Some(offer) = offers.iter().find(|o| o.ip_address == ip) else {
+            return Ok(false);
+        };
+        if !offer.mac_address.eq_ignore_ascii_case(mac) {
+            return Ok(false);
         }
+        let subnet_id = offer.subnet_id;
+        let static_ips =
+            with_retry("list_static_ips", || db.list_static_ips(Some(subnet_id))).await?;
+        Ok(!static_ips
+            .iter()
+            .any(|s| s.ip_address == ip && !s.mac_address.eq_ignore_ascii_case(mac)))
     }
 
     async fn handle_discover(
-        packet: &DhcpPacket,
+        received: &ReceivedPacket,
         iface_ips: &[Ipv4Addr],
         config: &Config,
         db: &dyn Database,
+        prober: Option<&dyn Prober>,
     ) -> Option<DhcpPacket> {
-        let mac = packet.chaddr.to_string();
+        let packet = &received.packet;
+        let iface = received.iface.as_deref();
+        let mac = match Self::client_key(received, db).await {
+            Ok(mac) => mac,
+            Err(e) => {
+                error!("Failed to look up client {}: {}", packet.chaddr, e);
+                return None;
+            }
+        };
 
-        // Check for static IP assignment on a subnet reachable via this interface
-        if let Ok(Some(static_ip)) = db.get_static_ip_by_mac(&mac).await {
-            let subnet = db.get_subnet(static_ip.subnet_id).await.ok()??;
-            if iface_in_subnet(iface_ips, &subnet) {
+        // Check for static IP assignment on a subnet reachable via this interface.
+        // A lookup error must not fall through to dynamic allocation.
+        let static_ip = match with_retry("get_static_ip_by_mac", || db.get_static_ip_by_mac(&mac))
+            .await
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to look up static IP of {}: {}", mac, e);
+                return None;
+            }
+        };
+        if let Some(static_ip) = static_ip {
+            let subnet = Self::lookup_subnet(db, static_ip.subnet_id).await?;
+            if serves_subnet(config, iface, iface_ips, &subnet) {
+                Self::record_offer(received, &mac, static_ip.ip_address, static_ip.subnet_id, db).await;
+                let subnet =
+                    Self::reply_subnet(static_ip.ip_address, subnet, iface_ips, db).await;
                 return Some(Self::create_offer(
                     packet,
                     static_ip.ip_address,
@@ -612,9 +999,18 @@ impl DhcpServer {
         }
 
         // Check for an existing lease on a subnet reachable via this interface
-        if let Ok(Some(lease)) = db.get_active_lease(&mac).await {
-            let subnet = db.get_subnet(lease.subnet_id).await.ok()??;
-            if iface_in_subnet(iface_ips, &subnet) {
+        let lease = match with_retry("get_active_lease", || db.get_active_lease(&mac)).await {
+            Ok(l) => l,
+            Err(e) => {
+                error!("Failed to look up lease of {}: {}", mac, e);
+                return None;
+            }
+        };
+        if let Some(lease) = lease {
+            let subnet = Self::lookup_subnet(db, lease.subnet_id).await?;
+            if serves_subnet(config, iface, iface_ips, &subnet) {
+                Self::record_offer(received, &mac, lease.ip_address, lease.subnet_id, db).await;
+                let subnet = Self::reply_subnet(lease.ip_address, subnet, iface_ips, db).await;
                 return Some(Self::create_offer(
                     packet,
                     lease.ip_address,
@@ -626,7 +1022,7 @@ impl DhcpServer {
 
         // Allocate a new IP from an enabled dynamic range, scoped to subnets
         // reachable via this interface.
-        let subnets = match db.list_subnets().await {
+        let subnets = match with_retry("list_subnets", || db.list_subnets()).await {
             Ok(s) => s,
             Err(e) => {
                 error!("Failed to list subnets: {}", e);
@@ -634,53 +1030,169 @@ impl DhcpServer {
             }
         };
 
-        // Build the set of IPs already in use to avoid double-allocation
-        let leased_ips: HashSet<Ipv4Addr> = match db.list_active_leases().await {
-            Ok(leases) => leases.into_iter().map(|l| l.ip_address).collect(),
+        // Build the set of IPs already in use to avoid double-allocation,
+        // including addresses offered to other clients that may still REQUEST them
+        let release_grace = config.dhcp.release_grace_seconds;
+        let used = with_retry("used_addresses", || {
+            allocator::used_addresses(db, release_grace, Some(&mac))
+        })
+        .await;
+        let mut used = match used {
+            Ok(used) => used,
             Err(e) => {
-                error!("Failed to list active leases: {}", e);
+                error!("Failed to list addresses in use: {}", e);
                 return None;
             }
         };
+        match with_retry("list_pending_offers", || db.list_pending_offers()).await {
+            Ok(offers) => used.extend(
+                offers
+                    .into_iter()
+                    .filter(|o| !o.mac_address.eq_ignore_ascii_case(&mac))
+                    .map(|o| o.ip_address),
+            ),
+            Err(e) => {
+                error!("Failed to list pending offers: {}", e);
+                return None;
+            }
+        }
 
-        for subnet in subnets.iter().filter(|s| iface_in_subnet(iface_ips, s)) {
+        match allocator::at_lease_cap(db, &mac, config.dhcp.max_leases_per_client, None).await {
+            Ok(false) => {}
+            Ok(true) => {
+                warn!("DISCOVER from {} refused: client is at its lease limit", mac);
+                return None;
+            }
+            Err(e) => {
+                error!("Failed to count leases of {}: {}", mac, e);
+                return None;
+            }
+        }
+
+        // Re-offer the address of a recently expired lease if it is still free
+        if let Some((ip, subnet_id, subnet)) =
+            Self::remembered_address(&mac, &subnets, &used, iface, iface_ips, config, db).await
+        {
+            debug!("Re-offering remembered IP {} to {}", ip, mac);
+            let subnet = Self::reply_subnet(ip, subnet.clone(), iface_ips, db).await;
+            Self::record_offer(received, &mac, ip, subnet.id.unwrap_or(subnet_id), db).await;
+            return Some(Self::create_offer(packet, ip, &subnet, config));
+        }
+
+        // A directly connected client is on the subnet of the local address
+        // its packet arrived on: try that one first, the others only once it is full
+        let mut served: Vec<_> = subnets
+            .iter()
+            .filter(|s| serves_subnet(config, iface, iface_ips, s))
+            .collect();
+        if let Some(local) = received.local_addr.filter(|_| !packet.is_relayed()) {
+            served.sort_by_key(|s| !s.contains(local));
+        }
+        for subnet in served {
             let subnet_id = match subnet.id {
                 Some(id) => id,
                 None => continue,
             };
-            let ranges = match db.list_ranges(Some(subnet_id)).await {
-                Ok(r) => r,
-                Err(e) => {
-                    error!("Failed to list ranges for subnet {}: {}", subnet_id, e);
-                    continue;
+            let strategy = config.dhcp.multi_range;
+            // An address that answers a probe is in use by a host the
+            // database does not know about: move on to the next free one
+            let candidate = loop {
+                let ip = match allocator::next_free_ip(db, subnet, &used, strategy).await {
+                    Ok(ip) => ip,
+                    Err(e) => {
+                        error!("Failed to list ranges for subnet {}: {}", subnet_id, e);
+                        None
+                    }
+                };
+                let Some(ip) = ip else { break None };
+                let Some(prober) = prober else { break Some(ip) };
+                if !probe::address_in_use(prober, ip, ProbeSettings::from_config(&config.dhcp))
+                    .await
+                {
+                    break Some(ip);
                 }
+                warn!("Skipping {} for {}: address answered a probe", ip, mac);
+                used.insert(ip);
             };
+            let Some(candidate) = candidate else { continue };
 
-            for range in ranges.iter().filter(|r| r.enabled) {
-                let start = u32::from(range.range_start);
-                let end = u32::from(range.range_end);
-
-                for ip_u32 in start..=end {
-                    let candidate = Ipv4Addr::from(ip_u32);
-                    if !leased_ips.contains(&candidate) {
-                        debug!("Offering dynamic IP {} to {}", candidate, mac);
-                        return Some(Self::create_offer(packet, candidate, subnet, config));
-                    }
-                }
-            }
+            debug!("Offering dynamic IP {} to {}", candidate, mac);
+            let subnet = Self::reply_subnet(candidate, subnet.clone(), iface_ips, db).await;
+            Self::record_offer(received, &mac, candidate, subnet.id.unwrap_or(subnet_id), db).await;
+            return Some(Self::create_offer(packet, candidate, &subnet, config));
         }
 
         warn!("No free IP available for DISCOVER from {}", mac);
         None
     }
 
+    /// Address of `mac`'s most recent lease that expired within the
+    /// `lease_memory_secs` window, if its subnet is served here and the
+    /// address is still free.
+    async fn remembered_address<'a>(
+        mac: &str,
+        subnets: &'a [crate::models::Subnet],
+        used: &std::collections::HashSet<Ipv4Addr>,
+        iface: Option<&str>,
+        iface_ips: &[Ipv4Addr],
+        config: &Config,
+        db: &dyn Database,
+    ) -> Option<(Ipv4Addr, i64, &'a crate::models::Subnet)> {
+        if config.dhcp.lease_memory_secs == 0 {
+            return None;
+        }
+        let since = chrono::Utc::now().timestamp() - config.dhcp.lease_memory_secs as i64;
+        let lease = match with_retry("get_recent_lease", || db.get_recent_lease(mac, since)).await
+        {
+            Ok(lease) => lease?,
+            Err(e) => {
+                warn!("Failed to look up remembered lease of {}: {}", mac, e);
+                return None;
+            }
+        };
+        let subnet = subnets
+            .iter()
+            .find(|s| s.id == Some(lease.subnet_id))
+            .filter(|s| serves_subnet(config, iface, iface_ips, s))?;
+        match allocator::is_free_ip(db, subnet, lease.ip_address, used).await {
+            Ok(true) => Some((lease.ip_address, lease.subnet_id, subnet)),
+            Ok(false) => None,
+            Err(e) => {
+                warn!("Failed to check remembered IP {}: {}", lease.ip_address, e);
+                None
+            }
+        }
+    }
+
     async fn handle_request(
-        packet: &DhcpPacket,
+        received: &ReceivedPacket,
         iface_ips: &[Ipv4Addr],
         config: &Config,
         db: &dyn Database,
     ) -> Option<DhcpPacket> {
-        let mac = packet.chaddr.to_string().to_lowercase();
+        let packet = &received.packet;
+        let iface = received.iface.as_deref();
+        let mac = match Self::client_key(received, db).await {
+            Ok(mac) => mac,
+            Err(e) => {
+                error!("Failed to look up client {}: {}", packet.chaddr, e);
+                return None;
+            }
+        };
+
+        // In SELECTING state the client names the server whose offer it took
+        // (option 54): if that is another server, we lost the race
+        let server_id = packet.options.iter().find_map(|opt| match opt {
+            DhcpOption::ServerIdentifier(ip) => Some(*ip),
+            _ => None,
+        });
+        if let Some(server_id) = server_id {
+            if !Self::is_our_server_id(server_id, iface_ips, db).await {
+                info!("REQUEST from {} selected server {}: staying silent", mac, server_id);
+                Self::forget_offers_to(&mac, db).await;
+                return None;
+            }
+        }
 
         // Extract requested IP: from option 50 (new request) or ciaddr (renewal)
         let requested_ip = packet
@@ -701,26 +1213,38 @@ impl DhcpServer {
                 }
             })?;
 
-        // Extract optional hostname sent by the client
+        // Extract optional hostname sent by the client, cleaned up for storage
         let hostname = packet.options.iter().find_map(|opt| {
             if let DhcpOption::Hostname(h) = opt {
-                Some(h.clone())
+                sanitize_hostname(h)
             } else {
                 None
             }
         });
 
         // Check for static IP assignment
-        if let Ok(Some(static_ip)) = db.get_static_ip_by_mac(&mac).await {
+        let static_ip = match with_retry("get_static_ip_by_mac", || db.get_static_ip_by_mac(&mac))
+            .await
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to look up static IP of {}: {}", mac, e);
+                return None;
+            }
+        };
+        if let Some(static_ip) = static_ip {
             if static_ip.ip_address == requested_ip {
-                let subnet = db.get_subnet(static_ip.subnet_id).await.ok()??;
-                if !iface_in_subnet(iface_ips, &subnet) {
+                let subnet = Self::lookup_subnet(db, static_ip.subnet_id).await?;
+                if !serves_subnet(config, iface, iface_ips, &subnet) {
                     warn!(
                         "Client {} static IP {} belongs to a subnet not reachable via this interface",
                         mac, requested_ip
                     );
-                    return None;
+                    return Self::refuse_request(received, requested_ip, iface_ips, config, db)
+                        .await;
                 }
+                Self::forget_offer(requested_ip, db).await;
+                let subnet = Self::reply_subnet(requested_ip, subnet, iface_ips, db).await;
                 return Some(Self::create_ack(packet, requested_ip, &subnet, config));
             }
             // Static IP exists but client requested a different one: NAK
@@ -728,11 +1252,11 @@ impl DhcpServer {
                 "Client {} requested {} but has static assignment {}",
                 mac, requested_ip, static_ip.ip_address
             );
-            return None;
+            return Self::refuse_request(received, requested_ip, iface_ips, config, db).await;
         }
 
         // Check if the requested IP falls within an enabled dynamic range
-        let ranges = match db.list_ranges(None).await {
+        let ranges = match with_retry("list_ranges", || db.list_ranges(None)).await {
             Ok(r) => r,
             Err(e) => {
                 error!("Failed to list dynamic ranges: {}", e);
@@ -749,11 +1273,10 @@ impl DhcpServer {
             if u32::from(r.range_start) <= u32::from(requested_ip)
                 && u32::from(requested_ip) <= u32::from(r.range_end)
             {
-                let subnet = match db.get_subnet(r.subnet_id).await {
-                    Ok(Some(s)) => s,
-                    _ => continue,
+                let Some(subnet) = Self::lookup_subnet(db, r.subnet_id).await else {
+                    continue;
                 };
-                if iface_in_subnet(iface_ips, &subnet) {
+                if serves_subnet(config, iface, iface_ips, &subnet) {
                     matching_range_and_subnet = Some((r.clone(), subnet));
                     break;
                 } else {
@@ -764,16 +1287,27 @@ impl DhcpServer {
                 }
             }
         }
-        let (matching_range, subnet) = matching_range_and_subnet.or_else(|| {
+        let Some((matching_range, subnet)) = matching_range_and_subnet else {
             warn!(
                 "REQUEST from {}: no enabled range covers {} on this interface (iface_ips={:?})",
                 mac, requested_ip, iface_ips
             );
-            None
-        })?;
+            return Self::refuse_request(received, requested_ip, iface_ips, config, db).await;
+        };
+
+        // A range may span the whole network; its special addresses still
+        // must not be leased
+        if subnet.is_reserved(requested_ip) {
+            warn!(
+                "REQUEST from {}: {} is the network, broadcast or gateway address of {}/{}",
+                mac, requested_ip, subnet.network, subnet.netmask
+            );
+            return Self::refuse_request(received, requested_ip, iface_ips, config, db).await;
+        }
 
         // Verify the IP is not already leased by a different MAC
-        let active_leases = match db.list_active_leases().await {
+        let active_leases = match with_retry("list_active_leases", || db.list_active_leases()).await
+        {
             Ok(l) => l,
             Err(e) => {
                 error!("Failed to list active leases: {}", e);
@@ -781,76 +1315,155 @@ impl DhcpServer {
             }
         };
 
-        if let Some(existing) = active_leases.iter().find(|l| l.ip_address == requested_ip) {
+        let existing = active_leases.iter().find(|l| l.ip_address == requested_ip);
+        if let Some(existing) = existing {
             if existing.mac_address != mac {
                 warn!(
                     "Client {} requested {} already leased to {}",
                     mac, requested_ip, existing.mac_address
                 );
-                return None;
+                return Self::refuse_request(received, requested_ip, iface_ips, config, db).await;
+            }
+        }
+
+        // A draining range only renews the leases it already holds
+        if matching_range.draining && existing.is_none() {
+            warn!(
+                "REQUEST from {}: {} is in a draining range, refusing new allocation",
+                mac, requested_ip
+            );
+            return Self::refuse_request(received, requested_ip, iface_ips, config, db).await;
+        }
+
+        match allocator::at_lease_cap(
+            db,
+            &mac,
+            config.dhcp.max_leases_per_client,
+            Some(requested_ip),
+        )
+        .await
+        {
+            Ok(false) => {}
+            Ok(true) => {
+                warn!(
+                    "REQUEST from {} for {} refused: client is at its lease limit",
+                    mac, requested_ip
+                );
+                return Self::refuse_request(received, requested_ip, iface_ips, config, db).await;
             }
-            // Same MAC renewing: expire old lease before creating a new one
-            if let Some(id) = existing.id {
-                let _ = db.expire_lease(id).await;
+            Err(e) => {
+                error!("Failed to count leases of {}: {}", mac, e);
+                return None;
             }
         }
 
-        // Create the lease
+        // Same MAC renewing: the new lease replaces the old one, only once the
+        // request is known to be granted. A hostname sent with the renewal
+        // replaces the stored one; a renewal without option 12 keeps it.
+        let renewed = existing.is_some();
+        let hostname = hostname.or_else(|| existing.and_then(|l| l.hostname.clone()));
+        let replaced = existing.and_then(|l| l.id);
+
+        // Create the lease, lasting as long as the ACK will announce, on the
+        // subnet whose options the ACK carries
+        let subnet = Self::reply_subnet(requested_ip, subnet, iface_ips, db).await;
         let now = chrono::Utc::now().timestamp();
         let lease = crate::models::Lease {
             id: None,
-            subnet_id: matching_range.subnet_id,
+            subnet_id: subnet.id.unwrap_or(matching_range.subnet_id),
             mac_address: mac.clone(),
             ip_address: requested_ip,
             lease_start: now,
-            lease_end: now + config.dhcp.default_lease_time as i64,
+            lease_end: now + Self::lease_time(packet, &subnet, config) as i64,
             hostname,
+            range_id: matching_range.id,
         };
 
-        if let Err(e) = db.create_lease(&lease).await {
-            error!("Failed to create lease for {}: {}", mac, e);
-            return None;
+        // Another REQUEST for the same address may have been granted since
+        // the leases were listed: the insert only succeeds if it is still free.
+        // A renewal ends the old lease in the same step, so a failure keeps it.
+        let created = match replaced {
+            Some(old_id) => with_retry("replace_lease", || db.replace_lease(old_id, &lease)).await,
+            None => with_retry("create_lease_if_free", || db.create_lease_if_free(&lease)).await,
+        };
+        match created {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                warn!("Client {} requested {} leased meanwhile to another client", mac, requested_ip);
+                return Self::refuse_request(received, requested_ip, iface_ips, config, db).await;
+            }
+            Err(e) => {
+                error!("Failed to create lease for {}: {}", mac, e);
+                return None;
+            }
         }
 
+        let kind = if renewed {
+            LeaseEventKind::Renewed
+        } else {
+            LeaseEventKind::Assigned
+        };
         info!(
-            "Dynamic lease created: {} -> {} (subnet {})",
-            mac, requested_ip, subnet.network
+            "Dynamic lease {:?}: {} -> {} (subnet {})",
+            kind, mac, requested_ip, subnet.network
         );
+        EVENTS.record(LeaseEvent {
+            kind,
+            mac_address: mac.clone(),
+            ip_address: requested_ip,
+            subnet_id: lease.subnet_id,
+            timestamp: received.received_at,
+        });
+        Self::forget_offer(requested_ip, db).await;
         Some(Self::create_ack(packet, requested_ip, &subnet, config))
     }
 
-    async fn handle_release(packet: &DhcpPacket, db: &dyn Database) {
-        let mac = packet.chaddr.to_string();
-
-        if let Ok(Some(lease)) = db.get_active_lease(&mac).await {
-            if let Some(id) = lease.id {
-                let _ = db.expire_lease(id).await;
+    /// Answer a BOOTP request with the client's static assignment.
+    ///
+    /// The address and server travel in the `yiaddr`/`siaddr` header fields;
+    /// mask, router, DNS and domain are sent as RFC 1497 vendor extensions.
+    /// There is no message type, lease time or server identifier.
+    async fn handle_bootp(
+        received: &ReceivedPacket,
+        iface_ips: &[Ipv4Addr],
+        config: &Config,
+        db: &dyn Database,
+    ) -> Option<DhcpPacket> {
+        let request = &received.packet;
+        let mac = request.chaddr.to_string();
+
+        let static_ip = match with_retry("get_static_ip_by_mac", || db.get_static_ip_by_mac(&mac))
+            .await
+        {
+            Ok(Some(s)) => s,
+            Ok(None) => {
+                debug!("Ignoring BOOTP request from {}: no static assignment", mac);
+                return None;
+            }
+            Err(e) => {
+                error!("Failed to look up static IP of {}: {}", mac, e);
+                return None;
             }
+        };
+        let subnet = Self::lookup_subnet(db, static_ip.subnet_id).await?;
+        if !serves_subnet(config, received.iface.as_deref(), iface_ips, &subnet) {
+            debug!(
+                "Ignoring BOOTP request from {}: subnet {} not served on this interface",
+                mac, subnet.network
+            );
+            return None;
         }
-    }
+        let subnet = Self::reply_subnet(static_ip.ip_address, subnet, iface_ips, db).await;
 
-    fn create_offer(
-        request: &DhcpPacket,
-        offered_ip: Ipv4Addr,
-        subnet: &crate::models::Subnet,
-        config: &Config,
-    ) -> DhcpPacket {
         let mut packet = DhcpPacket::new();
-        packet.op = 2; // BOOTREPLY
+        packet.set_op_code(OpCode::BootReply);
         packet.xid = request.xid;
-        packet.yiaddr = offered_ip;
+        packet.flags = request.flags;
+        packet.giaddr = request.giaddr;
         packet.chaddr = request.chaddr.clone();
+        packet.yiaddr = static_ip.ip_address;
         packet.siaddr = subnet.gateway;
 
-        packet
-            .options
-            .push(DhcpOption::MessageType(MessageType::Offer));
-        packet
-            .options
-            .push(DhcpOption::ServerIdentifier(subnet.gateway));
-        packet
-            .options
-            .push(DhcpOption::LeaseTime(config.dhcp.default_lease_time));
         packet
             .options
             .push(DhcpOption::SubnetMask(Self::netmask_from_prefix(
@@ -862,10 +1475,343 @@ impl DhcpServer {
         packet
             .options
             .push(DhcpOption::DnsServer(subnet.dns_servers.clone()));
-
         if let Some(domain) = &subnet.domain_name {
             packet.options.push(DhcpOption::DomainName(domain.clone()));
         }
+        packet
+            .options
+            .push(DhcpOption::NtpServers(subnet.ntp_servers.clone()));
+        Self::drop_empty_options(&mut packet);
+
+        info!("BOOTP reply: {} -> {}", mac, static_ip.ip_address);
+        Some(packet)
+    }
+
+    /// Hold `ip` for the client of `received` until it REQUESTs it or
+    /// [`PENDING_OFFER_TTL`] elapses. Failing to record it does not stop the OFFER.
+    async fn record_offer(
+        received: &ReceivedPacket,
+        mac: &str,
+        ip: Ipv4Addr,
+        subnet_id: i64,
+        db: &dyn Database,
+    ) {
+        let offered_at = received.received_at / 1000;
+        let offer = crate::models::PendingOffer {
+            subnet_id,
+            mac_address: mac.to_string(),
+            ip_address: ip,
+            xid: received.packet.xid,
+            offered_at,
+            expires_at: offered_at + PENDING_OFFER_TTL.as_secs() as i64,
+        };
+        let recorded = with_retry("record_pending_offer", || db.record_pending_offer(&offer)).await;
+        if let Err(e) = recorded {
+            warn!("Failed to record pending offer of {} to {}: {}", ip, offer.mac_address, e);
+        }
+    }
+
+    /// Release the pending offer of `ip` once its lease is committed.
+    async fn forget_offer(ip: Ipv4Addr, db: &dyn Database) {
+        if let Err(e) = with_retry("delete_pending_offer", || db.delete_pending_offer(ip)).await {
+            warn!("Failed to delete pending offer of {}: {}", ip, e);
+        }
+    }
+
+    /// Release every address still offered to `mac`, once it has accepted
+    /// another server's offer.
+    async fn forget_offers_to(mac: &str, db: &dyn Database) {
+        match with_retry("list_pending_offers", || db.list_pending_offers()).await {
+            Ok(offers) => {
+                for offer in offers.iter().filter(|o| o.mac_address.eq_ignore_ascii_case(mac)) {
+                    Self::forget_offer(offer.ip_address, db).await;
+                }
+            }
+            Err(e) => warn!("Failed to list pending offers of {}: {}", mac, e),
+        }
+    }
+
+    /// Whether `server_id` is an identifier this server hands out: the
+    /// gateway of one of its subnets, or an address of the receiving
+    /// interface. On a database error the request is given the benefit of
+    /// the doubt.
+    async fn is_our_server_id(server_id: Ipv4Addr, iface_ips: &[Ipv4Addr], db: &dyn Database) -> bool {
+        if iface_ips.contains(&server_id) {
+            return true;
+        }
+        match with_retry("list_subnets", || db.list_subnets()).await {
+            Ok(subnets) => subnets.iter().any(|s| s.gateway == server_id),
+            Err(e) => {
+                error!("Failed to list subnets: {}", e);
+                true
+            }
+        }
+    }
+
+    /// Fetch a subnet referenced by a static IP, lease or range, logging
+    /// database errors (after retries) and dangling references.
+    async fn lookup_subnet(db: &dyn Database, id: i64) -> Option<crate::models::Subnet> {
+        match with_retry("get_subnet", || db.get_subnet(id)).await {
+            Ok(Some(subnet)) => Some(subnet),
+            Ok(None) => {
+                warn!("Subnet {} referenced by a record does not exist", id);
+                None
+            }
+            Err(e) => {
+                error!("Failed to get subnet {}: {}", id, e);
+                None
+            }
+        }
+    }
+
+    /// Answer a REQUEST for `requested_ip` that cannot be granted.
+    ///
+    /// The subnet owning the address decides: if it is authoritative the
+    /// client gets a NAK, otherwise the server stays silent so a coexisting
+    /// server can answer. An address outside every subnet served here (e.g. a
+    /// client moved from another network) is NAKed when any of them is
+    /// authoritative.
+    async fn refuse_request(
+        received: &ReceivedPacket,
+        requested_ip: Ipv4Addr,
+        iface_ips: &[Ipv4Addr],
+        config: &Config,
+        db: &dyn Database,
+    ) -> Option<DhcpPacket> {
+        let subnets = match with_retry("list_subnets", || db.list_subnets()).await {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to list subnets: {}", e);
+                return None;
+            }
+        };
+        let iface = received.iface.as_deref();
+        let served: Vec<_> = subnets
+            .iter()
+            .filter(|s| serves_subnet(config, iface, iface_ips, s))
+            .collect();
+        let scope = match served.iter().find(|s| s.contains(requested_ip)) {
+            Some(owner) => Some(*owner).filter(|s| s.authoritative),
+            None => served.into_iter().find(|s| s.authoritative),
+        }?;
+
+        info!(
+            "NAK {} for {} (authoritative for {}/{})",
+            received.packet.chaddr, requested_ip, scope.network, scope.netmask
+        );
+        Some(Self::create_nak(&received.packet, scope.gateway))
+    }
+
+    async fn handle_release(received: &ReceivedPacket, config: &Config, db: &dyn Database) {
+        let packet = &received.packet;
+        let mac = match Self::client_key(received, db).await {
+            Ok(mac) => mac,
+            Err(e) => {
+                error!("Failed to look up client {}: {}", packet.chaddr, e);
+                return;
+            }
+        };
+
+        let lease = match with_retry("get_active_lease", || db.get_active_lease(&mac)).await {
+            Ok(Some(lease)) => lease,
+            Ok(None) => {
+                debug!("Ignoring RELEASE from {}: no active lease", mac);
+                return;
+            }
+            Err(e) => {
+                error!("Failed to look up lease of {}: {}", mac, e);
+                return;
+            }
+        };
+
+        // A release naming another client's address is spoofed or confused
+        if packet.ciaddr != Ipv4Addr::UNSPECIFIED && packet.ciaddr != lease.ip_address {
+            warn!(
+                "Ignoring RELEASE from {} for {}: its lease is on {}",
+                mac, packet.ciaddr, lease.ip_address
+            );
+            return;
+        }
+
+        // Our server identifier is the gateway of the lease's subnet
+        let server_id = packet.options.iter().find_map(|opt| match opt {
+            DhcpOption::ServerIdentifier(ip) => Some(*ip),
+            _ => None,
+        });
+        if let Some(server_id) = server_id {
+            let subnet_id = lease.subnet_id;
+            let ours = match with_retry("get_subnet", || db.get_subnet(subnet_id)).await {
+                Ok(subnet) => subnet.is_some_and(|s| s.gateway == server_id),
+                Err(e) => {
+                    error!("Failed to get subnet {}: {}", subnet_id, e);
+                    return;
+                }
+            };
+            if !ours {
+                warn!(
+                    "Ignoring RELEASE from {} addressed to server {}",
+                    mac, server_id
+                );
+                return;
+            }
+        }
+
+        // Within the grace period the ended lease keeps the address from
+        // being allocated to another client
+        if let Some(id) = lease.id {
+            let ended = if config.dhcp.release_grace_seconds > 0 {
+                let now = chrono::Utc::now().timestamp();
+                with_retry("release_lease", || db.release_lease(id, now)).await
+            } else {
+                with_retry("expire_lease", || db.expire_lease(id)).await
+            };
+            match ended {
+                Ok(()) => debug!("Released {} held by {}", lease.ip_address, mac),
+                Err(e) => error!("Failed to release {} held by {}: {}", lease.ip_address, mac, e),
+            }
+        }
+    }
+
+    /// Resolve the subnet whose mask/router/DNS options must be sent for `ip`,
+    /// with the options it leaves unset filled in from its option template.
+    async fn reply_subnet(
+        ip: Ipv4Addr,
+        matched: crate::models::Subnet,
+        iface_ips: &[Ipv4Addr],
+        db: &dyn Database,
+    ) -> crate::models::Subnet {
+        let subnet = Self::owning_subnet(ip, matched, iface_ips, db).await;
+        let Some(template_id) = subnet.template_id else {
+            return subnet;
+        };
+        match with_retry("get_option_template", || db.get_option_template(template_id)).await {
+            Ok(Some(template)) => subnet.with_template(&template),
+            Ok(None) => {
+                warn!("Option template {} of subnet {} does not exist", template_id, subnet.network);
+                subnet
+            }
+            Err(e) => {
+                error!("Failed to get option template {}: {}", template_id, e);
+                subnet
+            }
+        }
+    }
+
+    /// On a shared network several subnets are reachable via the same
+    /// interface; the options must describe the subnet that actually owns the
+    /// granted address, not whichever one matched first (e.g. a range or
+    /// static entry attached to a sibling subnet).
+    async fn owning_subnet(
+        ip: Ipv4Addr,
+        matched: crate::models::Subnet,
+        iface_ips: &[Ipv4Addr],
+        db: &dyn Database,
+    ) -> crate::models::Subnet {
+        if matched.contains(ip) {
+            return matched;
+        }
+        let subnets = match with_retry("list_subnets", || db.list_subnets()).await {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Failed to list subnets: {}", e);
+                return matched;
+            }
+        };
+        let reachable: Vec<_> = subnets
+            .into_iter()
+            .filter(|s| iface_in_subnet(iface_ips, s))
+            .collect();
+        match owning_subnet(ip, &reachable) {
+            Some(owner) => {
+                debug!(
+                    "IP {} belongs to subnet {}/{} rather than {}/{}",
+                    ip, owner.network, owner.netmask, matched.network, matched.netmask
+                );
+                owner.clone()
+            }
+            None => matched,
+        }
+    }
+
+    /// ACK a client of `subnet` would receive, built as for a plain REQUEST
+    /// (not relayed, no user or vendor class) without touching any lease.
+    /// Lets operators check the options resolved from the subnet, its
+    /// template and the configuration.
+    pub async fn preview_ack(
+        subnet: crate::models::Subnet,
+        config: &Config,
+        db: &dyn Database,
+    ) -> DhcpPacket {
+        let mut request = DhcpPacket::new();
+        request.set_op_code(OpCode::BootRequest);
+        request
+            .options
+            .push(DhcpOption::MessageType(MessageType::Request));
+        let ip = subnet.network_address();
+        let subnet = Self::reply_subnet(ip, subnet, &[], db).await;
+        Self::create_ack(&request, ip, &subnet, config)
+    }
+
+    /// Lease time granted to `request` on `subnet`: its user class policy's,
+    /// else the subnet's own default, both capped at `dhcp.max_lease_time`,
+    /// otherwise the global default
+    fn lease_time(request: &DhcpPacket, subnet: &crate::models::Subnet, config: &Config) -> u32 {
+        let policy = config
+            .dhcp
+            .policy_for_user_classes(request.get_user_classes())
+            .and_then(|p| p.lease_time);
+        match policy.or(subnet.default_lease_time) {
+            Some(lease_time) => lease_time.min(config.dhcp.max_lease_time),
+            None => config.dhcp.default_lease_time,
+        }
+    }
+
+    fn create_offer(
+        request: &DhcpPacket,
+        offered_ip: Ipv4Addr,
+        subnet: &crate::models::Subnet,
+        config: &Config,
+    ) -> DhcpPacket {
+        let mut packet = DhcpPacket::new();
+        packet.set_op_code(OpCode::BootReply);
+        packet.xid = request.xid;
+        packet.yiaddr = offered_ip;
+        packet.chaddr = request.chaddr.clone();
+        packet.siaddr = subnet.gateway;
+
+        packet
+            .options
+            .push(DhcpOption::MessageType(MessageType::Offer));
+        packet
+            .options
+            .push(DhcpOption::ServerIdentifier(subnet.gateway));
+        packet
+            .options
+            .push(DhcpOption::LeaseTime(Self::lease_time(request, subnet, config)));
+        packet
+            .options
+            .push(DhcpOption::SubnetMask(Self::netmask_from_prefix(
+                subnet.netmask,
+            )));
+        packet
+            .options
+            .push(DhcpOption::Router(vec![subnet.gateway]));
+        packet
+            .options
+            .push(DhcpOption::DnsServer(subnet.dns_servers.clone()));
+
+        if let Some(domain) = &subnet.domain_name {
+            packet.options.push(DhcpOption::DomainName(domain.clone()));
+        }
+        packet
+            .options
+            .push(DhcpOption::NtpServers(subnet.ntp_servers.clone()));
+
+        Self::apply_user_class(request, &mut packet, config);
+        Self::apply_pxe(request, &mut packet, config);
+        Self::apply_relay_agent_info(request, &mut packet, config);
+        Self::apply_parameter_request_list(request, &mut packet);
+        Self::drop_empty_options(&mut packet);
 
         packet
     }
@@ -877,7 +1823,7 @@ impl DhcpServer {
         config: &Config,
     ) -> DhcpPacket {
         let mut packet = DhcpPacket::new();
-        packet.op = 2; // BOOTREPLY
+        packet.set_op_code(OpCode::BootReply);
         packet.xid = request.xid;
         packet.yiaddr = assigned_ip;
         packet.chaddr = request.chaddr.clone();
@@ -889,15 +1835,12 @@ impl DhcpServer {
         packet
             .options
             .push(DhcpOption::ServerIdentifier(subnet.gateway));
+        let lease_time = Self::lease_time(request, subnet, config);
+        packet.options.push(DhcpOption::LeaseTime(lease_time));
+        packet.options.push(DhcpOption::RenewalTime(lease_time / 2));
         packet
             .options
-            .push(DhcpOption::LeaseTime(config.dhcp.default_lease_time));
-        packet
-            .options
-            .push(DhcpOption::RenewalTime(config.dhcp.default_lease_time / 2));
-        packet.options.push(DhcpOption::RebindingTime(
-            config.dhcp.default_lease_time * 7 / 8,
-        ));
+            .push(DhcpOption::RebindingTime(lease_time * 7 / 8));
         packet
             .options
             .push(DhcpOption::SubnetMask(Self::netmask_from_prefix(
@@ -913,55 +1856,174 @@ impl DhcpServer {
         if let Some(domain) = &subnet.domain_name {
             packet.options.push(DhcpOption::DomainName(domain.clone()));
         }
+        packet
+            .options
+            .push(DhcpOption::NtpServers(subnet.ntp_servers.clone()));
+
+        Self::apply_user_class(request, &mut packet, config);
+        Self::apply_pxe(request, &mut packet, config);
+        Self::apply_client_fqdn(request, &mut packet);
+        Self::apply_relay_agent_info(request, &mut packet, config);
+        Self::apply_parameter_request_list(request, &mut packet);
+        Self::drop_empty_options(&mut packet);
 
         packet
     }
 
-    fn netmask_from_prefix(prefix: u8) -> Ipv4Addr {
-        let mask = if prefix == 0 {
-            0u32
+    fn create_nak(request: &DhcpPacket, server_id: Ipv4Addr) -> DhcpPacket {
+        let mut packet = DhcpPacket::new();
+        packet.set_op_code(OpCode::BootReply);
+        packet.xid = request.xid;
+        packet.chaddr = request.chaddr.clone();
+        packet.giaddr = request.giaddr;
+        // A relay must broadcast the NAK onto the client's segment (RFC 2131 §4.3.2)
+        packet.flags = if request.is_relayed() {
+            request.flags | BROADCAST_FLAG
         } else {
-            !0u32 << (32 - prefix)
+            request.flags
+        };
+
+        packet
+            .options
+            .push(DhcpOption::MessageType(MessageType::Nak));
+        packet
+            .options
+            .push(DhcpOption::ServerIdentifier(server_id));
+
+        packet
+    }
+
+    /// Replace the subnet's DNS servers and domain name with those of the
+    /// client's user class (option 77) policy, if any. Its lease time is
+    /// applied by [`lease_time`](Self::lease_time).
+    fn apply_user_class(request: &DhcpPacket, packet: &mut DhcpPacket, config: &Config) {
+        let Some(policy) = config.dhcp.policy_for_user_classes(request.get_user_classes()) else {
+            return;
         };
-        Ipv4Addr::from(mask)
+
+        if let Some(dns_servers) = &policy.dns_servers {
+            packet.options.retain(|o| !matches!(o, DhcpOption::DnsServer(_)));
+            packet.options.push(DhcpOption::DnsServer(dns_servers.clone()));
+        }
+        if let Some(domain) = &policy.domain_name {
+            packet.options.retain(|o| !matches!(o, DhcpOption::DomainName(_)));
+            packet.options.push(DhcpOption::DomainName(domain.clone()));
+        }
     }
-}
 
-/// Returns the IPv4 addresses assigned to the given network interface.
-fn get_interface_ips(interface: &str) -> Vec<Ipv4Addr> {
-    let mut ips = Vec::new();
-    unsafe {
-        let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
-        if libc::getifaddrs(&mut ifaddrs) != 0 {
-            return ips;
-        }
-        let mut cur = ifaddrs;
-        while !cur.is_null() {
-            let ifa = &*cur;
-            if !ifa.ifa_addr.is_null() {
-                let name = std::ffi::CStr::from_ptr(ifa.ifa_name).to_string_lossy();
-                if name == interface && (*ifa.ifa_addr).sa_family as i32 == libc::AF_INET {
-                    let sin = ifa.ifa_addr as *const libc::sockaddr_in;
-                    let ip = u32::from_be((*sin).sin_addr.s_addr);
-                    ips.push(Ipv4Addr::from(ip));
-                }
+    /// Fill in network boot parameters (`siaddr`, options 66/67) when the
+    /// client's vendor class (option 60) matches a configured PXE entry.
+    fn apply_pxe(request: &DhcpPacket, packet: &mut DhcpPacket, config: &Config) {
+        let Some(vendor_class) = request.options.iter().find_map(|opt| {
+            if let DhcpOption::VendorClassIdentifier(v) = opt {
+                Some(v)
+            } else {
+                None
             }
-            cur = ifa.ifa_next;
+        }) else {
+            return;
+        };
+        let Some(boot) = config.dhcp.pxe_for_vendor_class(vendor_class) else {
+            return;
+        };
+
+        debug!("Vendor class {} matched PXE boot file {}", vendor_class, boot.boot_file);
+        if let Some(next_server) = boot.next_server {
+            packet.siaddr = next_server;
+        }
+        if let Some(name) = &boot.tftp_server_name {
+            packet.options.push(DhcpOption::TftpServerName(name.clone()));
+        }
+        packet
+            .options
+            .push(DhcpOption::BootfileName(boot.boot_file.clone()));
+    }
+
+    /// Echo the Client FQDN option (81) with reply flags telling the client
+    /// that this server performs no DNS update on its behalf.
+    fn apply_client_fqdn(request: &DhcpPacket, packet: &mut DhcpPacket) {
+        let Some((flags, name)) = request.get_client_fqdn() else {
+            return;
+        };
+        debug!(
+            "Client FQDN {} for {}: {:?} asked to update the forward record, server declines",
+            name,
+            request.chaddr,
+            super::ddns::forward_updater(flags)
+        );
+        packet.options.push(DhcpOption::ClientFqdn {
+            flags: super::ddns::reply_flags(flags),
+            rcode: dhcp_proto::option::fqdn_rcode::SERVER,
+            name: name.to_string(),
+        });
+    }
+
+    /// Copy the request's Relay Agent Information (option 82) into the reply
+    /// byte for byte, so the relay can match it to the client's circuit.
+    fn apply_relay_agent_info(request: &DhcpPacket, packet: &mut DhcpPacket, config: &Config) {
+        if !config.dhcp.echo_relay_agent_info {
+            return;
+        }
+        if let Some(info) = request
+            .options
+            .iter()
+            .find(|opt| matches!(opt, DhcpOption::RelayAgentInfo(_)))
+        {
+            packet.options.push(info.clone());
         }
-        libc::freeifaddrs(ifaddrs);
     }
-    ips
+
+    /// Keep only the optional parameters (router, DNS servers, domain name,
+    /// NTP servers) the client asked for in its Parameter Request List (option 55).
+    /// Options the protocol requires (message type, server identifier, lease
+    /// times, subnet mask) and those answering something the client or the
+    /// relay sent are always kept. Without a list every option is sent.
+    fn apply_parameter_request_list(request: &DhcpPacket, packet: &mut DhcpPacket) {
+        let Some(requested) = request.get_requested_parameters() else {
+            return;
+        };
+        packet.options.retain(|o| match o {
+            DhcpOption::Router(_) => requested.contains(&3),
+            DhcpOption::DnsServer(_) => requested.contains(&6),
+            DhcpOption::DomainName(_) => requested.contains(&15),
+            DhcpOption::NtpServers(_) => requested.contains(&42),
+            _ => true,
+        });
+    }
+
+    /// Remove options that ended up empty: address lists (router, DNS, NTP)
+    /// with no entries and an empty domain name. A zero-length option 3, 6,
+    /// 15 or 42 is malformed (RFC 2132 requires at least one address or
+    /// character) and some clients reject the whole reply.
+    fn drop_empty_options(packet: &mut DhcpPacket) {
+        packet.options.retain(|o| match o {
+            DhcpOption::Router(addrs)
+            | DhcpOption::DnsServer(addrs)
+            | DhcpOption::NtpServers(addrs) => !addrs.is_empty(),
+            DhcpOption::DomainName(domain) => !domain.is_empty(),
+            _ => true,
+        });
+    }
+
+    fn netmask_from_prefix(prefix: PrefixLen) -> Ipv4Addr {
+        prefix.netmask()
+    }
 }
 
 /// Returns true if any IP on the interface belongs to the given subnet.
 fn iface_in_subnet(iface_ips: &[Ipv4Addr], subnet: &crate::models::Subnet) -> bool {
-    let mask: u32 = if subnet.netmask == 0 {
-        0
-    } else {
-        !0u32 << (32 - subnet.netmask)
-    };
-    let net = u32::from(subnet.network) & mask;
-    iface_ips.iter().any(|ip| u32::from(*ip) & mask == net)
+    iface_ips.iter().any(|ip| subnet.contains(*ip))
+}
+
+/// Returns the most specific subnet containing `ip` among `subnets`.
+fn owning_subnet(
+    ip: Ipv4Addr,
+    subnets: &[crate::models::Subnet],
+) -> Option<&crate::models::Subnet> {
+    subnets
+        .iter()
+        .filter(|s| s.contains(ip))
+        .max_by_key(|s| s.netmask)
 }
 
 #[cfg(test)]
@@ -975,10 +2037,14 @@ mod tests {
         crate::models::Subnet {
             id: None,
             network,
-            netmask,
+            netmask: PrefixLen::new(netmask).unwrap(),
             gateway: network,
             dns_servers: vec![],
+            ntp_servers: vec![],
             domain_name: None,
+            authoritative: false,
+            default_lease_time: None,
+            template_id: None,
         }
     }
 
@@ -1038,6 +2104,72 @@ mod tests {
         ));
     }
 
+    fn offer_for(request: &DhcpPacket, yiaddr: Ipv4Addr) -> DhcpPacket {
+        let mut response = DhcpPacket::new();
+        response.set_op_code(OpCode::BootReply);
+        response.xid = request.xid;
+        response.yiaddr = yiaddr;
+        response
+    }
+
+    #[test]
+    fn test_response_dest_unicasts_when_broadcast_flag_clear() {
+        let request = create_discover_packet("AA:BB:CC:DD:EE:FF");
+        assert_eq!(request.flags & BROADCAST_FLAG, 0);
+        let response = offer_for(&request, Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(
+            response_dest(&request, &response),
+            "192.168.1.100:68".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_response_dest_broadcasts_when_broadcast_flag_set() {
+        let request = with_broadcast_flag(create_discover_packet("AA:BB:CC:DD:EE:FF"));
+        assert_ne!(request.flags & BROADCAST_FLAG, 0);
+        let response = offer_for(&request, Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(
+            response_dest(&request, &response),
+            "255.255.255.255:68".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_response_dest_ciaddr_overrides_broadcast_flag() {
+        let mut request = with_broadcast_flag(create_request_packet(
+            "AA:BB:CC:DD:EE:FF",
+            Ipv4Addr::new(192, 168, 1, 100),
+        ));
+        request.ciaddr = Ipv4Addr::new(192, 168, 1, 100);
+        let response = offer_for(&request, Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(
+            response_dest(&request, &response),
+            "192.168.1.100:68".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_response_dest_relay_overrides_broadcast_flag() {
+        let mut request = with_broadcast_flag(create_discover_packet("AA:BB:CC:DD:EE:FF"));
+        request.giaddr = Ipv4Addr::new(10, 0, 0, 1);
+        let response = offer_for(&request, Ipv4Addr::new(192, 168, 1, 100));
+        assert_eq!(
+            response_dest(&request, &response),
+            "10.0.0.1:67".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_response_dest_broadcasts_nak() {
+        let mut request = create_request_packet("AA:BB:CC:DD:EE:FF", Ipv4Addr::new(10, 9, 9, 9));
+        request.ciaddr = Ipv4Addr::new(10, 9, 9, 9);
+        let nak = DhcpServer::create_nak(&request, create_test_subnet().gateway);
+        assert_eq!(
+            response_dest(&request, &nak),
+            "255.255.255.255:68".parse::<SocketAddr>().unwrap()
+        );
+    }
+
     #[tokio::test]
     async fn test_handle_discover_with_static_ip() {
         let config = create_test_config();
@@ -1060,15 +2192,21 @@ mod tests {
         let packet = create_discover_packet("AA:BB:CC:DD:EE:FF");
 
         // Test handle_discover
-        let response =
-            DhcpServer::handle_discover(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
-                .await;
+        let received = ReceivedPacket::from_packet(packet);
+        let response = DhcpServer::handle_discover(
+            &received,
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            None,
+        )
+        .await;
 
         assert!(response.is_some());
         let offer = response.unwrap();
         assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 50));
         assert_eq!(offer.xid, 12345);
-        assert_eq!(offer.op, 2); // BOOTREPLY
+        assert_eq!(offer.op_code(), Some(OpCode::BootReply));
 
         // Verify options
         let msg_type = offer.get_message_type();
@@ -1094,6 +2232,7 @@ mod tests {
             lease_start: now,
             lease_end: now + 86400,
             hostname: None,
+            range_id: None,
         };
         db.create_lease(&lease).await.unwrap();
 
@@ -1101,9 +2240,15 @@ mod tests {
         let packet = create_discover_packet("11:22:33:44:55:66");
 
         // Test handle_discover
-        let response =
-            DhcpServer::handle_discover(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
-                .await;
+        let received = ReceivedPacket::from_packet(packet);
+        let response = DhcpServer::handle_discover(
+            &received,
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            None,
+        )
+        .await;
 
         assert!(response.is_some());
         let offer = response.unwrap();
@@ -1127,9 +2272,15 @@ mod tests {
         let packet = create_discover_packet("99:88:77:66:55:44");
 
         // Test handle_discover
-        let response =
-            DhcpServer::handle_discover(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
-                .await;
+        let received = ReceivedPacket::from_packet(packet);
+        let response = DhcpServer::handle_discover(
+            &received,
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            None,
+        )
+        .await;
 
         // Should return None: no static IP, no lease, and no dynamic ranges
         assert!(response.is_none());
@@ -1163,6 +2314,7 @@ mod tests {
             lease_start: now,
             lease_end: now + 86400,
             hostname: None,
+            range_id: None,
         };
         db.create_lease(&lease).await.unwrap();
 
@@ -1170,9 +2322,15 @@ mod tests {
         let packet = create_discover_packet("AA:BB:CC:DD:EE:00");
 
         // Test handle_discover - static IP should take precedence
-        let response =
-            DhcpServer::handle_discover(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
-                .await;
+        let received = ReceivedPacket::from_packet(packet);
+        let response = DhcpServer::handle_discover(
+            &received,
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            None,
+        )
+        .await;
 
         assert!(response.is_some());
         let offer = response.unwrap();
@@ -1189,19 +2347,18 @@ mod tests {
         let subnet_id = db.create_subnet(&subnet).await.unwrap();
 
         // Create a dynamic range
-        let range = crate::models::DynamicRange {
-            id: None,
-            subnet_id,
-            range_start: Ipv4Addr::new(192, 168, 1, 100),
-            range_end: Ipv4Addr::new(192, 168, 1, 200),
-            enabled: true,
-        };
-        db.create_range(&range).await.unwrap();
+        create_test_range(&db, subnet_id).await;
 
         let packet = create_discover_packet("AA:BB:CC:DD:EE:11");
-        let response =
-            DhcpServer::handle_discover(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
-                .await;
+        let received = ReceivedPacket::from_packet(packet);
+        let response = DhcpServer::handle_discover(
+            &received,
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            None,
+        )
+        .await;
 
         assert!(response.is_some());
         let offer = response.unwrap();
@@ -1210,6 +2367,27 @@ mod tests {
         assert_eq!(offer.get_message_type(), Some(MessageType::Offer));
     }
 
+    #[tokio::test]
+    async fn test_disabled_range_is_not_allocated_from() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        let range_id = create_test_range(&db, subnet_id).await;
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let received = ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:12"));
+
+        db.toggle_range(range_id, false).await.unwrap();
+        assert!(DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+            .await
+            .is_none());
+
+        db.toggle_range(range_id, true).await.unwrap();
+        let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+    }
+
     #[tokio::test]
     async fn test_handle_discover_dynamic_skips_leased_ips() {
         let config = create_test_config();
@@ -1218,14 +2396,7 @@ mod tests {
         let subnet = create_test_subnet();
         let subnet_id = db.create_subnet(&subnet).await.unwrap();
 
-        let range = crate::models::DynamicRange {
-            id: None,
-            subnet_id,
-            range_start: Ipv4Addr::new(192, 168, 1, 100),
-            range_end: Ipv4Addr::new(192, 168, 1, 200),
-            enabled: true,
-        };
-        db.create_range(&range).await.unwrap();
+        create_test_range(&db, subnet_id).await;
 
         // Lease .100 to another client
         let now = chrono::Utc::now().timestamp();
@@ -1237,19 +2408,80 @@ mod tests {
             lease_start: now,
             lease_end: now + 86400,
             hostname: None,
+            range_id: None,
         };
         db.create_lease(&lease).await.unwrap();
 
         let packet = create_discover_packet("AA:BB:CC:DD:EE:22");
-        let response =
-            DhcpServer::handle_discover(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
-                .await;
+        let received = ReceivedPacket::from_packet(packet);
+        let response = DhcpServer::handle_discover(
+            &received,
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            None,
+        )
+        .await;
 
         assert!(response.is_some());
         // Should skip .100 (leased) and offer .101
         assert_eq!(response.unwrap().yiaddr, Ipv4Addr::new(192, 168, 1, 101));
     }
 
+    /// Answers probes for `live` and records every address it was sent
+    struct FakeProber {
+        live: Vec<Ipv4Addr>,
+        probed: std::sync::Mutex<Vec<Ipv4Addr>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Prober for FakeProber {
+        async fn probe(&self, ip: Ipv4Addr, _timeout: std::time::Duration) -> bool {
+            self.probed.lock().unwrap().push(ip);
+            self.live.contains(&ip)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_discover_skips_addresses_answering_probes() {
+        let mut config = create_test_config();
+        config.dhcp.probe_retries = 0;
+        let db = InMemoryDatabase::new();
+
+        let subnet = create_test_subnet();
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+
+        // .100 and .101 are squatted by hosts the database does not know about
+        let prober = FakeProber {
+            live: vec![Ipv4Addr::new(192, 168, 1, 100), Ipv4Addr::new(192, 168, 1, 101)],
+            probed: std::sync::Mutex::new(Vec::new()),
+        };
+        let received = ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:23"));
+        let offer = DhcpServer::handle_discover(
+            &received,
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            Some(&prober),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 102));
+        assert_eq!(
+            *prober.probed.lock().unwrap(),
+            vec![
+                Ipv4Addr::new(192, 168, 1, 100),
+                Ipv4Addr::new(192, 168, 1, 101),
+                Ipv4Addr::new(192, 168, 1, 102),
+            ]
+        );
+        let offers = db.list_pending_offers().await.unwrap();
+        assert_eq!(offers.len(), 1);
+        assert_eq!(offers[0].ip_address, Ipv4Addr::new(192, 168, 1, 102));
+    }
+
     #[tokio::test]
     async fn test_handle_request_dynamic_creates_lease() {
         let config = create_test_config();
@@ -1258,30 +2490,65 @@ mod tests {
         let subnet = create_test_subnet();
         let subnet_id = db.create_subnet(&subnet).await.unwrap();
 
-        let range = crate::models::DynamicRange {
-            id: None,
-            subnet_id,
-            range_start: Ipv4Addr::new(192, 168, 1, 100),
-            range_end: Ipv4Addr::new(192, 168, 1, 200),
-            enabled: true,
-        };
-        db.create_range(&range).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        // An OFFER alone must not create a lease
+        let discover = ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:33"));
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .expect("offer");
+        assert!(db.get_active_lease("AA:BB:CC:DD:EE:33").await.unwrap().is_none());
 
-        let requested = Ipv4Addr::new(192, 168, 1, 100);
+        let requested = offer.yiaddr;
         let packet = create_request_packet("AA:BB:CC:DD:EE:33", requested);
-        let response =
-            DhcpServer::handle_request(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
-                .await;
+        let received = ReceivedPacket::from_packet(packet);
+        let response = DhcpServer::handle_request(&received, &iface_ips, &config, &db).await;
 
         assert!(response.is_some());
         let ack = response.unwrap();
         assert_eq!(ack.yiaddr, requested);
         assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
 
-        // Verify lease was persisted
+        // Verify lease was persisted for the default lease time
         let lease = db.get_active_lease("AA:BB:CC:DD:EE:33").await.unwrap();
         assert!(lease.is_some());
-        assert_eq!(lease.unwrap().ip_address, requested);
+        let lease = lease.unwrap();
+        assert_eq!(lease.ip_address, requested);
+        assert_eq!(
+            lease.lease_end - lease.lease_start,
+            config.dhcp.default_lease_time as i64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_lease_records_its_range() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        let mut range_ids = Vec::new();
+        for (start, end) in [(100, 149), (150, 200)] {
+            let range = crate::models::DynamicRange {
+                id: None,
+                subnet_id,
+                range_start: Ipv4Addr::new(192, 168, 1, start),
+                range_end: Ipv4Addr::new(192, 168, 1, end),
+                enabled: true,
+                draining: false,
+            };
+            range_ids.push(db.create_range(&range).await.unwrap());
+        }
+
+        let requested = Ipv4Addr::new(192, 168, 1, 160);
+        let packet = create_request_packet("AA:BB:CC:DD:EE:34", requested);
+        let received = ReceivedPacket::from_packet(packet);
+        DhcpServer::handle_request(&received, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
+            .await
+            .unwrap();
+
+        let lease = db.get_active_lease("AA:BB:CC:DD:EE:34").await.unwrap().unwrap();
+        assert_eq!(lease.range_id, Some(range_ids[1]));
     }
 
     #[tokio::test]
@@ -1292,14 +2559,7 @@ mod tests {
         let subnet = create_test_subnet();
         let subnet_id = db.create_subnet(&subnet).await.unwrap();
 
-        let range = crate::models::DynamicRange {
-            id: None,
-            subnet_id,
-            range_start: Ipv4Addr::new(192, 168, 1, 100),
-            range_end: Ipv4Addr::new(192, 168, 1, 200),
-            enabled: true,
-        };
-        db.create_range(&range).await.unwrap();
+        create_test_range(&db, subnet_id).await;
 
         // Another client already owns .100
         let now = chrono::Utc::now().timestamp();
@@ -1311,18 +2571,218 @@ mod tests {
             lease_start: now,
             lease_end: now + 86400,
             hostname: None,
+            range_id: None,
         };
         db.create_lease(&existing).await.unwrap();
 
         let packet = create_request_packet("AA:BB:CC:DD:EE:44", Ipv4Addr::new(192, 168, 1, 100));
+        let received = ReceivedPacket::from_packet(packet);
         let response =
-            DhcpServer::handle_request(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
+            DhcpServer::handle_request(&received, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
                 .await;
 
         // Should be rejected
         assert!(response.is_none());
     }
 
+    #[tokio::test]
+    async fn test_handle_request_records_assigned_then_renewed() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+
+        let subnet = create_test_subnet();
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+
+        // The buffer is process-wide: only look at this client's events
+        let mac = "aa:bb:cc:dd:ee:77";
+        let events_of = || -> Vec<LeaseEventKind> {
+            EVENTS
+                .recent()
+                .into_iter()
+                .filter(|e| e.mac_address == mac)
+                .map(|e| e.kind)
+                .collect()
+        };
+
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        for _ in 0..2 {
+            let request = create_request_packet(mac, Ipv4Addr::new(192, 168, 1, 150));
+            let received = ReceivedPacket::from_packet(request);
+            assert!(DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+                .await
+                .is_some());
+        }
+
+        assert_eq!(
+            events_of(),
+            vec![LeaseEventKind::Assigned, LeaseEventKind::Renewed]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_draining_range_renews_but_does_not_allocate() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        let mut range_ids = Vec::new();
+        for (start, end) in [(100, 110), (150, 160)] {
+            let range = crate::models::DynamicRange {
+                id: None,
+                subnet_id,
+                range_start: Ipv4Addr::new(192, 168, 1, start),
+                range_end: Ipv4Addr::new(192, 168, 1, end),
+                enabled: true,
+                draining: false,
+            };
+            range_ids.push(db.create_range(&range).await.unwrap());
+        }
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let request = |mac: &str, ip| {
+            ReceivedPacket::from_packet(create_request_packet(mac, Ipv4Addr::new(192, 168, 1, ip)))
+        };
+
+        let renewal = request("aa:bb:cc:dd:ee:80", 100);
+        let ack = DhcpServer::handle_request(&renewal, &iface_ips, &config, &db)
+            .await
+            .unwrap();
+        assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
+        db.set_range_draining(range_ids[0], true).await.unwrap();
+
+        // The lease already held in the draining range renews
+        let ack = DhcpServer::handle_request(&renewal, &iface_ips, &config, &db)
+            .await
+            .unwrap();
+        assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
+
+        // New clients are offered the other range and cannot claim a draining address
+        let discover = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:81"));
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 150));
+        let claim = request("aa:bb:cc:dd:ee:82", 101);
+        let reply = DhcpServer::handle_request(&claim, &iface_ips, &config, &db).await;
+        assert!(reply.is_none_or(|r| r.get_message_type() != Some(MessageType::Ack)));
+    }
+
+    #[tokio::test]
+    async fn test_renewal_updates_hostname() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+
+        let mac = "aa:bb:cc:dd:ee:42";
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let renew = |hostname: Option<&str>| {
+            let mut request = create_request_packet(mac, Ipv4Addr::new(192, 168, 1, 150));
+            if let Some(name) = hostname {
+                request.options.push(DhcpOption::Hostname(name.to_string()));
+            }
+            ReceivedPacket::from_packet(request)
+        };
+        let stored_hostname = || async {
+            db.get_active_lease(mac).await.unwrap().unwrap().hostname
+        };
+
+        let received = renew(Some("old-name"));
+        DhcpServer::handle_request(&received, &iface_ips, &config, &db).await.unwrap();
+        assert_eq!(stored_hostname().await.as_deref(), Some("old-name"));
+
+        let received = renew(Some("new-name"));
+        DhcpServer::handle_request(&received, &iface_ips, &config, &db).await.unwrap();
+        assert_eq!(stored_hostname().await.as_deref(), Some("new-name"));
+
+        // Renewing without option 12 keeps the last known name
+        let received = renew(None);
+        DhcpServer::handle_request(&received, &iface_ips, &config, &db).await.unwrap();
+        assert_eq!(stored_hostname().await.as_deref(), Some("new-name"));
+    }
+
+    #[tokio::test]
+    async fn test_client_hostname_is_sanitized() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let stored_hostname = |mac: &'static str, last_octet: u8, hostname: &str| {
+            let mut request = create_request_packet(mac, Ipv4Addr::new(192, 168, 1, last_octet));
+            request.options.push(DhcpOption::parse(12, hostname.as_bytes()));
+            let received = ReceivedPacket::from_packet(request);
+            let (config, db) = (&config, &db);
+            async move {
+                DhcpServer::handle_request(&received, &iface_ips, config, db).await.unwrap();
+                db.get_active_lease(mac).await.unwrap().unwrap().hostname
+            }
+        };
+
+        let nul = stored_hostname("aa:bb:cc:dd:ee:43", 151, "evil\0host\x1b[2J").await;
+        assert_eq!(nul.as_deref(), Some("evilhost-2J"));
+        let long = stored_hostname("aa:bb:cc:dd:ee:44", 152, &"x".repeat(300)).await;
+        assert_eq!(long.map(|h| h.len()), Some(63));
+        assert_eq!(stored_hostname("aa:bb:cc:dd:ee:45", 153, "").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_refused_past_lease_cap() {
+        let mut config = create_test_config();
+        config.dhcp.max_leases_per_client = Some(1);
+        let db = InMemoryDatabase::new();
+
+        let subnet = create_test_subnet();
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let first = create_request_packet("AA:BB:CC:DD:EE:66", Ipv4Addr::new(192, 168, 1, 100));
+        let received = ReceivedPacket::from_packet(first);
+        assert!(DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+            .await
+            .is_some());
+
+        // A second address for the same client is over the cap
+        let second = create_request_packet("AA:BB:CC:DD:EE:66", Ipv4Addr::new(192, 168, 1, 101));
+        let received = ReceivedPacket::from_packet(second);
+        assert!(DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+            .await
+            .is_none());
+
+        // Renewing the address it holds is still allowed
+        let renew = create_request_packet("AA:BB:CC:DD:EE:66", Ipv4Addr::new(192, 168, 1, 100));
+        let received = ReceivedPacket::from_packet(renew);
+        assert!(DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+            .await
+            .is_some());
+
+        // A refused renewal keeps the lease it was renewing: with the cap
+        // lowered under what the client already holds, renewing one address
+        // is refused because of the other
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&crate::models::Lease {
+            id: None,
+            subnet_id,
+            mac_address: "AA:BB:CC:DD:EE:66".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 102),
+            hostname: None,
+            lease_start: now,
+            lease_end: now + 3600,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+        let renew = create_request_packet("AA:BB:CC:DD:EE:66", Ipv4Addr::new(192, 168, 1, 100));
+        let received = ReceivedPacket::from_packet(renew);
+        assert!(DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+            .await
+            .is_none());
+        let held = db.list_active_leases().await.unwrap();
+        assert!(held.iter().any(|l| l.ip_address == Ipv4Addr::new(192, 168, 1, 100)));
+        assert_eq!(held.len(), 2);
+    }
+
     #[tokio::test]
     async fn test_handle_request_with_static_ip() {
         let config = create_test_config();
@@ -1345,15 +2805,16 @@ mod tests {
         let packet = create_request_packet("AA:BB:CC:DD:EE:FF", Ipv4Addr::new(192, 168, 1, 50));
 
         // Test handle_request
+        let received = ReceivedPacket::from_packet(packet);
         let response =
-            DhcpServer::handle_request(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
+            DhcpServer::handle_request(&received, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
                 .await;
 
         assert!(response.is_some());
         let ack = response.unwrap();
         assert_eq!(ack.yiaddr, Ipv4Addr::new(192, 168, 1, 50));
         assert_eq!(ack.xid, 67890);
-        assert_eq!(ack.op, 2); // BOOTREPLY
+        assert_eq!(ack.op_code(), Some(OpCode::BootReply));
 
         // Verify it's an ACK
         let msg_type = ack.get_message_type();
@@ -1382,13 +2843,147 @@ mod tests {
         let packet = create_request_packet("AA:BB:CC:DD:EE:FF", Ipv4Addr::new(192, 168, 1, 100));
 
         // Test handle_request - should return None as requested IP doesn't match static IP
+        let received = ReceivedPacket::from_packet(packet);
         let response =
-            DhcpServer::handle_request(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
+            DhcpServer::handle_request(&received, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
                 .await;
 
         assert!(response.is_none());
     }
 
+    #[tokio::test]
+    async fn test_handle_request_with_wrong_static_ip_naked_when_authoritative() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+
+        let subnet = crate::models::Subnet {
+            authoritative: true,
+            ..create_test_subnet()
+        };
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        let static_ip = StaticIP {
+            subnet_id,
+            mac_address: "AA:BB:CC:DD:EE:FF".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 50),
+            hostname: Some("test-host".to_string()),
+        };
+        db.create_static_ip(&static_ip).await.unwrap();
+
+        let packet = create_request_packet("AA:BB:CC:DD:EE:FF", Ipv4Addr::new(192, 168, 1, 100));
+        let received = ReceivedPacket::from_packet(packet);
+        let nak =
+            DhcpServer::handle_request(&received, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
+                .await
+                .expect("authoritative subnet must NAK");
+
+        assert_eq!(nak.get_message_type(), Some(MessageType::Nak));
+        assert_eq!(nak.yiaddr, Ipv4Addr::UNSPECIFIED);
+        assert!(nak.options.contains(&DhcpOption::ServerIdentifier(subnet.gateway)));
+        assert!(db.get_active_lease("aa:bb:cc:dd:ee:ff").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_static_ip_matched_by_client_identifier() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        // Type 0: an identifier that is not a hardware address
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "id:00:6c:61:70:74:6f:70".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 60),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let client_id = DhcpOption::ClientIdentifier(b"\0laptop".to_vec());
+
+        // The same client behind another NIC still gets its address
+        for mac in ["AA:BB:CC:DD:EE:01", "AA:BB:CC:DD:EE:02"] {
+            let mut discover = create_discover_packet(mac);
+            discover.options.push(client_id.clone());
+            let received = ReceivedPacket::from_packet(discover);
+            let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+                .await
+                .unwrap();
+            assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 60));
+
+            let mut request = create_request_packet(mac, offer.yiaddr);
+            request.options.push(client_id.clone());
+            let received = ReceivedPacket::from_packet(request);
+            let ack = DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+                .await
+                .unwrap();
+            assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
+            assert_eq!(ack.yiaddr, Ipv4Addr::new(192, 168, 1, 60));
+        }
+
+        // Without the identifier the chaddr is the key, which has no assignment
+        let received = ReceivedPacket::from_packet(create_request_packet(
+            "AA:BB:CC:DD:EE:01",
+            Ipv4Addr::new(192, 168, 1, 60),
+        ));
+        assert!(DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_client_identifier_falls_back_to_chaddr() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:61".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 61),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id,
+            range_id: None,
+            ip_address: Ipv4Addr::new(192, 168, 1, 162),
+            mac_address: "aa:bb:cc:dd:ee:62".to_string(),
+            hostname: None,
+            lease_start: now,
+            lease_end: now + 3600,
+        })
+        .await
+        .unwrap();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        // A DUID (type 255), as sent by systemd-networkd, known under no key
+        let client_id = DhcpOption::ClientIdentifier(vec![255, 0, 0, 0, 1, 0, 2, 0, 3]);
+
+        // Both the reservation and the lease keyed by chaddr still match
+        for (mac, ip) in [("AA:BB:CC:DD:EE:61", 61), ("AA:BB:CC:DD:EE:62", 162)] {
+            let mut discover = create_discover_packet(mac);
+            discover.options.push(client_id.clone());
+            let received = ReceivedPacket::from_packet(discover);
+            let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+                .await
+                .unwrap();
+            assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, ip));
+
+            let mut request = create_request_packet(mac, offer.yiaddr);
+            request.options.push(client_id.clone());
+            let received = ReceivedPacket::from_packet(request);
+            let ack = DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+                .await
+                .unwrap();
+            assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
+        }
+        // The renewed lease stays keyed by the MAC address
+        let leases = db.list_active_leases().await.unwrap();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].mac_address, "aa:bb:cc:dd:ee:62");
+    }
+
     #[tokio::test]
     async fn test_handle_request_without_requested_ip() {
         use dhcp_proto::MacAddress;
@@ -1402,7 +2997,7 @@ mod tests {
 
         // Create request packet without RequestedIpAddress option
         let mut packet = DhcpPacket::new();
-        packet.op = 1;
+        packet.set_op_code(OpCode::BootRequest);
         packet.xid = 67890;
         packet.chaddr = MacAddress::from_string("AA:BB:CC:DD:EE:FF").unwrap();
         packet
@@ -1410,13 +3005,106 @@ mod tests {
             .push(DhcpOption::MessageType(MessageType::Request));
 
         // Test handle_request - should return None without requested IP
+        let received = ReceivedPacket::from_packet(packet);
         let response =
-            DhcpServer::handle_request(&packet, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
+            DhcpServer::handle_request(&received, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
                 .await;
 
         assert!(response.is_none());
     }
 
+    #[tokio::test]
+    async fn test_handle_request_renewal_uses_ciaddr() {
+        use dhcp_proto::MacAddress;
+
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+
+        let subnet = create_test_subnet();
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+
+        create_test_range(&db, subnet_id).await;
+
+        // Lease about to expire, as seen at T1
+        let now = chrono::Utc::now().timestamp();
+        let current = Lease {
+            id: None,
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:55".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 120),
+            lease_start: now - 86000,
+            lease_end: now + 400,
+            hostname: None,
+            range_id: None,
+        };
+        db.create_lease(&current).await.unwrap();
+
+        // RENEWING: ciaddr carries the current address and option 50 is absent
+        let mut packet = DhcpPacket::new();
+        packet.set_op_code(OpCode::BootRequest);
+        packet.xid = 13579;
+        packet.ciaddr = Ipv4Addr::new(192, 168, 1, 120);
+        packet.chaddr = MacAddress::from_string("AA:BB:CC:DD:EE:55").unwrap();
+        packet
+            .options
+            .push(DhcpOption::MessageType(MessageType::Request));
+
+        let received = ReceivedPacket::from_packet(packet);
+        let ack =
+            DhcpServer::handle_request(&received, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
+                .await
+                .expect("renewal should be acknowledged");
+        assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
+        assert_eq!(ack.yiaddr, Ipv4Addr::new(192, 168, 1, 120));
+
+        let lease = db.get_active_lease("aa:bb:cc:dd:ee:55").await.unwrap().unwrap();
+        assert_eq!(lease.ip_address, Ipv4Addr::new(192, 168, 1, 120));
+        assert!(lease.lease_end >= now + config.dhcp.default_lease_time as i64);
+    }
+
+    #[tokio::test]
+    async fn test_renewal_does_not_depend_on_expiring_the_old_lease() {
+        let config = create_test_config();
+        let db = crate::db::SqliteDatabase::new("sqlite::memory:").await.unwrap();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let now = chrono::Utc::now().timestamp();
+        let current = Lease {
+            id: None,
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:56".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 121),
+            lease_start: now - 600,
+            lease_end: now + 600,
+            hostname: None,
+            range_id: None,
+        };
+        let id = db.create_lease(&current).await.unwrap();
+
+        // Deleting leases fails, as expire_lease does
+        sqlx::query(
+            "CREATE TRIGGER refuse_expire BEFORE DELETE ON leases \
+             BEGIN SELECT RAISE(ABORT, 'refused'); END",
+        )
+        .execute(db.pool())
+        .await
+        .unwrap();
+        assert!(db.expire_lease(id).await.is_err());
+
+        let request = create_request_packet("AA:BB:CC:DD:EE:56", current.ip_address);
+        let received = ReceivedPacket::from_packet(request);
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let ack = DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+            .await
+            .unwrap();
+        assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
+
+        let leases = db.list_active_leases().await.unwrap();
+        assert_eq!(leases.len(), 1);
+        assert_ne!(leases[0].id, Some(id));
+        assert!(leases[0].lease_end >= now + config.dhcp.default_lease_time as i64);
+    }
+
     #[tokio::test]
     async fn test_handle_release_with_active_lease() {
         let db = InMemoryDatabase::new();
@@ -1435,6 +3123,7 @@ mod tests {
             lease_start: now,
             lease_end: now + 86400,
             hostname: None,
+            range_id: None,
         };
         let _lease_id = db.create_lease(&lease).await.unwrap();
 
@@ -1446,13 +3135,1387 @@ mod tests {
         let packet = create_release_packet("11:22:33:44:55:66");
 
         // Test handle_release
-        DhcpServer::handle_release(&packet, &db).await;
+        DhcpServer::handle_release(&ReceivedPacket::from_packet(packet), &create_test_config(), &db)
+            .await;
 
         // Verify lease has been expired
         let active_lease_after = db.get_active_lease("11:22:33:44:55:66").await.unwrap();
         assert!(active_lease_after.is_none());
     }
 
+    #[tokio::test]
+    async fn test_released_address_held_during_grace_period() {
+        let mut config = create_test_config();
+        config.dhcp.release_grace_seconds = 60;
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let now = chrono::Utc::now().timestamp();
+        let lease_id = db
+            .create_lease(&Lease {
+                id: None,
+                subnet_id,
+                mac_address: "11:22:33:44:55:88".to_string(),
+                ip_address: Ipv4Addr::new(192, 168, 1, 100),
+                lease_start: now,
+                lease_end: now + 86400,
+                hostname: None,
+                range_id: None,
+            })
+            .await
+            .unwrap();
+
+        let packet = create_release_packet("11:22:33:44:55:88");
+        DhcpServer::handle_release(&ReceivedPacket::from_packet(packet), &config, &db).await;
+        assert!(db.get_active_lease("11:22:33:44:55:88").await.unwrap().is_none());
+
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let received = ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:88"));
+        let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 101));
+
+        // The client that released it is not kept away from it
+        let received = ReceivedPacket::from_packet(create_discover_packet("11:22:33:44:55:88"));
+        let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+        db.delete_pending_offer(offer.yiaddr).await.unwrap();
+
+        // Once the grace period is over it is free again
+        db.release_lease(lease_id, now - 61).await.unwrap();
+        let received = ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:89"));
+        let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+    }
+
+    fn subnet_mask_of(packet: &DhcpPacket) -> Option<Ipv4Addr> {
+        packet.options.iter().find_map(|o| match o {
+            DhcpOption::SubnetMask(m) => Some(*m),
+            _ => None,
+        })
+    }
+
+    fn router_of(packet: &DhcpPacket) -> Option<Vec<Ipv4Addr>> {
+        packet.options.iter().find_map(|o| match o {
+            DhcpOption::Router(r) => Some(r.clone()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_owning_subnet_prefers_most_specific() {
+        let wide = make_subnet(Ipv4Addr::new(10, 0, 0, 0), 16);
+        let narrow = make_subnet(Ipv4Addr::new(10, 0, 5, 0), 24);
+        let subnets = [wide, narrow];
+        let owner = owning_subnet(Ipv4Addr::new(10, 0, 5, 9), &subnets).unwrap();
+        assert_eq!(owner.netmask, 24);
+        let owner = owning_subnet(Ipv4Addr::new(10, 0, 6, 9), &subnets).unwrap();
+        assert_eq!(owner.netmask, 16);
+        assert!(owning_subnet(Ipv4Addr::new(192, 168, 0, 1), &subnets).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_empty_domain_name_is_not_sent() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet = crate::models::Subnet {
+            domain_name: Some(String::new()),
+            ..create_test_subnet()
+        };
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let has_domain = |packet: &DhcpPacket| {
+            packet.options.iter().any(|o| matches!(o, DhcpOption::DomainName(_)))
+        };
+
+        let discover = ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:53"));
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .expect("offer");
+        assert!(!has_domain(&offer));
+
+        let request = ReceivedPacket::from_packet(create_request_packet(
+            "AA:BB:CC:DD:EE:53",
+            offer.yiaddr,
+        ));
+        let ack = DhcpServer::handle_request(&request, &iface_ips, &config, &db)
+            .await
+            .expect("ack");
+        assert!(!has_domain(&ack));
+    }
+
+    /// Shared network: one interface carrying a /24 and a /16. A range that was
+    /// attached to the /24 but hands out addresses from the /16 must still
+    /// produce the /16 mask and router.
+    #[tokio::test]
+    async fn test_shared_network_reply_uses_owning_subnet_mask() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(10, 0, 0, 1)];
+
+        let small_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        let large = crate::models::Subnet::from_cidr("10.0.0.0/16").unwrap();
+        let large_id = db.create_subnet(&large).await.unwrap();
+
+        db.create_range(&crate::models::DynamicRange {
+            id: None,
+            subnet_id: small_id,
+            range_start: Ipv4Addr::new(10, 0, 5, 10),
+            range_end: Ipv4Addr::new(10, 0, 5, 20),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+
+        let offer = DhcpServer::handle_discover(
+            &ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:51")),
+            &iface_ips,
+            &config,
+            &db,
+            None,
+        )
+        .await
+        .expect("offer");
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(10, 0, 5, 10));
+        assert_eq!(subnet_mask_of(&offer), Some(Ipv4Addr::new(255, 255, 0, 0)));
+        assert_eq!(router_of(&offer), Some(vec![Ipv4Addr::new(10, 0, 0, 1)]));
+        let offers = db.list_pending_offers().await.unwrap();
+        assert_eq!(offers[0].subnet_id, large_id);
+
+        let ack = DhcpServer::handle_request(
+            &ReceivedPacket::from_packet(create_request_packet("AA:BB:CC:DD:EE:51", offer.yiaddr)),
+            &iface_ips,
+            &config,
+            &db,
+        )
+        .await
+        .expect("ack");
+        assert_eq!(subnet_mask_of(&ack), Some(Ipv4Addr::new(255, 255, 0, 0)));
+        // The lease is recorded against the subnet whose options were sent
+        let lease = db.get_active_lease("AA:BB:CC:DD:EE:51").await.unwrap().unwrap();
+        assert_eq!(lease.subnet_id, large_id);
+    }
+
+    /// Shared network where the first subnet is exhausted: the address comes
+    /// from the second subnet and carries its own mask.
+    #[tokio::test]
+    async fn test_shared_network_second_subnet_mask() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(10, 0, 0, 1)];
+
+        let small_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        let large_id = db
+            .create_subnet(&crate::models::Subnet::from_cidr("10.0.0.0/16").unwrap())
+            .await
+            .unwrap();
+        for (subnet_id, start, end) in [
+            (small_id, Ipv4Addr::new(192, 168, 1, 100), Ipv4Addr::new(192, 168, 1, 100)),
+            (large_id, Ipv4Addr::new(10, 0, 1, 1), Ipv4Addr::new(10, 0, 1, 10)),
+        ] {
+            db.create_range(&crate::models::DynamicRange {
+                id: None,
+                subnet_id,
+                range_start: start,
+                range_end: end,
+                enabled: true,
+                draining: false,
+            })
+            .await
+            .unwrap();
+        }
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id: small_id,
+            mac_address: "11:22:33:44:55:66".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 100),
+            lease_start: now,
+            lease_end: now + 86400,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+
+        let offer = DhcpServer::handle_discover(
+            &ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:52")),
+            &iface_ips,
+            &config,
+            &db,
+            None,
+        )
+        .await
+        .expect("offer");
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(10, 0, 1, 1));
+        assert_eq!(subnet_mask_of(&offer), Some(Ipv4Addr::new(255, 255, 0, 0)));
+    }
+
+    fn pxe_test_config() -> Config {
+        use crate::config::PxeBootConfig;
+
+        let mut config = create_test_config();
+        config.dhcp.pxe.insert(
+            "PXEClient".to_string(),
+            PxeBootConfig {
+                next_server: Some(Ipv4Addr::new(192, 168, 1, 5)),
+                boot_file: "undionly.kpxe".to_string(),
+                tftp_server_name: Some("tftp.lan".to_string()),
+            },
+        );
+        config.dhcp.pxe.insert(
+            "iPXE".to_string(),
+            PxeBootConfig {
+                next_server: None,
+                boot_file: "http://boot.lan/boot.ipxe".to_string(),
+                tftp_server_name: None,
+            },
+        );
+        config
+    }
+
+    fn bootfile_of(packet: &DhcpPacket) -> Option<String> {
+        packet.options.iter().find_map(|o| match o {
+            DhcpOption::BootfileName(f) => Some(f.clone()),
+            _ => None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pxe_vendor_classes_get_distinct_boot_files() {
+        let config = pxe_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        let mut pxe = create_discover_packet("AA:BB:CC:DD:EE:61");
+        pxe.options.push(DhcpOption::VendorClassIdentifier(
+            "PXEClient:Arch:00000:UNDI:002001".to_string(),
+        ));
+        let received = ReceivedPacket::from_packet(pxe);
+        let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.siaddr, Ipv4Addr::new(192, 168, 1, 5));
+        assert_eq!(bootfile_of(&offer).as_deref(), Some("undionly.kpxe"));
+        assert!(offer
+            .options
+            .contains(&DhcpOption::TftpServerName("tftp.lan".to_string())));
+
+        let mut ipxe = create_discover_packet("AA:BB:CC:DD:EE:62");
+        ipxe.options
+            .push(DhcpOption::VendorClassIdentifier("iPXE".to_string()));
+        let received = ReceivedPacket::from_packet(ipxe);
+        let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        // No next_server configured: siaddr stays on the subnet gateway
+        assert_eq!(offer.siaddr, Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(
+            bootfile_of(&offer).as_deref(),
+            Some("http://boot.lan/boot.ipxe")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_pxe_options_without_vendor_class() {
+        let config = pxe_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        let static_ip = StaticIP {
+            subnet_id,
+            mac_address: "AA:BB:CC:DD:EE:63".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 63),
+            hostname: None,
+        };
+        db.create_static_ip(&static_ip).await.unwrap();
+
+        let offer = DhcpServer::handle_discover(
+            &ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:63")),
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(bootfile_of(&offer).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_class_policy_overrides_lease_and_dns() {
+        use crate::config::UserClassPolicy;
+
+        let mut config = create_test_config();
+        config.dhcp.user_classes.insert(
+            "iot".to_string(),
+            UserClassPolicy {
+                dns_servers: Some(vec![Ipv4Addr::new(192, 168, 1, 53)]),
+                domain_name: None,
+                lease_time: Some(3600),
+            },
+        );
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let dns_of = |packet: &DhcpPacket| {
+            packet.options.iter().find_map(|o| match o {
+                DhcpOption::DnsServer(servers) => Some(servers.clone()),
+                _ => None,
+            })
+        };
+
+        let mut request =
+            create_request_packet("aa:bb:cc:dd:ee:69", Ipv4Addr::new(192, 168, 1, 100));
+        request.options.push(DhcpOption::UserClass(vec![b"iot".to_vec()]));
+        let received = ReceivedPacket::from_packet(request);
+        let ack = DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+            .await
+            .unwrap();
+        assert!(ack.options.contains(&DhcpOption::LeaseTime(3600)));
+        assert_eq!(dns_of(&ack), Some(vec![Ipv4Addr::new(192, 168, 1, 53)]));
+        // Fields the policy leaves unset keep the subnet's value
+        assert!(ack.options.contains(&DhcpOption::DomainName("test.local".to_string())));
+
+        // Another class gets the subnet defaults
+        let mut discover = create_discover_packet("aa:bb:cc:dd:ee:6a");
+        discover.options.push(DhcpOption::UserClass(vec![b"voip".to_vec()]));
+        let received = ReceivedPacket::from_packet(discover);
+        let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert!(offer.options.contains(&DhcpOption::LeaseTime(86400)));
+        assert_eq!(dns_of(&offer), Some(create_test_subnet().dns_servers));
+    }
+
+    #[tokio::test]
+    async fn test_ack_echoes_client_fqdn() {
+        use dhcp_proto::option::fqdn_flags;
+
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "AA:BB:CC:DD:EE:71".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 71),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+
+        let mut request =
+            create_request_packet("AA:BB:CC:DD:EE:71", Ipv4Addr::new(192, 168, 1, 71));
+        request.options.push(DhcpOption::ClientFqdn {
+            flags: fqdn_flags::SERVER_UPDATE | fqdn_flags::CANONICAL_ENCODING,
+            rcode: dhcp_proto::option::fqdn_rcode::CLIENT,
+            name: "laptop.test.local.".to_string(),
+        });
+        let received = ReceivedPacket::from_packet(request);
+        let ack =
+            DhcpServer::handle_request(&received, &[Ipv4Addr::new(192, 168, 1, 1)], &config, &db)
+                .await
+                .unwrap();
+        // The server performs no DNS update, so it overrides the S bit
+        assert_eq!(
+            ack.get_client_fqdn(),
+            Some((
+                fqdn_flags::NO_UPDATE | fqdn_flags::OVERRIDE | fqdn_flags::CANONICAL_ENCODING,
+                "laptop.test.local."
+            ))
+        );
+        // Answered with the server's RCODEs, not the client's zeros
+        assert!(ack.options.iter().any(|o| matches!(
+            o,
+            DhcpOption::ClientFqdn { rcode: dhcp_proto::option::fqdn_rcode::SERVER, .. }
+        )));
+    }
+
+    fn relay_agent_info_of(packet: &DhcpPacket) -> Option<Vec<u8>> {
+        // Go through the wire format so the check covers encoding too
+        DhcpPacket::parse(&packet.to_bytes().unwrap()).unwrap().options.into_iter().find_map(|opt| {
+            if let DhcpOption::RelayAgentInfo(data) = opt {
+                Some(data)
+            } else {
+                None
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_replies_echo_relay_agent_info() {
+        let mut config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "AA:BB:CC:DD:EE:82".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 82),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+
+        // Circuit ID "ge-0/0/1" and a Remote ID, as a relay would insert them
+        let info = b"\x01\x08ge-0/0/1\x02\x06\x00\x1b\x21\x3c\x4d\x5e".to_vec();
+        let mut discover = create_discover_packet("AA:BB:CC:DD:EE:82");
+        discover.options.push(DhcpOption::RelayAgentInfo(info.clone()));
+        let mut request =
+            create_request_packet("AA:BB:CC:DD:EE:82", Ipv4Addr::new(192, 168, 1, 82));
+        request.options.push(DhcpOption::RelayAgentInfo(info.clone()));
+        let discover = ReceivedPacket::from_packet(discover);
+        let request = ReceivedPacket::from_packet(request);
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        let ack = DhcpServer::handle_request(&request, &iface_ips, &config, &db)
+            .await
+            .unwrap();
+        assert_eq!(relay_agent_info_of(&offer), Some(info.clone()));
+        assert_eq!(relay_agent_info_of(&ack), Some(info));
+
+        config.dhcp.echo_relay_agent_info = false;
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert_eq!(relay_agent_info_of(&offer), None);
+    }
+
+    #[tokio::test]
+    async fn test_discover_served_from_subnet_of_local_address() {
+        let db = InMemoryDatabase::new();
+        let mut subnet_ids = Vec::new();
+        for third in [1, 2] {
+            let subnet = crate::models::Subnet {
+                id: None,
+                network: Ipv4Addr::new(192, 168, third, 0),
+                gateway: Ipv4Addr::new(192, 168, third, 1),
+                ..create_test_subnet()
+            };
+            let subnet_id = db.create_subnet(&subnet).await.unwrap();
+            db.create_range(&crate::models::DynamicRange {
+                id: None,
+                subnet_id,
+                range_start: Ipv4Addr::new(192, 168, third, 100),
+                range_end: Ipv4Addr::new(192, 168, third, 200),
+                enabled: true,
+                draining: false,
+            })
+            .await
+            .unwrap();
+            subnet_ids.push(subnet_id);
+        }
+        // One interface holding an address in each subnet, serving both
+        let mut config = create_test_config();
+        config.dhcp.interface_subnets.insert("eth0".to_string(), subnet_ids);
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 2, 1)];
+        let src: SocketAddr = "0.0.0.0:68".parse().unwrap();
+
+        for (local, expected) in [
+            (Ipv4Addr::new(192, 168, 2, 1), Ipv4Addr::new(192, 168, 2, 100)),
+            (Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 100)),
+        ] {
+            let received = ReceivedPacket::new(
+                create_discover_packet("aa:bb:cc:dd:ee:3b"),
+                src,
+                Some("eth0".to_string()),
+            )
+            .with_local_addr(Some(local));
+            let offer = DhcpServer::handle_discover(&received, &iface_ips, &config, &db, None)
+                .await
+                .unwrap();
+            assert_eq!(offer.yiaddr, expected, "received on {local}");
+            db.delete_pending_offer(offer.yiaddr).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_packet_offer_counts_rogue_server() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+
+        let mut packet = DhcpPacket::new();
+        packet.set_op_code(OpCode::BootReply);
+        packet.xid = 12345;
+        packet
+            .options
+            .push(DhcpOption::MessageType(MessageType::Offer));
+
+        let src: SocketAddr = "192.0.2.77:67".parse().unwrap();
+        let counter = &crate::metrics::METRICS.rogue_server_packets;
+        let before = counter.get(&["offer"]);
+
+        let received = ReceivedPacket::new(packet, src, Some("eth0".to_string()));
+        let response = DhcpServer::handle_packet(
+            &received,
+            &[Ipv4Addr::new(192, 168, 1, 1)],
+            &config,
+            &db,
+            None,
+            None,
+        )
+        .await;
+
+        assert!(response.is_none());
+        assert_eq!(counter.get(&["offer"]), before + 1);
+    }
+
+    #[tokio::test]
+    async fn test_handle_packet_records_latency() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        db.create_subnet(&create_test_subnet()).await.unwrap();
+
+        let histogram = &crate::metrics::METRICS.handle_duration;
+        let before = histogram.count(&["discover"]);
+        let received = ReceivedPacket::from_packet(create_discover_packet("AA:BB:CC:DD:EE:48"));
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None).await;
+
+        // Other tests may record concurrently; ours adds at least one observation
+        assert!(histogram.count(&["discover"]) > before);
+        assert!(crate::metrics::render().contains(
+            "ndhcpd_dhcp_handle_duration_seconds_bucket{message_type=\"discover\",le=\"+Inf\"}"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_handle_release_ignores_spoofed_ciaddr_and_server_id() {
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id,
+            mac_address: "11:22:33:44:55:77".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 100),
+            lease_start: now,
+            lease_end: now + 86400,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+
+        // Address belonging to someone else
+        let mut packet = create_release_packet("11:22:33:44:55:77");
+        packet.ciaddr = Ipv4Addr::new(192, 168, 1, 101);
+        DhcpServer::handle_release(&ReceivedPacket::from_packet(packet), &create_test_config(), &db)
+            .await;
+        assert!(db.get_active_lease("11:22:33:44:55:77").await.unwrap().is_some());
+
+        // Addressed to another server
+        let mut packet = create_release_packet("11:22:33:44:55:77");
+        packet.ciaddr = Ipv4Addr::new(192, 168, 1, 100);
+        packet
+            .options
+            .push(DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 254)));
+        DhcpServer::handle_release(&ReceivedPacket::from_packet(packet), &create_test_config(), &db)
+            .await;
+        assert!(db.get_active_lease("11:22:33:44:55:77").await.unwrap().is_some());
+
+        // Matching address and server identifier
+        let mut packet = create_release_packet("11:22:33:44:55:77");
+        packet.ciaddr = Ipv4Addr::new(192, 168, 1, 100);
+        packet
+            .options
+            .push(DhcpOption::ServerIdentifier(Ipv4Addr::new(192, 168, 1, 1)));
+        DhcpServer::handle_release(&ReceivedPacket::from_packet(packet), &create_test_config(), &db)
+            .await;
+        assert!(db.get_active_lease("11:22:33:44:55:77").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_packet_past_max_hops_is_dropped() {
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let config = create_test_config();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        let mut packet = create_discover_packet("aa:bb:cc:dd:ee:39");
+        packet.giaddr = Ipv4Addr::new(192, 168, 1, 254);
+        packet.hops = config.dhcp.max_hops;
+        let received = ReceivedPacket::from_packet(packet.clone());
+        assert!(DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .is_some());
+
+        packet.hops = config.dhcp.max_hops + 1;
+        let received = ReceivedPacket::from_packet(packet);
+        assert!(DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_non_ethernet_hardware_type_per_config() {
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let mut config = create_test_config();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        // IEEE 802
+        let mut packet = create_discover_packet("aa:bb:cc:dd:ee:3a");
+        packet.htype = 6;
+        let received = ReceivedPacket::from_packet(packet.clone());
+        let counter = &crate::metrics::METRICS.unsupported_hardware_packets;
+        let before = counter.get(&["6"]);
+        assert!(DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+        assert_eq!(counter.get(&["6"]), before + 1);
+
+        config.dhcp.allow_non_ethernet = true;
+        let offer = DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.get_message_type(), Some(MessageType::Offer));
+
+        // An address that does not fit a MAC is never served
+        packet.hlen = 8;
+        let received = ReceivedPacket::from_packet(packet);
+        assert!(DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_vendor_filter_allow_and_deny() {
+        use crate::config::{VendorFilter, VendorFilterMode};
+
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let mut config = create_test_config();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let allowed = ReceivedPacket::from_packet(create_discover_packet("00:1b:63:00:00:01"));
+        let other = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:00:00:02"));
+
+        config.dhcp.vendor_filter = Some(VendorFilter {
+            mode: VendorFilterMode::Allow,
+            ouis: vec![Oui::try_from("00-1B-63".to_string()).unwrap()],
+        });
+        assert!(DhcpServer::handle_packet(&allowed, &iface_ips, &config, &db, None, None)
+            .await
+            .is_some());
+        assert!(DhcpServer::handle_packet(&other, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+
+        config.dhcp.vendor_filter.as_mut().unwrap().mode = VendorFilterMode::Deny;
+        assert!(DhcpServer::handle_packet(&allowed, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+        assert!(DhcpServer::handle_packet(&other, &iface_ips, &config, &db, None, None)
+            .await
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_answer_after_secs_defers_to_primary() {
+        let mut config = create_test_config();
+        config.dhcp.answer_after_secs = 10;
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "AA:BB:CC:DD:EE:54".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 54),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        let mut discover = create_discover_packet("AA:BB:CC:DD:EE:54");
+        discover.secs = 3;
+        let received = ReceivedPacket::from_packet(discover.clone());
+        assert!(DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+
+        discover.secs = 10;
+        let received = ReceivedPacket::from_packet(discover);
+        let offer = DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 54));
+
+        // A SELECTING REQUEST is held back like the DISCOVER
+        let mut request =
+            create_request_packet("AA:BB:CC:DD:EE:54", Ipv4Addr::new(192, 168, 1, 54));
+        request.secs = 3;
+        let received = ReceivedPacket::from_packet(request);
+        assert!(DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+
+        // A renewal names its address in ciaddr and is answered at once
+        let mut renew =
+            create_request_packet("AA:BB:CC:DD:EE:54", Ipv4Addr::new(192, 168, 1, 54));
+        renew.options.retain(|o| !matches!(o, DhcpOption::RequestedIpAddress(_)));
+        renew.ciaddr = Ipv4Addr::new(192, 168, 1, 54);
+        let received = ReceivedPacket::from_packet(renew);
+        let ack = DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .unwrap();
+        assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
+        assert_eq!(ack.yiaddr, Ipv4Addr::new(192, 168, 1, 54));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_relay_subnet_is_counted_and_optionally_naked() {
+        let mut config = create_test_config();
+        let db = InMemoryDatabase::new();
+        db.create_subnet(&create_test_subnet()).await.unwrap();
+
+        let mut request =
+            create_request_packet("AA:BB:CC:DD:EE:52", Ipv4Addr::new(10, 99, 0, 20));
+        request.giaddr = Ipv4Addr::new(10, 99, 0, 1);
+        request.hops = 1;
+        let received = ReceivedPacket::from_packet(request);
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let counter = &crate::metrics::METRICS.unknown_relay_subnet;
+        let before = counter.get(&[]);
+
+        let response =
+            DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None).await;
+        assert!(response.is_none());
+        assert_eq!(counter.get(&[]), before + 1);
+
+        config.dhcp.nak_unknown_relay = true;
+        let nak = DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .unwrap();
+        assert_eq!(nak.get_message_type(), Some(MessageType::Nak));
+        assert_eq!(nak.giaddr, Ipv4Addr::new(10, 99, 0, 1));
+        assert_eq!(counter.get(&[]), before + 2);
+    }
+
+    #[tokio::test]
+    async fn test_foreign_request_naked_only_on_authoritative_subnet() {
+        let config = create_test_config();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        for authoritative in [false, true] {
+            let db = InMemoryDatabase::new();
+            let subnet = crate::models::Subnet {
+                authoritative,
+                ..create_test_subnet()
+            };
+            let subnet_id = db.create_subnet(&subnet).await.unwrap();
+            create_test_range(&db, subnet_id).await;
+
+            // Client moved from another network and tries to keep its old address
+            let packet = create_request_packet("aa:bb:cc:dd:ee:41", Ipv4Addr::new(10, 1, 2, 3));
+            let received = ReceivedPacket::from_packet(packet);
+            let response = DhcpServer::handle_request(&received, &iface_ips, &config, &db).await;
+
+            if authoritative {
+                let nak = response.expect("authoritative subnet must NAK");
+                assert_eq!(nak.get_message_type(), Some(MessageType::Nak));
+                assert_eq!(nak.yiaddr, Ipv4Addr::UNSPECIFIED);
+            } else {
+                assert!(response.is_none());
+            }
+
+            // A valid address in the range is still granted either way
+            let packet =
+                create_request_packet("aa:bb:cc:dd:ee:41", Ipv4Addr::new(192, 168, 1, 150));
+            let received = ReceivedPacket::from_packet(packet);
+            let ack = DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+                .await
+                .unwrap();
+            assert_eq!(ack.get_message_type(), Some(MessageType::Ack));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subnet_lease_time_overrides_global_default() {
+        let config = create_test_config();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        // No override, an override, and one above max_lease_time (604800)
+        let cases = [(None, 86400), (Some(3600), 3600), (Some(10_000_000), 604800)];
+        for (override_time, expected) in cases {
+            let db = InMemoryDatabase::new();
+            let subnet = crate::models::Subnet {
+                default_lease_time: override_time,
+                ..create_test_subnet()
+            };
+            let subnet_id = db.create_subnet(&subnet).await.unwrap();
+            create_test_range(&db, subnet_id).await;
+            let lease_time_of = |packet: &DhcpPacket| {
+                packet.options.iter().find_map(|o| match o {
+                    DhcpOption::LeaseTime(t) => Some(*t),
+                    _ => None,
+                })
+            };
+
+            let discover = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:58"));
+            let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+                .await
+                .unwrap();
+            assert_eq!(lease_time_of(&offer), Some(expected));
+
+            let now = chrono::Utc::now().timestamp();
+            let packet = create_request_packet("aa:bb:cc:dd:ee:58", offer.yiaddr);
+            let received = ReceivedPacket::from_packet(packet);
+            let ack = DhcpServer::handle_request(&received, &iface_ips, &config, &db)
+                .await
+                .unwrap();
+            assert_eq!(lease_time_of(&ack), Some(expected));
+            assert!(ack.options.contains(&DhcpOption::RenewalTime(expected / 2)));
+
+            let lease = db.get_active_lease("aa:bb:cc:dd:ee:58").await.unwrap().unwrap();
+            let granted = lease.lease_end - now;
+            assert!((expected as i64..=expected as i64 + 1).contains(&granted));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subnet_without_dns_servers_omits_option_6() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet = crate::models::Subnet {
+            dns_servers: vec![],
+            ..create_test_subnet()
+        };
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let discover = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:59"));
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert!(!offer.options.iter().any(|o| matches!(o, DhcpOption::DnsServer(_))));
+        assert!(offer.options.contains(&DhcpOption::Router(vec![subnet.gateway])));
+
+        // Nor does option 6 appear on the wire with a zero length
+        let bytes = offer.to_bytes().unwrap();
+        let mut options = &bytes[240..];
+        while let [code, rest @ ..] = options {
+            match code {
+                0 => options = rest,
+                255 => break,
+                _ => {
+                    assert_ne!(*code, 6);
+                    options = &rest[1 + rest[0] as usize..];
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subnet_ntp_servers_sent_as_option_42() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let ntp_servers = vec![Ipv4Addr::new(192, 168, 1, 123)];
+        let subnet = crate::models::Subnet {
+            ntp_servers: ntp_servers.clone(),
+            ..create_test_subnet()
+        };
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        let discover = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:5c"));
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert!(offer.options.contains(&DhcpOption::NtpServers(ntp_servers.clone())));
+
+        let request = ReceivedPacket::from_packet(create_request_packet(
+            "aa:bb:cc:dd:ee:5c",
+            offer.yiaddr,
+        ));
+        let ack = DhcpServer::handle_request(&request, &iface_ips, &config, &db)
+            .await
+            .unwrap();
+        assert!(ack.options.contains(&DhcpOption::NtpServers(ntp_servers)));
+
+        // Unset on the subnet: no zero-length option 42
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert!(!offer.options.iter().any(|o| matches!(o, DhcpOption::NtpServers(_))));
+    }
+
+    #[tokio::test]
+    async fn test_replies_carry_only_requested_parameters() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet = crate::models::Subnet {
+            domain_name: Some("lan".to_string()),
+            ..create_test_subnet()
+        };
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let has_dns =
+            |p: &DhcpPacket| p.options.iter().any(|o| matches!(o, DhcpOption::DnsServer(_)));
+        let has_domain =
+            |p: &DhcpPacket| p.options.iter().any(|o| matches!(o, DhcpOption::DomainName(_)));
+
+        // Only DNS servers requested: no router nor domain name
+        let mut packet = create_discover_packet("aa:bb:cc:dd:ee:5a");
+        packet.options.push(DhcpOption::ParameterRequestList(vec![6]));
+        let discover = ReceivedPacket::from_packet(packet);
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert!(has_dns(&offer));
+        assert_eq!(router_of(&offer), None);
+        assert!(!has_domain(&offer));
+        assert_eq!(subnet_mask_of(&offer), Some(Ipv4Addr::new(255, 255, 255, 0)));
+        assert!(offer.options.iter().any(|o| matches!(o, DhcpOption::LeaseTime(_))));
+
+        let mut packet = create_request_packet("aa:bb:cc:dd:ee:5a", offer.yiaddr);
+        packet.options.push(DhcpOption::ParameterRequestList(vec![1, 3, 15]));
+        let request = ReceivedPacket::from_packet(packet);
+        let ack = DhcpServer::handle_request(&request, &iface_ips, &config, &db)
+            .await
+            .unwrap();
+        assert!(!has_dns(&ack));
+        assert_eq!(router_of(&ack), Some(vec![subnet.gateway]));
+        assert!(has_domain(&ack));
+        assert!(ack.options.iter().any(|o| matches!(o, DhcpOption::RenewalTime(_))));
+
+        // No list at all: everything is sent
+        let discover = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:5b"));
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+            .await
+            .unwrap();
+        assert!(has_dns(&offer) && has_domain(&offer) && router_of(&offer).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_subnets_share_option_template() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let template_id = db
+            .create_option_template(&crate::models::OptionTemplate {
+                id: None,
+                name: "branch".to_string(),
+                dns_servers: vec![Ipv4Addr::new(10, 9, 9, 53)],
+                domain_name: Some("branch.lan".to_string()),
+                default_lease_time: Some(7200),
+            })
+            .await
+            .unwrap();
+        // The second subnet keeps its own domain name
+        let subnets = [
+            (1, None, "branch.lan"),
+            (2, Some("lab.lan".to_string()), "lab.lan"),
+        ];
+
+        for (octet, domain_name, expected_domain) in subnets {
+            let subnet_id = db
+                .create_subnet(&crate::models::Subnet {
+                    network: Ipv4Addr::new(192, 168, octet, 0),
+                    gateway: Ipv4Addr::new(192, 168, octet, 1),
+                    dns_servers: vec![],
+                    domain_name,
+                    template_id: Some(template_id),
+                    ..create_test_subnet()
+                })
+                .await
+                .unwrap();
+            db.create_range(&crate::models::DynamicRange {
+                id: None,
+                subnet_id,
+                range_start: Ipv4Addr::new(192, 168, octet, 100),
+                range_end: Ipv4Addr::new(192, 168, octet, 200),
+                enabled: true,
+                draining: false,
+            })
+            .await
+            .unwrap();
+
+            let iface_ips = [Ipv4Addr::new(192, 168, octet, 1)];
+            let mac = format!("aa:bb:cc:dd:ee:{:02x}", 0x70 + octet);
+            let discover = ReceivedPacket::from_packet(create_discover_packet(&mac));
+            let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+                .await
+                .unwrap();
+            assert!(offer
+                .options
+                .contains(&DhcpOption::DnsServer(vec![Ipv4Addr::new(10, 9, 9, 53)])));
+            assert!(offer
+                .options
+                .contains(&DhcpOption::DomainName(expected_domain.to_string())));
+            assert!(offer.options.contains(&DhcpOption::LeaseTime(7200)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retransmitted_discover_gets_the_same_offer() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        let range_id = create_test_range(db.as_ref(), subnet_id).await;
+        let server = DhcpServer::new(Arc::new(create_test_config()), db.clone());
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        let discover = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:62"));
+        let first = server.respond(&discover, &iface_ips).await.unwrap();
+        assert_eq!(first.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+
+        // The range is disabled meanwhile: allocating again would find
+        // nothing, the retransmission must still see .100
+        db.toggle_range(range_id, false).await.unwrap();
+        let second = server.respond(&discover, &iface_ips).await.unwrap();
+        assert_eq!(second.yiaddr, first.yiaddr);
+        assert_eq!(second.get_message_type(), Some(MessageType::Offer));
+        db.toggle_range(range_id, true).await.unwrap();
+
+        // Another client leases the address: the retransmission is offered
+        // the next free one instead
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&crate::models::Lease {
+            id: None,
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:63".to_string(),
+            ip_address: first.yiaddr,
+            lease_start: now,
+            lease_end: now + 3600,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+        let third = server.respond(&discover, &iface_ips).await.unwrap();
+        assert_eq!(third.yiaddr, Ipv4Addr::new(192, 168, 1, 101));
+
+        // A new transaction is allocated from scratch
+        let mut packet = create_discover_packet("aa:bb:cc:dd:ee:62");
+        packet.xid += 1;
+        let fresh = server
+            .respond(&ReceivedPacket::from_packet(packet), &iface_ips)
+            .await
+            .unwrap();
+        assert_eq!(fresh.yiaddr, Ipv4Addr::new(192, 168, 1, 101));
+    }
+
+    #[tokio::test]
+    async fn test_retransmitted_discover_still_goes_through_filters() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(db.as_ref(), subnet_id).await;
+        let config = create_test_config();
+        let max_hops = config.dhcp.max_hops;
+        let server = DhcpServer::new(Arc::new(config), db.clone());
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        let mut packet = create_discover_packet("aa:bb:cc:dd:ee:64");
+        assert!(server
+            .respond(&ReceivedPacket::from_packet(packet.clone()), &iface_ips)
+            .await
+            .is_some());
+
+        // Same transaction, but now over the hop limit
+        packet.hops = max_hops + 1;
+        assert!(server
+            .respond(&ReceivedPacket::from_packet(packet), &iface_ips)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_discover_reoffers_recently_expired_lease() {
+        let db: DynDatabase = Arc::new(InMemoryDatabase::new());
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        db.create_range(&crate::models::DynamicRange {
+            id: None,
+            subnet_id,
+            range_start: Ipv4Addr::new(192, 168, 1, 100),
+            range_end: Ipv4Addr::new(192, 168, 1, 110),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:66".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 105),
+            lease_start: now - 7200,
+            lease_end: now - 600,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+        let mut config = create_test_config();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let discover = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:66"));
+
+        // Without lease memory the first free address is offered
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, db.as_ref(), None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+
+        config.dhcp.lease_memory_secs = 3600;
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, db.as_ref(), None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 105));
+
+        // Outside the window the lease is forgotten
+        config.dhcp.lease_memory_secs = 300;
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, db.as_ref(), None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+    }
+
+    #[tokio::test]
+    async fn test_discover_creates_pending_offer_listed_by_api() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let db: DynDatabase = Arc::new(InMemoryDatabase::new());
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        db.create_range(&crate::models::DynamicRange {
+            id: None,
+            subnet_id,
+            range_start: Ipv4Addr::new(192, 168, 1, 100),
+            range_end: Ipv4Addr::new(192, 168, 1, 101),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+        let config = create_test_config();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        let discover = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:64"));
+        let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, db.as_ref(), None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+
+        // The offered address is held: another client gets the next one
+        let other = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:65"));
+        let other_offer =
+            DhcpServer::handle_discover(&other, &iface_ips, &config, db.as_ref(), None)
+                .await
+                .unwrap();
+        assert_eq!(other_offer.yiaddr, Ipv4Addr::new(192, 168, 1, 101));
+
+        let response = crate::create_router(db.clone(), Arc::new(Default::default()))
+            .oneshot(Request::builder().uri("/api/offers").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let offers: Vec<crate::models::PendingOffer> = serde_json::from_slice(&body).unwrap();
+        let first = offers
+            .iter()
+            .find(|o| o.ip_address == offer.yiaddr)
+            .expect("pending offer listed");
+        assert_eq!(first.mac_address, "aa:bb:cc:dd:ee:64");
+        assert_eq!(first.subnet_id, subnet_id);
+        assert_eq!(first.xid, discover.packet.xid);
+        assert!(first.expires_at > first.offered_at);
+
+        // The REQUEST commits the lease and releases the pending offer
+        let request = create_request_packet("aa:bb:cc:dd:ee:64", offer.yiaddr);
+        DhcpServer::handle_request(
+            &ReceivedPacket::from_packet(request),
+            &iface_ips,
+            &config,
+            db.as_ref(),
+        )
+        .await
+        .unwrap();
+        let offers = db.list_pending_offers().await.unwrap();
+        assert!(offers.iter().all(|o| o.ip_address != offer.yiaddr));
+    }
+
+    #[tokio::test]
+    async fn test_request_server_identifier_selects_the_server() {
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(&db, subnet_id).await;
+        let config = create_test_config();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+        let cases = [
+            ("aa:bb:cc:dd:ee:66", None, true),
+            ("aa:bb:cc:dd:ee:67", Some(Ipv4Addr::new(192, 168, 1, 1)), true),
+            ("aa:bb:cc:dd:ee:68", Some(Ipv4Addr::new(192, 168, 1, 254)), false),
+        ];
+
+        for (mac, server_id, acked) in cases {
+            let discover = ReceivedPacket::from_packet(create_discover_packet(mac));
+            let offer = DhcpServer::handle_discover(&discover, &iface_ips, &config, &db, None)
+                .await
+                .unwrap();
+
+            let mut request = create_request_packet(mac, offer.yiaddr);
+            if let Some(server_id) = server_id {
+                request.options.push(DhcpOption::ServerIdentifier(server_id));
+            }
+            let reply = DhcpServer::handle_request(
+                &ReceivedPacket::from_packet(request),
+                &iface_ips,
+                &config,
+                &db,
+            )
+            .await;
+
+            assert_eq!(reply.is_some(), acked, "{mac}");
+            assert_eq!(db.get_active_lease(mac).await.unwrap().is_some(), acked, "{mac}");
+            // Either way the offer is no longer held
+            let offers = db.list_pending_offers().await.unwrap();
+            assert!(offers.iter().all(|o| o.mac_address != mac), "{mac}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_special_addresses_are_never_leased() {
+        let config = create_test_config();
+        let db = InMemoryDatabase::new();
+        let subnet_id = db
+            .create_subnet(&crate::models::Subnet {
+                authoritative: true,
+                ..create_test_subnet()
+            })
+            .await
+            .unwrap();
+        // A range carelessly covering the whole /24
+        db.create_range(&crate::models::DynamicRange {
+            id: None,
+            subnet_id,
+            range_start: Ipv4Addr::new(192, 168, 1, 0),
+            range_end: Ipv4Addr::new(192, 168, 1, 255),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        for ip in [
+            Ipv4Addr::new(192, 168, 1, 0),
+            Ipv4Addr::new(192, 168, 1, 255),
+            Ipv4Addr::new(192, 168, 1, 1),
+        ] {
+            let request = create_request_packet("aa:bb:cc:dd:ee:68", ip);
+            let received = ReceivedPacket::from_packet(request);
+            let nak = DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+                .await
+                .unwrap();
+            assert_eq!(nak.get_message_type(), Some(MessageType::Nak), "{ip}");
+        }
+        assert!(db.list_active_leases().await.unwrap().is_empty());
+
+        let received = ReceivedPacket::from_packet(create_discover_packet("aa:bb:cc:dd:ee:68"));
+        let offer = DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_new_reservation_is_offered_through_subnet_cache() {
+        let inner: DynDatabase = Arc::new(InMemoryDatabase::new());
+        let ttl = std::time::Duration::from_secs(60);
+        let db = Arc::new(crate::db::CachedDatabase::new(inner, ttl));
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        create_test_range(db.as_ref(), subnet_id).await;
+        let server = DhcpServer::new(Arc::new(create_test_config()), db.clone());
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        // Warm the subnet cache with a first transaction
+        let mut packet = create_discover_packet("aa:bb:cc:dd:ee:64");
+        let offer = server
+            .respond(&ReceivedPacket::from_packet(packet.clone()), &iface_ips)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 100));
+
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:64".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 20),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+        packet.xid += 1;
+        let offer = server
+            .respond(&ReceivedPacket::from_packet(packet), &iface_ips)
+            .await
+            .unwrap();
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(192, 168, 1, 20));
+    }
+
+    #[tokio::test]
+    async fn test_bootp_request_answered_only_when_enabled() {
+        let db = InMemoryDatabase::new();
+        let subnet_id = db.create_subnet(&create_test_subnet()).await.unwrap();
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:46".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 46),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+        let iface_ips = [Ipv4Addr::new(192, 168, 1, 1)];
+
+        // A BOOTREQUEST without option 53
+        let mut request = create_discover_packet("aa:bb:cc:dd:ee:46");
+        request.options.clear();
+        request.xid = 0x0b007;
+        let received = ReceivedPacket::from_packet(request);
+
+        let mut config = create_test_config();
+        assert!(DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+
+        config.dhcp.enable_bootp = true;
+        let reply = DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .expect("BOOTP reply");
+        assert_eq!(reply.op_code(), Some(OpCode::BootReply));
+        assert_eq!(reply.xid, 0x0b007);
+        assert_eq!(reply.yiaddr, Ipv4Addr::new(192, 168, 1, 46));
+        assert_eq!(reply.siaddr, Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(reply.get_message_type(), None);
+        assert_eq!(subnet_mask_of(&reply), Some(Ipv4Addr::new(255, 255, 255, 0)));
+
+        // Unknown BOOTP clients get nothing: there is no dynamic pool for them
+        let mut stranger = create_discover_packet("aa:bb:cc:dd:ee:47");
+        stranger.options.clear();
+        let received = ReceivedPacket::from_packet(stranger);
+        assert!(DhcpServer::handle_packet(&received, &iface_ips, &config, &db, None, None)
+            .await
+            .is_none());
+    }
+
     #[tokio::test]
     async fn test_handle_release_without_lease() {
         let db = InMemoryDatabase::new();
@@ -1465,7 +4528,8 @@ mod tests {
         let packet = create_release_packet("99:88:77:66:55:44");
 
         // Test handle_release - should not fail even without lease
-        DhcpServer::handle_release(&packet, &db).await;
+        DhcpServer::handle_release(&ReceivedPacket::from_packet(packet), &create_test_config(), &db)
+            .await;
 
         // No assertion needed - just verify it doesn't panic
     }
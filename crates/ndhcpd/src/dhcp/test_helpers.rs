@@ -1,11 +1,13 @@
 #[cfg(test)]
 use super::*;
 #[cfg(test)]
-use crate::config::{ApiConfig, Config, DhcpConfig, LoggingConfig};
+use crate::config::{ApiConfig, Config, DhcpConfig, LoggingConfig, SecurityConfig};
 #[cfg(test)]
-use crate::models::Subnet;
+use crate::db::Database;
 #[cfg(test)]
-use dhcp_proto::MacAddress;
+use crate::models::{DynamicRange, Subnet};
+#[cfg(test)]
+use dhcp_proto::{MacAddress, OpCode};
 #[cfg(test)]
 use std::net::Ipv4Addr;
 
@@ -14,19 +16,26 @@ pub fn create_test_config() -> Config {
     Config {
         listen_interfaces: vec!["lo".to_string()],
         database_path: ":memory:".to_string(),
+        backup_path: None,
         api: ApiConfig {
             listen_address: "127.0.0.1".to_string(),
             port: 8080,
             unix_socket: None,
             require_authentication: Some(false),
+            enable_swagger: true,
+            max_connections: None,
         },
         dhcp: DhcpConfig {
             default_lease_time: 86400,
             max_lease_time: 604800,
             lease_cleanup_interval_minutes: 60,
+            ..DhcpConfig::default()
         },
         ra: None,
         logging: LoggingConfig::default(),
+        security: SecurityConfig::default(),
+        subnets: None,
+        worker_threads: None,
     }
 }
 
@@ -34,19 +43,41 @@ pub fn create_test_config() -> Config {
 pub fn create_test_subnet() -> Subnet {
     Subnet {
         id: Some(1),
-        network: Ipv4Addr::new(192, 168, 1, 0),
-        netmask: 24,
-        gateway: Ipv4Addr::new(192, 168, 1, 1),
         dns_servers: vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(8, 8, 4, 4)],
         domain_name: Some("test.local".to_string()),
+        ..Subnet::from_cidr("192.168.1.0/24").unwrap()
     }
 }
 
+/// Create the enabled 192.168.1.100-200 range of the subnet `subnet_id` of
+/// [`create_test_subnet`], returning its ID
+#[cfg(test)]
+pub async fn create_test_range(db: &dyn Database, subnet_id: i64) -> i64 {
+    db.create_range(&DynamicRange {
+        id: None,
+        subnet_id,
+        range_start: Ipv4Addr::new(192, 168, 1, 100),
+        range_end: Ipv4Addr::new(192, 168, 1, 200),
+        enabled: true,
+        draining: false,
+    })
+    .await
+    .unwrap()
+}
+
+/// Set the BROADCAST bit in `flags`, as a client that cannot receive
+/// unicast before it is configured would.
+#[cfg(test)]
+pub fn with_broadcast_flag(mut packet: DhcpPacket) -> DhcpPacket {
+    packet.flags |= super::server::BROADCAST_FLAG;
+    packet
+}
+
 /// Create a DHCP DISCOVER packet
 #[cfg(test)]
 pub fn create_discover_packet(mac: &str) -> DhcpPacket {
     let mut packet = DhcpPacket::new();
-    packet.op = 1; // BOOTREQUEST
+    packet.set_op_code(OpCode::BootRequest);
     packet.xid = 12345;
     packet.chaddr = MacAddress::from_string(mac).unwrap();
     packet
@@ -59,7 +90,7 @@ pub fn create_discover_packet(mac: &str) -> DhcpPacket {
 #[cfg(test)]
 pub fn create_request_packet(mac: &str, requested_ip: Ipv4Addr) -> DhcpPacket {
     let mut packet = DhcpPacket::new();
-    packet.op = 1; // BOOTREQUEST
+    packet.set_op_code(OpCode::BootRequest);
     packet.xid = 67890;
     packet.chaddr = MacAddress::from_string(mac).unwrap();
     packet
@@ -75,7 +106,7 @@ pub fn create_request_packet(mac: &str, requested_ip: Ipv4Addr) -> DhcpPacket {
 #[cfg(test)]
 pub fn create_release_packet(mac: &str) -> DhcpPacket {
     let mut packet = DhcpPacket::new();
-    packet.op = 1; // BOOTREQUEST
+    packet.set_op_code(OpCode::BootRequest);
     packet.xid = 11111;
     packet.chaddr = MacAddress::from_string(mac).unwrap();
     packet
@@ -88,7 +119,7 @@ pub fn create_release_packet(mac: &str) -> DhcpPacket {
 #[cfg(test)]
 pub fn create_inform_packet(mac: &str) -> DhcpPacket {
     let mut packet = DhcpPacket::new();
-    packet.op = 1; // BOOTREQUEST
+    packet.set_op_code(OpCode::BootRequest);
     packet.xid = 99999;
     packet.chaddr = MacAddress::from_string(mac).unwrap();
     packet
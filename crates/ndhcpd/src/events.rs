@@ -0,0 +1,95 @@
+//! Recent lease lifecycle events, kept in a bounded in-memory buffer.
+//!
+//! Like [`crate::metrics`], the buffer is a process-wide `static` so the DHCP
+//! packet handlers can record events without a handle threaded through them.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+/// Number of events retained; older ones are dropped first
+pub const EVENT_BUFFER_CAPACITY: usize = 1024;
+
+/// What happened to a lease
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaseEventKind {
+    /// A client was granted an address it did not hold
+    Assigned,
+    /// A client extended the lease it already held
+    Renewed,
+    /// A lease reached its rebinding time (T2) without being renewed
+    Rebinding,
+}
+
+/// A single lease lifecycle event
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LeaseEvent {
+    pub kind: LeaseEventKind,
+    pub mac_address: String,
+    #[schema(value_type = String)]
+    pub ip_address: Ipv4Addr,
+    pub subnet_id: i64,
+    /// When the triggering packet was received, or the lease was seen entering
+    /// its rebinding window (Unix timestamp in milliseconds)
+    pub timestamp: i64,
+}
+
+/// Bounded FIFO of recent events
+pub struct EventBuffer {
+    events: Mutex<VecDeque<LeaseEvent>>,
+    capacity: usize,
+}
+
+impl EventBuffer {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(VecDeque::new()),
+            capacity,
+        }
+    }
+
+    /// Append an event, evicting the oldest one when full.
+    pub fn record(&self, event: LeaseEvent) {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// All retained events, oldest first.
+    pub fn recent(&self) -> Vec<LeaseEvent> {
+        let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        events.iter().cloned().collect()
+    }
+}
+
+pub static EVENTS: EventBuffer = EventBuffer::new(EVENT_BUFFER_CAPACITY);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(last_octet: u8) -> LeaseEvent {
+        LeaseEvent {
+            kind: LeaseEventKind::Assigned,
+            mac_address: "aa:bb:cc:dd:ee:ff".to_string(),
+            ip_address: Ipv4Addr::new(10, 0, 0, last_octet),
+            subnet_id: 1,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_buffer_evicts_oldest() {
+        let buffer = EventBuffer::new(2);
+        buffer.record(event(1));
+        buffer.record(event(2));
+        buffer.record(event(3));
+        let ips: Vec<Ipv4Addr> = buffer.recent().iter().map(|e| e.ip_address).collect();
+        assert_eq!(ips, vec![Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 3)]);
+    }
+}
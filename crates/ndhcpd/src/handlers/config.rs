@@ -0,0 +1,61 @@
+use crate::{
+    config::Config,
+    db::seed::{self, SubnetsDiff},
+    AppState,
+};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use tracing::error;
+
+/// Compare the subnets declared in the configuration file with the stored ones
+///
+/// The file is read again, so edits made since the server started show up.
+/// Shows what reconciliation would create, update and (in `replace` mode)
+/// delete on the next start, and the problems that would make it refuse the
+/// file, without changing anything.
+#[utoipa::path(
+    get,
+    path = "/api/config/diff",
+    tag = "config",
+    responses(
+        (status = 200, description = "Declared and stored subnets compared", body = SubnetsDiff),
+        (status = 404, description = "No subnets are declared in the configuration file"),
+        (status = 422, description = "The configuration file cannot be read or parsed"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "The server was not started from a configuration file")
+    )
+)]
+pub async fn diff_config(State(state): State<AppState>) -> Result<Json<SubnetsDiff>, Response> {
+    let Some(path) = state.config_path.clone() else {
+        return Err(StatusCode::SERVICE_UNAVAILABLE.into_response());
+    };
+    let read = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || Config::from_file(&path.to_string_lossy())).await
+    };
+    let config = match read {
+        Ok(Ok(config)) => config,
+        Ok(Err(e)) => {
+            let message = format!("Failed to load {}: {}", path.display(), e);
+            return Err((StatusCode::UNPROCESSABLE_ENTITY, message).into_response());
+        }
+        Err(e) => {
+            error!("Failed to read {}: {}", path.display(), e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+    let Some(subnets) = &config.subnets else {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    };
+    seed::diff_subnets(subnets, state.db.as_ref())
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to diff configured subnets: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })
+}
@@ -0,0 +1,15 @@
+use crate::events::{LeaseEvent, EVENTS};
+use axum::Json;
+
+/// List recent lease events, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    tag = "events",
+    responses(
+        (status = 200, description = "Recent lease events", body = Vec<LeaseEvent>)
+    )
+)]
+pub async fn list_events() -> Json<Vec<LeaseEvent>> {
+    Json(EVENTS.recent())
+}
@@ -0,0 +1,22 @@
+use axum::{
+    http::{StatusCode, Uri},
+    Json,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct NotFoundResponse {
+    error: &'static str,
+    path: String,
+}
+
+/// Answer requests that match no route with a JSON body instead of an empty 404
+pub async fn not_found(uri: Uri) -> (StatusCode, Json<NotFoundResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(NotFoundResponse {
+            error: "not found",
+            path: uri.path().to_string(),
+        }),
+    )
+}
@@ -1,6 +1,19 @@
-use crate::{models::Lease, AppState};
-use axum::{extract::State, http::StatusCode, Json};
-use tracing::error;
+use crate::{
+    models::{Lease, LeaseImport},
+    utils::hostname::sanitize_hostname,
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use dhcp_proto::MacAddress;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use tracing::{error, info};
+use utoipa::ToSchema;
 
 /// List all active leases
 #[utoipa::path(
@@ -18,3 +31,132 @@ pub async fn list_leases(State(state): State<AppState>) -> Result<Json<Vec<Lease
         StatusCode::INTERNAL_SERVER_ERROR
     })
 }
+
+/// Get the active lease holding an IP address
+#[utoipa::path(
+    get,
+    path = "/api/leases/by-ip/{ip}",
+    tag = "leases",
+    params(
+        ("ip" = String, Path, description = "Leased IPv4 address")
+    ),
+    responses(
+        (status = 200, description = "Active lease found", body = Lease),
+        (status = 400, description = "Invalid IP address"),
+        (status = 404, description = "Address is not leased"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_lease_by_ip(
+    State(state): State<AppState>,
+    Path(ip): Path<Ipv4Addr>,
+) -> Result<Json<Lease>, StatusCode> {
+    match state.db.get_active_lease_by_ip(ip).await {
+        Ok(Some(lease)) => Ok(Json(lease)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to look up lease for ip={}: {}", ip, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Outcome of a bulk lease import
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportLeasesResponse {
+    /// Leases written to the database
+    pub imported: usize,
+    /// Leases left out: already expired, outside every subnet, for a
+    /// statically assigned address, or for an address or client that
+    /// already holds an active lease
+    pub skipped: usize,
+}
+
+/// Import leases from another DHCP server
+///
+/// Each lease is attached to the most specific subnet containing its address.
+/// Existing active leases and static IPs are never overwritten, and a client
+/// that already holds a lease is not given a second. Hostnames are cleaned up like those
+/// sent in option 12. The whole batch is refused if any MAC address is
+/// malformed.
+#[utoipa::path(
+    post,
+    path = "/api/leases/import",
+    tag = "leases",
+    request_body = Vec<LeaseImport>,
+    responses(
+        (status = 200, description = "Leases imported", body = ImportLeasesResponse),
+        (status = 400, description = "Malformed MAC address"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn import_leases(
+    State(state): State<AppState>,
+    Json(mut leases): Json<Vec<LeaseImport>>,
+) -> Result<Json<ImportLeasesResponse>, StatusCode> {
+    for lease in &mut leases {
+        lease.mac_address = MacAddress::from_string(&lease.mac_address)
+            .ok_or(StatusCode::BAD_REQUEST)?
+            .to_string();
+        lease.hostname = lease.hostname.as_deref().and_then(sanitize_hostname);
+    }
+
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to import leases: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    let subnets = state.db.list_subnets().await.map_err(internal_error)?;
+    let static_ips: HashSet<Ipv4Addr> = state
+        .db
+        .list_static_ips(None)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|s| s.ip_address)
+        .collect();
+    let now = chrono::Utc::now().timestamp();
+    let mut imported = 0;
+
+    for lease in &leases {
+        if lease.lease_end <= now || static_ips.contains(&lease.ip_address) {
+            continue;
+        }
+        let Some(subnet_id) = subnets
+            .iter()
+            .filter(|s| s.contains(lease.ip_address))
+            .max_by_key(|s| s.netmask)
+            .and_then(|s| s.id)
+        else {
+            continue;
+        };
+        let client_lease = state
+            .db
+            .get_active_lease(&lease.mac_address)
+            .await
+            .map_err(internal_error)?;
+        if client_lease.is_some() {
+            continue;
+        }
+        let created = state
+            .db
+            .create_lease_if_free(&Lease {
+                id: None,
+                subnet_id,
+                mac_address: lease.mac_address.clone(),
+                ip_address: lease.ip_address,
+                lease_start: lease.lease_start,
+                lease_end: lease.lease_end,
+                hostname: lease.hostname.clone(),
+                range_id: None,
+            })
+            .await
+            .map_err(internal_error)?;
+        if created.is_some() {
+            imported += 1;
+        }
+    }
+
+    let skipped = leases.len() - imported;
+    info!("Imported {} lease(s), skipped {}", imported, skipped);
+    Ok(Json(ImportLeasesResponse { imported, skipped }))
+}
@@ -0,0 +1,96 @@
+use crate::AppState;
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+/// Result of a manual lease reaper run
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReapLeasesResponse {
+    /// Number of expired leases removed
+    pub expired: u64,
+}
+
+/// Purge expired leases now instead of waiting for the cleanup interval
+///
+/// Only leases whose end time is already in the past are removed.
+#[utoipa::path(
+    post,
+    path = "/api/maintenance/reap-leases",
+    tag = "maintenance",
+    responses(
+        (status = 200, description = "Expired leases purged", body = ReapLeasesResponse),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn reap_leases(
+    State(state): State<AppState>,
+) -> Result<Json<ReapLeasesResponse>, StatusCode> {
+    let expired = state.db.delete_expired_leases().await.map_err(|e| {
+        error!("Failed to reap expired leases: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    info!("Manual lease cleanup: deleted {} expired lease(s)", expired);
+    Ok(Json(ReapLeasesResponse { expired }))
+}
+
+/// Drop the in-memory subnet cache so the next lookup reads the database
+///
+/// Writes made through the API already invalidate it; this is for changes
+/// made to the database file directly, which would otherwise only show up
+/// once `subnet_cache_ttl_secs` elapses. Static reservations and leases are
+/// never cached.
+#[utoipa::path(
+    post,
+    path = "/api/maintenance/refresh-cache",
+    tag = "maintenance",
+    responses(
+        (status = 204, description = "Cache dropped")
+    )
+)]
+pub async fn refresh_cache(State(state): State<AppState>) -> StatusCode {
+    state.db.refresh_cache();
+    info!("Database cache refreshed on request");
+    StatusCode::NO_CONTENT
+}
+
+/// Location and size of a database snapshot
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BackupResponse {
+    /// File the snapshot was written to
+    pub path: String,
+    /// Snapshot size in bytes
+    pub size_bytes: u64,
+}
+
+/// Snapshot the database to the configured `backup_path` while it keeps serving
+///
+/// The copy is transactionally consistent even under concurrent writes and
+/// replaces any previous backup at the same path.
+#[utoipa::path(
+    post,
+    path = "/api/maintenance/backup",
+    tag = "maintenance",
+    responses(
+        (status = 200, description = "Backup written", body = BackupResponse),
+        (status = 503, description = "No backup_path configured"),
+        (status = 500, description = "Backup failed")
+    )
+)]
+pub async fn backup_database(
+    State(state): State<AppState>,
+) -> Result<Json<BackupResponse>, StatusCode> {
+    let path = state
+        .backup_path
+        .as_deref()
+        .ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let size_bytes = state.db.backup_to(path).await.map_err(|e| {
+        error!("Failed to back up database to {}: {:#}", path.display(), e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    info!("Database backed up to {} ({} bytes)", path.display(), size_bytes);
+    Ok(Json(BackupResponse {
+        path: path.display().to_string(),
+        size_bytes,
+    }))
+}
@@ -0,0 +1,46 @@
+use crate::{dhcp::allocator, AppState};
+use axum::{extract::State, http::header, response::IntoResponse};
+use tracing::error;
+
+/// Prometheus scrape endpoint
+///
+/// Besides the process-wide counters, reports the size and free addresses of
+/// each subnet's dynamic pool, computed on scrape.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (status = 200, description = "Metrics in Prometheus text format", body = String)
+    ),
+    tag = "metrics"
+)]
+pub async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let mut body = crate::metrics::render();
+    match allocator::subnet_utilization(&*state.db).await {
+        Ok(subnets) => {
+            let samples = |f: fn(&allocator::Utilization) -> u64| -> Vec<(String, u64)> {
+                subnets
+                    .iter()
+                    .map(|(s, u)| (format!("{}/{}", s.network, s.netmask), f(u)))
+                    .collect()
+            };
+            crate::metrics::render_gauge(
+                &mut body,
+                "ndhcpd_dhcp_subnet_free_addresses",
+                "Addresses of the subnet's enabled ranges not leased or statically assigned",
+                "subnet",
+                &samples(|u| u.free),
+            );
+            crate::metrics::render_gauge(
+                &mut body,
+                "ndhcpd_dhcp_subnet_total_addresses",
+                "Addresses of the subnet's enabled ranges that may be handed out",
+                "subnet",
+                &samples(|u| u.total),
+            );
+        }
+        // The counters are still worth scraping without the database
+        Err(e) => error!("Failed to compute subnet utilization: {}", e),
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
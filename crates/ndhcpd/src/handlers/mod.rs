@@ -1,6 +1,13 @@
+pub mod config;
+pub mod events;
+pub mod fallback;
 pub mod health;
 pub mod ia_prefixes;
 pub mod leases;
+pub mod maintenance;
+pub mod metrics;
+pub mod offers;
+pub mod option_templates;
 pub mod ranges;
 pub mod static_ips;
 pub mod subnets;
@@ -12,10 +19,11 @@ use axum::{
     Router,
 };
 use std::sync::Arc;
-use tower_http::trace::TraceLayer;
+use tower::Layer;
+use tower_http::{normalize_path::NormalizePathLayer, trace::TraceLayer};
 
 use crate::{
-    auth,
+    auth, request_id,
     db::DynDatabase,
     config::RaConfig,
     AppState,
@@ -30,22 +38,48 @@ pub fn create_router_with_auth(
     ra_config: Arc<RaConfig>,
     require_auth: bool,
 ) -> Router {
-    let state = AppState::new(db.clone(), ra_config);
+    create_router_with_state(AppState::new(db, ra_config), require_auth)
+}
+
+pub fn create_router_with_state(state: AppState, require_auth: bool) -> Router {
+    let db = state.db.clone();
 
     let protected_routes = Router::new()
         // Subnet routes
         .route("/api/subnets", get(subnets::list_subnets))
         .route("/api/subnets", post(subnets::create_subnet))
+        .route("/api/subnets/full", post(subnets::create_subnet_full))
         .route("/api/subnets/{id}", get(subnets::get_subnet))
         .route("/api/subnets/{id}", put(subnets::update_subnet))
+        .route("/api/subnets/{id}", patch(subnets::patch_subnet))
         .route("/api/subnets/{id}", delete(subnets::delete_subnet))
+        .route(
+            "/api/subnets/{id}/allocate",
+            post(subnets::allocate_address),
+        )
+        .route(
+            "/api/subnets/{id}/addresses",
+            get(subnets::list_subnet_addresses),
+        )
+        .route("/api/subnets/{id}/clone", post(subnets::clone_subnet))
+        .route(
+            "/api/subnets/{id}/options",
+            get(subnets::get_subnet_options),
+        )
+        .route(
+            "/api/subnets/{id}/renumber",
+            post(subnets::renumber_subnet),
+        )
         // Dynamic range routes
         .route("/api/ranges", get(ranges::list_ranges))
         .route("/api/ranges", post(ranges::create_range))
         .route("/api/ranges/{id}", delete(ranges::delete_range))
+        .route("/api/ranges/{id}/toggle", patch(ranges::toggle_range))
+        .route("/api/ranges/{id}/draining", patch(ranges::set_range_draining))
         // Static IP routes
         .route("/api/static-ips", get(static_ips::list_static_ips))
         .route("/api/static-ips", post(static_ips::create_static_ip))
+        .route("/api/static-ips", delete(static_ips::delete_static_ips))
         .route("/api/static-ips/{ip}", delete(static_ips::delete_static_ip))
         .route(
             "/api/static-ips/{ip}/hostname",
@@ -53,11 +87,48 @@ pub fn create_router_with_auth(
         )
         // Lease routes
         .route("/api/leases", get(leases::list_leases))
+        .route("/api/leases/by-ip/{ip}", get(leases::get_lease_by_ip))
+        .route("/api/leases/import", post(leases::import_leases))
+        .route("/api/events", get(events::list_events))
+        .route("/api/offers", get(offers::list_offers))
+        .route("/api/config/diff", get(config::diff_config))
+        // Option template routes
+        .route(
+            "/api/option-templates",
+            get(option_templates::list_option_templates),
+        )
+        .route(
+            "/api/option-templates",
+            post(option_templates::create_option_template),
+        )
+        .route(
+            "/api/option-templates/{id}",
+            get(option_templates::get_option_template),
+        )
+        .route(
+            "/api/option-templates/{id}",
+            put(option_templates::update_option_template),
+        )
+        .route(
+            "/api/option-templates/{id}",
+            delete(option_templates::delete_option_template),
+        )
+        // Maintenance routes
+        .route(
+            "/api/maintenance/reap-leases",
+            post(maintenance::reap_leases),
+        )
+        .route("/api/maintenance/backup", post(maintenance::backup_database))
+        .route(
+            "/api/maintenance/refresh-cache",
+            post(maintenance::refresh_cache),
+        )
         // Token management routes
         .route("/api/tokens", get(tokens::list_tokens))
         .route("/api/tokens", post(tokens::create_token))
         .route("/api/tokens/{id}", delete(tokens::delete_token))
         .route("/api/tokens/{id}/toggle", patch(tokens::toggle_token))
+        .route("/api/auth/whoami", get(tokens::whoami))
         // IA Prefix routes (IPv6 for Router Advertisement)
         .route("/api/ia-prefixes", get(ia_prefixes::list_ia_prefixes))
         .route("/api/ia-prefixes", post(ia_prefixes::create_ia_prefix))
@@ -66,7 +137,9 @@ pub fn create_router_with_auth(
         .route(
             "/api/ia-prefixes/{id}",
             delete(ia_prefixes::delete_ia_prefix),
-        );
+        )
+        // Prometheus metrics
+        .route("/metrics", get(metrics::get_metrics));
 
     // Apply authentication middleware only if required
     let protected_routes = if require_auth {
@@ -78,12 +151,19 @@ pub fn create_router_with_auth(
         protected_routes
     };
 
-    Router::new()
+    let app = Router::new()
         .merge(protected_routes)
         // Health check - always public
         .route("/health", get(health::health_check))
+        .fallback(fallback::not_found)
         .layer(TraceLayer::new_for_http())
-        .with_state(state)
+        .layer(middleware::from_fn(request_id::request_id_middleware))
+        .with_state(state);
+
+    // Routing happens before any `Router::layer`, so the path has to be
+    // normalized by a service wrapping the whole router: `/api/subnets/`
+    // then matches `/api/subnets`
+    Router::new().fallback_service(NormalizePathLayer::trim_trailing_slash().layer(app))
 }
 
 #[cfg(test)]
@@ -143,11 +223,16 @@ mod tests {
     async fn test_routes_reachable_without_auth() {
         let routes = [
             (Method::GET, "/api/subnets"),
+            (Method::GET, "/api/subnets?ids=1,2"),
             (Method::GET, "/api/ranges"),
             (Method::GET, "/api/static-ips"),
             (Method::GET, "/api/leases"),
+            (Method::GET, "/api/events"),
+            (Method::GET, "/api/offers"),
+            (Method::GET, "/api/option-templates"),
             (Method::GET, "/api/tokens"),
             (Method::GET, "/api/ia-prefixes"),
+            (Method::GET, "/metrics"),
         ];
         for (method, path) in routes {
             let router = create_router_with_auth(make_db(), make_ra_config(), false);
@@ -169,8 +254,29 @@ mod tests {
             (Method::GET, "/api/ranges"),
             (Method::GET, "/api/static-ips"),
             (Method::GET, "/api/leases"),
+            (Method::GET, "/api/events"),
+            (Method::GET, "/api/offers"),
+            (Method::GET, "/api/option-templates"),
             (Method::GET, "/api/tokens"),
             (Method::GET, "/api/ia-prefixes"),
+            (Method::GET, "/metrics"),
+            (Method::POST, "/api/maintenance/reap-leases"),
+            (Method::POST, "/api/maintenance/backup"),
+            (Method::POST, "/api/maintenance/refresh-cache"),
+            (Method::POST, "/api/subnets/1/allocate"),
+            (Method::PATCH, "/api/ranges/1/toggle"),
+            (Method::PATCH, "/api/ranges/1/draining"),
+            (Method::GET, "/api/leases/by-ip/192.168.1.50"),
+            (Method::GET, "/api/subnets/1/addresses"),
+            (Method::GET, "/api/subnets/1/options"),
+            (Method::POST, "/api/leases/import"),
+            (Method::POST, "/api/subnets/1/clone"),
+            (Method::POST, "/api/subnets/1/renumber"),
+            (Method::POST, "/api/subnets/full"),
+            (Method::GET, "/api/config/diff"),
+            (Method::GET, "/api/auth/whoami"),
+            (Method::PATCH, "/api/subnets/1"),
+            (Method::DELETE, "/api/static-ips?subnet_id=1"),
         ];
         for (method, path) in routes {
             let router = create_router_with_auth(make_db(), make_ra_config(), true);
@@ -183,4 +289,1426 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_reap_leases_only_removes_expired() {
+        use crate::models::{Lease, Subnet};
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let subnet_id = db
+            .create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap())
+            .await
+            .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        for (mac, last_octet, lease_end) in [
+            ("aa:bb:cc:dd:ee:01", 10, now - 60),
+            ("aa:bb:cc:dd:ee:02", 11, now + 3600),
+        ] {
+            db.create_lease(&Lease {
+                id: None,
+                subnet_id,
+                mac_address: mac.to_string(),
+                ip_address: Ipv4Addr::new(192, 168, 1, last_octet),
+                lease_start: now - 7200,
+                lease_end,
+                hostname: None,
+                range_id: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        let response = create_router(db.clone(), make_ra_config())
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/maintenance/reap-leases")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["expired"], 1);
+
+        let remaining = db.list_active_leases().await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].mac_address, "aa:bb:cc:dd:ee:02");
+    }
+
+    #[tokio::test]
+    async fn test_delete_static_ips_by_subnet() {
+        use crate::models::{StaticIP, Subnet};
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let mut subnet_ids = Vec::new();
+        for third_octet in [1, 2] {
+            subnet_ids.push(
+                db.create_subnet(&Subnet::from_cidr(&format!("192.168.{third_octet}.0/24")).unwrap())
+                    .await
+                    .unwrap(),
+            );
+        }
+        for (subnet_id, mac, ip) in [
+            (subnet_ids[0], "aa:bb:cc:dd:ee:01", Ipv4Addr::new(192, 168, 1, 10)),
+            (subnet_ids[0], "aa:bb:cc:dd:ee:02", Ipv4Addr::new(192, 168, 1, 11)),
+            (subnet_ids[1], "aa:bb:cc:dd:ee:03", Ipv4Addr::new(192, 168, 2, 10)),
+        ] {
+            db.create_static_ip(&StaticIP {
+                subnet_id,
+                mac_address: mac.to_string(),
+                ip_address: ip,
+                hostname: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        // Without a subnet nothing is deleted
+        let router = create_router(db.clone(), make_ra_config());
+        let status = send(router, Method::DELETE, "/api/static-ips").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(db.list_static_ips(None).await.unwrap().len(), 3);
+
+        let response = create_router(db.clone(), make_ra_config())
+            .oneshot(
+                Request::builder()
+                    .method(Method::DELETE)
+                    .uri(format!("/api/static-ips?subnet_id={}", subnet_ids[0]))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["deleted"], 2);
+
+        let remaining = db.list_static_ips(None).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].subnet_id, subnet_ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_create_static_ip_with_static_arp_outside_interface_subnets() {
+        use crate::models::Subnet;
+
+        let db = make_db();
+        let subnet_id = db
+            .create_subnet(&Subnet::from_cidr("192.168.37.0/24").unwrap())
+            .await
+            .unwrap();
+        // Loopback holds no address in the subnet, so nothing is pinned
+        let neighbors = Arc::new(RecordedNeighbors::default());
+        let state = AppState::new(db.clone(), make_ra_config())
+            .with_static_arp_interfaces(vec!["lo".to_string()])
+            .with_neighbor_table(neighbors.clone());
+        let body = serde_json::json!({
+            "subnet_id": subnet_id,
+            "mac_address": "aa:bb:cc:dd:ee:37",
+            "ip_address": "192.168.37.10",
+            "hostname": null
+        });
+        let response = create_router_with_state(state, false)
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/static-ips")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(db.list_static_ips(Some(subnet_id)).await.unwrap().len(), 1);
+        assert!(neighbors.take().is_empty());
+    }
+
+    /// Neighbour table recording the entries written to it
+    #[derive(Default)]
+    struct RecordedNeighbors(std::sync::Mutex<Vec<String>>);
+
+    impl RecordedNeighbors {
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::utils::network::NeighborTable for RecordedNeighbors {
+        async fn pin(&self, iface: &str, ip: std::net::Ipv4Addr, mac: &str) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(format!("pin {} {} {}", iface, ip, mac));
+            Ok(())
+        }
+
+        async fn unpin(&self, iface: &str, ip: std::net::Ipv4Addr) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(format!("unpin {} {}", iface, ip));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_static_arp_follows_static_ips() {
+        use crate::models::Subnet;
+
+        let db = make_db();
+        // Loopback holds 127.0.0.1, so the subnet is served on it
+        let subnet_id = db
+            .create_subnet(&Subnet::from_cidr("127.0.0.0/24").unwrap())
+            .await
+            .unwrap();
+        let neighbors = Arc::new(RecordedNeighbors::default());
+        let state = AppState::new(db.clone(), make_ra_config())
+            .with_static_arp_interfaces(vec!["lo".to_string()])
+            .with_neighbor_table(neighbors.clone());
+        let router = create_router_with_state(state, false);
+        let call = |method: Method, uri: String, body: Option<serde_json::Value>| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(body.map_or(Body::empty(), |b| Body::from(b.to_string())))
+                .unwrap();
+            let router = router.clone();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+        let create = |host: u8| {
+            let body = serde_json::json!({
+                "subnet_id": subnet_id,
+                "mac_address": format!("aa:bb:cc:19:77:{:02x}", host),
+                "ip_address": format!("127.0.0.{}", host),
+                "hostname": null
+            });
+            call(Method::POST, "/api/static-ips".into(), Some(body))
+        };
+
+        assert_eq!(create(10).await, StatusCode::CREATED);
+        assert_eq!(neighbors.take(), ["pin lo 127.0.0.10 aa:bb:cc:19:77:0a"]);
+        let status = call(Method::DELETE, "/api/static-ips/127.0.0.10".into(), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(neighbors.take(), ["unpin lo 127.0.0.10"]);
+
+        // Bulk deletion unpins every static IP of the subnet
+        assert_eq!(create(11).await, StatusCode::CREATED);
+        neighbors.take();
+        let uri = format!("/api/static-ips?subnet_id={}", subnet_id);
+        assert_eq!(call(Method::DELETE, uri, None).await, StatusCode::OK);
+        assert_eq!(neighbors.take(), ["unpin lo 127.0.0.11"]);
+
+        // Renumbering moves the entries along with the static IPs
+        assert_eq!(create(12).await, StatusCode::CREATED);
+        neighbors.take();
+        let body = serde_json::json!({"network": "127.0.0.0", "netmask": 16});
+        let uri = format!("/api/subnets/{}/renumber", subnet_id);
+        assert_eq!(call(Method::POST, uri, Some(body)).await, StatusCode::OK);
+        assert_eq!(
+            neighbors.take(),
+            ["unpin lo 127.0.0.12", "pin lo 127.0.0.12 aa:bb:cc:19:77:0c"]
+        );
+
+        // A reservation keyed by a Client Identifier has no MAC to pin
+        let body = serde_json::json!({
+            "subnet_id": subnet_id,
+            "mac_address": "id:00:6c:61:70:74:6f:70",
+            "ip_address": "127.0.0.13",
+            "hostname": null
+        });
+        let status = call(Method::POST, "/api/static-ips".into(), Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(neighbors.take().is_empty());
+
+        // Deleting the subnet drops its static IPs from the table
+        let uri = format!("/api/subnets/{}", subnet_id);
+        assert_eq!(call(Method::DELETE, uri, None).await, StatusCode::NO_CONTENT);
+        assert_eq!(neighbors.take(), ["unpin lo 127.0.0.12"]);
+
+        // Static IPs created along with their subnet are pinned too
+        let body = serde_json::json!({
+            "subnet": {
+                "network": "127.0.0.0",
+                "netmask": 24,
+                "gateway": "127.0.0.1",
+                "dns_servers": []
+            },
+            "static_ips": [{"mac_address": "aa:bb:cc:19:77:0e", "ip_address": "127.0.0.14"}]
+        });
+        let status = call(Method::POST, "/api/subnets/full".into(), Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(neighbors.take(), ["pin lo 127.0.0.14 aa:bb:cc:19:77:0e"]);
+    }
+
+    #[tokio::test]
+    async fn test_backup_unavailable_without_path() {
+        let router = create_router(make_db(), make_ra_config());
+        let status = send(router, Method::POST, "/api/maintenance/backup").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_cache() {
+        let router = create_router(make_db(), make_ra_config());
+        let status = send(router, Method::POST, "/api/maintenance/refresh-cache").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_create_subnet_reports_all_validation_errors() {
+        let body = serde_json::json!({
+            "network": "192.168.1.0",
+            "netmask": 0,
+            "gateway": "10.0.0.1",
+            "dns_servers": [],
+            "domain_name": ""
+        });
+        let response = create_router(make_db(), make_ra_config())
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/subnets")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let fields: Vec<&str> = json["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, vec!["netmask", "network", "domain_name"]);
+    }
+
+    #[tokio::test]
+    async fn test_create_subnet_rejects_invalid_domain_name() {
+        let body = serde_json::json!({
+            "network": "192.168.1.0",
+            "netmask": 24,
+            "gateway": "192.168.1.1",
+            "dns_servers": [],
+            "domain_name": "my home.lan"
+        });
+        let response = create_router(make_db(), make_ra_config())
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/subnets")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["errors"][0]["field"], "domain_name");
+    }
+
+    #[tokio::test]
+    async fn test_config_diff_lists_differing_subnet() {
+        use crate::config::{Config, ReconcileMode, SubnetsConfig};
+        use crate::db::seed::SubnetsDiff;
+        use crate::models::Subnet;
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let id = db
+            .create_subnet(&Subnet::from_cidr("192.168.41.0/24").unwrap())
+            .await
+            .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let get_diff = |path: Option<std::path::PathBuf>| {
+            let state = AppState::new(db.clone(), make_ra_config()).with_config_path(path);
+            create_router_with_state(state, false).oneshot(
+                Request::builder()
+                    .uri("/api/config/diff")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+        let diff_of = |config: Config| {
+            config.to_file(path.to_str().unwrap()).unwrap();
+            get_diff(Some(path.clone()))
+        };
+
+        // Not started from a file: nothing to read
+        let response = get_diff(None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // Seeding disabled: nothing to compare with
+        let response = diff_of(Config::default()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        std::fs::write(&path, "subnets: [").unwrap();
+        let response = get_diff(Some(path.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let declared = Subnet {
+            dns_servers: vec![Ipv4Addr::new(192, 168, 41, 53)],
+            ..Subnet::from_cidr("192.168.41.0/24").unwrap()
+        };
+        let config = |networks: Vec<Subnet>| Config {
+            subnets: Some(SubnetsConfig {
+                reconcile: ReconcileMode::Replace,
+                networks,
+            }),
+            ..Config::default()
+        };
+        // The file is read on each call, so the edit shows up
+        let response = diff_of(config(vec![declared.clone()])).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let diff: SubnetsDiff = serde_json::from_slice(&body).unwrap();
+        assert!(diff.problems.is_empty());
+        assert!(diff.only_in_config.is_empty());
+        assert!(diff.only_in_database.is_empty());
+        assert_eq!(diff.differing.len(), 1);
+        assert_eq!(diff.differing[0].stored.id, Some(id));
+        assert_eq!(diff.differing[0].fields, ["dns_servers"]);
+
+        // A network declared twice would be refused on the next start
+        let response = diff_of(config(vec![declared.clone(), declared])).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let diff: SubnetsDiff = serde_json::from_slice(&body).unwrap();
+        assert_eq!(diff.problems, ["192.168.41.0/24: network declared more than once"]);
+        assert!(db.get_subnet(id).await.unwrap().unwrap().dns_servers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_subnet_options_are_resolved_with_template() {
+        use crate::models::{OptionTemplate, Subnet};
+        use subnets::ResolvedOption;
+
+        let db = make_db();
+        let template_id = db
+            .create_option_template(&OptionTemplate {
+                id: None,
+                name: "office".to_string(),
+                dns_servers: vec![],
+                domain_name: Some("office.lan".to_string()),
+                default_lease_time: Some(7200),
+            })
+            .await
+            .unwrap();
+        let subnet = Subnet {
+            dns_servers: vec!["192.168.1.53".parse().unwrap()],
+            template_id: Some(template_id),
+            ..Subnet::from_cidr("192.168.1.0/24").unwrap()
+        };
+        let subnet_id = db.create_subnet(&subnet).await.unwrap();
+
+        let get = |id: i64| {
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .uri(format!("/api/subnets/{}/options", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+        assert_eq!(get(subnet_id + 1).await.unwrap().status(), StatusCode::NOT_FOUND);
+
+        let response = get(subnet_id).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let options: Vec<ResolvedOption> = serde_json::from_slice(&body).unwrap();
+        let value_of = |code: u8| {
+            options
+                .iter()
+                .find(|o| o.code == code)
+                .map(|o| (o.name.as_str(), o.value.as_str()))
+        };
+        assert_eq!(value_of(1), Some(("subnet_mask", "255.255.255.0")));
+        assert_eq!(value_of(3), Some(("router", "192.168.1.1")));
+        assert_eq!(value_of(6), Some(("dns_server", "192.168.1.53")));
+        assert_eq!(value_of(15), Some(("domain_name", "\"office.lan\"")));
+        assert_eq!(value_of(51), Some(("lease_time", "7200s")));
+        assert_eq!(value_of(53), None);
+    }
+
+    #[tokio::test]
+    async fn test_create_subnet_full_is_all_or_nothing() {
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let create = |body: serde_json::Value| {
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/subnets/full")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let subnet = serde_json::json!({
+            "network": "192.168.7.0",
+            "netmask": 24,
+            "gateway": "192.168.7.1",
+            "dns_servers": ["192.168.7.1"]
+        });
+
+        // The second range lies outside the subnet: nothing is created
+        let response = create(serde_json::json!({
+            "subnet": subnet,
+            "ranges": [
+                {"range_start": "192.168.7.100", "range_end": "192.168.7.150"},
+                {"range_start": "192.168.8.100", "range_end": "192.168.8.150"}
+            ],
+            "static_ips": [{"mac_address": "aa:bb:cc:dd:ee:01", "ip_address": "192.168.7.10"}]
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["errors"][0]["field"], "ranges[1].range_start");
+        assert!(db.list_subnets().await.unwrap().is_empty());
+
+        // A MAC address given twice is refused before touching the database
+        let response = create(serde_json::json!({
+            "subnet": subnet,
+            "static_ips": [
+                {"mac_address": "aa:bb:cc:dd:ee:01", "ip_address": "192.168.7.10"},
+                {"mac_address": "AA:BB:CC:DD:EE:01", "ip_address": "192.168.7.11"}
+            ]
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["errors"][0]["field"], "static_ips[1].mac_address");
+        assert!(db.list_subnets().await.unwrap().is_empty());
+        assert!(db.list_ranges(None).await.unwrap().is_empty());
+        assert!(db.list_static_ips(None).await.unwrap().is_empty());
+
+        let response = create(serde_json::json!({
+            "subnet": subnet,
+            "ranges": [
+                {"range_start": "192.168.7.100", "range_end": "192.168.7.150"},
+                {"range_start": "192.168.7.200", "range_end": "192.168.7.250"}
+            ],
+            "static_ips": [{"mac_address": "aa:bb:cc:dd:ee:01", "ip_address": "192.168.7.10"}]
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let created: subnets::CreateSubnetFullResponse = serde_json::from_slice(&body).unwrap();
+        let ranges = db.list_ranges(Some(created.subnet_id)).await.unwrap();
+        let range_ids: Vec<_> = ranges.iter().filter_map(|r| r.id).collect();
+        assert_eq!(range_ids, created.range_ids);
+        let static_ips = db.list_static_ips(Some(created.subnet_id)).await.unwrap();
+        assert_eq!(static_ips.len(), 1);
+        assert_eq!(static_ips[0].ip_address, Ipv4Addr::new(192, 168, 7, 10));
+    }
+
+    #[tokio::test]
+    async fn test_create_range_must_lie_within_its_subnet() {
+        use crate::models::Subnet;
+
+        let db = make_db();
+        let subnet_id = db.create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap()).await.unwrap();
+        let create = |subnet_id: i64, start: &str, end: &str| {
+            let body = serde_json::json!({
+                "subnet_id": subnet_id,
+                "range_start": start,
+                "range_end": end
+            });
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/ranges")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let error_fields = |body: &[u8]| -> Vec<String> {
+            let json: serde_json::Value = serde_json::from_slice(body).unwrap();
+            json["errors"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|e| e["field"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        for (start, end, expected) in [
+            ("10.0.0.100", "10.0.0.200", vec!["range_start", "range_end"]),
+            ("192.168.1.200", "192.168.1.100", vec!["range_end"]),
+        ] {
+            let response = create(subnet_id, start, end).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{start}-{end}");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(error_fields(&body), expected, "{start}-{end}");
+        }
+
+        let response = create(subnet_id + 1, "192.168.1.100", "192.168.1.200").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = create(subnet_id, "192.168.1.100", "192.168.1.200").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(db.list_ranges(Some(subnet_id)).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_option_template_crud_and_subnet_reference() {
+        let db = make_db();
+        let request = |method: Method, uri: &str, body: serde_json::Value| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let subnet = |template_id: i64| {
+            serde_json::json!({
+                "network": "192.168.7.0",
+                "netmask": 24,
+                "gateway": "192.168.7.1",
+                "dns_servers": [],
+                "domain_name": null,
+                "template_id": template_id
+            })
+        };
+
+        let template = serde_json::json!({
+            "name": "office",
+            "dns_servers": ["192.168.0.53"],
+            "domain_name": "office.lan"
+        });
+        let response = create_router(db.clone(), make_ra_config())
+            .oneshot(request(Method::POST, "/api/option-templates", template.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let id: i64 = serde_json::from_slice(&body).unwrap();
+
+        let status = send(
+            create_router(db.clone(), make_ra_config()),
+            Method::GET,
+            &format!("/api/option-templates/{id}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // A subnet may only reference an existing template
+        let response = create_router(db.clone(), make_ra_config())
+            .oneshot(request(Method::POST, "/api/subnets", subnet(id + 1)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = create_router(db.clone(), make_ra_config())
+            .oneshot(request(Method::POST, "/api/subnets", subnet(id)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = create_router(db.clone(), make_ra_config())
+            .oneshot(request(
+                Method::PUT,
+                &format!("/api/option-templates/{}", id + 1),
+                template,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let uri = format!("/api/option-templates/{id}");
+        let status = send(create_router(db.clone(), make_ra_config()), Method::DELETE, &uri).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let status = send(create_router(db.clone(), make_ra_config()), Method::GET, &uri).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let subnets = db.list_subnets().await.unwrap();
+        assert_eq!(subnets[0].template_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_prefix_is_rejected_at_deserialization() {
+        let db = make_db();
+        for (method, uri, body) in [
+            (
+                Method::POST,
+                "/api/subnets",
+                serde_json::json!({
+                    "network": "192.168.1.0",
+                    "netmask": 33,
+                    "gateway": "192.168.1.1",
+                    "dns_servers": [],
+                    "domain_name": null
+                }),
+            ),
+            (Method::PATCH, "/api/subnets/1", serde_json::json!({ "netmask": 33 })),
+        ] {
+            let response = create_router(db.clone(), make_ra_config())
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .header("content-type", "application/json")
+                        .body(Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let message = String::from_utf8_lossy(&body);
+            assert!(message.contains("got 33"), "{message}");
+        }
+        assert!(db.list_subnets().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_toggle_range() {
+        use crate::models::{DynamicRange, Subnet};
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let subnet_id = db.create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap()).await.unwrap();
+        let range_id = db
+            .create_range(&DynamicRange {
+                id: None,
+                subnet_id,
+                range_start: Ipv4Addr::new(192, 168, 1, 100),
+                range_end: Ipv4Addr::new(192, 168, 1, 200),
+                enabled: true,
+                draining: false,
+            })
+            .await
+            .unwrap();
+
+        let toggle = |id: i64| {
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(format!("/api/ranges/{}/toggle", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let response = toggle(range_id).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let range: DynamicRange = serde_json::from_slice(&body).unwrap();
+        assert!(!range.enabled);
+        assert!(!db.list_ranges(Some(subnet_id)).await.unwrap()[0].enabled);
+
+        assert_eq!(toggle(range_id).await.unwrap().status(), StatusCode::OK);
+        assert!(db.list_ranges(Some(subnet_id)).await.unwrap()[0].enabled);
+
+        assert_eq!(toggle(range_id + 1).await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_allocate_until_pool_exhausted() {
+        use crate::models::{DynamicRange, Subnet};
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let subnet_id = db
+            .create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap())
+            .await
+            .unwrap();
+        db.create_range(&DynamicRange {
+            id: None,
+            subnet_id,
+            range_start: Ipv4Addr::new(192, 168, 1, 10),
+            range_end: Ipv4Addr::new(192, 168, 1, 11),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+
+        let uri = format!("/api/subnets/{}/allocate", subnet_id);
+        let allocate = |body: serde_json::Value| {
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(&uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        let response = allocate(serde_json::json!({ "mac_address": "AA:BB:CC:DD:EE:01" }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["ip_address"], "192.168.1.10");
+        assert_eq!(json["static"], false);
+        assert!(json["expires_at"].is_i64());
+
+        let response =
+            allocate(serde_json::json!({ "mac_address": "aa:bb:cc:dd:ee:02", "static": true }))
+                .await
+                .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let statics = db.list_static_ips(Some(subnet_id)).await.unwrap();
+        assert_eq!(statics[0].ip_address, Ipv4Addr::new(192, 168, 1, 11));
+
+        let response = allocate(serde_json::json!({ "mac_address": "aa:bb:cc:dd:ee:03" }))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        // A MAC is required, for reservations as for static assignments
+        let response = allocate(serde_json::json!({})).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = allocate(serde_json::json!({ "static": true })).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_allocations_get_distinct_addresses() {
+        use crate::models::{DynamicRange, Subnet};
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let subnet_id = db
+            .create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap())
+            .await
+            .unwrap();
+        db.create_range(&DynamicRange {
+            id: None,
+            subnet_id,
+            range_start: Ipv4Addr::new(192, 168, 1, 10),
+            range_end: Ipv4Addr::new(192, 168, 1, 14),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+
+        let uri = format!("/api/subnets/{}/allocate", subnet_id);
+        let responses = futures::future::join_all((0..6).map(|i| {
+            let body = serde_json::json!({ "mac_address": format!("aa:bb:cc:dd:ee:{:02x}", i) });
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(&uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        }))
+        .await;
+
+        let mut statuses: Vec<_> = responses.iter().map(|r| r.as_ref().unwrap().status()).collect();
+        statuses.sort();
+        assert_eq!(statuses[..5], [StatusCode::CREATED; 5]);
+        assert_eq!(statuses[5], StatusCode::CONFLICT);
+        let mut leased: Vec<_> = db
+            .list_active_leases()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|l| l.ip_address)
+            .collect();
+        leased.sort();
+        leased.dedup();
+        assert_eq!(leased.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_import_leases_skips_expired_and_unknown() {
+        use crate::models::Subnet;
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let subnet_id = db
+            .create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap())
+            .await
+            .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let lease = |ip: &str, lease_end: i64| {
+            serde_json::json!({
+                "mac_address": "aa:bb:cc:dd:ee:01",
+                "ip_address": ip,
+                "lease_start": now - 60,
+                "lease_end": lease_end,
+                "hostname": "imported",
+            })
+        };
+        let body = serde_json::json!([
+            lease("192.168.1.50", now + 3600),
+            lease("192.168.1.51", now - 1),
+            lease("10.0.0.50", now + 3600),
+        ]);
+
+        let response = create_router(db.clone(), make_ra_config())
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/leases/import")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["imported"], 1);
+        assert_eq!(json["skipped"], 2);
+
+        let leases = db.list_active_leases().await.unwrap();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].subnet_id, subnet_id);
+        assert_eq!(leases[0].ip_address, Ipv4Addr::new(192, 168, 1, 50));
+        assert_eq!(leases[0].hostname.as_deref(), Some("imported"));
+    }
+
+    #[tokio::test]
+    async fn test_import_leases_checks_client_mac() {
+        use crate::models::{Lease, StaticIP, Subnet};
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let subnet_id = db
+            .create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap())
+            .await
+            .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:02".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 20),
+            lease_start: now - 60,
+            lease_end: now + 3600,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+        let lease = |mac: &str, ip: &str| {
+            serde_json::json!({
+                "mac_address": mac,
+                "ip_address": ip,
+                "lease_start": now - 60,
+                "lease_end": now + 3600,
+                "hostname": null,
+            })
+        };
+        let import = |body: serde_json::Value| {
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/leases/import")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        // One malformed MAC refuses the whole batch
+        let response = import(serde_json::json!([
+            lease("aa:bb:cc:dd:ee:03", "192.168.1.51"),
+            lease("aa:bb:cc:dd:ee", "192.168.1.52"),
+        ]))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(db.list_active_leases().await.unwrap().len(), 1);
+
+        // A client that already holds a lease is not given a second one
+        let response = import(serde_json::json!([
+            lease("AA:BB:CC:DD:EE:02", "192.168.1.50"),
+            lease("AA:BB:CC:DD:EE:03", "192.168.1.51"),
+        ]))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["imported"], 1);
+        assert_eq!(json["skipped"], 1);
+
+        let mut leases = db.list_active_leases().await.unwrap();
+        leases.sort_by_key(|l| l.ip_address);
+        assert_eq!(leases.len(), 2);
+        assert_eq!(leases[0].ip_address, Ipv4Addr::new(192, 168, 1, 20));
+        assert_eq!(leases[1].ip_address, Ipv4Addr::new(192, 168, 1, 51));
+        assert_eq!(leases[1].mac_address, "aa:bb:cc:dd:ee:03");
+
+        // Neither held nor statically assigned addresses are taken over
+        db.create_static_ip(&StaticIP {
+            subnet_id,
+            mac_address: "aa:bb:cc:dd:ee:06".to_string(),
+            ip_address: Ipv4Addr::new(192, 168, 1, 70),
+            hostname: None,
+        })
+        .await
+        .unwrap();
+        let response = import(serde_json::json!([
+            lease("aa:bb:cc:dd:ee:07", "192.168.1.20"),
+            lease("aa:bb:cc:dd:ee:08", "192.168.1.70"),
+        ]))
+        .await
+        .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["imported"], 0);
+        assert_eq!(json["skipped"], 2);
+        assert_eq!(db.list_active_leases().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_import_leases_sanitizes_hostnames() {
+        use crate::models::Subnet;
+
+        let db = make_db();
+        db.create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap())
+        .await
+        .unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let lease = |mac: &str, ip: &str, hostname: &str| {
+            serde_json::json!({
+                "mac_address": mac,
+                "ip_address": ip,
+                "lease_start": now - 60,
+                "lease_end": now + 3600,
+                "hostname": hostname,
+            })
+        };
+        let body = serde_json::json!([
+            lease("aa:bb:cc:dd:ee:04", "192.168.1.60", "evil\u{1b}[31m\0host"),
+            lease("aa:bb:cc:dd:ee:05", "192.168.1.61", "\0\u{1b}"),
+        ]);
+
+        let response = create_router(db.clone(), make_ra_config())
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/leases/import")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let mut leases = db.list_active_leases().await.unwrap();
+        leases.sort_by_key(|l| l.ip_address);
+        assert_eq!(leases.len(), 2);
+        assert_eq!(leases[0].hostname.as_deref(), Some("evil-31mhost"));
+        assert_eq!(leases[1].hostname, None);
+    }
+
+    #[tokio::test]
+    async fn test_clone_subnet_rebases_ranges() {
+        use crate::models::{DynamicRange, Subnet};
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let source_id = db
+            .create_subnet(&Subnet {
+                dns_servers: vec![Ipv4Addr::new(192, 168, 1, 53)],
+                domain_name: Some("prod.lan".to_string()),
+                authoritative: true,
+                ..Subnet::from_cidr("192.168.1.0/24").unwrap()
+            })
+            .await
+            .unwrap();
+        db.create_range(&DynamicRange {
+            id: None,
+            subnet_id: source_id,
+            range_start: Ipv4Addr::new(192, 168, 1, 100),
+            range_end: Ipv4Addr::new(192, 168, 1, 199),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+
+        let clone = |body: serde_json::Value| {
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/subnets/{}/clone", source_id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        let response = clone(serde_json::json!({
+            "network": "10.20.0.0",
+            "gateway": "10.20.0.1",
+            "include_ranges": true
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let cloned: Subnet = serde_json::from_slice(&body).unwrap();
+        assert_eq!(cloned.netmask, 24);
+        assert_eq!(cloned.domain_name.as_deref(), Some("prod.lan"));
+        assert!(cloned.authoritative);
+
+        let ranges = db.list_ranges(cloned.id).await.unwrap();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].range_start, Ipv4Addr::new(10, 20, 0, 100));
+        assert_eq!(ranges[0].range_end, Ipv4Addr::new(10, 20, 0, 199));
+
+        // The copy itself now occupies 10.20.0.0/24
+        let response = clone(serde_json::json!({
+            "network": "10.20.0.0",
+            "gateway": "10.20.0.1"
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_renumber_subnet() {
+        use crate::handlers::subnets::RenumberResponse;
+        use crate::models::{DynamicRange, Lease, StaticIP, Subnet};
+        use std::net::Ipv4Addr;
+
+        let ip = |third: u8, fourth: u8| Ipv4Addr::new(192, 168, third, fourth);
+        let db = make_db();
+        let id = db.create_subnet(&Subnet::from_cidr("192.168.1.0/24").unwrap()).await.unwrap();
+        db.create_range(&DynamicRange {
+            id: None,
+            subnet_id: id,
+            range_start: ip(1, 100),
+            range_end: ip(1, 199),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+        for (mac, last_octet) in [("aa:bb:cc:dd:ee:01", 50), ("aa:bb:cc:dd:ee:02", 200)] {
+            db.create_static_ip(&StaticIP {
+                subnet_id: id,
+                mac_address: mac.to_string(),
+                ip_address: ip(1, last_octet),
+                hostname: None,
+            })
+            .await
+            .unwrap();
+        }
+        let now = chrono::Utc::now().timestamp();
+        db.create_lease(&Lease {
+            id: None,
+            subnet_id: id,
+            mac_address: "aa:bb:cc:dd:ee:03".to_string(),
+            ip_address: ip(1, 120),
+            lease_start: now,
+            lease_end: now + 3600,
+            hostname: None,
+            range_id: None,
+        })
+        .await
+        .unwrap();
+
+        let renumber = |body: serde_json::Value| {
+            let db = db.clone();
+            async move {
+                let response = create_router(db, make_ra_config())
+                    .oneshot(
+                        Request::builder()
+                            .method(Method::POST)
+                            .uri(format!("/api/subnets/{}/renumber", id))
+                            .header("content-type", "application/json")
+                            .body(Body::from(body.to_string()))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                serde_json::from_slice::<RenumberResponse>(&body).unwrap()
+            }
+        };
+        let ranges = || async { db.list_ranges(Some(id)).await.unwrap() };
+        let static_ips = || async {
+            let mut ips: Vec<_> = db
+                .list_static_ips(Some(id))
+                .await
+                .unwrap()
+                .iter()
+                .map(|s| s.ip_address)
+                .collect();
+            ips.sort();
+            ips
+        };
+
+        // Widening /24 to the enclosing /23 keeps every address
+        let report = renumber(serde_json::json!({"network": "192.168.0.0", "netmask": 23})).await;
+        assert_eq!(report.subnet.gateway, ip(1, 1));
+        assert!(report.ranges_dropped.is_empty() && report.static_ips_dropped.is_empty());
+        assert!(report.leases_expired.is_empty());
+        assert_eq!((ranges().await[0].range_start, ranges().await[0].range_end), (ip(1, 100), ip(1, 199)));
+        assert_eq!(static_ips().await, [ip(1, 50), ip(1, 200)]);
+        assert!(db.get_active_lease_by_ip(ip(1, 120)).await.unwrap().is_some());
+
+        // Moving to another third octet keeps host offsets and expires the leases
+        let report = renumber(serde_json::json!({"network": "192.168.6.0", "netmask": 23})).await;
+        let subnet = db.get_subnet(id).await.unwrap().unwrap();
+        assert_eq!((subnet.network, subnet.netmask.get()), (ip(6, 0), 23));
+        assert_eq!(subnet.gateway, ip(7, 1));
+        assert_eq!((ranges().await[0].range_start, ranges().await[0].range_end), (ip(7, 100), ip(7, 199)));
+        assert_eq!(static_ips().await, [ip(7, 50), ip(7, 200)]);
+        assert_eq!(report.leases_expired.len(), 1);
+        assert!(db.list_active_leases().await.unwrap().is_empty());
+
+        // What no longer fits a narrower prefix is reported and dropped
+        let report = renumber(serde_json::json!({
+            "network": "192.168.7.0",
+            "netmask": 25
+        }))
+        .await;
+        assert_eq!((ranges().await[0].range_start, ranges().await[0].range_end), (ip(7, 100), ip(7, 126)));
+        assert_eq!(static_ips().await, [ip(7, 50)]);
+        assert_eq!(report.static_ips_dropped.len(), 1);
+        assert_eq!(report.static_ips_dropped[0].ip_address, ip(7, 200));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_report_subnet_free_addresses() {
+        use crate::models::{DynamicRange, Lease, Subnet};
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let subnet_id = db.create_subnet(&Subnet::from_cidr("192.168.7.0/24").unwrap()).await.unwrap();
+        db.create_range(&DynamicRange {
+            id: None,
+            subnet_id,
+            range_start: Ipv4Addr::new(192, 168, 7, 100),
+            range_end: Ipv4Addr::new(192, 168, 7, 109),
+            enabled: true,
+            draining: false,
+        })
+        .await
+        .unwrap();
+
+        let scrape = || async {
+            let response = create_router(db.clone(), make_ra_config())
+                .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+        let body = scrape().await;
+        assert!(body.contains("ndhcpd_dhcp_subnet_total_addresses{subnet=\"192.168.7.0/24\"} 10\n"));
+        assert!(body.contains("ndhcpd_dhcp_subnet_free_addresses{subnet=\"192.168.7.0/24\"} 10\n"));
+
+        let now = chrono::Utc::now().timestamp();
+        for last_octet in [100, 101, 102] {
+            db.create_lease(&Lease {
+                id: None,
+                subnet_id,
+                mac_address: format!("aa:bb:cc:dd:07:{:02x}", last_octet),
+                ip_address: Ipv4Addr::new(192, 168, 7, last_octet),
+                lease_start: now,
+                lease_end: now + 3600,
+                hostname: None,
+                range_id: None,
+            })
+            .await
+            .unwrap();
+        }
+        let body = scrape().await;
+        assert!(body.contains("ndhcpd_dhcp_subnet_free_addresses{subnet=\"192.168.7.0/24\"} 7\n"));
+    }
+
+    #[tokio::test]
+    async fn test_whoami_reports_authenticating_token() {
+        use crate::auth::token;
+        use crate::handlers::tokens::WhoAmIResponse;
+
+        let db = make_db();
+        let raw = token::generate();
+        let (token_hash, salt) = token::hash(&raw).unwrap();
+        let id = db.create_token("ops-laptop", &token_hash, &salt).await.unwrap();
+
+        let whoami = |router: Router, authorization: Option<String>| {
+            let mut request = Request::builder().uri("/api/auth/whoami");
+            if let Some(value) = authorization {
+                request = request.header("Authorization", value);
+            }
+            router.oneshot(request.body(Body::empty()).unwrap())
+        };
+        let body_of = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<WhoAmIResponse>(&body).unwrap()
+        };
+
+        let router = create_router_with_auth(db.clone(), make_ra_config(), true);
+        let response = whoami(router, Some(format!("Bearer {}", raw))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let identity = body_of(response).await;
+        assert!(identity.authenticated);
+        assert_eq!(identity.token_id, Some(id));
+        assert_eq!(identity.token_name.as_deref(), Some("ops-laptop"));
+
+        let router = create_router_with_auth(db.clone(), make_ra_config(), true);
+        let response = whoami(router, Some("Bearer not-a-token".to_string())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Without authentication nothing is checked, so there is no identity
+        let response = whoami(create_router(db, make_ra_config()), None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let identity = body_of(response).await;
+        assert!(!identity.authenticated);
+        assert_eq!(identity.token_name, None);
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_generated_or_echoed() {
+        let request = |id: Option<&str>| {
+            let mut request = Request::builder().uri("/health");
+            if let Some(id) = id {
+                request = request.header("x-request-id", id);
+            }
+            create_router(make_db(), make_ra_config())
+                .oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = request(None).await.unwrap();
+        let generated = response.headers()["x-request-id"].to_str().unwrap();
+        assert_eq!(generated.len(), 32);
+        let response = request(None).await.unwrap();
+        assert_ne!(response.headers()["x-request-id"], generated);
+
+        let response = request(Some("client-42")).await.unwrap();
+        assert_eq!(response.headers()["x-request-id"], "client-42");
+    }
+
+    #[tokio::test]
+    async fn test_patch_subnet_validates_merged_subnet() {
+        use crate::models::Subnet;
+        use std::net::Ipv4Addr;
+
+        let db = make_db();
+        let id = db
+            .create_subnet(&Subnet {
+                domain_name: Some("home.lan".to_string()),
+                ..Subnet::from_cidr("192.168.1.0/24").unwrap()
+            })
+            .await
+            .unwrap();
+        let patch = |body: serde_json::Value| {
+            create_router(db.clone(), make_ra_config()).oneshot(
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(format!("/api/subnets/{}", id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        // The gateway alone is valid, but not inside the stored /24
+        let response = patch(serde_json::json!({"gateway": "10.0.0.1"})).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = patch(serde_json::json!({"domain_name": "lan"})).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let subnet = db.get_subnet(id).await.unwrap().unwrap();
+        assert_eq!(subnet.gateway, Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(subnet.domain_name.as_deref(), Some("lan"));
+
+        // Moving onto another subnet's network conflicts
+        db.create_subnet(&Subnet {
+            network: Ipv4Addr::new(192, 168, 2, 0),
+            gateway: Ipv4Addr::new(192, 168, 2, 1),
+            ..subnet.clone()
+        })
+        .await
+        .unwrap();
+        let response = patch(serde_json::json!({
+            "network": "192.168.2.0",
+            "gateway": "192.168.2.254",
+        }))
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let subnet = db.get_subnet(id).await.unwrap().unwrap();
+        assert_eq!(subnet.network, Ipv4Addr::new(192, 168, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_trailing_slash_and_unknown_paths() {
+        let router = create_router(make_db(), make_ra_config());
+        let status = send(router, Method::GET, "/api/subnets/").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let response = create_router(make_db(), make_ra_config())
+            .oneshot(Request::builder().uri("/api/nope").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json, serde_json::json!({"error": "not found", "path": "/api/nope"}));
+    }
 }
@@ -0,0 +1,25 @@
+use crate::{models::PendingOffer, AppState};
+use axum::{extract::State, http::StatusCode, Json};
+use tracing::error;
+
+/// List addresses offered to clients that have not REQUESTed them yet
+///
+/// A client listed here for several attempts received OFFERs but never
+/// completed the handshake.
+#[utoipa::path(
+    get,
+    path = "/api/offers",
+    tag = "offers",
+    responses(
+        (status = 200, description = "Pending offers", body = Vec<PendingOffer>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_offers(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PendingOffer>>, StatusCode> {
+    state.db.list_pending_offers().await.map(Json).map_err(|e| {
+        error!("Failed to list pending offers: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
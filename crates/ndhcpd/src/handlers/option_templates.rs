@@ -0,0 +1,174 @@
+use crate::{
+    db::is_unique_violation,
+    models::OptionTemplate,
+    validation::{Validate, ValidationErrors},
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use tracing::error;
+
+/// List all option templates
+#[utoipa::path(
+    get,
+    path = "/api/option-templates",
+    tag = "option-templates",
+    responses(
+        (status = 200, description = "List of option templates", body = Vec<OptionTemplate>),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_option_templates(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<OptionTemplate>>, StatusCode> {
+    state
+        .db
+        .list_option_templates()
+        .await
+        .map(Json)
+        .map_err(|e| {
+            error!("Failed to list option templates: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Create a new option template
+#[utoipa::path(
+    post,
+    path = "/api/option-templates",
+    tag = "option-templates",
+    request_body = OptionTemplate,
+    responses(
+        (status = 201, description = "Option template created", body = i64),
+        (status = 400, description = "Validation failed", body = ValidationErrors),
+        (status = 409, description = "An option template with this name already exists"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn create_option_template(
+    State(state): State<AppState>,
+    Json(template): Json<OptionTemplate>,
+) -> Result<(StatusCode, Json<i64>), Response> {
+    template.validate().map_err(IntoResponse::into_response)?;
+
+    state
+        .db
+        .create_option_template(&template)
+        .await
+        .map(|id| (StatusCode::CREATED, Json(id)))
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                return StatusCode::CONFLICT.into_response();
+            }
+            error!("Failed to create option template {:?}: {}", template.name, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })
+}
+
+/// Get an option template by ID
+#[utoipa::path(
+    get,
+    path = "/api/option-templates/{id}",
+    tag = "option-templates",
+    params(
+        ("id" = i64, Path, description = "Option template ID")
+    ),
+    responses(
+        (status = 200, description = "Option template found", body = OptionTemplate),
+        (status = 404, description = "Option template not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_option_template(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<OptionTemplate>, StatusCode> {
+    state
+        .db
+        .get_option_template(id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get option template id={}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Update an option template
+///
+/// Takes effect on the next reply to a client of any subnet using it.
+#[utoipa::path(
+    put,
+    path = "/api/option-templates/{id}",
+    tag = "option-templates",
+    params(
+        ("id" = i64, Path, description = "Option template ID")
+    ),
+    request_body = OptionTemplate,
+    responses(
+        (status = 200, description = "Option template updated"),
+        (status = 400, description = "Validation failed", body = ValidationErrors),
+        (status = 404, description = "Option template not found"),
+        (status = 409, description = "An option template with this name already exists"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn update_option_template(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(template): Json<OptionTemplate>,
+) -> Result<StatusCode, Response> {
+    template.validate().map_err(IntoResponse::into_response)?;
+    let internal_error = |e: anyhow::Error| {
+        if is_unique_violation(&e) {
+            return StatusCode::CONFLICT.into_response();
+        }
+        error!("Failed to update option template id={}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    };
+
+    if state.db.get_option_template(id).await.map_err(internal_error)?.is_none() {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+    state
+        .db
+        .update_option_template(id, &template)
+        .await
+        .map(|_| StatusCode::OK)
+        .map_err(internal_error)
+}
+
+/// Delete an option template
+///
+/// Subnets using it are detached and keep only their own options.
+#[utoipa::path(
+    delete,
+    path = "/api/option-templates/{id}",
+    tag = "option-templates",
+    params(
+        ("id" = i64, Path, description = "Option template ID")
+    ),
+    responses(
+        (status = 204, description = "Option template deleted"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_option_template(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    state
+        .db
+        .delete_option_template(id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(|e| {
+            error!("Failed to delete option template id={}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
@@ -1,7 +1,12 @@
-use crate::{models::DynamicRange, AppState};
+use crate::{
+    models::DynamicRange,
+    validation::{validate_range_in, Validate, ValidationErrors},
+    AppState,
+};
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::Deserialize;
@@ -51,14 +56,28 @@ pub async fn list_ranges(
     request_body = DynamicRange,
     responses(
         (status = 201, description = "Range created", body = i64),
-        (status = 400, description = "Bad request"),
+        (status = 400, description = "Validation failed, e.g. the range is outside its subnet", body = ValidationErrors),
         (status = 500, description = "Internal server error")
     )
 )]
 pub async fn create_range(
     State(state): State<AppState>,
     Json(range): Json<DynamicRange>,
-) -> Result<(StatusCode, Json<i64>), StatusCode> {
+) -> Result<(StatusCode, Json<i64>), Response> {
+    let subnet = match state.db.get_subnet(range.subnet_id).await {
+        Ok(Some(subnet)) => subnet,
+        Ok(None) => {
+            let mut errors = range.validate().err().unwrap_or_default();
+            errors.add("subnet_id", format!("no subnet has ID {}", range.subnet_id));
+            return Err(errors.into_response());
+        }
+        Err(e) => {
+            error!("Failed to get subnet id={}: {}", range.subnet_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+    validate_range_in(&range, &subnet).map_err(IntoResponse::into_response)?;
+
     state
         .db
         .create_range(&range)
@@ -69,10 +88,104 @@ pub async fn create_range(
                 "Failed to create range (subnet_id={}, start={}, end={}): {}",
                 range.subnet_id, range.range_start, range.range_end, e
             );
-            StatusCode::INTERNAL_SERVER_ERROR
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         })
 }
 
+/// Enable/disable a dynamic range
+///
+/// A disabled range stays stored but no address is allocated from it;
+/// existing leases are kept until they expire.
+#[utoipa::path(
+    patch,
+    path = "/api/ranges/{id}/toggle",
+    tag = "ranges",
+    params(
+        ("id" = i64, Path, description = "Range ID")
+    ),
+    responses(
+        (status = 200, description = "Range toggled", body = DynamicRange),
+        (status = 404, description = "Range not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn toggle_range(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<DynamicRange>, StatusCode> {
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to toggle range id={}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    let mut range = state
+        .db
+        .list_ranges(None)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .find(|r| r.id == Some(id))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    range.enabled = !range.enabled;
+    state
+        .db
+        .toggle_range(id, range.enabled)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(range))
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SetDrainingRequest {
+    /// Whether to stop new allocations from the range
+    pub draining: bool,
+}
+
+/// Start or stop draining a dynamic range
+///
+/// A draining range hands out no new addresses, while clients holding a
+/// lease in it keep renewing it. Used to empty a range before deleting it.
+#[utoipa::path(
+    patch,
+    path = "/api/ranges/{id}/draining",
+    tag = "ranges",
+    request_body = SetDrainingRequest,
+    params(
+        ("id" = i64, Path, description = "Range ID")
+    ),
+    responses(
+        (status = 200, description = "Draining state updated", body = DynamicRange),
+        (status = 404, description = "Range not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn set_range_draining(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(body): Json<SetDrainingRequest>,
+) -> Result<Json<DynamicRange>, StatusCode> {
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to set draining on range id={}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    let mut range = state
+        .db
+        .list_ranges(None)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .find(|r| r.id == Some(id))
+        .ok_or(StatusCode::NOT_FOUND)?;
+    range.draining = body.draining;
+    state
+        .db
+        .set_range_draining(id, range.draining)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(range))
+}
+
 /// Delete a dynamic range
 #[utoipa::path(
     delete,
@@ -1,11 +1,17 @@
-use crate::{db::is_unique_violation, models::StaticIP, AppState};
+use crate::{
+    db::is_unique_violation,
+    dhcp::MacAddress,
+    models::{StaticIP, Subnet},
+    utils::network,
+    AppState,
+};
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
-use serde::Deserialize;
-use tracing::error;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, warn};
 
 #[derive(Deserialize)]
 pub struct StaticIpQuery {
@@ -64,7 +70,6 @@ pub async fn create_static_ip(
         .db
         .create_static_ip(&static_ip)
         .await
-        .map(|_| StatusCode::CREATED)
         .map_err(|e| {
             if is_unique_violation(&e) {
                 return StatusCode::CONFLICT;
@@ -74,7 +79,94 @@ pub async fn create_static_ip(
                 static_ip.subnet_id, static_ip.mac_address, static_ip.ip_address, e
             );
             StatusCode::INTERNAL_SERVER_ERROR
-        })
+        })?;
+    if let Some(subnet) = arp_subnet(&state, static_ip.subnet_id).await {
+        if let Some(iface) = arp_interface(&state, &subnet) {
+            pin_neighbors(&state, iface, std::slice::from_ref(&static_ip)).await;
+        }
+    }
+    Ok(StatusCode::CREATED)
+}
+
+/// Subnet `id`, when static IPs are kept in the ARP table. Lookup failures
+/// are only logged: the ARP table is then left alone.
+pub(crate) async fn arp_subnet(state: &AppState, id: i64) -> Option<Subnet> {
+    if state.static_arp_interfaces.is_empty() {
+        return None;
+    }
+    match state.db.get_subnet(id).await {
+        Ok(subnet) => subnet,
+        Err(e) => {
+            warn!("Cannot update the ARP table for subnet {}: {}", id, e);
+            None
+        }
+    }
+}
+
+/// The static IPs of subnet `id` currently in the ARP table, with their
+/// subnet, read before they are removed or moved.
+pub(crate) async fn pinned_static_ips(state: &AppState, id: i64) -> Option<(Subnet, Vec<StaticIP>)> {
+    let subnet = arp_subnet(state, id).await?;
+    match state.db.list_static_ips(Some(id)).await {
+        Ok(static_ips) => Some((subnet, static_ips)),
+        Err(e) => {
+            warn!("Cannot update the ARP table for subnet {}: {}", id, e);
+            None
+        }
+    }
+}
+
+/// First `static_arp_interfaces` entry holding an address in `subnet`
+pub(crate) fn arp_interface<'a>(state: &'a AppState, subnet: &Subnet) -> Option<&'a str> {
+    if state.static_arp_interfaces.is_empty() {
+        return None;
+    }
+    let iface = state.static_arp_interfaces.iter().find(|iface| {
+        network::get_interface_ips(iface)
+            .iter()
+            .any(|ip| subnet.contains(*ip))
+    });
+    if iface.is_none() {
+        debug!(
+            "No listen interface in {}/{}, leaving the ARP table alone",
+            subnet.network, subnet.netmask
+        );
+    }
+    iface.map(String::as_str)
+}
+
+/// Add `static_ips` to the ARP table of `iface`. Failures are only logged:
+/// the reservations themselves are already stored. Reservations keyed by a
+/// Client Identifier rather than a MAC address have no entry to pin.
+pub(crate) async fn pin_neighbors(state: &AppState, iface: &str, static_ips: &[StaticIP]) {
+    for static_ip in static_ips {
+        let ip = static_ip.ip_address;
+        let Some(mac) = MacAddress::from_string(&static_ip.mac_address) else {
+            debug!("Not pinning {}: {} is not a MAC address", ip, static_ip.mac_address);
+            continue;
+        };
+        let mac = mac.to_string();
+        match state.neighbors.pin(iface, ip, &mac).await {
+            Ok(()) => debug!("Pinned {} -> {} in the ARP table of {}", ip, mac, iface),
+            Err(e) => warn!("Failed to pin {} in the ARP table: {}", ip, e),
+        }
+    }
+}
+
+/// Remove `static_ips` from the ARP table of `iface`, once they were deleted
+/// or moved. Failures are only logged.
+pub(crate) async fn unpin_neighbors(state: &AppState, iface: &str, static_ips: &[StaticIP]) {
+    for static_ip in static_ips {
+        let ip = static_ip.ip_address;
+        if MacAddress::from_string(&static_ip.mac_address).is_none() {
+            // Never pinned, see `pin_neighbors`
+            continue;
+        }
+        match state.neighbors.unpin(iface, ip).await {
+            Ok(()) => debug!("Removed {} from the ARP table of {}", ip, iface),
+            Err(e) => warn!("Failed to remove {} from the ARP table: {}", ip, e),
+        }
+    }
 }
 
 /// Delete a static IP assignment
@@ -94,15 +186,73 @@ pub async fn delete_static_ip(
     State(state): State<AppState>,
     Path(ip): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
-    state
+    let pinned = if state.static_arp_interfaces.is_empty() {
+        None
+    } else {
+        match state.db.list_static_ips(None).await {
+            Ok(static_ips) => static_ips.into_iter().find(|s| s.ip_address.to_string() == ip),
+            Err(e) => {
+                warn!("Cannot update the ARP table for {}: {}", ip, e);
+                None
+            }
+        }
+    };
+    state.db.delete_static_ip(&ip).await.map_err(|e| {
+        error!("Failed to delete static IP ip={}: {}", ip, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Some(static_ip) = pinned {
+        if let Some(subnet) = arp_subnet(&state, static_ip.subnet_id).await {
+            if let Some(iface) = arp_interface(&state, &subnet) {
+                unpin_neighbors(&state, iface, std::slice::from_ref(&static_ip)).await;
+            }
+        }
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeleteStaticIpsResponse {
+    /// Number of static IPs removed
+    pub deleted: u64,
+}
+
+/// Delete every static IP assignment of a subnet
+///
+/// `subnet_id` is required so that a bare request cannot wipe all reservations.
+#[utoipa::path(
+    delete,
+    path = "/api/static-ips",
+    tag = "static-ips",
+    params(
+        ("subnet_id" = i64, Query, description = "Subnet whose static IPs are deleted")
+    ),
+    responses(
+        (status = 200, description = "Static IPs deleted", body = DeleteStaticIpsResponse),
+        (status = 400, description = "Missing subnet_id"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn delete_static_ips(
+    State(state): State<AppState>,
+    Query(query): Query<StaticIpQuery>,
+) -> Result<Json<DeleteStaticIpsResponse>, StatusCode> {
+    let subnet_id = query.subnet_id.ok_or(StatusCode::BAD_REQUEST)?;
+    let pinned = pinned_static_ips(&state, subnet_id).await;
+    let deleted = state
         .db
-        .delete_static_ip(&ip)
+        .delete_static_ips_by_subnet(subnet_id)
         .await
-        .map(|_| StatusCode::NO_CONTENT)
         .map_err(|e| {
-            error!("Failed to delete static IP ip={}: {}", ip, e);
+            error!("Failed to delete static IPs (subnet_id={}): {}", subnet_id, e);
             StatusCode::INTERNAL_SERVER_ERROR
-        })
+        })?;
+    if let Some((subnet, static_ips)) = pinned {
+        if let Some(iface) = arp_interface(&state, &subnet) {
+            unpin_neighbors(&state, iface, &static_ips).await;
+        }
+    }
+    Ok(Json(DeleteStaticIpsResponse { deleted }))
 }
 
 #[derive(Deserialize, utoipa::ToSchema)]
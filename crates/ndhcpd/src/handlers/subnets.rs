@@ -1,28 +1,127 @@
-use crate::{db::is_unique_violation, models::Subnet, utils::network::subnets_overlap, AppState};
+use crate::{
+    db::{is_unique_violation, Renumbering, SubnetContents},
+    dhcp::{allocator, DhcpOption, DhcpServer, MacAddress},
+    handlers::static_ips::{arp_interface, pin_neighbors, pinned_static_ips, unpin_neighbors},
+    models::{DynamicRange, Lease, PrefixLen, StaticIP, Subnet, SubnetAddress, SubnetPatch},
+    utils::network::subnets_overlap,
+    validation::{validate_range_in, Validate, ValidationErrors},
+    AppState,
+};
 use axum::{
-    extract::{Path, State},
+    extract::{rejection::JsonRejection, Path, Query, State},
     http::StatusCode,
+    response::{IntoResponse, Response},
     Json,
 };
-use tracing::error;
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+
+/// Lifetime of an allocation reservation when the caller does not pick one
+const DEFAULT_RESERVATION_SECS: u32 = 300;
 
-/// List all subnets
+#[derive(Deserialize)]
+pub struct SubnetQuery {
+    /// Comma-separated list of subnet IDs to fetch
+    ids: Option<String>,
+}
+
+/// Parse a comma-separated id list such as `1,2,3`.
+fn parse_ids(ids: &str) -> Option<Vec<i64>> {
+    ids.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().ok())
+        .collect()
+}
+
+/// List all subnets, or only those matching `ids`
 #[utoipa::path(
     get,
     path = "/api/subnets",
     tag = "subnets",
+    params(
+        ("ids" = Option<String>, Query, description = "Comma-separated subnet IDs to fetch (e.g. 1,2,3)")
+    ),
     responses(
         (status = 200, description = "List of subnets", body = Vec<Subnet>),
+        (status = 400, description = "Invalid ids parameter"),
         (status = 500, description = "Internal server error")
     )
 )]
-pub async fn list_subnets(State(state): State<AppState>) -> Result<Json<Vec<Subnet>>, StatusCode> {
-    state.db.list_subnets().await.map(Json).map_err(|e| {
-        error!("Failed to list subnets: {}", e);
+pub async fn list_subnets(
+    State(state): State<AppState>,
+    Query(query): Query<SubnetQuery>,
+) -> Result<Json<Vec<Subnet>>, StatusCode> {
+    let result = match query.ids.as_deref() {
+        Some(ids) => {
+            let ids = parse_ids(ids).ok_or(StatusCode::BAD_REQUEST)?;
+            state.db.get_subnets_by_ids(&ids).await
+        }
+        None => state.db.list_subnets().await,
+    };
+    result.map(Json).map_err(|e| {
+        error!("Failed to list subnets (ids={:?}): {}", query.ids, e);
         StatusCode::INTERNAL_SERVER_ERROR
     })
 }
 
+/// Bodies that parse as JSON but not as the expected type, such as a prefix
+/// length above 32, are reported as 400 like validation failures
+fn bad_body(rejection: JsonRejection) -> Response {
+    match rejection {
+        JsonRejection::JsonDataError(e) => (StatusCode::BAD_REQUEST, e.body_text()).into_response(),
+        other => other.into_response(),
+    }
+}
+
+/// Reject `subnet` with 409 when it overlaps an existing subnet other than `skip`
+async fn ensure_no_overlap(
+    state: &AppState,
+    subnet: &Subnet,
+    skip: Option<i64>,
+) -> Result<(), Response> {
+    let existing = state.db.list_subnets().await.map_err(|e| {
+        error!("Failed to list subnets for overlap check: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+
+    for existing_subnet in existing.iter().filter(|s| skip.is_none() || s.id != skip) {
+        if subnets_overlap(
+            subnet.network,
+            subnet.netmask,
+            existing_subnet.network,
+            existing_subnet.netmask,
+        ) {
+            return Err(StatusCode::CONFLICT.into_response());
+        }
+    }
+    Ok(())
+}
+
+/// Reject with 400 a `template_id` that names no option template
+async fn ensure_template_exists(
+    state: &AppState,
+    template_id: Option<i64>,
+) -> Result<(), Response> {
+    let Some(template_id) = template_id else {
+        return Ok(());
+    };
+    match state.db.get_option_template(template_id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => {
+            let mut errors = ValidationErrors::new();
+            errors.add("template_id", format!("no option template has ID {}", template_id));
+            Err(errors.into_response())
+        }
+        Err(e) => {
+            error!("Failed to get option template id={}: {}", template_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
 /// Create a new subnet
 #[utoipa::path(
     post,
@@ -31,31 +130,19 @@ pub async fn list_subnets(State(state): State<AppState>) -> Result<Json<Vec<Subn
     request_body = Subnet,
     responses(
         (status = 201, description = "Subnet created", body = i64),
-        (status = 400, description = "Bad request"),
+        (status = 400, description = "Validation failed", body = ValidationErrors),
         (status = 409, description = "Subnet already exists"),
         (status = 500, description = "Internal server error")
     )
 )]
 pub async fn create_subnet(
     State(state): State<AppState>,
-    Json(subnet): Json<Subnet>,
-) -> Result<(StatusCode, Json<i64>), StatusCode> {
-    // Check for overlap with existing subnets
-    let existing = state.db.list_subnets().await.map_err(|e| {
-        error!("Failed to list subnets for overlap check: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    for existing_subnet in &existing {
-        if subnets_overlap(
-            subnet.network,
-            subnet.netmask,
-            existing_subnet.network,
-            existing_subnet.netmask,
-        ) {
-            return Err(StatusCode::CONFLICT);
-        }
-    }
+    payload: Result<Json<Subnet>, JsonRejection>,
+) -> Result<(StatusCode, Json<i64>), Response> {
+    let Json(subnet) = payload.map_err(bad_body)?;
+    subnet.validate().map_err(IntoResponse::into_response)?;
+    ensure_template_exists(&state, subnet.template_id).await?;
+    ensure_no_overlap(&state, &subnet, None).await?;
 
     state
         .db
@@ -64,16 +151,273 @@ pub async fn create_subnet(
         .map(|id| (StatusCode::CREATED, Json(id)))
         .map_err(|e| {
             if is_unique_violation(&e) {
-                return StatusCode::CONFLICT;
+                return StatusCode::CONFLICT.into_response();
             }
             error!(
                 "Failed to create subnet (network={}/{}, gateway={}): {}",
                 subnet.network, subnet.netmask, subnet.gateway, e
             );
-            StatusCode::INTERNAL_SERVER_ERROR
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         })
 }
 
+/// A dynamic range created together with its subnet; it starts enabled
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewRange {
+    #[schema(value_type = String)]
+    pub range_start: Ipv4Addr,
+    #[schema(value_type = String)]
+    pub range_end: Ipv4Addr,
+}
+
+/// A static IP assignment created together with its subnet
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewStaticIp {
+    pub mac_address: String,
+    #[schema(value_type = String)]
+    pub ip_address: Ipv4Addr,
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+/// Request body for creating a subnet with its ranges and static IPs
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateSubnetFullRequest {
+    pub subnet: Subnet,
+    #[serde(default)]
+    pub ranges: Vec<NewRange>,
+    #[serde(default)]
+    pub static_ips: Vec<NewStaticIp>,
+}
+
+/// IDs of a subnet created with its ranges, the latter in request order
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateSubnetFullResponse {
+    pub subnet_id: i64,
+    pub range_ids: Vec<i64>,
+}
+
+/// Record the violations of `errors` under the `prefix` of a list element,
+/// e.g. `ranges[1].range_start`
+fn add_nested(target: &mut ValidationErrors, prefix: &str, errors: ValidationErrors) {
+    for error in errors.errors {
+        target.add(&format!("{}.{}", prefix, error.field), error.message);
+    }
+}
+
+/// Create a subnet together with its dynamic ranges and static IPs
+///
+/// Everything is validated first, then created in a single transaction: if
+/// any part fails, nothing is created.
+#[utoipa::path(
+    post,
+    path = "/api/subnets/full",
+    tag = "subnets",
+    request_body = CreateSubnetFullRequest,
+    responses(
+        (status = 201, description = "Subnet, ranges and static IPs created", body = CreateSubnetFullResponse),
+        (status = 400, description = "Validation failed", body = ValidationErrors),
+        (status = 409, description = "The subnet overlaps an existing one, or a static IP is already assigned"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn create_subnet_full(
+    State(state): State<AppState>,
+    payload: Result<Json<CreateSubnetFullRequest>, JsonRejection>,
+) -> Result<(StatusCode, Json<CreateSubnetFullResponse>), Response> {
+    let Json(request) = payload.map_err(bad_body)?;
+    let subnet = request.subnet;
+
+    let mut errors = subnet.validate().err().unwrap_or_default();
+    let ranges: Vec<DynamicRange> = request
+        .ranges
+        .iter()
+        .map(|r| DynamicRange {
+            id: None,
+            subnet_id: 0,
+            range_start: r.range_start,
+            range_end: r.range_end,
+            enabled: true,
+            draining: false,
+        })
+        .collect();
+    for (i, range) in ranges.iter().enumerate() {
+        if let Err(e) = validate_range_in(range, &subnet) {
+            add_nested(&mut errors, &format!("ranges[{}]", i), e);
+        }
+    }
+    for (i, static_ip) in request.static_ips.iter().enumerate() {
+        let field = format!("static_ips[{}].ip_address", i);
+        let ip = static_ip.ip_address;
+        if !subnet.contains(ip) {
+            errors.add(
+                &field,
+                format!("{} is outside subnet {}/{}", ip, subnet.network, subnet.netmask),
+            );
+        } else if subnet.is_reserved(ip) {
+            errors.add(&field, format!("{} is the network, broadcast or gateway address", ip));
+        } else if request.static_ips[..i].iter().any(|s| s.ip_address == ip) {
+            errors.add(&field, format!("{} is assigned more than once", ip));
+        }
+        let mac = &static_ip.mac_address;
+        errors.check(
+            request.static_ips[..i]
+                .iter()
+                .any(|s| s.mac_address.eq_ignore_ascii_case(mac)),
+            &format!("static_ips[{}].mac_address", i),
+            format!("{} is assigned more than once", mac),
+        );
+    }
+    errors.into_result().map_err(IntoResponse::into_response)?;
+    ensure_template_exists(&state, subnet.template_id).await?;
+    ensure_no_overlap(&state, &subnet, None).await?;
+
+    let mut static_ips: Vec<StaticIP> = request
+        .static_ips
+        .into_iter()
+        .map(|s| StaticIP {
+            subnet_id: 0,
+            mac_address: s.mac_address,
+            ip_address: s.ip_address,
+            hostname: s.hostname,
+        })
+        .collect();
+    let (subnet_id, range_ids) = state
+        .db
+        .create_subnet_with_ranges(&subnet, &ranges, &static_ips)
+        .await
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                return StatusCode::CONFLICT.into_response();
+            }
+            error!(
+                "Failed to create subnet {}/{} with {} range(s) and {} static IP(s): {}",
+                subnet.network,
+                subnet.netmask,
+                ranges.len(),
+                static_ips.len(),
+                e
+            );
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?;
+    info!(
+        "Created subnet {}/{} (id={}) with {} range(s) and {} static IP(s)",
+        subnet.network,
+        subnet.netmask,
+        subnet_id,
+        range_ids.len(),
+        static_ips.len()
+    );
+    if let Some(iface) = arp_interface(&state, &subnet) {
+        for static_ip in &mut static_ips {
+            static_ip.subnet_id = subnet_id;
+        }
+        pin_neighbors(&state, iface, &static_ips).await;
+    }
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateSubnetFullResponse {
+            subnet_id,
+            range_ids,
+        }),
+    ))
+}
+
+/// List every address of a subnet held by an active lease or a static assignment
+#[utoipa::path(
+    get,
+    path = "/api/subnets/{id}/addresses",
+    tag = "subnets",
+    params(
+        ("id" = i64, Path, description = "Subnet ID")
+    ),
+    responses(
+        (status = 200, description = "Occupied addresses sorted by IP", body = Vec<SubnetAddress>),
+        (status = 404, description = "Subnet not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn list_subnet_addresses(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<SubnetAddress>>, StatusCode> {
+    match state.db.get_subnet(id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to get subnet id={}: {}", id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    state.db.list_subnet_addresses(id).await.map(Json).map_err(|e| {
+        error!("Failed to list addresses of subnet id={}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// One option of a reply, decoded
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ResolvedOption {
+    /// Option code (e.g. 6)
+    pub code: u8,
+    /// Option name (e.g. `dns_server`)
+    pub name: String,
+    /// Human-readable value (e.g. `8.8.8.8,8.8.4.4`)
+    pub value: String,
+}
+
+impl From<&DhcpOption> for ResolvedOption {
+    fn from(option: &DhcpOption) -> Self {
+        Self {
+            code: option.code(),
+            name: option.name().to_string(),
+            value: option.value().to_string(),
+        }
+    }
+}
+
+/// Options clients of a subnet receive
+///
+/// Resolved as for an ACK to a plain client: the subnet's own options, those
+/// it leaves unset taken from its option template, and the configured lease
+/// times. User class, PXE and relay specific options are not included.
+#[utoipa::path(
+    get,
+    path = "/api/subnets/{id}/options",
+    tag = "subnets",
+    params(
+        ("id" = i64, Path, description = "Subnet ID")
+    ),
+    responses(
+        (status = 200, description = "Options in the order they are sent", body = Vec<ResolvedOption>),
+        (status = 404, description = "Subnet not found"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn get_subnet_options(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<ResolvedOption>>, StatusCode> {
+    let subnet = state
+        .db
+        .get_subnet(id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get subnet id={}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let ack = DhcpServer::preview_ack(subnet, &state.config, &*state.db).await;
+    Ok(Json(
+        ack.options
+            .iter()
+            .filter(|o| !matches!(o, DhcpOption::MessageType(_)))
+            .map(ResolvedOption::from)
+            .collect(),
+    ))
+}
+
 /// Get a subnet by ID
 #[utoipa::path(
     get,
@@ -115,6 +459,7 @@ pub async fn get_subnet(
     request_body = Subnet,
     responses(
         (status = 200, description = "Subnet updated"),
+        (status = 400, description = "Validation failed", body = ValidationErrors),
         (status = 404, description = "Subnet not found"),
         (status = 500, description = "Internal server error")
     )
@@ -122,8 +467,12 @@ pub async fn get_subnet(
 pub async fn update_subnet(
     State(state): State<AppState>,
     Path(id): Path<i64>,
-    Json(subnet): Json<Subnet>,
-) -> Result<StatusCode, StatusCode> {
+    payload: Result<Json<Subnet>, JsonRejection>,
+) -> Result<StatusCode, Response> {
+    let Json(subnet) = payload.map_err(bad_body)?;
+    subnet.validate().map_err(IntoResponse::into_response)?;
+    ensure_template_exists(&state, subnet.template_id).await?;
+
     state
         .db
         .update_subnet(id, &subnet)
@@ -131,8 +480,424 @@ pub async fn update_subnet(
         .map(|_| StatusCode::OK)
         .map_err(|e| {
             error!("Failed to update subnet id={}: {}", id, e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })
+}
+
+/// Why a patched or renumbered subnet was refused inside the transaction
+/// storing it
+#[derive(Debug)]
+enum SubnetRejected {
+    Invalid(ValidationErrors),
+    Overlap,
+}
+
+impl std::fmt::Display for SubnetRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(errors) => write!(f, "{} validation error(s)", errors.errors.len()),
+            Self::Overlap => write!(f, "overlaps another subnet"),
+        }
+    }
+}
+
+impl std::error::Error for SubnetRejected {}
+
+/// Change only the given fields of a subnet
+///
+/// Fields missing from the body keep their stored value, and `null` clears
+/// `domain_name`, `default_lease_time` or `template_id`. The merged subnet must
+/// pass the same validation as a full update and must not overlap another
+/// subnet; both are checked in the transaction that stores it.
+#[utoipa::path(
+    patch,
+    path = "/api/subnets/{id}",
+    tag = "subnets",
+    params(
+        ("id" = i64, Path, description = "Subnet ID")
+    ),
+    request_body = SubnetPatch,
+    responses(
+        (status = 200, description = "Subnet updated", body = Subnet),
+        (status = 400, description = "Validation failed", body = ValidationErrors),
+        (status = 404, description = "Subnet not found"),
+        (status = 409, description = "The merged subnet overlaps another one"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn patch_subnet(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    payload: Result<Json<SubnetPatch>, JsonRejection>,
+) -> Result<Json<Subnet>, Response> {
+    let Json(patch) = payload.map_err(bad_body)?;
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to patch subnet id={}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    };
+
+    ensure_template_exists(&state, patch.template_id.flatten()).await?;
+
+    let check = |merged: &Subnet, others: &[Subnet]| -> anyhow::Result<()> {
+        merged.validate().map_err(SubnetRejected::Invalid)?;
+        let overlaps = others
+            .iter()
+            .any(|s| subnets_overlap(merged.network, merged.netmask, s.network, s.netmask));
+        if overlaps {
+            return Err(SubnetRejected::Overlap.into());
+        }
+        Ok(())
+    };
+    match state.db.patch_subnet(id, &patch, &check).await {
+        Ok(Some(subnet)) => Ok(Json(subnet)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => match e.downcast::<SubnetRejected>() {
+            Ok(SubnetRejected::Invalid(errors)) => Err(errors.into_response()),
+            Ok(SubnetRejected::Overlap) => Err(StatusCode::CONFLICT.into_response()),
+            Err(e) => Err(internal_error(e)),
+        },
+    }
+}
+
+/// Request body for cloning a subnet onto another network
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CloneSubnetRequest {
+    /// Network address of the copy; the prefix length is the source's
+    #[schema(value_type = String)]
+    pub network: Ipv4Addr,
+    /// Gateway of the copy
+    #[schema(value_type = String)]
+    pub gateway: Ipv4Addr,
+    /// Also copy the dynamic ranges, moved to the same host offsets in the new network
+    #[serde(default)]
+    pub include_ranges: bool,
+}
+
+/// Move `range` from `source` to the same host offsets within `network`
+fn rebase_range(range: &DynamicRange, source: &Subnet, network: Ipv4Addr) -> DynamicRange {
+    let host_bits = !source.mask_bits();
+    let base = u32::from(network) & source.mask_bits();
+    let rebase = |ip: Ipv4Addr| Ipv4Addr::from(base | (u32::from(ip) & host_bits));
+    DynamicRange {
+        id: None,
+        subnet_id: 0,
+        range_start: rebase(range.range_start),
+        range_end: rebase(range.range_end),
+        enabled: range.enabled,
+        draining: range.draining,
+    }
+}
+
+/// Create a copy of a subnet on another network
+///
+/// DNS servers, domain name and the authoritative flag are copied as is. With
+/// `include_ranges`, the source's dynamic ranges are recreated at the same host
+/// offsets; the subnet and its ranges are created in a single transaction.
+#[utoipa::path(
+    post,
+    path = "/api/subnets/{id}/clone",
+    tag = "subnets",
+    params(
+        ("id" = i64, Path, description = "ID of the subnet to copy")
+    ),
+    request_body = CloneSubnetRequest,
+    responses(
+        (status = 201, description = "Subnet cloned", body = Subnet),
+        (status = 400, description = "Validation failed", body = ValidationErrors),
+        (status = 404, description = "Source subnet not found"),
+        (status = 409, description = "The new network overlaps an existing subnet"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn clone_subnet(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(request): Json<CloneSubnetRequest>,
+) -> Result<(StatusCode, Json<Subnet>), Response> {
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to clone subnet id={}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    };
+    let source = state
+        .db
+        .get_subnet(id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+
+    let mut subnet = Subnet {
+        id: None,
+        network: request.network,
+        gateway: request.gateway,
+        ..source.clone()
+    };
+    subnet.validate().map_err(IntoResponse::into_response)?;
+    ensure_no_overlap(&state, &subnet, None).await?;
+
+    let ranges: Vec<DynamicRange> = if request.include_ranges {
+        state
+            .db
+            .list_ranges(Some(id))
+            .await
+            .map_err(internal_error)?
+            .iter()
+            .map(|range| rebase_range(range, &source, subnet.network))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let new_id = state
+        .db
+        .create_subnet_with_ranges(&subnet, &ranges, &[])
+        .await
+        .map(|(new_id, _)| new_id)
+        .map_err(|e| {
+            if is_unique_violation(&e) {
+                return StatusCode::CONFLICT.into_response();
+            }
+            internal_error(e)
+        })?;
+    info!(
+        "Cloned subnet {} into {}/{} (id={}, {} range(s))",
+        id,
+        subnet.network,
+        subnet.netmask,
+        new_id,
+        ranges.len()
+    );
+    subnet.id = Some(new_id);
+    Ok((StatusCode::CREATED, Json(subnet)))
+}
+
+/// Request body for moving a subnet to another prefix
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RenumberRequest {
+    /// New network address
+    #[schema(value_type = String)]
+    pub network: Ipv4Addr,
+    /// New prefix length
+    #[schema(value_type = u8, minimum = 0, maximum = 32)]
+    pub netmask: PrefixLen,
+    /// New gateway; defaults to the current one moved onto the new network
+    #[schema(value_type = Option<String>)]
+    pub gateway: Option<Ipv4Addr>,
+}
+
+/// Outcome of a renumbering: what moved to the new prefix and what did not
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RenumberResponse {
+    /// The subnet on its new prefix
+    pub subnet: Subnet,
+    /// Ranges on the new prefix; an end that no longer fits is cut to the last usable address
+    pub ranges_migrated: Vec<DynamicRange>,
+    /// Ranges whose start does not fit the new prefix, deleted
+    pub ranges_dropped: Vec<DynamicRange>,
+    /// Static IPs on the new prefix
+    pub static_ips_migrated: Vec<StaticIP>,
+    /// Static IPs that do not fit the new prefix or land on a reserved address, deleted
+    pub static_ips_dropped: Vec<StaticIP>,
+    /// Leases whose address changed, expired so their clients get a new one
+    pub leases_expired: Vec<Lease>,
+}
+
+/// Where `ip` of `source` lands in `target`: unchanged if `target` still
+/// contains it (e.g. when widening the prefix), otherwise at the same host
+/// offset if that fits within `target`'s host bits
+fn rebase_host(ip: Ipv4Addr, source: &Subnet, target: &Subnet) -> Option<Ipv4Addr> {
+    if target.contains(ip) {
+        return Some(ip);
+    }
+    let host = u32::from(ip) & !source.mask_bits();
+    if host & target.mask_bits() != 0 {
+        return None;
+    }
+    Some(Ipv4Addr::from(u32::from(target.network_address()) | host))
+}
+
+/// Move a subnet to a new prefix, keeping its ranges and static IPs
+///
+/// Ranges and static IPs keep their host offsets within the new prefix where
+/// these fit. Leases stay valid only when their address does not change; the
+/// others are expired. Everything is read, checked and applied in a single
+/// transaction.
+#[utoipa::path(
+    post,
+    path = "/api/subnets/{id}/renumber",
+    tag = "subnets",
+    params(
+        ("id" = i64, Path, description = "Subnet ID")
+    ),
+    request_body = RenumberRequest,
+    responses(
+        (status = 200, description = "Subnet renumbered", body = RenumberResponse),
+        (status = 400, description = "Validation failed", body = ValidationErrors),
+        (status = 404, description = "Subnet not found"),
+        (status = 409, description = "The new prefix overlaps another subnet"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn renumber_subnet(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    payload: Result<Json<RenumberRequest>, JsonRejection>,
+) -> Result<Json<RenumberResponse>, Response> {
+    let Json(request) = payload.map_err(bad_body)?;
+    let plan = |contents: &SubnetContents| plan_renumbering(&request, contents);
+    let (contents, renumbering) = match state.db.renumber_subnet(id, &plan).await {
+        Ok(Some(renumbered)) => renumbered,
+        Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => {
+            if is_unique_violation(&e) {
+                return Err(StatusCode::CONFLICT.into_response());
+            }
+            return Err(match e.downcast::<SubnetRejected>() {
+                Ok(SubnetRejected::Invalid(errors)) => errors.into_response(),
+                Ok(SubnetRejected::Overlap) => StatusCode::CONFLICT.into_response(),
+                Err(e) => {
+                    error!("Failed to renumber subnet id={}: {}", id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            });
+        }
+    };
+    let SubnetContents {
+        subnet: source,
+        ranges,
+        static_ips,
+        leases,
+        ..
+    } = contents;
+    let Renumbering {
+        subnet,
+        ranges: ranges_migrated,
+        static_ips: static_ips_migrated,
+        expired_leases,
+    } = renumbering;
+    let ranges_dropped: Vec<DynamicRange> = ranges
+        .into_iter()
+        .filter(|r| !ranges_migrated.iter().any(|m| m.id == r.id))
+        .collect();
+    let static_ips_dropped: Vec<StaticIP> = static_ips
+        .iter()
+        .filter(|s| !static_ips_migrated.iter().any(|m| m.mac_address == s.mac_address))
+        .cloned()
+        .collect();
+    let leases_expired: Vec<Lease> = leases
+        .into_iter()
+        .filter(|l| l.id.is_some_and(|lid| expired_leases.contains(&lid)))
+        .collect();
+    info!(
+        "Renumbered subnet {} from {}/{} to {}/{} ({} range(s), {} static IP(s) moved, {} dropped, {} lease(s) expired)",
+        id,
+        source.network,
+        source.netmask,
+        subnet.network,
+        subnet.netmask,
+        ranges_migrated.len(),
+        static_ips_migrated.len(),
+        ranges_dropped.len() + static_ips_dropped.len(),
+        leases_expired.len()
+    );
+
+    // Every static IP left its old address: re-pin those still reserved
+    if let Some(iface) = arp_interface(&state, &source) {
+        unpin_neighbors(&state, iface, &static_ips).await;
+    }
+    if let Some(iface) = arp_interface(&state, &subnet) {
+        pin_neighbors(&state, iface, &static_ips_migrated).await;
+    }
+
+    Ok(Json(RenumberResponse {
+        subnet,
+        ranges_migrated,
+        ranges_dropped,
+        static_ips_migrated,
+        static_ips_dropped,
+        leases_expired,
+    }))
+}
+
+/// Plan the move of `contents` onto the prefix of `request`, rejecting a
+/// target that is invalid or overlaps another subnet
+fn plan_renumbering(
+    request: &RenumberRequest,
+    contents: &SubnetContents,
+) -> anyhow::Result<Renumbering> {
+    let source = &contents.subnet;
+    let mut subnet = Subnet {
+        network: request.network,
+        netmask: request.netmask,
+        ..source.clone()
+    };
+    let gateway = request
+        .gateway
+        .or_else(|| rebase_host(source.gateway, source, &subnet));
+    match gateway {
+        Some(gateway) => subnet.gateway = gateway,
+        None => {
+            let mut errors = ValidationErrors::new();
+            errors.add(
+                "gateway",
+                format!("{} does not fit the new prefix, give one explicitly", source.gateway),
+            );
+            return Err(SubnetRejected::Invalid(errors).into());
+        }
+    }
+    subnet.validate().map_err(SubnetRejected::Invalid)?;
+    let overlaps = contents
+        .others
+        .iter()
+        .any(|s| subnets_overlap(subnet.network, subnet.netmask, s.network, s.netmask));
+    if overlaps {
+        return Err(SubnetRejected::Overlap.into());
+    }
+
+    let (_, last_usable) = subnet.usable_range();
+    let ranges = contents
+        .ranges
+        .iter()
+        .filter_map(|range| {
+            let start = rebase_host(range.range_start, source, &subnet)?;
+            if start > last_usable {
+                return None;
+            }
+            let end = rebase_host(range.range_end, source, &subnet).unwrap_or(last_usable);
+            Some(DynamicRange {
+                range_start: start,
+                range_end: end.min(last_usable),
+                ..range.clone()
+            })
+        })
+        .collect();
+    let static_ips = contents
+        .static_ips
+        .iter()
+        .filter_map(|static_ip| {
+            let ip_address = rebase_host(static_ip.ip_address, source, &subnet)
+                .filter(|ip| !subnet.is_reserved(*ip))?;
+            Some(StaticIP {
+                ip_address,
+                ..static_ip.clone()
+            })
         })
+        .collect();
+    let expired_leases = contents
+        .leases
+        .iter()
+        .filter(|l| {
+            rebase_host(l.ip_address, source, &subnet) != Some(l.ip_address)
+                || subnet.is_reserved(l.ip_address)
+        })
+        .filter_map(|l| l.id)
+        .collect();
+
+    Ok(Renumbering {
+        subnet,
+        ranges,
+        static_ips,
+        expired_leases,
+    })
 }
 
 /// Delete a subnet
@@ -152,15 +917,189 @@ pub async fn delete_subnet(
     State(state): State<AppState>,
     Path(id): Path<i64>,
 ) -> Result<StatusCode, StatusCode> {
-    state
+    let pinned = pinned_static_ips(&state, id).await;
+    state.db.delete_subnet(id).await.map_err(|e| {
+        error!("Failed to delete subnet id={}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Some((subnet, static_ips)) = pinned {
+        if let Some(iface) = arp_interface(&state, &subnet) {
+            unpin_neighbors(&state, iface, &static_ips).await;
+        }
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Request body for reserving the next free address of a subnet
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct AllocateRequest {
+    /// MAC address of the device the address is meant for (required)
+    pub mac_address: Option<String>,
+    /// Create a permanent static assignment instead of a reservation
+    #[serde(default, rename = "static")]
+    pub make_static: bool,
+    /// Optional hostname recorded with the allocation
+    pub hostname: Option<String>,
+    /// Reservation lifetime in seconds (ignored for static assignments)
+    pub ttl_secs: Option<u32>,
+}
+
+/// Address returned by the allocation endpoint
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AllocateResponse {
+    /// Allocated address
+    #[schema(value_type = String)]
+    pub ip_address: Ipv4Addr,
+    /// Whether a static assignment was created
+    #[serde(rename = "static")]
+    pub is_static: bool,
+    /// End of the reservation (Unix timestamp); absent for static assignments
+    pub expires_at: Option<i64>,
+}
+
+/// Reserve the next free address of a subnet
+///
+/// The address is picked with the same logic as DHCP DISCOVER handling and is
+/// recorded either as a short-lived lease (the default) or as a static
+/// assignment, so the DHCP server will not hand it to another client. An
+/// address taken between picking and recording it is skipped for the next
+/// free one, so concurrent allocations never return the same address.
+#[utoipa::path(
+    post,
+    path = "/api/subnets/{id}/allocate",
+    tag = "subnets",
+    params(
+        ("id" = i64, Path, description = "Subnet ID")
+    ),
+    request_body = AllocateRequest,
+    responses(
+        (status = 201, description = "Address allocated", body = AllocateResponse),
+        (status = 400, description = "Missing or invalid MAC address"),
+        (status = 404, description = "Subnet not found"),
+        (status = 409, description = "No free address left in the subnet's ranges"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+pub async fn allocate_address(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    body: Option<Json<AllocateRequest>>,
+) -> Result<(StatusCode, Json<AllocateResponse>), StatusCode> {
+    let Json(request) = body.unwrap_or_default();
+
+    let mac = request
+        .mac_address
+        .as_deref()
+        .and_then(MacAddress::from_string)
+        .ok_or(StatusCode::BAD_REQUEST)?
+        .to_string();
+
+    let subnet = state
         .db
-        .delete_subnet(id)
+        .get_subnet(id)
         .await
-        .map(|_| StatusCode::NO_CONTENT)
         .map_err(|e| {
-            error!("Failed to delete subnet id={}: {}", id, e);
+            error!("Failed to get subnet id={}: {}", id, e);
             StatusCode::INTERNAL_SERVER_ERROR
-        })
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let mut used = allocator::used_addresses(&*state.db, state.release_grace_secs, None)
+        .await
+        .map_err(|e| {
+            error!("Failed to list addresses in use: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let ttl = request.ttl_secs.unwrap_or(DEFAULT_RESERVATION_SECS);
+    // The address is only claimed if still free when written: one taken in
+    // the meantime is marked used and the next free one is tried
+    let (ip, lease) = loop {
+        let ip = allocator::next_free_ip(&*state.db, &subnet, &used, state.multi_range)
+            .await
+            .map_err(|e| {
+                error!("Failed to list ranges for subnet {}: {}", id, e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .ok_or(StatusCode::CONFLICT)?;
+
+        let now = chrono::Utc::now().timestamp();
+        let lease = (!request.make_static).then(|| Lease {
+            id: None,
+            subnet_id: id,
+            mac_address: mac.clone(),
+            ip_address: ip,
+            lease_start: now,
+            lease_end: now + ttl as i64,
+            hostname: request.hostname.clone(),
+            range_id: None,
+        });
+        let claimed = match &lease {
+            Some(lease) => state.db.create_lease_if_free(lease).await.map(|id| id.is_some()),
+            None => {
+                let static_ip = StaticIP {
+                    subnet_id: id,
+                    mac_address: mac.clone(),
+                    ip_address: ip,
+                    hostname: request.hostname.clone(),
+                };
+                state.db.create_static_ip_if_free(&static_ip).await
+            }
+        };
+        match claimed {
+            Ok(true) => break (ip, lease),
+            Ok(false) => {
+                debug!("{} was taken while allocating it, trying the next one", ip);
+                used.insert(ip);
+            }
+            // The MAC already has a static assignment
+            Err(e) if is_unique_violation(&e) => return Err(StatusCode::CONFLICT),
+            Err(e) => {
+                error!("Failed to allocate {} in subnet {} for {}: {}", ip, id, mac, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    };
+
+    let Some(lease) = lease else {
+        info!("Allocated {} as static assignment for {}", ip, mac);
+        if let Some(iface) = arp_interface(&state, &subnet) {
+            let static_ip = StaticIP {
+                subnet_id: id,
+                mac_address: mac.clone(),
+                ip_address: ip,
+                hostname: request.hostname.clone(),
+            };
+            pin_neighbors(&state, iface, &[static_ip]).await;
+        }
+        return Ok((
+            StatusCode::CREATED,
+            Json(AllocateResponse {
+                ip_address: ip,
+                is_static: true,
+                expires_at: None,
+            }),
+        ));
+    };
+    info!("Reserved {} for {}s (mac={})", ip, ttl, mac);
+    Ok((
+        StatusCode::CREATED,
+        Json(AllocateResponse {
+            ip_address: ip,
+            is_static: false,
+            expires_at: Some(lease.lease_end),
+        }),
+    ))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
+    #[test]
+    fn test_parse_ids() {
+        assert_eq!(parse_ids("1,2,3"), Some(vec![1, 2, 3]));
+        assert_eq!(parse_ids(" 4 , 5,"), Some(vec![4, 5]));
+        assert_eq!(parse_ids(""), Some(vec![]));
+        assert_eq!(parse_ids("1,abc"), None);
+    }
+}
@@ -2,12 +2,14 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
+use serde::{Deserialize, Serialize};
 use tracing::error;
+use utoipa::ToSchema;
 
 use crate::{
-    auth::token,
+    auth::{token, AuthenticatedToken},
     db::is_unique_violation,
     models::{ApiToken, CreateTokenRequest, CreateTokenResponse},
     AppState,
@@ -143,3 +145,56 @@ pub async fn toggle_token(
         },
     }
 }
+
+/// Identity of the caller, as seen by the authentication middleware
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WhoAmIResponse {
+    /// Whether a token was checked; false when authentication is disabled or
+    /// the request came through the Unix socket
+    pub authenticated: bool,
+
+    /// ID of the token that authenticated the request
+    pub token_id: Option<i64>,
+
+    /// Name of the token that authenticated the request
+    pub token_name: Option<String>,
+}
+
+/// Show which token authenticated the request
+#[utoipa::path(
+    get,
+    path = "/api/auth/whoami",
+    responses(
+        (status = 200, description = "Caller identity", body = WhoAmIResponse),
+        (status = 401, description = "Missing, invalid or disabled token"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "tokens"
+)]
+pub async fn whoami(
+    State(state): State<AppState>,
+    authenticated: Option<Extension<AuthenticatedToken>>,
+) -> Result<Json<WhoAmIResponse>, (StatusCode, &'static str)> {
+    let Some(Extension(authenticated)) = authenticated else {
+        return Ok(Json(WhoAmIResponse {
+            authenticated: false,
+            token_id: None,
+            token_name: None,
+        }));
+    };
+
+    let tokens = state.db.list_api_tokens().await.map_err(|e| {
+        error!("Failed to list tokens: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to look up token")
+    })?;
+    let name = tokens
+        .into_iter()
+        .find(|t| t.id == Some(authenticated.id))
+        .map(|t| t.name);
+
+    Ok(Json(WhoAmIResponse {
+        authenticated: true,
+        token_id: Some(authenticated.id),
+        token_name: name,
+    }))
+}
@@ -2,17 +2,30 @@ pub mod auth;
 pub mod config;
 pub mod db;
 pub mod dhcp;
+pub mod events;
 pub mod handlers;
+pub mod metrics;
 pub mod models;
 pub mod ra;
+pub mod request_id;
 pub mod utils;
+pub mod validation;
 
+pub use auth::ConnectionType;
 pub use config::{Config, RaConfig};
 pub use db::{create_database, Database, DynDatabase, InMemoryDatabase, SqliteDatabase};
-pub use models::{DynamicRange, IAPrefix, StaticIP, Subnet};
+pub use models::{DynamicRange, IAPrefix, OptionTemplate, PrefixLen, StaticIP, Subnet};
 pub use ra::RaServer;
 
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
 use std::sync::Arc;
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower_http::compression::{
+    predicate::{DefaultPredicate, Predicate, SizeAbove},
+    CompressionLayer,
+};
+use utils::network::{KernelNeighbors, NeighborTable};
 use utoipa::OpenApi;
 #[cfg(feature = "swagger-ui")]
 use utoipa_swagger_ui::SwaggerUi;
@@ -22,38 +35,92 @@ use utoipa_swagger_ui::SwaggerUi;
     paths(
         handlers::subnets::list_subnets,
         handlers::subnets::create_subnet,
+        handlers::subnets::create_subnet_full,
         handlers::subnets::get_subnet,
         handlers::subnets::update_subnet,
+        handlers::subnets::patch_subnet,
         handlers::subnets::delete_subnet,
+        handlers::subnets::allocate_address,
+        handlers::subnets::list_subnet_addresses,
+        handlers::subnets::clone_subnet,
+        handlers::subnets::renumber_subnet,
+        handlers::subnets::get_subnet_options,
         handlers::ranges::list_ranges,
         handlers::ranges::create_range,
         handlers::ranges::delete_range,
+        handlers::ranges::toggle_range,
+        handlers::ranges::set_range_draining,
         handlers::static_ips::list_static_ips,
         handlers::static_ips::create_static_ip,
         handlers::static_ips::delete_static_ip,
+        handlers::static_ips::delete_static_ips,
         handlers::static_ips::update_static_ip_hostname,
         handlers::leases::list_leases,
+        handlers::leases::get_lease_by_ip,
+        handlers::leases::import_leases,
+        handlers::events::list_events,
+        handlers::offers::list_offers,
+        handlers::config::diff_config,
+        handlers::option_templates::list_option_templates,
+        handlers::option_templates::create_option_template,
+        handlers::option_templates::get_option_template,
+        handlers::option_templates::update_option_template,
+        handlers::option_templates::delete_option_template,
+        handlers::maintenance::reap_leases,
+        handlers::maintenance::backup_database,
+        handlers::maintenance::refresh_cache,
         handlers::tokens::list_tokens,
         handlers::tokens::create_token,
         handlers::tokens::delete_token,
         handlers::tokens::toggle_token,
+        handlers::tokens::whoami,
         handlers::ia_prefixes::list_ia_prefixes,
         handlers::ia_prefixes::create_ia_prefix,
         handlers::ia_prefixes::get_ia_prefix,
         handlers::ia_prefixes::update_ia_prefix,
         handlers::ia_prefixes::delete_ia_prefix,
+        handlers::metrics::get_metrics,
     ),
     components(
         schemas(
             models::Subnet,
+            models::SubnetPatch,
             models::DynamicRange,
             models::StaticIP,
             models::Lease,
+            models::SubnetAddress,
+            models::AddressSource,
+            models::LeaseImport,
+            models::PendingOffer,
+            models::OptionTemplate,
+            handlers::leases::ImportLeasesResponse,
+            events::LeaseEvent,
+            events::LeaseEventKind,
             models::ApiToken,
             models::CreateTokenRequest,
             models::CreateTokenResponse,
+            handlers::tokens::WhoAmIResponse,
             models::IAPrefix,
+            handlers::subnets::AllocateRequest,
+            handlers::subnets::AllocateResponse,
+            handlers::subnets::CloneSubnetRequest,
+            handlers::subnets::CreateSubnetFullRequest,
+            handlers::subnets::CreateSubnetFullResponse,
+            handlers::subnets::NewRange,
+            handlers::subnets::NewStaticIp,
+            handlers::subnets::RenumberRequest,
+            handlers::subnets::RenumberResponse,
+            handlers::subnets::ResolvedOption,
             handlers::static_ips::UpdateHostnameRequest,
+            handlers::ranges::SetDrainingRequest,
+            handlers::static_ips::DeleteStaticIpsResponse,
+            handlers::maintenance::ReapLeasesResponse,
+            handlers::maintenance::BackupResponse,
+            db::seed::SubnetsDiff,
+            db::seed::SubnetDifference,
+            config::ReconcileMode,
+            validation::FieldError,
+            validation::ValidationErrors,
         )
     ),
     tags(
@@ -61,8 +128,14 @@ use utoipa_swagger_ui::SwaggerUi;
         (name = "ranges", description = "Dynamic range management endpoints"),
         (name = "static-ips", description = "Static IP management endpoints"),
         (name = "leases", description = "Lease information endpoints"),
+        (name = "events", description = "Recent lease lifecycle events"),
+        (name = "offers", description = "Addresses offered but not yet requested"),
+        (name = "config", description = "Configuration file compared with the database"),
+        (name = "option-templates", description = "Named sets of DHCP options shared by subnets"),
         (name = "tokens", description = "API token management endpoints"),
         (name = "ia-prefixes", description = "IPv6 prefix (IA Prefix) management for Router Advertisement"),
+        (name = "maintenance", description = "Operational maintenance endpoints"),
+        (name = "metrics", description = "Prometheus metrics"),
     )
 )]
 pub struct ApiDoc;
@@ -72,11 +145,71 @@ pub struct ApiDoc;
 pub struct AppState {
     pub db: DynDatabase,
     pub ra_config: Arc<RaConfig>,
+    /// Destination of database backups, if enabled
+    pub backup_path: Option<PathBuf>,
+    /// Interfaces whose ARP table holds the static IPs; empty disables it
+    pub static_arp_interfaces: Vec<String>,
+    /// Neighbour table the static IPs are pinned into
+    pub neighbors: Arc<dyn NeighborTable>,
+    /// Seconds a released address is held back from allocation
+    pub release_grace_secs: u64,
+    /// How allocations are spread over a subnet's ranges
+    pub multi_range: config::MultiRangeStrategy,
+    /// Server configuration, to preview the replies it builds
+    pub config: Arc<Config>,
+    /// Configuration file the server was started from, re-read to preview
+    /// edits before a restart applies them
+    pub config_path: Option<PathBuf>,
 }
 
 impl AppState {
     pub fn new(db: DynDatabase, ra_config: Arc<RaConfig>) -> Self {
-        Self { db, ra_config }
+        Self {
+            db,
+            ra_config,
+            backup_path: None,
+            static_arp_interfaces: Vec::new(),
+            neighbors: Arc::new(KernelNeighbors),
+            release_grace_secs: 0,
+            multi_range: config::MultiRangeStrategy::default(),
+            config: Arc::new(Config::default()),
+            config_path: None,
+        }
+    }
+
+    pub fn with_backup_path(mut self, backup_path: Option<PathBuf>) -> Self {
+        self.backup_path = backup_path;
+        self
+    }
+
+    pub fn with_static_arp_interfaces(mut self, interfaces: Vec<String>) -> Self {
+        self.static_arp_interfaces = interfaces;
+        self
+    }
+
+    pub fn with_neighbor_table(mut self, neighbors: Arc<dyn NeighborTable>) -> Self {
+        self.neighbors = neighbors;
+        self
+    }
+
+    pub fn with_release_grace_secs(mut self, secs: u64) -> Self {
+        self.release_grace_secs = secs;
+        self
+    }
+
+    pub fn with_multi_range(mut self, strategy: config::MultiRangeStrategy) -> Self {
+        self.multi_range = strategy;
+        self
+    }
+
+    pub fn with_config(mut self, config: Arc<Config>) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_config_path(mut self, path: Option<PathBuf>) -> Self {
+        self.config_path = path;
+        self
     }
 }
 
@@ -88,9 +221,177 @@ pub fn create_router_with_auth(
     db: DynDatabase,
     ra_config: Arc<RaConfig>,
     require_auth: bool,
+    enable_swagger: bool,
 ) -> axum::Router {
-    let app = handlers::create_router_with_auth(db, ra_config, require_auth);
+    create_app(AppState::new(db, ra_config), require_auth, enable_swagger)
+}
+
+/// Build the full API application from a prepared state
+pub fn create_app(state: AppState, require_auth: bool, enable_swagger: bool) -> axum::Router {
+    let app = handlers::create_router_with_state(state, require_auth);
     #[cfg(feature = "swagger-ui")]
-    let app = app.merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()));
+    let app = if enable_swagger {
+        app.merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+    } else {
+        app
+    };
+    #[cfg(not(feature = "swagger-ui"))]
+    let _ = enable_swagger;
     app
 }
+
+/// Responses smaller than this are sent uncompressed
+pub const COMPRESSION_MIN_SIZE: u16 = 1024;
+
+/// Compress large responses with gzip or deflate for clients that send
+/// `Accept-Encoding`. Only worth applying to the TCP listener: Unix socket
+/// clients are local.
+pub fn with_compression(app: axum::Router) -> axum::Router {
+    app.layer(
+        CompressionLayer::new()
+            .gzip(true)
+            .deflate(true)
+            .compress_when(DefaultPredicate::new().and(SizeAbove::new(COMPRESSION_MIN_SIZE))),
+    )
+}
+
+/// Serve at most `max` requests at once; further ones wait for a slot.
+/// The limit is shared by all routes of `app`. `None` leaves it unlimited.
+pub fn with_concurrency_limit(app: axum::Router, max: Option<NonZeroUsize>) -> axum::Router {
+    match max {
+        // `Router::layer` wraps each route separately: the global variant
+        // makes them share one semaphore
+        Some(max) => app.layer(GlobalConcurrencyLimitLayer::new(max.get())),
+        None => app,
+    }
+}
+
+/// Tag every request with the listener it arrived on. The authentication
+/// middleware lets [`ConnectionType::UnixSocket`] requests through without a
+/// token, relying on the socket's file permissions instead.
+pub fn with_connection_type(app: axum::Router, connection: ConnectionType) -> axum::Router {
+    app.layer(axum::Extension(connection))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    async fn status(app: axum::Router, uri: &str) -> StatusCode {
+        app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    fn make_app(enable_swagger: bool) -> axum::Router {
+        create_router_with_auth(
+            Arc::new(InMemoryDatabase::new()),
+            Arc::new(RaConfig::default()),
+            false,
+            enable_swagger,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_swagger_routes_absent_when_disabled() {
+        for uri in ["/swagger-ui/", "/api-docs/openapi.json"] {
+            assert_eq!(status(make_app(false), uri).await, StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[cfg(feature = "swagger-ui")]
+    #[tokio::test]
+    async fn test_swagger_routes_present_when_enabled() {
+        assert_eq!(
+            status(make_app(true), "/api-docs/openapi.json").await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_list_is_gzip_compressed() {
+        let db = Arc::new(InMemoryDatabase::new());
+        for i in 0..100u8 {
+            db.create_subnet(&Subnet {
+                dns_servers: vec![std::net::Ipv4Addr::new(10, i, 0, 53)],
+                domain_name: Some("example.lan".to_string()),
+                ..Subnet::from_cidr(&format!("10.{i}.0.0/24")).unwrap()
+            })
+            .await
+            .unwrap();
+        }
+        let app = with_compression(create_router_with_auth(
+            db,
+            Arc::new(RaConfig::default()),
+            false,
+            false,
+        ));
+
+        let request = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header("accept-encoding", "gzip")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(request("/api/subnets")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+
+        // Tiny bodies are not worth compressing
+        let response = app.oneshot(request("/health")).await.unwrap();
+        assert!(response.headers().get("content-encoding").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requests_beyond_concurrency_limit_wait() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let (in_flight, peak) = (in_flight.clone(), peak.clone());
+            move || async move {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            }
+        };
+        let app = axum::Router::new()
+            .route("/slow", axum::routing::get(handler.clone()))
+            .route("/also-slow", axum::routing::get(handler));
+        let app = with_concurrency_limit(app, NonZeroUsize::new(2));
+
+        let requests = (0..8).map(|i| {
+            let uri = if i % 2 == 0 { "/slow" } else { "/also-slow" };
+            status(app.clone(), uri)
+        });
+        for status in futures::future::join_all(requests).await {
+            assert_eq!(status, StatusCode::OK);
+        }
+        // Every request was served, but never more than two at once
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unix_socket_requests_bypass_authentication() {
+        let app = create_router_with_auth(
+            Arc::new(InMemoryDatabase::new()),
+            Arc::new(RaConfig::default()),
+            true,
+            false,
+        );
+
+        let unix = with_connection_type(app.clone(), ConnectionType::UnixSocket);
+        assert_eq!(status(unix, "/api/subnets").await, StatusCode::OK);
+
+        let tcp = with_connection_type(app.clone(), ConnectionType::Tcp);
+        assert_eq!(status(tcp, "/api/subnets").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(app, "/api/subnets").await, StatusCode::UNAUTHORIZED);
+    }
+}
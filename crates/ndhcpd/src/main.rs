@@ -1,8 +1,12 @@
 use anyhow::Result;
 use clap::Parser;
 use ndhcpd::{
-    config::RaConfig, create_database, create_router_with_auth, dhcp::DhcpServer,
-    utils::logging::SyslogLayer, Config, RaServer,
+    config::RaConfig, create_app, create_database,
+    db::{seed, CachedDatabase, EncryptedDatabase},
+    dhcp::{self, probe::PingProber, DhcpServer},
+    utils::logging::{RollingFile, SyslogLayer},
+    with_compression, with_concurrency_limit, with_connection_type, AppState, Config,
+    ConnectionType, DynDatabase, RaServer,
 };
 use std::sync::Arc;
 use tower::ServiceExt;
@@ -29,12 +33,20 @@ struct Args {
     /// Unix socket path for API communication
     #[arg(short = 's', long)]
     unix_socket: Option<String>,
+
+    /// Print the JSON Schema of the configuration file and exit
+    #[arg(long)]
+    print_config_schema: bool,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let args = Args::parse();
 
+    if args.print_config_schema {
+        println!("{}", serde_json::to_string_pretty(&Config::json_schema())?);
+        return Ok(());
+    }
+
     // ── 1. Resolve config path (before tracing is ready) ─────────────────────
     let config_path = if std::path::Path::new(&args.config).exists() {
         args.config.clone()
@@ -50,9 +62,18 @@ async fn main() -> Result<()> {
     };
 
     // ── 2. Load configuration (before tracing so syslog opt is known) ────────
-    let mut config = Config::from_file(&config_path).unwrap_or_else(|_| Config::default());
+    let config = Config::from_file(&config_path).unwrap_or_else(|_| Config::default());
 
-    // ── 3. Initialize tracing (syslog if enabled, otherwise stdout) ──────────
+    // The runtime is sized from the configuration, so it is built by hand
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(worker_threads) = config.worker_threads {
+        runtime.worker_threads(worker_threads.get());
+    }
+    runtime.enable_all().build()?.block_on(run(args, config_path, config))
+}
+
+async fn run(args: Args, config_path: String, mut config: Config) -> Result<()> {
+    // ── 3. Initialize tracing (syslog and/or a log file, otherwise stdout) ───
     let default_filter = format!("ndhcpd={}", config.logging.level);
     let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| default_filter.as_str().into());
@@ -69,9 +90,33 @@ async fn main() -> Result<()> {
         (Some(tracing_subscriber::fmt::layer()), None)
     };
 
+    // A log file replaces stdout; if it cannot be opened, stdout stays.
+    // It is written from a background thread, which flushes what is still
+    // queued when `_log_guard` is dropped on exit.
+    let (stdout_layer, file_layer, _log_guard) = match &config.logging.file {
+        Some(path) => {
+            let max_size = config.logging.max_size_mb.saturating_mul(1024 * 1024);
+            match RollingFile::open(path, config.logging.rotation, max_size) {
+                Ok(file) => {
+                    let (writer, guard) = tracing_appender::non_blocking(file);
+                    let layer = tracing_subscriber::fmt::layer()
+                        .with_ansi(false)
+                        .with_writer(writer);
+                    (None, Some(layer), Some(guard))
+                }
+                Err(e) => {
+                    eprintln!("Warning: failed to open log file {path}: {e}, logging to stdout");
+                    (stdout_layer.or_else(|| Some(tracing_subscriber::fmt::layer())), None, None)
+                }
+            }
+        }
+        None => (stdout_layer, None, None),
+    };
+
     tracing_subscriber::registry()
         .with(env_filter)
         .with(stdout_layer)
+        .with(file_layer)
         .with(syslog_layer)
         .init();
 
@@ -108,7 +153,7 @@ async fn main() -> Result<()> {
 
     // Create RaConfig from config or use defaults
     let ra_config: Arc<RaConfig> =
-        Arc::new(config.ra.clone().unwrap_or_else(|| RaConfig::default()));
+        Arc::new(config.ra.clone().unwrap_or_default());
 
     // Initialize database
     let db_url = format!("sqlite:{}", config.database_path);
@@ -123,6 +168,62 @@ async fn main() -> Result<()> {
         }
     };
 
+    // Wrap the database with transparent PII encryption if requested
+    let db: DynDatabase = if config.security.encrypt_pii {
+        let key = match config.security.resolve_pii_key() {
+            Ok(k) => k,
+            Err(e) => {
+                error!("Failed to load PII encryption key: {}", e);
+                return Err(e);
+            }
+        };
+        let encrypted = EncryptedDatabase::new(db, &key);
+        if let Err(e) = encrypted.migrate_plaintext().await {
+            error!("Failed to encrypt existing plaintext rows: {}", e);
+            return Err(e);
+        }
+        info!("PII encryption at rest enabled");
+        Arc::new(encrypted)
+    } else {
+        db
+    };
+
+    // Reconcile the subnets declared in the configuration file
+    if let Some(subnets) = &config.subnets {
+        match seed::reconcile_subnets(subnets, db.as_ref()).await {
+            Ok(report) => info!(
+                "Reconciled configured subnets ({:?}): {} created, {} updated, {} deleted",
+                subnets.reconcile, report.created, report.updated, report.deleted
+            ),
+            Err(e) => {
+                error!("Failed to reconcile configured subnets: {}", e);
+                return Err(e);
+            }
+        }
+    }
+
+    // Serve the hot-path subnet lookups from memory
+    let db: DynDatabase = match config.dhcp.subnet_cache_ttl_secs {
+        0 => db,
+        ttl => Arc::new(CachedDatabase::new(db, std::time::Duration::from_secs(ttl))),
+    };
+
+    // Refuse to serve with an interface mapping that does not match the database
+    if let Err(e) = dhcp::interface_map::validate(&config, db.as_ref()).await {
+        error!("{}", e);
+        return Err(e);
+    }
+
+    // Static IPs created through the API are pinned in the ARP table (Linux only)
+    let static_arp_interfaces = if !config.dhcp.static_arp {
+        Vec::new()
+    } else if cfg!(target_os = "linux") {
+        config.listen_interfaces.clone()
+    } else {
+        warn!("dhcp.static_arp is only supported on Linux, ignoring it");
+        Vec::new()
+    };
+
     // Start API server
     let api_addr = format!("{}:{}", config.api.listen_address, config.api.port);
     let unix_socket_path = config.api.unix_socket.clone();
@@ -134,8 +235,20 @@ async fn main() -> Result<()> {
         // Remove existing socket file if it exists
         let _ = std::fs::remove_file(&socket_path);
 
-        // Unix socket: no authentication required
-        let app = create_router_with_auth(api_db_unix, ra_config.clone(), false);
+        // Unix socket: same router as TCP, the auth middleware exempts it
+        let state = AppState::new(api_db_unix, ra_config.clone())
+            .with_backup_path(config.backup_path.as_ref().map(Into::into))
+            .with_static_arp_interfaces(static_arp_interfaces.clone())
+            .with_release_grace_secs(config.dhcp.release_grace_seconds)
+            .with_multi_range(config.dhcp.multi_range)
+            .with_config(Arc::clone(&config))
+            .with_config_path(Some(config_path.clone().into()));
+        let require_auth = config.api.require_authentication.unwrap_or(false);
+        let app = with_connection_type(
+            create_app(state, require_auth, config.api.enable_swagger),
+            ConnectionType::UnixSocket,
+        );
+        let app = with_concurrency_limit(app, config.api.max_connections);
 
         let listener = tokio::net::UnixListener::bind(&socket_path).map_err(|e| {
             error!("Failed to bind Unix socket at {}: {}", socket_path, e);
@@ -205,7 +318,19 @@ async fn main() -> Result<()> {
     // Start TCP API server
     let api_db = Arc::clone(&db);
     let require_auth = config.api.require_authentication.unwrap_or(false);
-    let app = create_router_with_auth(api_db, ra_config, require_auth);
+    let state = AppState::new(api_db, ra_config)
+        .with_backup_path(config.backup_path.as_ref().map(Into::into))
+        .with_static_arp_interfaces(static_arp_interfaces)
+        .with_release_grace_secs(config.dhcp.release_grace_seconds)
+        .with_multi_range(config.dhcp.multi_range)
+        .with_config(Arc::clone(&config))
+        .with_config_path(Some(config_path.into()));
+    let app = with_connection_type(
+        create_app(state, require_auth, config.api.enable_swagger),
+        ConnectionType::Tcp,
+    );
+    let app = with_compression(app);
+    let app = with_concurrency_limit(app, config.api.max_connections);
 
     let listener = tokio::net::TcpListener::bind(&api_addr)
         .await
@@ -235,7 +360,7 @@ async fn main() -> Result<()> {
     });
 
     // Start Router Advertisement server (IPv6) if enabled
-    if config.ra.as_ref().map_or(false, |ra| ra.enabled) {
+    if config.ra.as_ref().is_some_and(|ra| ra.enabled) {
         let ra_server = RaServer::new(Arc::clone(&config), Arc::clone(&db));
         tokio::spawn(async move {
             if let Err(e) = ra_server.run().await {
@@ -245,7 +370,10 @@ async fn main() -> Result<()> {
     }
 
     // Start DHCP server
-    let dhcp_server = DhcpServer::new(Arc::clone(&config), Arc::clone(&db));
+    let mut dhcp_server = DhcpServer::new(Arc::clone(&config), Arc::clone(&db));
+    if config.dhcp.probe_before_offer {
+        dhcp_server = dhcp_server.with_prober(Arc::new(PingProber));
+    }
 
     info!(
         "DHCP server starting on interfaces: {:?}",
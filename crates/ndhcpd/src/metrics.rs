@@ -0,0 +1,317 @@
+//! Process-wide counters and histograms exposed in the Prometheus text exposition format.
+//!
+//! The registry is a plain `static` so that the DHCP packet handlers, which
+//! are free-standing associated functions, can record events without having
+//! a handle threaded through every call.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A monotonically increasing counter partitioned by label values.
+pub struct CounterVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Increment the counter identified by `label_values` (one per label, in order).
+    pub fn inc(&self, label_values: &[&str]) {
+        debug_assert_eq!(label_values.len(), self.labels.len());
+        let key = label_values.iter().map(|v| v.to_string()).collect();
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        *values.entry(key).or_insert(0) += 1;
+    }
+
+    /// Current value of the counter identified by `label_values`.
+    pub fn get(&self, label_values: &[&str]) -> u64 {
+        let key: Vec<String> = label_values.iter().map(|v| v.to_string()).collect();
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        values.get(&key).copied().unwrap_or(0)
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        for (key, value) in values.iter() {
+            let _ = writeln!(out, "{}{} {}", self.name, render_labels(self.labels, key), value);
+        }
+    }
+}
+
+/// Upper bounds, in seconds, of the buckets used for DHCP handling latency.
+/// Handling is dominated by database lookups, so the range spans a fast
+/// in-memory answer up to the point where clients start retransmitting.
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Observations of one label set: per-bucket (non-cumulative) counts, plus
+/// the sum and count of every observation.
+#[derive(Default)]
+struct HistogramSeries {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// A histogram with fixed buckets, partitioned by label values.
+pub struct HistogramVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    bounds: &'static [f64],
+    values: Mutex<BTreeMap<Vec<String>, HistogramSeries>>,
+}
+
+impl HistogramVec {
+    pub const fn new(
+        name: &'static str,
+        help: &'static str,
+        labels: &'static [&'static str],
+        bounds: &'static [f64],
+    ) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            bounds,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record `elapsed` for the series identified by `label_values`.
+    pub fn observe(&self, label_values: &[&str], elapsed: Duration) {
+        debug_assert_eq!(label_values.len(), self.labels.len());
+        let secs = elapsed.as_secs_f64();
+        let key = label_values.iter().map(|v| v.to_string()).collect();
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        let series = values.entry(key).or_default();
+        if series.buckets.is_empty() {
+            series.buckets = vec![0; self.bounds.len()];
+        }
+        // Observations above the last bound only show up in the +Inf bucket
+        if let Some(i) = self.bounds.iter().position(|bound| secs <= *bound) {
+            series.buckets[i] += 1;
+        }
+        series.sum += secs;
+        series.count += 1;
+    }
+
+    /// Number of observations recorded for `label_values`.
+    pub fn count(&self, label_values: &[&str]) -> u64 {
+        let key: Vec<String> = label_values.iter().map(|v| v.to_string()).collect();
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        values.get(&key).map_or(0, |series| series.count)
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+        let mut names = self.labels.to_vec();
+        names.push("le");
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        for (key, series) in values.iter() {
+            let mut bucket_key = key.clone();
+            bucket_key.push(String::new());
+            let mut cumulative = 0;
+            for (bound, count) in self.bounds.iter().zip(&series.buckets) {
+                cumulative += count;
+                bucket_key[key.len()] = bound.to_string();
+                let labels = render_labels(&names, &bucket_key);
+                let _ = writeln!(out, "{}_bucket{} {}", self.name, labels, cumulative);
+            }
+            bucket_key[key.len()] = "+Inf".to_string();
+            let labels = render_labels(&names, &bucket_key);
+            let _ = writeln!(out, "{}_bucket{} {}", self.name, labels, series.count);
+            let labels = render_labels(self.labels, key);
+            let _ = writeln!(out, "{}_sum{} {}", self.name, labels, series.sum);
+            let _ = writeln!(out, "{}_count{} {}", self.name, labels, series.count);
+        }
+    }
+}
+
+/// Format a `{name="value",...}` label set, escaping values per the exposition format.
+fn render_labels(names: &[&str], values: &[String]) -> String {
+    if names.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = names
+        .iter()
+        .zip(values)
+        .map(|(n, v)| {
+            let escaped = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", n, escaped)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// All metrics exported by the daemon.
+pub struct Metrics {
+    /// Server-origin messages (OFFER/ACK/NAK) received on the server port,
+    /// which indicate another DHCP server or a relay loop on the segment.
+    pub rogue_server_packets: CounterVec,
+    /// Received packets carrying more options than `dhcp.max_options`.
+    pub truncated_option_packets: CounterVec,
+    /// Failed database calls on the DHCP packet path, retries included.
+    pub db_errors: CounterVec,
+    /// Relayed packets whose giaddr lies in no configured subnet.
+    pub unknown_relay_subnet: CounterVec,
+    /// Packets dropped because their hardware type is not served.
+    pub unsupported_hardware_packets: CounterVec,
+    /// DISCOVERs resent by their client with a higher `secs`, i.e. after
+    /// waiting in vain for an OFFER.
+    pub client_retransmissions: CounterVec,
+    /// Time spent handling one received DHCP packet, by its message type.
+    pub handle_duration: HistogramVec,
+}
+
+pub static METRICS: Metrics = Metrics {
+    rogue_server_packets: CounterVec::new(
+        "ndhcpd_rogue_server_packets_total",
+        "Server-origin DHCP messages received from another host",
+        &["message_type"],
+    ),
+    truncated_option_packets: CounterVec::new(
+        "ndhcpd_truncated_option_packets_total",
+        "Received DHCP packets whose options were cut off at the configured cap",
+        &[],
+    ),
+    db_errors: CounterVec::new(
+        "ndhcpd_db_errors_total",
+        "Database calls from the DHCP server that returned an error",
+        &["operation"],
+    ),
+    unknown_relay_subnet: CounterVec::new(
+        "ndhcpd_unknown_relay_subnet_packets_total",
+        "Relayed DHCP packets whose relay address matches no configured subnet",
+        &[],
+    ),
+    unsupported_hardware_packets: CounterVec::new(
+        "ndhcpd_unsupported_hardware_packets_total",
+        "DHCP packets dropped because their hardware type is not served",
+        &["htype"],
+    ),
+    client_retransmissions: CounterVec::new(
+        "ndhcpd_dhcp_client_retransmissions_total",
+        "DISCOVERs retransmitted by clients that had not received an OFFER in time",
+        &[],
+    ),
+    handle_duration: HistogramVec::new(
+        "ndhcpd_dhcp_handle_duration_seconds",
+        "Time spent handling a received DHCP packet, database lookups included",
+        &["message_type"],
+        LATENCY_BUCKETS,
+    ),
+};
+
+/// Render a gauge family whose `samples` (label value, value) are computed
+/// at scrape time rather than recorded as events happen.
+pub fn render_gauge(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    label: &str,
+    samples: &[(String, u64)],
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for (value, sample) in samples {
+        let labels = render_labels(&[label], std::slice::from_ref(value));
+        let _ = writeln!(out, "{}{} {}", name, labels, sample);
+    }
+}
+
+/// Render every metric in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    METRICS.rogue_server_packets.render(&mut out);
+    METRICS.truncated_option_packets.render(&mut out);
+    METRICS.db_errors.render(&mut out);
+    METRICS.unknown_relay_subnet.render(&mut out);
+    METRICS.unsupported_hardware_packets.render(&mut out);
+    METRICS.client_retransmissions.render(&mut out);
+    METRICS.handle_duration.render(&mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_vec_inc_and_get() {
+        let counter = CounterVec::new("test_total", "Test counter", &["a"]);
+        assert_eq!(counter.get(&["x"]), 0);
+        counter.inc(&["x"]);
+        counter.inc(&["x"]);
+        counter.inc(&["y"]);
+        assert_eq!(counter.get(&["x"]), 2);
+        assert_eq!(counter.get(&["y"]), 1);
+    }
+
+    #[test]
+    fn test_counter_vec_render() {
+        let counter = CounterVec::new("test_total", "Test counter", &["a", "b"]);
+        counter.inc(&["x", "quo\"te"]);
+        let mut out = String::new();
+        counter.render(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_total Test counter\n# TYPE test_total counter\ntest_total{a=\"x\",b=\"quo\\\"te\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn test_render_gauge() {
+        let mut out = String::new();
+        render_gauge(&mut out, "test_free", "Test gauge", "subnet", &[("10.0.0.0/24".to_string(), 7)]);
+        assert_eq!(
+            out,
+            "# HELP test_free Test gauge\n# TYPE test_free gauge\ntest_free{subnet=\"10.0.0.0/24\"} 7\n"
+        );
+    }
+
+    #[test]
+    fn test_histogram_vec_observe_and_render() {
+        let histogram = HistogramVec::new("test_seconds", "Test histogram", &["a"], &[0.1, 1.0]);
+        histogram.observe(&["x"], Duration::from_millis(50));
+        histogram.observe(&["x"], Duration::from_millis(500));
+        histogram.observe(&["x"], Duration::from_secs(5));
+        assert_eq!(histogram.count(&["x"]), 3);
+        assert_eq!(histogram.count(&["y"]), 0);
+
+        let mut out = String::new();
+        histogram.render(&mut out);
+        assert_eq!(
+            out,
+            "# HELP test_seconds Test histogram\n# TYPE test_seconds histogram\n\
+             test_seconds_bucket{a=\"x\",le=\"0.1\"} 1\n\
+             test_seconds_bucket{a=\"x\",le=\"1\"} 2\n\
+             test_seconds_bucket{a=\"x\",le=\"+Inf\"} 3\n\
+             test_seconds_sum{a=\"x\"} 5.55\n\
+             test_seconds_count{a=\"x\"} 3\n"
+        );
+    }
+}
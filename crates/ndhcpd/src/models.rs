@@ -12,7 +12,8 @@ pub struct Subnet {
     pub network: Ipv4Addr,
 
     /// Subnet mask (e.g., 24 for /24)
-    pub netmask: u8,
+    #[schema(value_type = u8, minimum = 0, maximum = 32)]
+    pub netmask: PrefixLen,
 
     /// Gateway/router address
     #[schema(value_type = String)]
@@ -22,10 +23,142 @@ pub struct Subnet {
     #[schema(value_type = Vec<String>)]
     pub dns_servers: Vec<Ipv4Addr>,
 
+    /// NTP servers sent as option 42 (comma-separated in DB)
+    #[serde(default)]
+    #[schema(value_type = Vec<String>)]
+    pub ntp_servers: Vec<Ipv4Addr>,
+
+    /// Domain name
+    pub domain_name: Option<String>,
+
+    /// NAK requests this server cannot grant on the subnet's segment instead
+    /// of staying silent, leaving other subnets to coexisting DHCP servers
+    #[serde(default)]
+    pub authoritative: bool,
+
+    /// Lease time in seconds for clients of this subnet, overriding
+    /// `dhcp.default_lease_time`; capped at `dhcp.max_lease_time`
+    #[serde(default)]
+    pub default_lease_time: Option<u32>,
+
+    /// Option template supplying the DNS servers, domain name and lease
+    /// time this subnet leaves unset
+    #[serde(default)]
+    pub template_id: Option<i64>,
+}
+
+/// A named set of DHCP options shared by several subnets. Each subnet
+/// referencing it keeps its own values and takes only those it leaves unset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct OptionTemplate {
+    pub id: Option<i64>,
+
+    /// Unique name (e.g., "office")
+    pub name: String,
+
+    /// DNS servers
+    #[serde(default)]
+    #[schema(value_type = Vec<String>)]
+    pub dns_servers: Vec<Ipv4Addr>,
+
     /// Domain name
+    #[serde(default)]
     pub domain_name: Option<String>,
+
+    /// Lease time in seconds, see [`Subnet::default_lease_time`]
+    #[serde(default)]
+    pub default_lease_time: Option<u32>,
+}
+
+impl OptionTemplate {
+    /// Convert DNS servers to comma-separated string for DB storage
+    pub fn dns_servers_to_string(&self) -> String {
+        self.dns_servers
+            .iter()
+            .map(|ip| ip.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Parse DNS servers from comma-separated string, see
+    /// [`Subnet::dns_servers_from_string`]
+    pub fn dns_servers_from_string(s: &str) -> Vec<Ipv4Addr> {
+        Subnet::dns_servers_from_string(s)
+    }
+}
+
+/// IPv4 prefix length, the `24` of `/24`. Always between 0 and 32, so a
+/// value above 32 fails at deserialization instead of reaching the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct PrefixLen(u8);
+
+impl PrefixLen {
+    /// `len` as a prefix length, unless it is above 32
+    pub fn new(len: u8) -> Result<Self, PrefixLenError> {
+        if len > 32 {
+            return Err(PrefixLenError(len));
+        }
+        Ok(Self(len))
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    /// Network mask as a host-order bitmask (e.g. /24 -> 0xffffff00)
+    pub fn mask_bits(self) -> u32 {
+        if self.0 == 0 {
+            0
+        } else {
+            !0u32 << (32 - self.0)
+        }
+    }
+
+    /// Network mask in dotted-quad form (e.g. /24 -> 255.255.255.0)
+    pub fn netmask(self) -> Ipv4Addr {
+        Ipv4Addr::from(self.mask_bits())
+    }
+}
+
+impl TryFrom<u8> for PrefixLen {
+    type Error = PrefixLenError;
+
+    fn try_from(len: u8) -> Result<Self, Self::Error> {
+        Self::new(len)
+    }
+}
+
+impl From<PrefixLen> for u8 {
+    fn from(len: PrefixLen) -> Self {
+        len.0
+    }
+}
+
+impl PartialEq<u8> for PrefixLen {
+    fn eq(&self, other: &u8) -> bool {
+        self.0 == *other
+    }
+}
+
+impl std::fmt::Display for PrefixLen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Error returned by [`PrefixLen::new`]: the length is above 32
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixLenError(pub u8);
+
+impl std::fmt::Display for PrefixLenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "prefix length must be between 0 and 32, got {}", self.0)
+    }
 }
 
+impl std::error::Error for PrefixLenError {}
+
 fn default_true() -> bool {
     true
 }
@@ -49,6 +182,11 @@ pub struct DynamicRange {
     /// Whether this range is enabled
     #[serde(default = "default_true")]
     pub enabled: bool,
+
+    /// Whether this range is being drained: no new address is allocated from
+    /// it, but clients holding a lease in it can still renew
+    #[serde(default)]
+    pub draining: bool,
 }
 
 /// A static IP assignment
@@ -91,10 +229,144 @@ pub struct Lease {
 
     /// Optional hostname
     pub hostname: Option<String>,
+
+    /// Dynamic range the address was allocated from; `None` for other
+    /// leases or once the range is deleted
+    #[serde(default)]
+    pub range_id: Option<i64>,
+}
+
+/// An address offered to a client that has not REQUESTed it yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PendingOffer {
+    /// Foreign key to subnet
+    pub subnet_id: i64,
+
+    /// MAC address of the client
+    pub mac_address: String,
+
+    /// Offered IP address
+    #[schema(value_type = String)]
+    pub ip_address: Ipv4Addr,
+
+    /// Transaction ID of the DISCOVER
+    pub xid: u32,
+
+    /// When the OFFER was sent (Unix timestamp)
+    pub offered_at: i64,
+
+    /// When the address stops being held for the client (Unix timestamp)
+    pub expires_at: i64,
+}
+
+/// Where an occupied address comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressSource {
+    Lease,
+    Static,
+}
+
+/// An address in use within a subnet, either leased or statically assigned
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubnetAddress {
+    #[schema(value_type = String)]
+    pub ip: Ipv4Addr,
+
+    /// MAC address of the occupant
+    pub mac: String,
+
+    /// Optional hostname
+    pub hostname: Option<String>,
+
+    pub source: AddressSource,
 }
 
+/// A lease carried over from another DHCP server, before it is matched to a subnet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LeaseImport {
+    /// MAC address of the client
+    pub mac_address: String,
+
+    /// Leased IP address
+    #[schema(value_type = String)]
+    pub ip_address: Ipv4Addr,
+
+    /// Lease start time (Unix timestamp)
+    pub lease_start: i64,
+
+    /// Lease end time (Unix timestamp)
+    pub lease_end: i64,
+
+    /// Optional hostname
+    pub hostname: Option<String>,
+}
+
+/// Error returned by [`Subnet::from_cidr`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CidrError {
+    /// No `/prefix` part
+    MissingPrefix,
+    /// The part before the slash is not an IPv4 address
+    InvalidAddress(String),
+    /// The prefix length is not a number between 1 and 32
+    InvalidPrefix(String),
+    /// The address is not the network address of the prefix
+    HostBitsSet,
+}
+
+impl std::fmt::Display for CidrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingPrefix => write!(f, "missing /prefix length"),
+            Self::InvalidAddress(addr) => write!(f, "{:?} is not an IPv4 address", addr),
+            Self::InvalidPrefix(prefix) => {
+                write!(f, "prefix length must be between 1 and 32, got {:?}", prefix)
+            }
+            Self::HostBitsSet => write!(f, "address has host bits set for its prefix length"),
+        }
+    }
+}
+
+impl std::error::Error for CidrError {}
+
 // Helper functions for converting between String and Ipv4Addr for sqlx
 impl Subnet {
+    /// Subnet for a CIDR block such as `192.168.1.0/24`, with the first usable
+    /// address as gateway and no DNS servers or domain name
+    pub fn from_cidr(cidr: &str) -> Result<Self, CidrError> {
+        let (network, prefix) = cidr.trim().split_once('/').ok_or(CidrError::MissingPrefix)?;
+        let network: Ipv4Addr = network
+            .parse()
+            .map_err(|_| CidrError::InvalidAddress(network.to_string()))?;
+        let netmask = prefix
+            .parse::<u8>()
+            .ok()
+            .filter(|len| *len > 0)
+            .and_then(|len| PrefixLen::new(len).ok())
+            .ok_or_else(|| CidrError::InvalidPrefix(prefix.to_string()))?;
+
+        let mut subnet = Subnet {
+            id: None,
+            network,
+            netmask,
+            gateway: network,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            domain_name: None,
+            authoritative: false,
+            default_lease_time: None,
+            template_id: None,
+        };
+        if subnet.network_address() != network {
+            return Err(CidrError::HostBitsSet);
+        }
+        subnet.gateway = subnet.usable_range().0;
+        Ok(subnet)
+    }
+
+    /// Convert DNS servers to comma-separated string for DB storage, in the
+    /// same dotted form the API uses
     pub fn dns_servers_to_string(&self) -> String {
         self.dns_servers
             .iter()
@@ -103,11 +375,166 @@ impl Subnet {
             .join(",")
     }
 
+    /// Parse DNS servers from comma-separated string. Whitespace around
+    /// entries is ignored, as are empty and unparseable entries.
     pub fn dns_servers_from_string(s: &str) -> Vec<Ipv4Addr> {
         s.split(',')
             .filter_map(|ip| ip.trim().parse().ok())
             .collect()
     }
+
+    /// Convert NTP servers to comma-separated string for DB storage
+    pub fn ntp_servers_to_string(&self) -> String {
+        self.ntp_servers
+            .iter()
+            .map(|ip| ip.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Parse NTP servers from comma-separated string, see
+    /// [`dns_servers_from_string`](Self::dns_servers_from_string)
+    pub fn ntp_servers_from_string(s: &str) -> Vec<Ipv4Addr> {
+        Self::dns_servers_from_string(s)
+    }
+
+    /// Network mask as a host-order bitmask (e.g. /24 -> 0xffffff00)
+    pub fn mask_bits(&self) -> u32 {
+        self.netmask.mask_bits()
+    }
+
+    /// Returns true if `ip` belongs to this subnet's network.
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        let mask = self.mask_bits();
+        u32::from(ip) & mask == u32::from(self.network) & mask
+    }
+
+    /// Network address with any host bits of `network` cleared
+    pub fn network_address(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.network) & self.mask_bits())
+    }
+
+    /// Directed broadcast address (all host bits set)
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.network) | !self.mask_bits())
+    }
+
+    /// First and last assignable host addresses. The network and broadcast
+    /// addresses are excluded, except on /31 point-to-point links (RFC 3021)
+    /// and /32 single hosts where every address is usable.
+    pub fn usable_range(&self) -> (Ipv4Addr, Ipv4Addr) {
+        let (first, last) = (self.network_address(), self.broadcast());
+        if self.netmask.get() >= 31 {
+            (first, last)
+        } else {
+            (Ipv4Addr::from(u32::from(first) + 1), Ipv4Addr::from(u32::from(last) - 1))
+        }
+    }
+
+    /// Whether `ip` is this subnet's network, broadcast or gateway address,
+    /// none of which may be leased. /31 and /32 have no network or broadcast
+    /// address, see [`usable_range`](Self::usable_range).
+    pub fn is_reserved(&self, ip: Ipv4Addr) -> bool {
+        let (first, last) = self.usable_range();
+        ip == self.gateway || (self.contains(ip) && !(first..=last).contains(&ip))
+    }
+
+    /// Number of assignable host addresses, see [`usable_range`](Self::usable_range)
+    pub fn host_count(&self) -> u64 {
+        let (first, last) = self.usable_range();
+        u64::from(u32::from(last) - u32::from(first)) + 1
+    }
+
+    /// This subnet with the options it leaves unset (no DNS servers, no
+    /// domain name, no lease time) taken from `template`
+    pub fn with_template(mut self, template: &OptionTemplate) -> Self {
+        if self.dns_servers.is_empty() {
+            self.dns_servers = template.dns_servers.clone();
+        }
+        if self.domain_name.is_none() {
+            self.domain_name = template.domain_name.clone();
+        }
+        if self.default_lease_time.is_none() {
+            self.default_lease_time = template.default_lease_time;
+        }
+        self
+    }
+}
+
+/// Partial update of a subnet: absent fields keep their current value.
+/// For the optional fields, an explicit `null` clears the value.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct SubnetPatch {
+    #[schema(value_type = Option<String>)]
+    pub network: Option<Ipv4Addr>,
+
+    #[schema(value_type = Option<u8>, minimum = 0, maximum = 32)]
+    pub netmask: Option<PrefixLen>,
+
+    #[schema(value_type = Option<String>)]
+    pub gateway: Option<Ipv4Addr>,
+
+    #[schema(value_type = Option<Vec<String>>)]
+    pub dns_servers: Option<Vec<Ipv4Addr>>,
+
+    #[schema(value_type = Option<Vec<String>>)]
+    pub ntp_servers: Option<Vec<Ipv4Addr>>,
+
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    pub domain_name: Option<Option<String>>,
+
+    pub authoritative: Option<bool>,
+
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<u32>)]
+    pub default_lease_time: Option<Option<u32>>,
+
+    #[serde(default, deserialize_with = "present")]
+    #[schema(value_type = Option<i64>)]
+    pub template_id: Option<Option<i64>>,
+}
+
+/// Tell a field sent as `null` (`Some(None)`) from a missing one (`None`)
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl SubnetPatch {
+    /// Overwrite the fields of `subnet` that this patch sets
+    pub fn apply(&self, subnet: &mut Subnet) {
+        if let Some(network) = self.network {
+            subnet.network = network;
+        }
+        if let Some(netmask) = self.netmask {
+            subnet.netmask = netmask;
+        }
+        if let Some(gateway) = self.gateway {
+            subnet.gateway = gateway;
+        }
+        if let Some(dns_servers) = &self.dns_servers {
+            subnet.dns_servers = dns_servers.clone();
+        }
+        if let Some(ntp_servers) = &self.ntp_servers {
+            subnet.ntp_servers = ntp_servers.clone();
+        }
+        if let Some(domain_name) = &self.domain_name {
+            subnet.domain_name = domain_name.clone();
+        }
+        if let Some(authoritative) = self.authoritative {
+            subnet.authoritative = authoritative;
+        }
+        if let Some(default_lease_time) = self.default_lease_time {
+            subnet.default_lease_time = default_lease_time;
+        }
+        if let Some(template_id) = self.template_id {
+            subnet.template_id = template_id;
+        }
+    }
 }
 
 /// An API token for authentication
@@ -213,6 +640,77 @@ mod tests {
     use super::*;
     use std::net::Ipv6Addr;
 
+    fn cidr(network: Ipv4Addr, netmask: u8) -> Subnet {
+        Subnet {
+            id: None,
+            network,
+            netmask: PrefixLen::new(netmask).unwrap(),
+            gateway: network,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            domain_name: None,
+            authoritative: false,
+            default_lease_time: None,
+            template_id: None,
+        }
+    }
+
+    #[test]
+    fn test_subnet_cidr_helpers() {
+        let subnet = cidr(Ipv4Addr::new(192, 168, 1, 77), 24);
+        assert_eq!(subnet.network_address(), Ipv4Addr::new(192, 168, 1, 0));
+        assert_eq!(subnet.broadcast(), Ipv4Addr::new(192, 168, 1, 255));
+        assert_eq!(
+            subnet.usable_range(),
+            (Ipv4Addr::new(192, 168, 1, 1), Ipv4Addr::new(192, 168, 1, 254))
+        );
+        assert_eq!(subnet.host_count(), 254);
+
+        assert_eq!(cidr(Ipv4Addr::new(10, 0, 0, 0), 8).host_count(), 16_777_214);
+        assert_eq!(cidr(Ipv4Addr::new(10, 0, 0, 0), 31).host_count(), 2);
+        assert_eq!(cidr(Ipv4Addr::new(10, 0, 0, 5), 32).host_count(), 1);
+        assert_eq!(cidr(Ipv4Addr::new(0, 0, 0, 0), 0).host_count(), 4_294_967_294);
+    }
+
+    #[test]
+    fn test_prefix_len_bounds() {
+        let any = PrefixLen::new(0).unwrap();
+        assert_eq!(any.netmask(), Ipv4Addr::new(0, 0, 0, 0));
+        let host = PrefixLen::new(32).unwrap();
+        assert_eq!(host.netmask(), Ipv4Addr::new(255, 255, 255, 255));
+        assert_eq!(PrefixLen::new(24).unwrap().netmask(), Ipv4Addr::new(255, 255, 255, 0));
+        assert_eq!(PrefixLen::new(33), Err(PrefixLenError(33)));
+
+        assert_eq!(serde_json::from_str::<PrefixLen>("32").unwrap(), host);
+        assert_eq!(serde_json::to_string(&host).unwrap(), "32");
+        let err = serde_json::from_str::<PrefixLen>("33").unwrap_err().to_string();
+        assert!(err.contains("between 0 and 32, got 33"), "{err}");
+    }
+
+    #[test]
+    fn test_subnet_from_cidr() {
+        let subnet = Subnet::from_cidr("192.168.1.0/24").unwrap();
+        assert_eq!(subnet.network, Ipv4Addr::new(192, 168, 1, 0));
+        assert_eq!(subnet.netmask, 24);
+        assert_eq!(subnet.gateway, Ipv4Addr::new(192, 168, 1, 1));
+
+        let host = Subnet::from_cidr("10.0.0.5/32").unwrap();
+        assert_eq!((host.netmask.get(), host.gateway), (32, Ipv4Addr::new(10, 0, 0, 5)));
+
+        assert_eq!(Subnet::from_cidr("192.168.1.0").unwrap_err(), CidrError::MissingPrefix);
+        assert_eq!(
+            Subnet::from_cidr("192.168.1/24").unwrap_err(),
+            CidrError::InvalidAddress("192.168.1".to_string())
+        );
+        for prefix in ["0", "33", "x", ""] {
+            assert_eq!(
+                Subnet::from_cidr(&format!("0.0.0.0/{}", prefix)).unwrap_err(),
+                CidrError::InvalidPrefix(prefix.to_string())
+            );
+        }
+        assert_eq!(Subnet::from_cidr("192.168.1.7/24").unwrap_err(), CidrError::HostBitsSet);
+    }
+
     #[test]
     fn test_ia_prefix_dns_servers_to_string_single() {
         let prefix = IAPrefix {
@@ -338,12 +836,9 @@ mod tests {
     #[test]
     fn test_subnet_dns_servers_to_string() {
         let subnet = Subnet {
-            id: None,
-            network: Ipv4Addr::new(192, 168, 1, 0),
-            netmask: 24,
-            gateway: Ipv4Addr::new(192, 168, 1, 1),
             dns_servers: vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(1, 1, 1, 1)],
             domain_name: Some("local".to_string()),
+            ..Subnet::from_cidr("192.168.1.0/24").unwrap()
         };
 
         assert_eq!(subnet.dns_servers_to_string(), "8.8.8.8,1.1.1.1");
@@ -357,4 +852,51 @@ mod tests {
         assert_eq!(result[0], Ipv4Addr::new(8, 8, 8, 8));
         assert_eq!(result[1], Ipv4Addr::new(1, 1, 1, 1));
     }
+
+    #[test]
+    fn test_subnet_dns_servers_from_string_with_spaces() {
+        let result = Subnet::dns_servers_from_string(" 8.8.8.8 , 1.1.1.1 ,,");
+
+        assert_eq!(result, vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(1, 1, 1, 1)]);
+        assert!(Subnet::dns_servers_from_string("").is_empty());
+    }
+
+    #[test]
+    fn test_subnet_api_and_db_representations_agree() {
+        let mut subnet = cidr(Ipv4Addr::new(192, 168, 1, 0), 24);
+        subnet.id = Some(1);
+        subnet.gateway = Ipv4Addr::new(192, 168, 1, 1);
+        subnet.dns_servers = vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(1, 1, 1, 1)];
+        subnet.domain_name = Some("lan".to_string());
+
+        let json = serde_json::to_value(&subnet).unwrap();
+        assert_eq!(json["network"], "192.168.1.0");
+        assert_eq!(json["gateway"], "192.168.1.1");
+        assert_eq!(json["dns_servers"], serde_json::json!(["8.8.8.8", "1.1.1.1"]));
+        let deserialized: Subnet = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(deserialized.network, subnet.network);
+        assert_eq!(deserialized.gateway, subnet.gateway);
+        assert_eq!(deserialized.dns_servers, subnet.dns_servers);
+
+        // The DB column holds the same addresses, in the same textual form
+        let stored = subnet.dns_servers_to_string();
+        let api: Vec<&str> = json["dns_servers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|ip| ip.as_str().unwrap())
+            .collect();
+        assert_eq!(stored, api.join(","));
+        assert_eq!(Subnet::dns_servers_from_string(&stored), subnet.dns_servers);
+
+        let template = OptionTemplate {
+            id: None,
+            name: "office".to_string(),
+            dns_servers: subnet.dns_servers.clone(),
+            domain_name: None,
+            default_lease_time: None,
+        };
+        assert_eq!(template.dns_servers_to_string(), stored);
+        assert_eq!(OptionTemplate::dns_servers_from_string(&stored), template.dns_servers);
+    }
 }
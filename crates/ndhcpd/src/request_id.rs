@@ -0,0 +1,45 @@
+//! `X-Request-Id` propagation for API requests.
+//!
+//! A client-supplied id is kept so client and server logs can be correlated;
+//! otherwise a random one is generated. The id is attached to a tracing span
+//! wrapping the request, so every log line emitted while handling it carries
+//! `request_id`, and it is echoed in the response headers.
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use rand::RngExt;
+use tracing::{info_span, Instrument};
+
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client-supplied id that is accepted as is
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Middleware assigning a request id, see the module documentation
+pub async fn request_id_middleware(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(generate);
+
+    let span = info_span!("request", request_id = %id);
+    let mut response = next.run(request).instrument(span).await;
+
+    // Only visible ASCII reaches here, so the header value is always valid
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    response
+}
+
+/// Random 128-bit id, hex-encoded
+fn generate() -> String {
+    format!("{:032x}", rand::rng().random::<u128>())
+}
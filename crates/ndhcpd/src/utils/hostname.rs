@@ -0,0 +1,109 @@
+//! Cleanup of client-supplied hostnames (option 12) before they are stored.
+//!
+//! Clients send arbitrary bytes: trailing NULs, spaces, apostrophes, even
+//! terminal escape sequences. The stored name ends up in logs, the leases
+//! table and DNS tooling, so it is reduced to RFC 1035 labels: letters,
+//! digits and inner hyphens, at most 63 characters per label and 253 overall.
+//! Domain names configured for option 15 are checked against the same rules.
+
+/// Longest label allowed by RFC 1035
+pub const MAX_LABEL_LEN: usize = 63;
+
+/// Longest name allowed by RFC 1035, in its dotted text form
+pub const MAX_HOSTNAME_LEN: usize = 253;
+
+/// Reduce `raw` to a valid hostname, or `None` if nothing usable is left.
+///
+/// Control characters are removed, any other invalid character becomes a
+/// hyphen (`John's iPhone` becomes `John-s-iPhone`), and over-long labels are
+/// truncated. Labels past the overall length cap are dropped.
+pub fn sanitize_hostname(raw: &str) -> Option<String> {
+    let mut name = String::new();
+    for label in raw.split('.') {
+        let label: String = label
+            .chars()
+            .filter(|c| !c.is_control())
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let label = label.trim_matches('-');
+        let label = label[..label.len().min(MAX_LABEL_LEN)].trim_end_matches('-');
+        if label.is_empty() {
+            continue;
+        }
+        let sep = usize::from(!name.is_empty());
+        if name.len() + sep + label.len() > MAX_HOSTNAME_LEN {
+            break;
+        }
+        if sep == 1 {
+            name.push('.');
+        }
+        name.push_str(label);
+    }
+    (!name.is_empty()).then_some(name)
+}
+
+/// Whether `name` is a valid domain name: dot-separated labels of letters,
+/// digits and inner hyphens within the RFC 1035 length limits. A trailing
+/// dot (fully qualified form) is accepted.
+pub fn is_valid_domain_name(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name);
+    !name.is_empty()
+        && name.len() <= MAX_HOSTNAME_LEN
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= MAX_LABEL_LEN
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_hostnames_are_kept() {
+        assert_eq!(sanitize_hostname("laptop").as_deref(), Some("laptop"));
+        assert_eq!(sanitize_hostname("nas-01.home.lan").as_deref(), Some("nas-01.home.lan"));
+    }
+
+    #[test]
+    fn test_empty_hostname_is_none() {
+        assert_eq!(sanitize_hostname(""), None);
+        assert_eq!(sanitize_hostname("\0"), None);
+        assert_eq!(sanitize_hostname(" - . "), None);
+    }
+
+    #[test]
+    fn test_control_characters_are_stripped() {
+        assert_eq!(sanitize_hostname("laptop\0").as_deref(), Some("laptop"));
+        assert_eq!(sanitize_hostname("evil\0host").as_deref(), Some("evilhost"));
+        assert_eq!(sanitize_hostname("\x1b[31mred\x1b[0m").as_deref(), Some("31mred-0m"));
+        assert_eq!(sanitize_hostname("John's iPhone").as_deref(), Some("John-s-iPhone"));
+    }
+
+    #[test]
+    fn test_domain_name_validity() {
+        assert!(is_valid_domain_name("lan"));
+        assert!(is_valid_domain_name("office.example.com"));
+        assert!(is_valid_domain_name("example.com."));
+        assert!(!is_valid_domain_name(""));
+        assert!(!is_valid_domain_name("my domain"));
+        assert!(!is_valid_domain_name("a..b"));
+        assert!(!is_valid_domain_name("-lan"));
+        assert!(!is_valid_domain_name("under_score.lan"));
+        assert!(!is_valid_domain_name(&"a".repeat(MAX_LABEL_LEN + 1)));
+    }
+
+    #[test]
+    fn test_over_length_hostname_is_capped() {
+        let long = "a".repeat(100);
+        assert_eq!(sanitize_hostname(&long), Some("a".repeat(MAX_LABEL_LEN)));
+
+        let labels = vec!["b".repeat(60); 10].join(".");
+        let name = sanitize_hostname(&labels).unwrap();
+        assert!(name.len() <= MAX_HOSTNAME_LEN);
+        assert_eq!(name.split('.').count(), 4);
+    }
+}
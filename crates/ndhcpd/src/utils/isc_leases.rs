@@ -0,0 +1,302 @@
+//! Reader for ISC dhcpd `dhcpd.leases` files, used to migrate existing leases.
+//!
+//! The file is an append-only journal: every change to a lease appends a new
+//! `lease <ip> { ... }` block, so the last block for an address wins. Only
+//! leases whose `binding state` is `active` are returned.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::NaiveDateTime;
+use dhcp_proto::MacAddress;
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+use crate::models::LeaseImport;
+use crate::utils::hostname::sanitize_hostname;
+
+/// Lexical unit of the lease file
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Open,
+    Close,
+    Semicolon,
+}
+
+fn tokenize(contents: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => {
+                // Comment up to the end of the line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            ';' => tokens.push(Token::Semicolon),
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => value.extend(chars.next()),
+                        Some(c) => value.push(c),
+                        None => bail!("unterminated string"),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || matches!(next, '{' | '}' | ';' | '"') {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Value of a token as text, whether quoted or not
+fn text(token: &Token) -> Option<&str> {
+    match token {
+        Token::Word(s) | Token::Quoted(s) => Some(s),
+        _ => None,
+    }
+}
+
+/// End of an infinite (`ends never`) lease: the last second a `u32` timestamp
+/// can hold, so adding lease times or grace periods to it cannot overflow.
+pub const NEVER: i64 = u32::MAX as i64;
+
+/// Convert the arguments of `starts`/`ends` to a Unix timestamp. ISC writes
+/// either `<weekday> YYYY/MM/DD HH:MM:SS` in UTC or `epoch <seconds>`;
+/// `never` (infinite leases) maps to [`NEVER`].
+fn parse_time(args: &[&str]) -> Result<i64> {
+    match args {
+        ["never"] => Ok(NEVER),
+        ["epoch", secs, ..] => secs.parse().with_context(|| format!("invalid epoch {secs}")),
+        [_weekday, date, time, ..] => {
+            let datetime = format!("{date} {time}");
+            NaiveDateTime::parse_from_str(&datetime, "%Y/%m/%d %H:%M:%S")
+                .map(|dt| dt.and_utc().timestamp())
+                .with_context(|| format!("invalid date {datetime}"))
+        }
+        _ => bail!("invalid time {:?}", args.join(" ")),
+    }
+}
+
+/// Lease fields gathered from one `lease` block
+#[derive(Default)]
+struct Block {
+    mac_address: Option<String>,
+    lease_start: Option<i64>,
+    lease_end: Option<i64>,
+    hostname: Option<String>,
+    active: bool,
+}
+
+impl Block {
+    fn apply(&mut self, statement: &[&str]) -> Result<()> {
+        match statement {
+            ["hardware", "ethernet", mac] => {
+                let parsed = MacAddress::from_string(mac)
+                    .ok_or_else(|| anyhow!("invalid hardware ethernet {mac}"))?;
+                self.mac_address = Some(parsed.to_string());
+            }
+            ["starts", args @ ..] => self.lease_start = Some(parse_time(args)?),
+            ["ends", args @ ..] => self.lease_end = Some(parse_time(args)?),
+            ["client-hostname", name] => self.hostname = sanitize_hostname(name),
+            // `next binding state` and `rewind binding state` describe
+            // future transitions, not the current one
+            ["binding", "state", state] => self.active = *state == "active",
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Parse the contents of a `dhcpd.leases` file into the leases that are
+/// still bound, one per address, ordered by address.
+pub fn parse(contents: &str) -> Result<Vec<LeaseImport>> {
+    let tokens = tokenize(contents)?;
+    let mut leases = BTreeMap::new();
+    let mut pos = 0;
+
+    while pos < tokens.len() {
+        // Top-level statement: collect words up to `;` or a block
+        let start = pos;
+        while pos < tokens.len() && !matches!(tokens[pos], Token::Semicolon | Token::Open) {
+            pos += 1;
+        }
+        let head: Vec<&str> = tokens[start..pos].iter().filter_map(text).collect();
+        if pos >= tokens.len() || tokens[pos] == Token::Semicolon {
+            pos += 1;
+            continue;
+        }
+        pos += 1; // Open
+
+        let ip = match head.as_slice() {
+            ["lease", ip] => Some(
+                ip.parse::<Ipv4Addr>()
+                    .map_err(|_| anyhow!("invalid lease address {ip}"))?,
+            ),
+            // failover, host, lease6 and other blocks are skipped
+            _ => None,
+        };
+
+        let mut block = Block::default();
+        let mut statement = Vec::new();
+        let mut depth = 1;
+        while depth > 0 {
+            let token = tokens
+                .get(pos)
+                .ok_or_else(|| anyhow!("unterminated block {}", head.join(" ")))?;
+            pos += 1;
+            match token {
+                Token::Open => depth += 1,
+                Token::Close => depth -= 1,
+                Token::Semicolon => {
+                    if depth == 1 {
+                        block.apply(&statement)?;
+                    }
+                    statement.clear();
+                }
+                _ => statement.extend(text(token)),
+            }
+        }
+
+        let Some(ip) = ip else { continue };
+        if !block.active {
+            leases.remove(&ip);
+            continue;
+        }
+        let (Some(mac_address), Some(lease_start), Some(lease_end)) =
+            (block.mac_address, block.lease_start, block.lease_end)
+        else {
+            bail!("lease {ip} is missing hardware ethernet, starts or ends");
+        };
+        leases.insert(
+            ip,
+            LeaseImport {
+                mac_address,
+                ip_address: ip,
+                lease_start,
+                lease_end,
+                hostname: block.hostname,
+            },
+        );
+    }
+
+    Ok(leases.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"# The format of this file is documented in the dhcpd.leases(5) manual page.
+# This lease file was written by isc-dhcp-4.4.3
+
+# authoring-byte-order entry is generated, DO NOT DELETE
+authoring-byte-order little-endian;
+
+server-duid "\000\001\000\001)\224\032\017\000\014)\272\323\207";
+
+lease 192.168.1.100 {
+  starts 2 2024/01/16 08:00:00;
+  ends 3 2024/01/17 08:00:00;
+  cltt 2 2024/01/16 08:00:00;
+  binding state active;
+  next binding state free;
+  rewind binding state free;
+  hardware ethernet 00:11:22:AA:BB:CC;
+  uid "\001\000\021\"\252\273\314";
+  client-hostname "laptop";
+}
+lease 192.168.1.101 {
+  starts epoch 1705392000; # 2024/01/16 08:00:00
+  ends epoch 1705478400;
+  binding state active;
+  hardware ethernet 00:11:22:33:44:55;
+}
+lease 192.168.1.102 {
+  starts 1 2024/01/15 08:00:00;
+  ends 2 2024/01/16 08:00:00;
+  binding state free;
+  hardware ethernet 00:11:22:33:44:66;
+}
+lease 192.168.1.101 {
+  starts 2 2024/01/16 09:00:00;
+  ends never;
+  binding state active;
+  hardware ethernet 00:11:22:33:44:55;
+  client-hostname "printer";
+}
+"#;
+
+    #[test]
+    fn test_parse_sample_file() {
+        let leases = parse(SAMPLE).unwrap();
+        assert_eq!(
+            leases,
+            vec![
+                LeaseImport {
+                    mac_address: "00:11:22:aa:bb:cc".to_string(),
+                    ip_address: Ipv4Addr::new(192, 168, 1, 100),
+                    lease_start: 1705392000,
+                    lease_end: 1705478400,
+                    hostname: Some("laptop".to_string()),
+                },
+                // The later block for .101 replaces the earlier one
+                LeaseImport {
+                    mac_address: "00:11:22:33:44:55".to_string(),
+                    ip_address: Ipv4Addr::new(192, 168, 1, 101),
+                    lease_start: 1705395600,
+                    lease_end: NEVER,
+                    hostname: Some("printer".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_freed_lease_drops_earlier_binding() {
+        let contents = r#"
+lease 10.0.0.5 {
+  starts epoch 1; ends epoch 2;
+  binding state active;
+  hardware ethernet 00:00:00:00:00:05;
+}
+lease 10.0.0.5 {
+  starts epoch 1; ends epoch 2;
+  binding state free;
+}
+"#;
+        assert!(parse(contents).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_malformed_mac_is_rejected() {
+        let contents = r#"
+lease 10.0.0.5 {
+  starts epoch 1; ends epoch 2;
+  binding state active;
+  hardware ethernet 00:00:00:00:05;
+}
+"#;
+        let err = parse(contents).unwrap_err();
+        assert!(err.to_string().contains("00:00:00:00:05"), "{err}");
+    }
+}
@@ -1,9 +1,15 @@
 use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
+use chrono::{DateTime, Local, NaiveDate};
 use syslog::{Facility, Formatter3164, Severity};
 use tracing::{Event, Level, Subscriber};
-use tracing_subscriber::{layer::Context, Layer};
+use tracing_subscriber::{fmt::MakeWriter, layer::Context, Layer};
+
+use crate::config::LogRotation;
 
 /// A `tracing_subscriber` layer that forwards log events to syslog.
 pub struct SyslogLayer {
@@ -87,3 +93,167 @@ impl<S: Subscriber> Layer<S> for SyslogLayer {
         }
     }
 }
+
+// ── rolling log file ─────────────────────────────────────────────────────────
+
+/// Log file rotated according to a [`LogRotation`] policy, usable as the
+/// writer of a `tracing_subscriber::fmt` layer.
+///
+/// Each write appends under a lock and may rotate the file, so the daemon
+/// wraps it in `tracing_appender::non_blocking` to keep that I/O off the
+/// threads emitting events.
+pub struct RollingFile {
+    path: PathBuf,
+    rotation: LogRotation,
+    max_size: u64,
+    state: Mutex<FileState>,
+}
+
+struct FileState {
+    file: File,
+    size: u64,
+    opened_on: NaiveDate,
+}
+
+impl RollingFile {
+    /// Open (or create) `path` for appending. `max_size` is in bytes and only
+    /// used with [`LogRotation::Size`].
+    pub fn open(
+        path: impl Into<PathBuf>,
+        rotation: LogRotation,
+        max_size: u64,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let state = Self::open_state(&path)?;
+        Ok(Self {
+            path,
+            rotation,
+            max_size,
+            state: Mutex::new(state),
+        })
+    }
+
+    fn open_state(path: &Path) -> io::Result<FileState> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        // A file left from an earlier day is rotated on the first event
+        let opened_on = metadata
+            .modified()
+            .map(|t| DateTime::<Local>::from(t).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+        Ok(FileState {
+            file,
+            size: metadata.len(),
+            opened_on,
+        })
+    }
+
+    fn needs_rotation(&self, state: &FileState, incoming: usize) -> bool {
+        match self.rotation {
+            LogRotation::Daily => state.opened_on != Local::now().date_naive(),
+            LogRotation::Size => state.size > 0 && state.size + incoming as u64 > self.max_size,
+            LogRotation::Never => false,
+        }
+    }
+
+    /// Move the current file aside and start a new one
+    fn rotate(&self, state: &mut FileState) -> io::Result<()> {
+        let suffix = match self.rotation {
+            LogRotation::Daily => state.opened_on.format("%Y-%m-%d").to_string(),
+            _ => Local::now().format("%Y%m%d-%H%M%S").to_string(),
+        };
+        let rotated = |suffix: &str| self.path.with_extension(Self::extension(&self.path, suffix));
+        let mut target = rotated(&suffix);
+        let mut n = 1;
+        while target.exists() {
+            target = rotated(&format!("{suffix}.{n}"));
+            n += 1;
+        }
+        std::fs::rename(&self.path, &target)?;
+        *state = Self::open_state(&self.path)?;
+        Ok(())
+    }
+
+    /// Extension of the rotated file: the original one followed by `suffix`
+    fn extension(path: &Path, suffix: &str) -> String {
+        match path.extension() {
+            Some(ext) => format!("{}.{}", ext.to_string_lossy(), suffix),
+            None => suffix.to_string(),
+        }
+    }
+}
+
+/// Handle returned by [`RollingFile::make_writer`] for one event
+pub struct RollingFileWriter<'a>(&'a RollingFile);
+
+impl io::Write for RollingFileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        if self.0.needs_rotation(&state, buf.len()) {
+            if let Err(e) = self.0.rotate(&mut state) {
+                // Keep logging to the current file rather than losing events
+                eprintln!("Failed to rotate {}: {e}", self.0.path.display());
+            }
+        }
+        state.file.write_all(buf)?;
+        state.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut state = self.0.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.file.flush()
+    }
+}
+
+impl io::Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        RollingFileWriter(self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        RollingFileWriter(self).flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingFile {
+    type Writer = RollingFileWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RollingFileWriter(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_size_rotation_moves_full_file_aside() {
+        let dir = std::env::temp_dir().join(format!(
+            "ndhcpd-logs-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ndhcpd.log");
+
+        let log = RollingFile::open(&path, LogRotation::Size, 16).unwrap();
+        log.make_writer().write_all(b"first line\n").unwrap();
+        log.make_writer().write_all(b"second line\n").unwrap();
+        log.make_writer().write_all(b"third line\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "third line\n");
+        let mut rotated: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p != &path)
+            .map(|p| std::fs::read_to_string(p).unwrap())
+            .collect();
+        rotated.sort();
+        assert_eq!(rotated, ["first line\n", "second line\n"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,2 +1,4 @@
+pub mod hostname;
+pub mod isc_leases;
 pub mod logging;
 pub mod network;
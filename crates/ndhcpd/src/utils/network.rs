@@ -1,22 +1,16 @@
 use std::net::Ipv4Addr;
 
+use crate::models::PrefixLen;
+
 /// Returns true if two subnets overlap (one contains or is contained by the other).
 pub fn subnets_overlap(
     net_a: Ipv4Addr,
-    prefix_a: u8,
+    prefix_a: PrefixLen,
     net_b: Ipv4Addr,
-    prefix_b: u8,
+    prefix_b: PrefixLen,
 ) -> bool {
-    let mask_a = if prefix_a == 0 {
-        0u32
-    } else {
-        u32::MAX << (32 - prefix_a)
-    };
-    let mask_b = if prefix_b == 0 {
-        0u32
-    } else {
-        u32::MAX << (32 - prefix_b)
-    };
+    let mask_a = prefix_a.mask_bits();
+    let mask_b = prefix_b.mask_bits();
     let start_a = u32::from(net_a) & mask_a;
     let end_a = start_a | !mask_a;
     let start_b = u32::from(net_b) & mask_b;
@@ -24,6 +18,119 @@ pub fn subnets_overlap(
     start_a <= end_b && start_b <= end_a
 }
 
+/// Returns the IPv4 addresses assigned to the given network interface.
+pub fn get_interface_ips(interface: &str) -> Vec<Ipv4Addr> {
+    let mut ips = Vec::new();
+    unsafe {
+        let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
+        if libc::getifaddrs(&mut ifaddrs) != 0 {
+            return ips;
+        }
+        let mut cur = ifaddrs;
+        while !cur.is_null() {
+            let ifa = &*cur;
+            if !ifa.ifa_addr.is_null() {
+                let name = std::ffi::CStr::from_ptr(ifa.ifa_name).to_string_lossy();
+                if name == interface && (*ifa.ifa_addr).sa_family as i32 == libc::AF_INET {
+                    let sin = ifa.ifa_addr as *const libc::sockaddr_in;
+                    let ip = u32::from_be((*sin).sin_addr.s_addr);
+                    ips.push(Ipv4Addr::from(ip));
+                }
+            }
+            cur = ifa.ifa_next;
+        }
+        libc::freeifaddrs(ifaddrs);
+    }
+    ips
+}
+
+/// Pin `ip` to `mac` in the kernel neighbour (ARP) table of `iface`, as
+/// `ip neigh replace ... nud permanent` does.
+#[cfg(target_os = "linux")]
+pub async fn set_static_neighbor(iface: &str, ip: Ipv4Addr, mac: &str) -> anyhow::Result<()> {
+    let ip = ip.to_string();
+    let output = tokio::process::Command::new("ip")
+        .args([
+            "neigh",
+            "replace",
+            &ip,
+            "lladdr",
+            mac,
+            "dev",
+            iface,
+            "nud",
+            "permanent",
+        ])
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("cannot run ip(8): {}", e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "ip neigh replace {} on {} failed: {}",
+            ip,
+            iface,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Neighbour table population is only implemented on Linux.
+#[cfg(not(target_os = "linux"))]
+pub async fn set_static_neighbor(_iface: &str, _ip: Ipv4Addr, _mac: &str) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Remove the entry of `ip` from the kernel neighbour (ARP) table of
+/// `iface`, as `ip neigh del` does.
+#[cfg(target_os = "linux")]
+pub async fn del_static_neighbor(iface: &str, ip: Ipv4Addr) -> anyhow::Result<()> {
+    let ip = ip.to_string();
+    let output = tokio::process::Command::new("ip")
+        .args(["neigh", "del", &ip, "dev", iface])
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("cannot run ip(8): {}", e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "ip neigh del {} on {} failed: {}",
+            ip,
+            iface,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Neighbour table population is only implemented on Linux.
+#[cfg(not(target_os = "linux"))]
+pub async fn del_static_neighbor(_iface: &str, _ip: Ipv4Addr) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Writes permanent entries to a neighbour (ARP) table
+#[async_trait::async_trait]
+pub trait NeighborTable: Send + Sync {
+    /// Pin `ip` to `mac` on `iface`, replacing any existing entry.
+    async fn pin(&self, iface: &str, ip: Ipv4Addr, mac: &str) -> anyhow::Result<()>;
+    /// Remove the entry of `ip` on `iface`.
+    async fn unpin(&self, iface: &str, ip: Ipv4Addr) -> anyhow::Result<()>;
+}
+
+/// The kernel neighbour table, driven through ip(8)
+pub struct KernelNeighbors;
+
+#[async_trait::async_trait]
+impl NeighborTable for KernelNeighbors {
+    async fn pin(&self, iface: &str, ip: Ipv4Addr, mac: &str) -> anyhow::Result<()> {
+        set_static_neighbor(iface, ip, mac).await
+    }
+
+    async fn unpin(&self, iface: &str, ip: Ipv4Addr) -> anyhow::Result<()> {
+        del_static_neighbor(iface, ip).await
+    }
+}
+
 /// Returns the Ethernet MAC address of `iface`, or `None` on failure.
 ///
 /// * Linux   – `ioctl(SIOCGIFHWADDR)`
@@ -222,57 +329,61 @@ mod tests {
         s.parse().unwrap()
     }
 
+    fn len(l: u8) -> PrefixLen {
+        PrefixLen::new(l).unwrap()
+    }
+
     #[test]
     fn test_identical_subnets_overlap() {
-        assert!(subnets_overlap(ip("192.168.1.0"), 24, ip("192.168.1.0"), 24));
+        assert!(subnets_overlap(ip("192.168.1.0"), len(24), ip("192.168.1.0"), len(24)));
     }
 
     #[test]
     fn test_disjoint_subnets_no_overlap() {
-        assert!(!subnets_overlap(ip("192.168.1.0"), 24, ip("192.168.2.0"), 24));
+        assert!(!subnets_overlap(ip("192.168.1.0"), len(24), ip("192.168.2.0"), len(24)));
     }
 
     #[test]
     fn test_adjacent_subnets_no_overlap() {
         // 192.168.0.0/24 ends at .255, 192.168.1.0/24 starts at .1.0 — no overlap
-        assert!(!subnets_overlap(ip("192.168.0.0"), 24, ip("192.168.1.0"), 24));
+        assert!(!subnets_overlap(ip("192.168.0.0"), len(24), ip("192.168.1.0"), len(24)));
     }
 
     #[test]
     fn test_subnet_contained_in_larger() {
         // 192.168.1.0/24 is fully inside 192.168.0.0/16
-        assert!(subnets_overlap(ip("192.168.1.0"), 24, ip("192.168.0.0"), 16));
+        assert!(subnets_overlap(ip("192.168.1.0"), len(24), ip("192.168.0.0"), len(16)));
     }
 
     #[test]
     fn test_larger_contains_smaller() {
         // 192.168.0.0/16 encompasses 192.168.1.0/24
-        assert!(subnets_overlap(ip("192.168.0.0"), 16, ip("192.168.1.0"), 24));
+        assert!(subnets_overlap(ip("192.168.0.0"), len(16), ip("192.168.1.0"), len(24)));
     }
 
     #[test]
     fn test_partial_overlap() {
         // 10.0.0.0/8 and 10.128.0.0/9 overlap (10.128–255 is inside 10.0.0.0/8)
-        assert!(subnets_overlap(ip("10.0.0.0"), 8, ip("10.128.0.0"), 9));
+        assert!(subnets_overlap(ip("10.0.0.0"), len(8), ip("10.128.0.0"), len(9)));
     }
 
     #[test]
     fn test_completely_different_ranges_no_overlap() {
-        assert!(!subnets_overlap(ip("10.0.0.0"), 8, ip("172.16.0.0"), 12));
+        assert!(!subnets_overlap(ip("10.0.0.0"), len(8), ip("172.16.0.0"), len(12)));
     }
 
     #[test]
     fn test_prefix_zero_matches_everything() {
         // /0 covers all addresses and overlaps with anything
-        assert!(subnets_overlap(ip("0.0.0.0"), 0, ip("192.168.1.0"), 24));
-        assert!(subnets_overlap(ip("192.168.1.0"), 24, ip("0.0.0.0"), 0));
+        assert!(subnets_overlap(ip("0.0.0.0"), len(0), ip("192.168.1.0"), len(24)));
+        assert!(subnets_overlap(ip("192.168.1.0"), len(24), ip("0.0.0.0"), len(0)));
     }
 
     #[test]
     fn test_prefix_32_single_host() {
         // /32 overlaps only with ranges that contain that exact address
-        assert!(subnets_overlap(ip("192.168.1.1"), 32, ip("192.168.1.0"), 24));
-        assert!(!subnets_overlap(ip("192.168.1.1"), 32, ip("192.168.2.0"), 24));
+        assert!(subnets_overlap(ip("192.168.1.1"), len(32), ip("192.168.1.0"), len(24)));
+        assert!(!subnets_overlap(ip("192.168.1.1"), len(32), ip("192.168.2.0"), len(24)));
     }
 
     // --- ip_checksum tests ---
@@ -0,0 +1,333 @@
+//! Accumulating validation of API models.
+//!
+//! Validators collect every violation instead of stopping at the first one so
+//! that clients (typically admin forms) can report all problems at once.
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::net::Ipv4Addr;
+use utoipa::ToSchema;
+
+use crate::models::{DynamicRange, OptionTemplate, Subnet};
+use crate::utils::hostname::is_valid_domain_name;
+
+/// A single validation failure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct FieldError {
+    /// Name of the offending field
+    pub field: String,
+    /// Human-readable description of the problem
+    pub message: String,
+}
+
+/// All validation failures for a request body, returned as a 400 response
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a violation on `field`.
+    pub fn add(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Record a violation on `field` when `failed` is true.
+    pub fn check(&mut self, failed: bool, field: &str, message: impl Into<String>) {
+        if failed {
+            self.add(field, message);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// `Ok(())` when nothing was recorded, otherwise `Err(self)`.
+    pub fn into_result(self) -> Result<(), Self> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl IntoResponse for ValidationErrors {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_REQUEST, Json(self)).into_response()
+    }
+}
+
+/// Models that can check their own consistency
+pub trait Validate {
+    /// Returns every violation found, or `Ok(())` if the value is valid.
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// Most IPv4 addresses one DHCP option can carry (255 bytes of data)
+const MAX_OPTION_ADDRESSES: usize = 63;
+
+/// Record a violation when a list sent as a single DHCP option is too long
+fn check_option_addresses(errors: &mut ValidationErrors, field: &str, addrs: &[Ipv4Addr]) {
+    errors.check(
+        addrs.len() > MAX_OPTION_ADDRESSES,
+        field,
+        format!("at most {} addresses fit in one DHCP option", MAX_OPTION_ADDRESSES),
+    );
+}
+
+/// Record a violation when `domain_name` is set but blank or not a valid
+/// domain name
+fn check_domain_name(errors: &mut ValidationErrors, domain_name: Option<&str>) {
+    match domain_name {
+        Some(d) if d.trim().is_empty() => errors.add("domain_name", "must not be empty when set"),
+        Some(d) if !is_valid_domain_name(d) => errors.add(
+            "domain_name",
+            format!("{:?} is not a domain name: use letters, digits and hyphens", d),
+        ),
+        _ => {}
+    }
+}
+
+impl Validate for Subnet {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+
+        errors.check(
+            // Above 32 is already rejected by `PrefixLen`
+            self.netmask == 0,
+            "netmask",
+            format!("prefix length must be between 1 and 32, got {}", self.netmask),
+        );
+        errors.check(
+            u32::from(self.network) & !self.mask_bits() != 0,
+            "network",
+            format!("{} has host bits set for a /{}", self.network, self.netmask),
+        );
+        errors.check(
+            !self.contains(self.gateway),
+            "gateway",
+            format!(
+                "{} is not inside {}/{}",
+                self.gateway, self.network, self.netmask
+            ),
+        );
+        errors.check(
+            self.dns_servers
+                .iter()
+                .any(|ip| ip.is_unspecified() || ip.is_broadcast()),
+            "dns_servers",
+            "DNS servers must be unicast addresses",
+        );
+        check_option_addresses(&mut errors, "dns_servers", &self.dns_servers);
+        errors.check(
+            self.ntp_servers
+                .iter()
+                .any(|ip| ip.is_unspecified() || ip.is_broadcast()),
+            "ntp_servers",
+            "NTP servers must be unicast addresses",
+        );
+        check_option_addresses(&mut errors, "ntp_servers", &self.ntp_servers);
+        check_domain_name(&mut errors, self.domain_name.as_deref());
+        errors.check(
+            self.default_lease_time == Some(0),
+            "default_lease_time",
+            "must be at least one second when set",
+        );
+
+        errors.into_result()
+    }
+}
+
+impl Validate for DynamicRange {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+
+        errors.check(
+            u32::from(self.range_start) > u32::from(self.range_end),
+            "range_end",
+            format!(
+                "{} is before range_start {}",
+                self.range_end, self.range_start
+            ),
+        );
+        errors.check(
+            self.range_start == Ipv4Addr::UNSPECIFIED,
+            "range_start",
+            "must not be 0.0.0.0",
+        );
+
+        errors.into_result()
+    }
+}
+
+/// [`Validate::validate`] of `range`, plus checking that both of its ends
+/// lie in `subnet`, the subnet it is attached to
+pub fn validate_range_in(range: &DynamicRange, subnet: &Subnet) -> Result<(), ValidationErrors> {
+    let mut errors = range.validate().err().unwrap_or_default();
+    let outside = |ip: Ipv4Addr| {
+        format!("{} is outside subnet {}/{}", ip, subnet.network, subnet.netmask)
+    };
+
+    errors.check(
+        !subnet.contains(range.range_start),
+        "range_start",
+        outside(range.range_start),
+    );
+    errors.check(
+        !subnet.contains(range.range_end),
+        "range_end",
+        outside(range.range_end),
+    );
+
+    errors.into_result()
+}
+
+impl Validate for OptionTemplate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+
+        errors.check(self.name.trim().is_empty(), "name", "must not be empty");
+        errors.check(
+            self.dns_servers
+                .iter()
+                .any(|ip| ip.is_unspecified() || ip.is_broadcast()),
+            "dns_servers",
+            "DNS servers must be unicast addresses",
+        );
+        check_option_addresses(&mut errors, "dns_servers", &self.dns_servers);
+        check_domain_name(&mut errors, self.domain_name.as_deref());
+        errors.check(
+            self.default_lease_time == Some(0),
+            "default_lease_time",
+            "must be at least one second when set",
+        );
+
+        errors.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PrefixLen;
+
+    fn valid_subnet() -> Subnet {
+        Subnet {
+            dns_servers: vec![Ipv4Addr::new(1, 1, 1, 1)],
+            domain_name: Some("lan".to_string()),
+            ..Subnet::from_cidr("192.168.1.0/24").unwrap()
+        }
+    }
+
+    fn fields(errors: &ValidationErrors) -> Vec<&str> {
+        errors.errors.iter().map(|e| e.field.as_str()).collect()
+    }
+
+    #[test]
+    fn test_valid_subnet() {
+        assert!(valid_subnet().validate().is_ok());
+    }
+
+    #[test]
+    fn test_subnet_reports_all_violations() {
+        let subnet = Subnet {
+            netmask: PrefixLen::new(0).unwrap(),
+            domain_name: Some(" ".to_string()),
+            ..valid_subnet()
+        };
+        let errors = subnet.validate().unwrap_err();
+        assert_eq!(fields(&errors), vec!["netmask", "network", "domain_name"]);
+    }
+
+    #[test]
+    fn test_subnet_host_bits_and_empty_domain() {
+        let subnet = Subnet {
+            network: Ipv4Addr::new(192, 168, 1, 7),
+            domain_name: Some(" ".to_string()),
+            ..valid_subnet()
+        };
+        let errors = subnet.validate().unwrap_err();
+        assert_eq!(fields(&errors), vec!["network", "domain_name"]);
+    }
+
+    #[test]
+    fn test_subnet_invalid_domain_name() {
+        for domain in ["my domain", "lan..local", "bad_name.lan"] {
+            let subnet = Subnet {
+                domain_name: Some(domain.to_string()),
+                ..valid_subnet()
+            };
+            let errors = subnet.validate().unwrap_err();
+            assert_eq!(fields(&errors), vec!["domain_name"], "{domain}");
+        }
+    }
+
+    #[test]
+    fn test_subnet_server_lists_fit_in_one_option() {
+        let subnet = Subnet {
+            dns_servers: vec![Ipv4Addr::new(1, 1, 1, 1); 63],
+            ntp_servers: vec![Ipv4Addr::new(10, 0, 0, 1); 64],
+            ..valid_subnet()
+        };
+        let errors = subnet.validate().unwrap_err();
+        assert_eq!(fields(&errors), vec!["ntp_servers"]);
+    }
+
+    #[test]
+    fn test_range_inverted() {
+        let range = DynamicRange {
+            id: None,
+            subnet_id: 1,
+            range_start: Ipv4Addr::new(192, 168, 1, 200),
+            range_end: Ipv4Addr::new(192, 168, 1, 100),
+            enabled: true,
+            draining: false,
+        };
+        let errors = range.validate().unwrap_err();
+        assert_eq!(fields(&errors), vec!["range_end"]);
+        let errors = validate_range_in(&range, &valid_subnet()).unwrap_err();
+        assert_eq!(fields(&errors), vec!["range_end"]);
+    }
+
+    #[test]
+    fn test_range_outside_subnet() {
+        let range = DynamicRange {
+            id: None,
+            subnet_id: 1,
+            range_start: Ipv4Addr::new(192, 168, 1, 100),
+            range_end: Ipv4Addr::new(192, 168, 1, 200),
+            enabled: true,
+            draining: false,
+        };
+        assert!(validate_range_in(&range, &valid_subnet()).is_ok());
+
+        let range = DynamicRange {
+            range_start: Ipv4Addr::new(10, 0, 0, 100),
+            range_end: Ipv4Addr::new(10, 0, 0, 200),
+            ..range
+        };
+        let errors = validate_range_in(&range, &valid_subnet()).unwrap_err();
+        assert_eq!(fields(&errors), vec!["range_start", "range_end"]);
+
+        let range = DynamicRange {
+            range_start: Ipv4Addr::new(192, 168, 1, 100),
+            range_end: Ipv4Addr::new(192, 168, 2, 10),
+            ..range
+        };
+        let errors = validate_range_in(&range, &valid_subnet()).unwrap_err();
+        assert_eq!(fields(&errors), vec!["range_end"]);
+    }
+}
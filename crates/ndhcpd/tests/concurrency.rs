@@ -0,0 +1,145 @@
+//! API writes and DHCP handling sharing one SQLite file, as in production.
+
+use std::collections::HashSet;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use dhcp_proto::OpCode;
+use ndhcpd::dhcp::packet::ReceivedPacket;
+use ndhcpd::dhcp::{DhcpOption, DhcpPacket, DhcpServer, MacAddress, MessageType};
+use ndhcpd::{Config, DynDatabase, DynamicRange, RaConfig, SqliteDatabase, Subnet};
+use tower::ServiceExt;
+
+const CLIENTS: u8 = 200;
+const API_ROUNDS: u8 = 100;
+
+const SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 1);
+
+fn client_packet(mac: &MacAddress, xid: u32, message_type: MessageType) -> DhcpPacket {
+    let mut packet = DhcpPacket::new();
+    packet.set_op_code(OpCode::BootRequest);
+    packet.xid = xid;
+    packet.chaddr = mac.clone();
+    packet.options.push(DhcpOption::MessageType(message_type));
+    packet
+}
+
+/// Run DISCOVER then REQUEST for the offered address, which must be ACKed.
+async fn acquire(server: &DhcpServer, mac: &MacAddress, xid: u32) -> Ipv4Addr {
+    let iface_ips = [SERVER_IP];
+    let discover = client_packet(mac, xid, MessageType::Discover);
+    let offer = server
+        .respond(&ReceivedPacket::from_packet(discover), &iface_ips)
+        .await
+        .unwrap_or_else(|| panic!("no OFFER for {}", mac));
+    assert_eq!(offer.get_message_type(), Some(MessageType::Offer));
+
+    let mut request = client_packet(mac, xid, MessageType::Request);
+    request.options.push(DhcpOption::RequestedIpAddress(offer.yiaddr));
+    request.options.push(DhcpOption::ServerIdentifier(SERVER_IP));
+    let ack = server
+        .respond(&ReceivedPacket::from_packet(request), &iface_ips)
+        .await
+        .unwrap_or_else(|| panic!("no reply to REQUEST from {}", mac));
+    assert_eq!(ack.get_message_type(), Some(MessageType::Ack), "{}", mac);
+    assert_eq!(ack.yiaddr, offer.yiaddr);
+    ack.yiaddr
+}
+
+/// Neither side may see an error (e.g. `database is locked`) under load, and
+/// every client ends up with its own address.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_api_mutations_and_dhcp_traffic() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!("sqlite:{}", dir.path().join("dhcp.db").display());
+    let db: DynDatabase = Arc::new(SqliteDatabase::new(&url).await.unwrap());
+    let subnet_id = db
+        .create_subnet(&Subnet {
+            gateway: SERVER_IP,
+            // Refused REQUESTs are NAKed rather than ignored
+            authoritative: true,
+            ..Subnet::from_cidr("192.168.1.0/24").unwrap()
+        })
+        .await
+        .unwrap();
+    db.create_range(&DynamicRange {
+        id: None,
+        subnet_id,
+        range_start: Ipv4Addr::new(192, 168, 1, 10),
+        range_end: Ipv4Addr::new(192, 168, 1, 250),
+        enabled: true,
+        draining: false,
+    })
+    .await
+    .unwrap();
+    let server = Arc::new(DhcpServer::new(Arc::new(Config::default()), db.clone()));
+    let router = ndhcpd::create_router(db.clone(), Arc::new(RaConfig::default()));
+
+    let mut tasks = Vec::new();
+    for i in 0..API_ROUNDS {
+        let router = router.clone();
+        tasks.push(tokio::spawn(async move {
+            let call = |method: Method, uri: String, body: Option<serde_json::Value>| {
+                let request = Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(body.map_or(Body::empty(), |b| Body::from(b.to_string())))
+                    .unwrap();
+                router.clone().oneshot(request)
+            };
+            let subnet = serde_json::json!({
+                "network": format!("10.{}.0.0", i),
+                "netmask": 24,
+                "gateway": format!("10.{}.0.1", i),
+                "dns_servers": [],
+                "domain_name": null
+            });
+            let response = call(Method::POST, "/api/subnets".into(), Some(subnet)).await;
+            let response = response.unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let id: i64 = serde_json::from_slice(&body).unwrap();
+
+            let range = serde_json::json!({
+                "subnet_id": id,
+                "range_start": format!("10.{}.0.100", i),
+                "range_end": format!("10.{}.0.200", i)
+            });
+            let response = call(Method::POST, "/api/ranges".into(), Some(range)).await;
+            assert_eq!(response.unwrap().status(), StatusCode::CREATED);
+
+            let response = call(Method::DELETE, format!("/api/subnets/{}", id), None).await;
+            assert!(response.unwrap().status().is_success());
+        }));
+    }
+    // The listen loop answers one packet at a time, while API calls run
+    // concurrently with it
+    let dhcp = tokio::spawn(async move {
+        let mut acked = Vec::new();
+        for i in 0..CLIENTS {
+            let mac = MacAddress::new([0xaa, 0xbb, 0xcc, 0xdd, 0x70, i]);
+            acked.push((mac.to_string(), acquire(&server, &mac, u32::from(i)).await));
+        }
+        acked
+    });
+    for task in tasks {
+        task.await.unwrap();
+    }
+    let acked = dhcp.await.unwrap();
+
+    let subnets = db.list_subnets().await.unwrap();
+    assert_eq!(subnets.len(), 1);
+    let leases = db.list_active_leases().await.unwrap();
+    assert_eq!(leases.len(), CLIENTS as usize);
+    let distinct: HashSet<_> = leases.iter().map(|l| l.ip_address).collect();
+    assert_eq!(distinct.len(), leases.len());
+    // Each ACK matches the lease stored for its client
+    for (mac, ip) in acked {
+        assert!(leases.iter().any(|l| l.mac_address == mac && l.ip_address == ip), "{mac}");
+    }
+}